@@ -0,0 +1,100 @@
+//! Reads an LVM PV through a device that only transfers whole 512-byte blocks into
+//! a fixed buffer, the way firmware talks to flash or an SD card. The library side
+//! builds without std:
+//!
+//! ```text
+//! cargo run --no-default-features --example block_device -- <image>
+//! ```
+//!
+//! Only loading the image into memory and printing use std.
+use acid_io::{Read, Seek, SeekFrom};
+use exhume_lvm::{Lvm2, Lvm2Options};
+
+const BLOCK_SIZE: usize = 512;
+
+struct BlockDevice<'a> {
+    storage: &'a [u8],
+    block: [u8; BLOCK_SIZE],
+    loaded: Option<u64>,
+    position: u64,
+}
+
+impl<'a> BlockDevice<'a> {
+    fn new(storage: &'a [u8]) -> Self {
+        BlockDevice {
+            storage,
+            block: [0; BLOCK_SIZE],
+            loaded: None,
+            position: 0,
+        }
+    }
+
+    fn len(&self) -> u64 {
+        self.storage.len() as u64
+    }
+
+    // stands in for the driver call that transfers one block
+    fn load_block(&mut self, index: u64) {
+        if self.loaded == Some(index) {
+            return;
+        }
+        let start = index as usize * BLOCK_SIZE;
+        let end = (start + BLOCK_SIZE).min(self.storage.len());
+        self.block = [0; BLOCK_SIZE];
+        self.block[..end - start].copy_from_slice(&self.storage[start..end]);
+        self.loaded = Some(index);
+    }
+}
+
+impl Read for BlockDevice<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        if self.position >= self.len() {
+            return Ok(0);
+        }
+        self.load_block(self.position / BLOCK_SIZE as u64);
+        let offset = (self.position % BLOCK_SIZE as u64) as usize;
+        let available = (BLOCK_SIZE - offset).min((self.len() - self.position) as usize);
+        let len = available.min(buf.len());
+        buf[..len].copy_from_slice(&self.block[offset..offset + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for BlockDevice<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+        };
+        self.position = position.ok_or(acid_io::Error::new(
+            acid_io::ErrorKind::InvalidInput,
+            "seek before the start of the device",
+        ))?;
+        Ok(self.position)
+    }
+}
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: block_device <image>");
+    let storage = std::fs::read(path).expect("failed to read the image");
+    let mut device = BlockDevice::new(&storage);
+
+    // a small device has little room for metadata, and neither does its RAM
+    let options = Lvm2Options::new().max_metadata_size(64 << 10);
+    let lvm = match Lvm2::open_with(&mut device, &options) {
+        Ok(lvm) => lvm,
+        Err(e) => {
+            eprintln!("not an LVM PV: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    println!("VG {} ({})", lvm.vg_name(), lvm.vg_id());
+    for lv in lvm.lvs() {
+        println!("  LV {} {} bytes", lv.name(), lv.size_bytes());
+    }
+}
@@ -0,0 +1,54 @@
+//! Parses an LVM PV image handed over from JavaScript as an `ArrayBuffer` and
+//! returns the summary as JSON. Build with
+//!
+//! ```text
+//! cargo build --release --example wasm_parse --target wasm32-unknown-unknown
+//! ```
+//!
+//! and serve `wasm_parse.html` next to the resulting `wasm_parse.wasm`.
+use std::io::Cursor;
+
+use exhume_lvm::Lvm2;
+
+/// Allocates a buffer of `len` bytes for the caller to copy the image into.
+#[no_mangle]
+pub extern "C" fn buffer_alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::<u8>::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// Frees a buffer returned by `buffer_alloc` or `summarize`.
+///
+/// # Safety
+/// `ptr` and `len` must describe a buffer returned by this module that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn buffer_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Parses the `len` bytes at `ptr` and returns a new buffer holding a little-endian
+/// `u32` length followed by that many bytes of JSON: either the summary or `{"error": ...}`.
+///
+/// # Safety
+/// `ptr` must point to `len` initialized bytes obtained from `buffer_alloc`.
+#[no_mangle]
+pub unsafe extern "C" fn summarize(ptr: *const u8, len: usize) -> *mut u8 {
+    let image = std::slice::from_raw_parts(ptr, len);
+    let json = match Lvm2::open(&mut Cursor::new(image)) {
+        Ok(lvm) => serde_json::to_string(&lvm.to_summary()),
+        Err(e) => serde_json::to_string(&serde_json::json!({ "error": e.to_string() })),
+    }
+    .unwrap_or_default();
+
+    let mut out = Vec::with_capacity(4 + json.len());
+    out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    out.extend_from_slice(json.as_bytes());
+    let ptr = out.as_mut_ptr();
+    std::mem::forget(out);
+    ptr
+}
+
+// the exports above are the interface; there is nothing to run natively
+fn main() {}
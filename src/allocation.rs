@@ -0,0 +1,289 @@
+// allocation.rs
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+
+use crate::{Lvm2, VolumeGroup, LV, PV};
+
+/// Which LV each physical extent of a PV is allocated to, as runs of extents with
+/// the same owner, from [`VolumeGroup::pv_allocation`].
+#[derive(Clone)]
+pub struct PvAllocation<'a> {
+    pv: PV<'a>,
+    runs: Vec<ExtentRun<'a>>,
+}
+
+/// Physical extents of a PV with the same owner.
+#[derive(Clone)]
+pub struct ExtentRun<'a> {
+    pub extents: Range<u64>,
+    /// `None` if the extents are unallocated.
+    pub lv: Option<LV<'a>>,
+}
+
+impl<'a> PvAllocation<'a> {
+    pub fn pv(&self) -> PV<'a> {
+        self.pv
+    }
+
+    /// Every extent of the PV in extent order, in runs of the same owner.
+    pub fn runs(&self) -> &[ExtentRun<'a>] {
+        &self.runs
+    }
+
+    /// The runs allocated to an LV.
+    pub fn allocated(&self) -> impl Iterator<Item = &ExtentRun<'a>> + '_ {
+        self.runs.iter().filter(|run| run.lv.is_some())
+    }
+
+    /// The runs of extents no LV maps.
+    pub fn unallocated(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.runs
+            .iter()
+            .filter(|run| run.lv.is_none())
+            .map(|run| run.extents.clone())
+    }
+
+    /// The runs allocated to the LV named `lv`.
+    pub fn extents_of<'s>(&'s self, lv: &'s str) -> impl Iterator<Item = Range<u64>> + 's {
+        self.runs
+            .iter()
+            .filter(move |run| run.lv.is_some_and(|owner| owner.name() == lv))
+            .map(|run| run.extents.clone())
+    }
+
+    /// The LV physical extent `pe` is allocated to.
+    pub fn owner(&self, pe: u64) -> Option<LV<'a>> {
+        let i = self.runs.partition_point(|run| run.extents.end <= pe);
+        self.runs
+            .get(i)
+            .filter(|run| run.extents.contains(&pe))
+            .and_then(|run| run.lv)
+    }
+
+    pub fn is_allocated(&self, pe: u64) -> bool {
+        self.owner(pe).is_some()
+    }
+
+    /// The PV's size in extents, as `pvs -o pv_pe_count` shows it.
+    pub fn extents_total(&self) -> u64 {
+        self.pv.pe_count()
+    }
+
+    /// Extents allocated to an LV; one mapped by several LVs counts once.
+    pub fn extents_allocated(&self) -> u64 {
+        self.allocated()
+            .map(|run| run.extents.end - run.extents.start)
+            .sum()
+    }
+
+    pub fn extents_free(&self) -> u64 {
+        self.extents_total() - self.extents_allocated()
+    }
+
+    pub fn usage(&self, extent_size: u64) -> PvUsage<'a> {
+        PvUsage {
+            pv: self.pv.name(),
+            extents_total: self.extents_total(),
+            extents_allocated: self.extents_allocated(),
+            extents_free: self.extents_free(),
+            size_bytes: self.extents_total().saturating_mul(extent_size),
+            free_bytes: self.extents_free().saturating_mul(extent_size),
+        }
+    }
+
+    /// One bit per physical extent, set if it is allocated: bit `pe % 64` of word
+    /// `pe / 64`. Words past the last allocated extent are left out.
+    pub fn bitmap(&self) -> Vec<u64> {
+        let end = self.allocated().last().map_or(0, |run| run.extents.end);
+        let mut words = alloc::vec![0u64; end.div_ceil(64) as usize];
+        for run in self.allocated() {
+            let mut pe = run.extents.start;
+            while pe < run.extents.end {
+                let bits = (64 - pe % 64).min(run.extents.end - pe);
+                // `bits` ones, shifted up to where the run starts in its word
+                let mask = (u64::MAX >> (64 - bits)) << (pe % 64);
+                words[(pe / 64) as usize] |= mask;
+                pe += bits;
+            }
+        }
+        words
+    }
+}
+
+/// How much of a PV is allocated, like a row of `pvs`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PvUsage<'a> {
+    /// Name of the PV in the VG metadata.
+    pub pv: &'a str,
+    pub extents_total: u64,
+    pub extents_allocated: u64,
+    pub extents_free: u64,
+    pub size_bytes: u64,
+    pub free_bytes: u64,
+}
+
+/// How much of a VG is allocated, like a row of `vgs`, rolled up from its PVs.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct VgUsage<'a> {
+    pub extent_size: u64,
+    pub extents_total: u64,
+    pub extents_allocated: u64,
+    pub extents_free: u64,
+    pub size_bytes: u64,
+    pub free_bytes: u64,
+    pub pvs: Vec<PvUsage<'a>>,
+}
+
+impl VolumeGroup {
+    /// Which LV each extent of the PV named `pv` is allocated to, from the segments
+    /// of every LV. Extents mapped more than once go to the first LV, in name order,
+    /// mapping them; [`Lvm2::findings`] reports such overlaps.
+    pub fn pv_allocation(&self, pv: &str) -> Option<PvAllocation<'_>> {
+        let pv = self.pv_by_name(pv)?;
+        let pe_count = pv.pe_count();
+        let mut areas: Vec<(Range<u64>, LV<'_>)> = Vec::new();
+        for lv in self.lvs_all() {
+            for (_, seg) in lv.segments() {
+                for (_, extents) in seg.pv_extents().filter(|&(name, _)| name == pv.name()) {
+                    let end = extents.end.min(pe_count);
+                    if extents.start < end {
+                        areas.push((extents.start..end, lv));
+                    }
+                }
+            }
+        }
+
+        // sweep over where areas start and end, keeping the areas covering the
+        // extents in between by their index; the first of them owns those extents
+        let mut bounds: Vec<(u64, usize)> = areas
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (extents, _))| [(extents.start, i), (extents.end, i)])
+            .collect();
+        bounds.sort_unstable();
+        let mut covering: BTreeSet<usize> = BTreeSet::new();
+        let mut runs: Vec<ExtentRun<'_>> = Vec::new();
+        let mut at = 0;
+        for (pe, i) in bounds {
+            if pe > at {
+                push_run(
+                    &mut runs,
+                    at..pe,
+                    covering.first().map(|&first| areas[first].1),
+                );
+                at = pe;
+            }
+            // an area's start always comes before its end
+            if !covering.remove(&i) {
+                covering.insert(i);
+            }
+        }
+        if at < pe_count {
+            push_run(&mut runs, at..pe_count, None);
+        }
+        Some(PvAllocation { pv, runs })
+    }
+
+    /// [`VolumeGroup::pv_allocation`] of every PV, in name order.
+    pub fn pv_allocations(&self) -> Vec<PvAllocation<'_>> {
+        self.pvs()
+            .filter_map(|pv| self.pv_allocation(pv.name()))
+            .collect()
+    }
+
+    /// Size and free space of every PV and of the VG as a whole.
+    pub fn usage(&self) -> VgUsage<'_> {
+        let pvs: Vec<PvUsage<'_>> = self
+            .pv_allocations()
+            .iter()
+            .map(|allocation| allocation.usage(self.extent_size()))
+            .collect();
+        let sum = |field: fn(&PvUsage<'_>) -> u64| pvs.iter().map(field).sum::<u64>();
+        VgUsage {
+            extent_size: self.extent_size(),
+            extents_total: sum(|pv| pv.extents_total),
+            extents_allocated: sum(|pv| pv.extents_allocated),
+            extents_free: sum(|pv| pv.extents_free),
+            size_bytes: sum(|pv| pv.size_bytes),
+            free_bytes: sum(|pv| pv.free_bytes),
+            pvs,
+        }
+    }
+}
+
+// appends `extents` to the last run if it has the same owner
+fn push_run<'a>(runs: &mut Vec<ExtentRun<'a>>, extents: Range<u64>, lv: Option<LV<'a>>) {
+    match runs.last_mut() {
+        Some(run) if run.lv.map(|lv| lv.name()) == lv.map(|lv| lv.name()) => {
+            run.extents.end = extents.end;
+        }
+        _ => runs.push(ExtentRun { extents, lv }),
+    }
+}
+
+impl Lvm2 {
+    /// [`VolumeGroup::pv_allocation`] of the opened PV.
+    pub fn pv_allocation(&self) -> Option<PvAllocation<'_>> {
+        self.vg().pv_allocation(self.pv_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_image;
+    use alloc::string::String;
+
+    fn linear(start_extent: u64, extent_count: u64, pe: u64) -> String {
+        alloc::format!(
+            "start_extent = {start_extent}\nextent_count = {extent_count}\ntype = \"striped\"\n\
+             stripe_count = 1\nstripes = [\"pv0\", {pe}]"
+        )
+    }
+
+    fn runs(allocation: &PvAllocation<'_>) -> Vec<(Range<u64>, Option<String>)> {
+        allocation
+            .runs()
+            .iter()
+            .map(|run| (run.extents.clone(), run.lv.map(|lv| lv.name().into())))
+            .collect()
+    }
+
+    #[test]
+    fn runs_follow_the_segments() {
+        let lvs = [
+            test_image::lv("a", &[&linear(0, 4, 2), &linear(4, 2, 10)]),
+            test_image::lv("b", &[&linear(0, 4, 4)]),
+        ]
+        .concat();
+        let vg = VolumeGroup::parse(&test_image::metadata_text(&lvs, 16)).unwrap();
+        let allocation = vg.pv_allocation("pv0").unwrap();
+        let owner = |name: &str| Some(String::from(name));
+        // b's first two extents are a's already
+        assert_eq!(
+            runs(&allocation),
+            [
+                (0..2, None),
+                (2..6, owner("a")),
+                (6..8, owner("b")),
+                (8..10, None),
+                (10..12, owner("a")),
+                (12..16, None),
+            ]
+        );
+        assert_eq!(allocation.extents_allocated(), 8);
+        assert_eq!(allocation.bitmap(), [0b1100_1111_1100]);
+    }
+
+    #[test]
+    fn huge_pvs_take_no_memory_per_extent() {
+        let lvs = test_image::lv("a", &[&linear(0, 2, 1 << 40)]);
+        let text = test_image::metadata_text(&lvs, 1 << 48);
+        let vg = VolumeGroup::parse(&text).unwrap();
+        let allocation = vg.pv_allocation("pv0").unwrap();
+        assert_eq!(allocation.runs().len(), 3);
+        assert_eq!(allocation.extents_free(), (1 << 48) - 2);
+    }
+}
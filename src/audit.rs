@@ -0,0 +1,78 @@
+// audit.rs
+use alloc::string::String;
+use alloc::sync::Arc;
+use serde::Serialize;
+
+/// Why a byte range of the source was read.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReadReason {
+    /// One of the first four sectors, searched for the PV label.
+    Label,
+    /// The header sheet of metadata area `mda`.
+    MetadataAreaHeader { mda: usize },
+    /// The current VG config text in metadata area `mda`.
+    MetadataText { mda: usize },
+    /// The whole metadata area, scanned for older generations.
+    MetadataHistory { mda: usize },
+    /// The text ring of metadata area `mda` outside the current metadata.
+    MetadataSlack { mda: usize },
+    /// The gap between the metadata area and the first physical extent.
+    PreDataSlack,
+    /// The area reserved for a bootloader.
+    BootloaderArea,
+    /// Contents of an LV.
+    LvData { lv: String, segment: String },
+    /// Physical extents `first_pe..=last_pe`, which no LV maps.
+    Unallocated { first_pe: u64, last_pe: u64 },
+}
+
+/// A byte range read from the source, relative to the start of the reader handed to
+/// the library.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PhysicalRead {
+    pub offset: u64,
+    pub len: u64,
+    pub reason: ReadReason,
+}
+
+/// Receives every physical read made on behalf of the library, see
+/// [`Lvm2Options::record_reads`](crate::Lvm2Options::record_reads).
+pub trait ReadRecorder: Send + Sync {
+    fn record(&self, read: PhysicalRead);
+}
+
+// keeps Lvm2Options Clone + Debug
+#[derive(Clone)]
+pub(crate) struct RecorderHandle(pub(crate) Arc<dyn ReadRecorder>);
+impl core::fmt::Debug for RecorderHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("ReadRecorder")
+    }
+}
+
+/// A [`ReadRecorder`] that keeps every read in memory, in order.
+#[cfg(feature = "std")]
+#[derive(Default, Debug)]
+pub struct AccessLog(std::sync::Mutex<alloc::vec::Vec<PhysicalRead>>);
+
+#[cfg(feature = "std")]
+impl AccessLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The reads recorded so far.
+    pub fn reads(&self) -> alloc::vec::Vec<PhysicalRead> {
+        self.0.lock().map(|reads| reads.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ReadRecorder for AccessLog {
+    fn record(&self, read: PhysicalRead) {
+        if let Ok(mut reads) = self.0.lock() {
+            reads.push(read);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// fuse.rs
+use exhume_body::BodySlice;
+use exhume_lvm::{Lvm2, LV};
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyOpen, Request, FUSE_ROOT_ID,
+};
+use std::ffi::OsStr;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+// nothing changes while mounted
+const TTL: Duration = Duration::from_secs(3600);
+const VG_INO: u64 = FUSE_ROOT_ID + 1;
+// LV `i` is inode FIRST_LV_INO + i
+const FIRST_LV_INO: u64 = VG_INO + 1;
+
+/// Blocks until the filesystem is unmounted.
+/// Hidden LVs are only served with `all`.
+pub fn mount(lvm: &Lvm2, partition: BodySlice, mountpoint: &Path, all: bool) -> io::Result<()> {
+    let fs = LvFs {
+        lvm,
+        lvs: lvm.lvs_all().filter(|lv| all || !lv.is_hidden()).collect(),
+        partition,
+        // SAFETY: getuid and getgid cannot fail
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+    };
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("exhume_lvm".to_owned()),
+        MountOption::Subtype("lvm".to_owned()),
+        MountOption::DefaultPermissions,
+    ];
+    fuser::mount2(fs, mountpoint, &options)
+}
+
+struct LvFs<'a> {
+    lvm: &'a Lvm2,
+    lvs: Vec<LV<'a>>,
+    partition: BodySlice,
+    uid: u32,
+    gid: u32,
+}
+
+impl<'a> LvFs<'a> {
+    fn lv(&self, ino: u64) -> Option<LV<'a>> {
+        let index = ino.checked_sub(FIRST_LV_INO)?;
+        self.lvs.get(usize::try_from(index).ok()?).copied()
+    }
+
+    fn attr(&self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, size, time) = match ino {
+            FUSE_ROOT_ID | VG_INO => {
+                // when the metadata was written
+                let written = self.lvm.vg().header().creation_time.unwrap_or_default();
+                let time = written.to_system_time();
+                (FileType::Directory, 0o555, 0, time)
+            }
+            _ => {
+                let lv = self.lv(ino)?;
+                let time = lv.raw_metadata().creation_time.to_system_time();
+                (FileType::RegularFile, 0o444, lv.size_bytes(), time)
+            }
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+
+    fn read_lv(&mut self, lv: LV<'a>, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+        let mut open = self.lvm.open_lv(lv, &mut self.partition);
+        open.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::with_capacity(size);
+        open.take(size as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl Filesystem for LvFs<'_> {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let ino = match parent {
+            FUSE_ROOT_ID if name == self.lvm.vg_name() => Some(VG_INO),
+            VG_INO => {
+                let lv = name.to_str().and_then(|name| name.strip_suffix(".img"));
+                self.lvs
+                    .iter()
+                    .position(|candidate| Some(candidate.name()) == lv)
+                    .map(|i| FIRST_LV_INO + i as u64)
+            }
+            _ => None,
+        };
+        match ino.and_then(|ino| self.attr(ino)) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if self.lv(ino).is_none() {
+            reply.error(libc::ENOENT);
+        } else if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            reply.error(libc::EROFS);
+        } else {
+            reply.opened(0, 0);
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let (Some(lv), Ok(offset)) = (self.lv(ino), u64::try_from(offset)) else {
+            return reply.error(libc::EINVAL);
+        };
+        match self.read_lv(lv, offset, size as usize) {
+            Ok(data) => reply.data(&data),
+            Err(e) => {
+                warn!("Error reading LV '{}' at {:#x}: {}", lv.name(), offset, e);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_owned()),
+            (FUSE_ROOT_ID, FileType::Directory, "..".to_owned()),
+        ];
+        match ino {
+            FUSE_ROOT_ID => {
+                entries.push((VG_INO, FileType::Directory, self.lvm.vg_name().to_owned()))
+            }
+            VG_INO => entries.extend(self.lvs.iter().enumerate().map(|(i, lv)| {
+                (
+                    FIRST_LV_INO + i as u64,
+                    FileType::RegularFile,
+                    format!("{}.img", lv.name()),
+                )
+            })),
+            _ => return reply.error(libc::ENOTDIR),
+        }
+        let skip = usize::try_from(offset).unwrap_or(0);
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(skip) {
+            // the offset handed back is where the next call resumes
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
@@ -0,0 +1,3299 @@
+use clap::*;
+use clap_num::maybe_hex;
+use exhume_body::{Body, BodySlice};
+use exhume_lvm::metadata::{AllocationPolicy, LVDesc, MetadataValue, Segment};
+use exhume_lvm::{
+    group_by_vg, AccessLog, CacheMetadata, DmTable, ExportRecord, ExtentRun, LVSummary, LegCheck,
+    LogicalToPhysical, LvRole, LvValidation, Lvm2, Lvm2Options, LvmUuid, Mda, MetadataAreaUsage,
+    MetadataComparison, MetadataGeneration, MirrorLog, PhysicalRead, Pv, SalvagedKind,
+    SalvagedRange, SegmentProblem, SnapshotDelta, ThinPoolUsage, ThinProvisioning, VgCheck,
+    VgValidation, VolumeGroup, LV, PV,
+};
+use prettytable::{Cell, Row, Table};
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+mod fuse;
+#[cfg(feature = "nbd")]
+mod nbd;
+mod output;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+
+use output::{fail, or_exit, print_json, write_json_lines, JsonOutput, Units};
+
+#[cfg(not(feature = "partitions"))]
+const OFFSET_HELP: &str = "LVM partition starts at address 0x...";
+#[cfg(feature = "partitions")]
+const OFFSET_HELP: &str =
+    "LVM partition starts at address 0x... Found in the partition table when omitted";
+
+fn main() {
+    let command = Command::new("exhume_lvm")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Exhumes and displays LVM information")
+        .arg(
+            Arg::new("body")
+                .short('b')
+                .long("body")
+                .value_parser(value_parser!(String))
+                .required(true)
+                .help("Path to the partition body file"),
+        )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_parser(value_parser!(String))
+                .required(true)
+                .help("File format: either 'raw' or 'ewf'"),
+        )
+        .arg(
+            Arg::new("offset")
+                .short('o')
+                .long("offset")
+                .value_parser(maybe_hex::<u64>)
+                .required(!cfg!(feature = "partitions"))
+                .requires("size")
+                .help(OFFSET_HELP),
+        )
+        .arg(
+            Arg::new("size")
+                .short('s')
+                .long("size")
+                .value_parser(maybe_hex::<u64>)
+                .required(!cfg!(feature = "partitions"))
+                .requires("offset")
+                .help("LVM partition size."),
+        )
+        .arg(
+            Arg::new("sector_size")
+                .long("sector-size")
+                .value_parser(parse_sector_size)
+                .help("Bytes per sector of the disk imaged, e.g. 4096 for a 4Kn disk; -s and sector offsets are in these. Defaults to what the body reports, 512 for raw bodies"),
+        )
+        .arg(
+            Arg::new("log_level")
+                .short('l')
+                .long("log-level")
+                .value_parser(["error", "warn", "info", "debug", "trace"])
+                .default_value("info")
+                .help("Set the log verbosity level"),
+        )
+        .arg(
+            Arg::new("json")
+                .short('j')
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Print the LVM summary as JSON instead of a table"),
+        )
+        .arg(
+            Arg::new("json-lines")
+                .long("json-lines")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("json")
+                .help("List the LVs as JSON Lines, one object per LV written as it is made, for VGs too large for one document"),
+        )
+        .arg(
+            Arg::new("all")
+                .short('a')
+                .long("all")
+                .action(ArgAction::SetTrue)
+                .help("Also list and select hidden LVs, such as thin pool and RAID sub-LVs"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_parser(["name", "size", "created", "segments"])
+                .default_value("name")
+                .help("Order the listed LVs by this attribute"),
+        )
+        .arg(
+            Arg::new("options")
+                .long("options")
+                .value_parser(builder::PossibleValuesParser::new(LV_FIELDS))
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("List one LV per row with these fields only, comma separated, as `lvs -o` does"),
+        )
+        .arg(
+            Arg::new("descending")
+                .long("descending")
+                .action(ArgAction::SetTrue)
+                .help("List the LVs in descending order of --sort"),
+        )
+        .arg(
+            Arg::new("units")
+                .long("units")
+                .value_parser(["b", "s", "k", "m", "g", "t", "e"])
+                .help("Show sizes and offsets in tables in bytes, sectors, KiB, MiB, GiB, TiB or extents"),
+        )
+        .arg(
+            Arg::new("mda")
+                .long("mda")
+                .value_parser(parse_mda)
+                .help("Read the metadata from this area only: primary, secondary or its index"),
+        )
+        .arg(
+            Arg::new("metadata_file")
+                .long("metadata-file")
+                .value_parser(value_parser!(String))
+                .help("Take the VG metadata from this vgcfgbackup or archive file instead of the PV"),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .value_parser(value_parser!(String))
+                .action(ArgAction::Append)
+                .help("Add the metadata generations in this archive file, or every file in this directory, to the history"),
+        )
+        .arg(
+            Arg::new("access_log")
+                .long("access-log")
+                .value_parser(value_parser!(String))
+                .help("Write every byte range read from the body, with its purpose, to this JSON file"),
+        )
+        .subcommand(
+            Command::new("carve")
+                .about("Extract every run of unallocated physical extents into its own file")
+                .arg(
+                    Arg::new("output")
+                        .short('d')
+                        .long("output-dir")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Directory receiving one file per unallocated extent run"),
+                ),
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Copy the contents of an LV to a file")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Name or UUID of the LV"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('O')
+                        .long("output")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("File receiving the LV contents"),
+                )
+                .arg(
+                    Arg::new("verify_sha256")
+                        .long("verify-sha256")
+                        .value_parser(value_parser!(String))
+                        .help("Exit with status 3 unless the extracted data has this SHA-256 (hex)"),
+                )
+                .arg(
+                    Arg::new("verify_legs")
+                        .long("verify-legs")
+                        .value_parser(["warn", "fail"])
+                        .help("Read every leg of a RAID1 or mirror LV and compare them, warning or failing where they differ"),
+                )
+                .arg(
+                    Arg::new("map")
+                        .long("map")
+                        .value_parser(value_parser!(String))
+                        .conflicts_with("verify_legs")
+                        .help("Zero-fill what cannot be read instead of failing, and write a JSON map of the data, hole and unreadable ranges to this file"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Search LVs and unallocated extents for byte patterns")
+                .arg(
+                    Arg::new("regex")
+                        .short('e')
+                        .long("regex")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Byte regex to search for, may be repeated"),
+                )
+                .arg(
+                    Arg::new("hex")
+                        .short('x')
+                        .long("hex")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Byte string in hex to search for, may be repeated"),
+                )
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only search this LV (name or UUID), may be repeated. Defaults to all"),
+                )
+                .arg(
+                    Arg::new("unallocated")
+                        .long("unallocated")
+                        .action(ArgAction::SetTrue)
+                        .help("Also search the extents no LV maps"),
+                )
+                .group(
+                    ArgGroup::new("patterns")
+                        .args(["regex", "hex"])
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("entropy")
+                .about("Compute the Shannon entropy of LVs and unallocated extents, chunk by chunk")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only analyse this LV (name or UUID), may be repeated. Defaults to all"),
+                )
+                .arg(
+                    Arg::new("unallocated")
+                        .long("unallocated")
+                        .action(ArgAction::SetTrue)
+                        .help("Also analyse the extents no LV maps"),
+                )
+                .arg(
+                    Arg::new("chunk")
+                        .long("chunk")
+                        .value_parser(maybe_hex::<u64>)
+                        .help("Chunk size in bytes. Defaults to the extent size"),
+                )
+                .arg(
+                    Arg::new("threshold")
+                        .long("threshold")
+                        .value_parser(value_parser!(f64))
+                        .default_value("7.5")
+                        .help("Entropy in bits per byte above which a chunk is flagged as likely encrypted or compressed"),
+                ),
+        )
+        .subcommand(
+            Command::new("dmtable")
+                .about("Print the dmsetup table of an LV, e.g. for `dmsetup create --readonly <name>`")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Name or UUID of the LV"),
+                )
+                .arg(
+                    Arg::new("deleted")
+                        .long("deleted")
+                        .action(ArgAction::SetTrue)
+                        .help("Look the LV up in older metadata generations instead"),
+                )
+                .arg(
+                    Arg::new("device")
+                        .long("device")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("PV=PATH: the block device a PV is available as, may be repeated. Defaults to the device in the metadata"),
+                ),
+        )
+        .subcommand(
+            Command::new("handoff")
+                .about("Print the --body/--format/--offset/--size arguments other exhume tools need to read an LV")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Name or UUID of the LV"),
+                ),
+        )
+        .subcommand(
+            Command::new("layout").about(
+                "List the PV's layout like TSK's mmls, in sectors of the body as TSK tools take with -o",
+            ),
+        )
+        .subcommand(
+            Command::new("thin")
+                .about("Report how much of each thin LV is provisioned in its pool")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only report this thin LV (name or UUID), may be repeated. Defaults to all"),
+                )
+                .arg(
+                    Arg::new("ranges")
+                        .long("ranges")
+                        .action(ArgAction::SetTrue)
+                        .help("Also list the provisioned ranges of each LV"),
+                ),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check whether each LV can be read in full before extracting it")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only check this LV (name or UUID), may be repeated. Defaults to all"),
+                ),
+        )
+        .subcommand(
+            Command::new("allocmap")
+                .about("Draw which LV each physical extent of the PV belongs to")
+                .arg(
+                    Arg::new("pv")
+                        .long("pv")
+                        .value_parser(value_parser!(String))
+                        .help("Draw this PV of the VG, by its name in the metadata. Defaults to the opened PV"),
+                )
+                .arg(
+                    Arg::new("svg")
+                        .long("svg")
+                        .action(ArgAction::SetTrue)
+                        .help("Draw the map as SVG instead of text"),
+                )
+                .arg(
+                    Arg::new("columns")
+                        .long("columns")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("64")
+                        .help("Cells per row of the map"),
+                )
+                .arg(
+                    Arg::new("cell")
+                        .long("cell")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .help("Extents per cell. Defaults to as few as fit the map in 32 rows"),
+                ),
+        )
+        .subcommand(
+            Command::new("cache")
+                .about("Report which blocks of each cached LV its cache holds, and which are dirty")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only report this cached LV (name or UUID), may be repeated. Defaults to all"),
+                )
+                .arg(
+                    Arg::new("blocks")
+                        .long("blocks")
+                        .action(ArgAction::SetTrue)
+                        .help("Also list the cached blocks of each LV"),
+                ),
+        )
+        .subcommand(
+            Command::new("mirror")
+                .about("Report which regions of each old-style mirror its log marks as out of sync")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only report this mirror (name or UUID), may be repeated. Defaults to all"),
+                )
+                .arg(
+                    Arg::new("regions")
+                        .long("regions")
+                        .action(ArgAction::SetTrue)
+                        .help("Also list the byte ranges out of sync in each mirror"),
+                ),
+        )
+        .subcommand(
+            Command::new("depends")
+                .about("List the LVs and PVs each LV needs to be read, and the LVs needing it")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .action(ArgAction::Append)
+                        .help("Only report this LV (name or UUID), may be repeated. Defaults to all"),
+                ),
+        )
+        .subcommand(
+            Command::new("tree").about(
+                "Show the LVs as a tree, like lsblk: pools with their thin LVs, origins with their snapshots, and the images and other LVs each is made of",
+            ),
+        )
+        .subcommand(
+            Command::new("snapdiff")
+                .about("List the byte ranges where an old-style snapshot differs from its origin")
+                .arg(
+                    Arg::new("lv")
+                        .long("lv")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Name or UUID of the snapshot LV"),
+                ),
+        )
+        .subcommand(
+            Command::new("slack")
+                .about("Extract the slack after the current metadata and before the first physical extent")
+                .arg(
+                    Arg::new("output")
+                        .short('d')
+                        .long("output-dir")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Directory receiving one file per slack region"),
+                ),
+        )
+        .subcommand(
+            Command::new("bootloader")
+                .about("Extract the bootloader area reserved on the PV")
+                .arg(
+                    Arg::new("output")
+                        .short('O')
+                        .long("output")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("File receiving the bootloader area"),
+                ),
+        )
+        .subcommand(
+            Command::new("findings")
+                .about("Report suspicious metadata states, e.g. signs of tampering, as JSON")
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .action(ArgAction::SetTrue)
+                        .help("Also recover older metadata generations to check against"),
+                )
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Run every structural check on the VG and report which pass")
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .action(ArgAction::SetTrue)
+                        .help("Also recover older metadata generations to check against"),
+                )
+        )
+        .subcommand(
+            Command::new("history")
+                .about("List every known metadata generation and what changed in each")
+                .arg(
+                    Arg::new("changes")
+                        .long("changes")
+                        .action(ArgAction::SetTrue)
+                        .help("Also list each changed value"),
+                ),
+        )
+        .subcommand(
+            Command::new("metadata")
+                .about("Print the VG metadata for review or for processing with jq")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["pretty", "json", "raw"])
+                        .default_value("pretty")
+                        .help("Normalized config text, JSON keeping every key, or the text as on disk"),
+                ),
+        )
+        .subcommand(
+            Command::new("get")
+                .about("Print one metadata value, e.g. logical_volumes/root/creation_time")
+                .arg(
+                    Arg::new("path")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("/-separated path of keys and array indices within the VG section"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the VG, PVs, current and deleted LVs, segments and findings as JSON lines")
+                .arg(
+                    Arg::new("output")
+                        .short('O')
+                        .long("output")
+                        .value_parser(value_parser!(String))
+                        .help("File receiving the records instead of stdout"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["jsonl", "dot"])
+                        .default_value("jsonl")
+                        .help("JSON lines, or a Graphviz graph of the VG's LVs, segments and PVs"),
+                ),
+        )
+        .subcommand(
+            Command::new("mdas")
+                .about("Compare the copies of the metadata kept in each metadata area of the PV")
+                .arg(
+                    Arg::new("usage")
+                        .long("usage")
+                        .action(ArgAction::SetTrue)
+                        .help("Report how full each metadata area is instead"),
+                ),
+        )
+        .subcommand(
+            Command::new("scan")
+                .about("Search the partition for PV labels, e.g. on a whole-disk image")
+                .arg(
+                    Arg::new("step")
+                        .long("step")
+                        .value_parser(maybe_hex::<u64>)
+                        .default_value("512")
+                        .help("Distance in bytes between candidate PV start offsets"),
+                )
+                .arg(
+                    Arg::new("vg")
+                        .long("vg")
+                        .help("Only list the PVs of the VG with this name or UUID"),
+                ),
+        );
+    #[cfg(feature = "partitions")]
+    let command = command.arg(
+        Arg::new("partition")
+            .long("partition")
+            .value_parser(value_parser!(usize))
+            .conflicts_with("offset")
+            .help("Which LVM partition of the partition table to open, counting from 1"),
+    );
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    let command = command.arg(
+        Arg::new("io_uring")
+            .long("io-uring")
+            .action(ArgAction::SetTrue)
+            .help("Read LV and extent contents of a raw body through io_uring, with readahead"),
+    );
+    #[cfg(all(feature = "fuse", target_os = "linux"))]
+    let command = command.subcommand(
+        Command::new("mount")
+            .about("Serve each LV as a read-only <vg>/<lv>.img file over FUSE until unmounted")
+            .arg(
+                Arg::new("mountpoint")
+                    .value_parser(value_parser!(String))
+                    .required(true)
+                    .help("Directory to mount on"),
+            ),
+    );
+    #[cfg(feature = "nbd")]
+    let command = command.subcommand(
+        Command::new("serve-nbd")
+            .about(
+                "Export an LV read-only over NBD, e.g. for `nbd-client -N <lv> <host> /dev/nbd0`",
+            )
+            .arg(
+                Arg::new("lv")
+                    .long("lv")
+                    .value_parser(value_parser!(String))
+                    .required(true)
+                    .help("Name or UUID of the LV, also the export name"),
+            )
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .value_parser(value_parser!(String))
+                    .default_value("127.0.0.1:10809")
+                    .help("Address to accept NBD clients on"),
+            ),
+    );
+    let matches = command.get_matches();
+
+    // Initialize logger.
+    let log_level_str = matches.get_one::<String>("log_level").unwrap();
+    // the library logs through tracing, which forwards to env_logger via its log feature
+    let level_filter = match log_level_str.as_str() {
+        "error" | "warn" | "info" | "debug" | "trace" => log_level_str.as_str(),
+        _ => "info",
+    };
+    env_logger::Builder::new()
+        .parse_filters(level_filter)
+        .init();
+
+    let body_path = matches.get_one::<String>("body").unwrap();
+    let format = matches.get_one::<String>("format").unwrap();
+
+    #[allow(unused_mut)]
+    let mut body = Body::new(body_path.clone(), format);
+
+    let body_sector_size = u64::from(body.get_sector_size());
+    let sector_size = match matches.get_one::<u64>("sector_size") {
+        Some(&sector_size) => {
+            if format != "raw" && sector_size != body_sector_size {
+                warn!(
+                    "The body reports {}-byte sectors, using --sector-size {} instead",
+                    body_sector_size, sector_size
+                );
+            }
+            sector_size
+        }
+        None => body_sector_size,
+    };
+
+    let (offset, size) = match (
+        matches.get_one::<u64>("offset"),
+        matches.get_one::<u64>("size"),
+    ) {
+        (Some(&offset), Some(&size)) => (offset, size * sector_size),
+        #[cfg(feature = "partitions")]
+        _ => match find_lvm_partition(&mut body, matches.get_one::<usize>("partition").copied()) {
+            Ok(partition) => partition,
+            Err(e) => fail(e),
+        },
+        #[cfg(not(feature = "partitions"))]
+        _ => unreachable!("clap requires --offset and --size"),
+    };
+
+    let mut partition = BodySlice::new(&body, offset, size).unwrap();
+    debug!("Created Body from '{}'", body_path);
+
+    // the subcommands copying LV or extent contents in bulk read through this one
+    #[allow(unused_mut)]
+    let mut data: Box<dyn ReadSeek> = Box::new(partition.clone());
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    if matches.get_flag("io_uring") {
+        if format != "raw" {
+            warn!("--io-uring only applies to raw bodies, reading through the body instead");
+        } else {
+            match uring::UringSlice::open(Path::new(body_path), offset, size) {
+                Ok(slice) => data = Box::new(slice),
+                Err(e) => warn!(
+                    "io_uring is unavailable, reading through the body instead: {}",
+                    e
+                ),
+            }
+        }
+    }
+
+    let access_log = matches
+        .get_one::<String>("access_log")
+        .map(|path| (path, Arc::new(AccessLog::new())));
+    let all = matches.get_flag("all");
+    let units = Units {
+        unit: matches.get_one::<String>("units").map(String::as_str),
+        extent_size: None,
+    };
+    let order = LvOrder {
+        key: matches.get_one::<String>("sort").unwrap(),
+        descending: matches.get_flag("descending"),
+    };
+    let mut options = Lvm2Options::new();
+    if let Some((_, log)) = &access_log {
+        options = options.record_reads(log.clone());
+    }
+    if let Some(&mda) = matches.get_one::<Mda>("mda") {
+        options = options.only_mda(mda);
+    }
+
+    match matches.subcommand() {
+        Some(("scan", sub)) => {
+            let step = *sub.get_one::<u64>("step").unwrap();
+            let vg = sub.get_one::<String>("vg").map(String::as_str);
+            print_scan(&mut partition, size, step, vg, &options, &units);
+        }
+        Some(("mdas", sub)) => {
+            let pv = or_exit(
+                Pv::open_with(&mut partition, &options),
+                "Error opening the PV",
+            );
+            if sub.get_flag("usage") {
+                let usage = pv.metadata_area_usage(&mut partition);
+                if matches.get_flag("json") {
+                    print_json(&usage, "metadata area usage")
+                } else {
+                    print_metadata_area_usage(&usage, &units);
+                }
+            } else {
+                let comparison = pv.compare_metadata_areas(&mut partition);
+                if matches.get_flag("json") {
+                    print_json(&comparison, "metadata comparison")
+                } else {
+                    print_metadata_comparison(&comparison, &units);
+                }
+            }
+        }
+        subcommand => {
+            match subcommand {
+                Some(("findings" | "verify", sub)) => {
+                    options = options.load_history(sub.get_flag("history"))
+                }
+                Some(("dmtable", sub)) => options = options.load_history(sub.get_flag("deleted")),
+                Some(("export" | "history", _)) => options = options.load_history(true),
+                _ => (),
+            }
+            let opened = match matches.get_one::<String>("metadata_file") {
+                Some(path) => open_with_metadata_file(&mut partition, &options, Path::new(path)),
+                None => Lvm2::open_with(&mut partition, &options),
+            };
+            let mut lvm = match opened {
+                Ok(lvm) => lvm,
+                Err(e) => fail(format_args!("Error opening LVM partition: {:?}", e)),
+            };
+            if let Some(&sectors) = matches.get_one::<u64>("size") {
+                check_sector_size(lvm.pv_header().pv_size, sectors, sector_size);
+            }
+            let units = Units {
+                extent_size: Some(lvm.vg().extent_size()),
+                ..units
+            };
+            let archives: Vec<&String> =
+                matches.get_many("archive").into_iter().flatten().collect();
+            if !archives.is_empty() {
+                lvm.add_history(read_archives(&archives));
+            }
+
+            match subcommand {
+                Some(("carve", sub)) => {
+                    let output = sub.get_one::<String>("output").unwrap();
+                    or_exit(
+                        carve_unallocated(&lvm, &mut data, Path::new(output)),
+                        "Error carving unallocated extents",
+                    );
+                }
+                Some(("extract", sub)) => {
+                    let name = sub.get_one::<String>("lv").unwrap();
+                    let output = sub.get_one::<String>("output").unwrap();
+                    let expected = sub.get_one::<String>("verify_sha256");
+                    let legs = match sub.get_one::<String>("verify_legs").map(String::as_str) {
+                        Some("warn") => LegCheck::Warn,
+                        Some("fail") => LegCheck::Fail,
+                        _ => LegCheck::Off,
+                    };
+                    let map = sub.get_one::<String>("map").map(Path::new);
+                    match extract_lv(
+                        &lvm,
+                        &mut data,
+                        name,
+                        Path::new(output),
+                        expected,
+                        legs,
+                        map,
+                    ) {
+                        Ok(()) => (),
+                        Err(e @ exhume_lvm::Error::HashMismatch { .. }) => {
+                            error!("Verification failed for LV '{}': {}", name, e);
+                            process::exit(3);
+                        }
+                        Err(e) => fail(format_args!("Error extracting LV '{}': {}", name, e)),
+                    }
+                }
+                Some(("search", sub)) => {
+                    let patterns = or_exit(search_patterns(sub), "Invalid search pattern");
+                    let lvs: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    let unallocated = sub.get_flag("unallocated");
+                    match search(&lvm, &mut data, offset, &patterns, &lvs, all, unallocated) {
+                        Ok(hits) if matches.get_flag("json") => print_json(&hits, "search hits"),
+                        Ok(hits) => print_search_hits(&hits, &units),
+                        Err(e) => fail(format_args!("Error searching: {}", e)),
+                    }
+                }
+                Some(("entropy", sub)) => {
+                    let lvs: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    let analysis = EntropyAnalysis {
+                        chunk: sub
+                            .get_one::<u64>("chunk")
+                            .copied()
+                            .unwrap_or(lvm.extent_size())
+                            .max(1),
+                        threshold: *sub.get_one::<f64>("threshold").unwrap(),
+                        unallocated: sub.get_flag("unallocated"),
+                    };
+                    match analyse_entropy(&lvm, &mut data, offset, &lvs, all, &analysis) {
+                        Ok(chunks) if matches.get_flag("json") => {
+                            print_json(&chunks, "entropy analysis")
+                        }
+                        Ok(chunks) => print_entropy(&chunks, &units),
+                        Err(e) => fail(format_args!("Error analysing entropy: {}", e)),
+                    }
+                }
+                Some(("dmtable", sub)) => {
+                    let name = sub.get_one::<String>("lv").unwrap();
+                    let devices: Vec<&String> =
+                        sub.get_many("device").into_iter().flatten().collect();
+                    match dm_table(&lvm, name, sub.get_flag("deleted"), &devices) {
+                        Ok(table) => print!("{}", table),
+                        Err(e) => fail(format_args!(
+                            "Error building the dm table of LV '{}': {}",
+                            name, e
+                        )),
+                    }
+                }
+                Some(("handoff", sub)) => {
+                    let name = sub.get_one::<String>("lv").unwrap();
+                    match handoff(&lvm, &partition, name, offset, sector_size) {
+                        Ok(args) if matches.get_flag("json") => {
+                            let args = serde_json::json!({
+                                "body": body_path,
+                                "format": format,
+                                "offset": args.0,
+                                "size": args.1,
+                            });
+                            println!("{}", args);
+                        }
+                        Ok((offset, size)) => println!(
+                            "--body {} --format {} --offset {:#x} --size {}",
+                            body_path, format, offset, size
+                        ),
+                        Err(e) => fail(format_args!("Error handing off LV '{}': {}", name, e)),
+                    }
+                }
+                Some(("layout", _)) => {
+                    let slots = tsk_layout(&lvm, offset, sector_size);
+                    if matches.get_flag("json") {
+                        print_json(&slots, "layout")
+                    } else {
+                        print_tsk_layout(&lvm, &slots, sector_size);
+                    }
+                }
+                Some(("thin", sub)) => {
+                    let lvs: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    match thin_report(&lvm, &mut data, &lvs) {
+                        Ok(report) if matches.get_flag("json") => {
+                            print_json(&report, "thin provisioning report")
+                        }
+                        Ok(report) => print_thin_report(&report, sub.get_flag("ranges"), &units),
+                        Err(e) => fail(format_args!("Error reading thin provisioning: {}", e)),
+                    }
+                }
+                Some(("allocmap", sub)) => {
+                    let pv = sub
+                        .get_one::<String>("pv")
+                        .map_or(lvm.pv_name(), String::as_str);
+                    let Some(allocation) = lvm.vg().pv_allocation(pv) else {
+                        fail(format_args!("The VG has no PV {}", pv))
+                    };
+                    let runs = allocation.runs();
+                    if matches.get_flag("json") {
+                        let runs: Vec<AllocationRun> = runs
+                            .iter()
+                            .map(|run| AllocationRun {
+                                start_extent: run.extents.start,
+                                extent_count: run.extents.end - run.extents.start,
+                                lv: run.lv.map(|lv| lv.name()),
+                            })
+                            .collect();
+                        print_json(&runs, "allocation map")
+                    } else {
+                        let columns = *sub.get_one::<u64>("columns").unwrap();
+                        let pe_count = allocation.pv().pe_count();
+                        let cell = sub
+                            .get_one::<u64>("cell")
+                            .copied()
+                            .unwrap_or_else(|| pe_count.div_ceil(columns * MAP_ROWS).max(1));
+                        let map = AllocationMap::new(runs, cell, columns);
+                        if sub.get_flag("svg") {
+                            print!("{}", map.to_svg(pv, &lvm));
+                        } else {
+                            print!("{}", map.to_text(pv, &lvm));
+                        }
+                    }
+                }
+                Some(("validate", sub)) => {
+                    let names: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    let lvs = or_exit(select_lvs(&lvm, &names, all), "Error selecting LVs");
+                    let report: Vec<ValidationReport> = lvs
+                        .iter()
+                        .map(|lv| ValidationReport {
+                            lv: lv.name(),
+                            validation: lv.validate(&lvm),
+                        })
+                        .collect();
+                    if matches.get_flag("json") {
+                        print_json(&report, "validation report")
+                    } else {
+                        print_validation_report(&report);
+                    }
+                }
+                Some(("cache", sub)) => {
+                    let lvs: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    match cache_report(&lvm, &mut data, &lvs) {
+                        Ok(report) if matches.get_flag("json") => {
+                            print_json(&report, "cache report")
+                        }
+                        Ok(report) => print_cache_report(&report, sub.get_flag("blocks"), &units),
+                        Err(e) => fail(format_args!("Error reading cache metadata: {}", e)),
+                    }
+                }
+                Some(("mirror", sub)) => {
+                    let lvs: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    match mirror_report(&lvm, &mut data, &lvs) {
+                        Ok(report) if matches.get_flag("json") => {
+                            print_json(&report, "mirror report")
+                        }
+                        Ok(report) => print_mirror_report(&report, sub.get_flag("regions"), &units),
+                        Err(e) => fail(format_args!("Error reading mirror log: {}", e)),
+                    }
+                }
+                Some(("depends", sub)) => {
+                    let lvs: Vec<&String> = sub.get_many("lv").into_iter().flatten().collect();
+                    match select_lvs(&lvm, &lvs, all).map(|lvs| depends_report(&lvm, &lvs)) {
+                        Ok(report) if matches.get_flag("json") => {
+                            print_json(&report, "dependency report")
+                        }
+                        Ok(report) => print_depends_report(&report),
+                        Err(e) => fail(e),
+                    }
+                }
+                Some(("tree", _)) => {
+                    let tree = lv_tree(&lvm, all);
+                    if matches.get_flag("json") {
+                        print_json(&tree, "LV tree")
+                    } else {
+                        print_lv_tree(lvm.vg().name(), &tree, &units);
+                    }
+                }
+                Some(("snapdiff", sub)) => {
+                    let name = sub.get_one::<String>("lv").unwrap();
+                    let delta = select_lvs(&lvm, &[name], all)
+                        .map_err(|source| exhume_lvm::Error::Io { source })
+                        .and_then(|lvs| lvm.snapshot_delta(lvs[0], &mut data));
+                    match delta {
+                        Ok(delta) if matches.get_flag("json") => {
+                            print_json(&delta, "snapshot delta")
+                        }
+                        Ok(delta) => print_snapshot_delta(&delta, &units),
+                        Err(e) => fail(format_args!("Error reading snapshot '{}': {}", name, e)),
+                    }
+                }
+                #[cfg(all(feature = "fuse", target_os = "linux"))]
+                Some(("mount", sub)) => {
+                    let mountpoint = sub.get_one::<String>("mountpoint").unwrap();
+                    info!(
+                        "Serving the LVs of '{}' under '{}', unmount with `fusermount -u {}` to stop",
+                        lvm.vg_name(),
+                        mountpoint,
+                        mountpoint
+                    );
+                    if let Err(e) = fuse::mount(&lvm, partition.clone(), Path::new(mountpoint), all)
+                    {
+                        fail(format_args!("Error mounting on '{}': {}", mountpoint, e))
+                    }
+                }
+                #[cfg(feature = "nbd")]
+                Some(("serve-nbd", sub)) => {
+                    let name = sub.get_one::<String>("lv").unwrap();
+                    let listen = sub.get_one::<String>("listen").unwrap();
+                    let served = select_lvs(&lvm, &[name], all)
+                        .and_then(|lvs| nbd::serve(&lvm, lvs[0], &partition, listen));
+                    if let Err(e) = served {
+                        fail(format_args!("Error serving LV '{}' over NBD: {}", name, e))
+                    }
+                }
+                Some(("slack", sub)) => {
+                    let output = sub.get_one::<String>("output").unwrap();
+                    or_exit(
+                        extract_slack(&lvm, &mut data, Path::new(output)),
+                        "Error extracting slack",
+                    );
+                }
+                Some(("bootloader", sub)) => {
+                    let output = sub.get_one::<String>("output").unwrap();
+                    let Some(area) = lvm.bootloader_area() else {
+                        fail("The PV has no bootloader area")
+                    };
+                    let written = lvm
+                        .read_bootloader_area(&mut data)
+                        .map_err(io::Error::other)
+                        .and_then(|bytes| std::fs::write(output, &bytes).map(|_| bytes.len()));
+                    match written {
+                        Ok(len) => info!(
+                            "Extracted bootloader area {:#x}-{:#x} ({} bytes) to '{}'",
+                            area.start, area.end, len, output
+                        ),
+                        Err(e) => fail(format_args!("Error extracting bootloader area: {}", e)),
+                    }
+                }
+                Some(("findings", _)) => print_json(&lvm.findings(), "findings"),
+                Some(("verify", _)) => {
+                    let validation = lvm.validate();
+                    if matches.get_flag("json") {
+                        print_json(&validation, "validation report")
+                    } else {
+                        print_vg_validation(&validation);
+                    }
+                }
+                Some(("history", sub)) => {
+                    let history = lvm.history();
+                    if matches.get_flag("json") {
+                        print_json(&history, "history")
+                    } else {
+                        print_history(&history, sub.get_flag("changes"));
+                    }
+                }
+                Some(("metadata", sub)) => {
+                    match sub.get_one::<String>("format").map(String::as_str) {
+                        Some("json") => println!("{}", lvm.vg().full_metadata().to_json()),
+                        Some("raw") => print!("{}", lvm.vg().raw_metadata_text()),
+                        _ => print!("{}", lvm.vg().to_pretty_text()),
+                    }
+                }
+                Some(("get", sub)) => {
+                    let path = sub.get_one::<String>("path").unwrap();
+                    match lvm.metadata_get(path) {
+                        Some(value) if matches.get_flag("json") => println!("{}", value.to_json()),
+                        Some(value) => print!("{}", metadata_value_text(value)),
+                        None => fail(format_args!("No metadata value at '{}'", path)),
+                    }
+                }
+                Some(("export", sub)) => {
+                    let output = sub.get_one::<String>("output").map(Path::new);
+                    let exported = match sub.get_one::<String>("format").map(String::as_str) {
+                        Some("dot") => match output {
+                            Some(path) => std::fs::write(path, lvm.vg().to_dot()),
+                            None => io::stdout().write_all(lvm.vg().to_dot().as_bytes()),
+                        },
+                        _ => export_records(&lvm, offset, output),
+                    };
+                    or_exit(exported, "Error exporting records");
+                }
+                _ if matches.contains_id("options") => {
+                    let fields: Vec<&String> =
+                        matches.get_many("options").into_iter().flatten().collect();
+                    let json = match (matches.get_flag("json"), matches.get_flag("json-lines")) {
+                        (_, true) => Some(JsonOutput::Lines),
+                        (true, _) => Some(JsonOutput::Document),
+                        _ => None,
+                    };
+                    print_lv_fields(&lvm, all, &order, &fields, json, &units);
+                }
+                _ if matches.get_flag("json-lines") => {
+                    let written = write_json_lines(
+                        sorted_lvs(&lvm, all, &order)
+                            .into_iter()
+                            .map(LVSummary::from),
+                    );
+                    or_exit(written, "Error writing LVs");
+                }
+                _ if matches.get_flag("json") => {
+                    let mut summary = lvm.to_summary();
+                    summary.logical_volumes.retain(|lv| all || !lv.hidden);
+                    summary.logical_volumes.sort_by(|a, b| {
+                        order.compare(
+                            (a.name, a.size_bytes, a.desc),
+                            (b.name, b.size_bytes, b.desc),
+                        )
+                    });
+                    print_json(&summary, "LVM summary")
+                }
+                _ => print_lvm_info(&lvm, offset, all, &order, &units),
+            }
+        }
+    }
+
+    if let Some((path, log)) = access_log {
+        or_exit(
+            write_access_log(&log, offset, Path::new(path)),
+            "Error writing the access log",
+        );
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+// the library records offsets within the partition; the case file wants them in the body
+fn write_access_log(log: &AccessLog, partition_offset: u64, path: &Path) -> io::Result<()> {
+    let reads: Vec<PhysicalRead> = log
+        .reads()
+        .into_iter()
+        .map(|read| PhysicalRead {
+            offset: read.offset + partition_offset,
+            ..read
+        })
+        .collect();
+    serde_json::to_writer_pretty(File::create(path)?, &reads).map_err(io::Error::other)
+}
+
+// one record per line; image offsets are rebased onto the body like the access log's
+fn export_records(lvm: &Lvm2, partition_offset: u64, output: Option<&Path>) -> io::Result<()> {
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    lvm.try_for_each_export_record(|mut record| {
+        if let ExportRecord::Segment { stripes, .. } = &mut record {
+            for stripe in stripes {
+                stripe.image_offset = stripe.image_offset.map(|o| o + partition_offset);
+            }
+        }
+        serde_json::to_writer(&mut out, &record).map_err(io::Error::other)?;
+        writeln!(out)
+    })?;
+    out.flush()
+}
+
+fn print_scan<T: Read + Seek>(
+    reader: &mut T,
+    size: u64,
+    step: u64,
+    selected: Option<&str>,
+    options: &Lvm2Options,
+    units: &Units,
+) {
+    let mut found = Lvm2::scan_with(reader, 0..size, step, options);
+    let vgs: Vec<_> = found
+        .iter_mut()
+        .map(|(_, pv)| pv.volume_group(reader))
+        .collect();
+    let groups = group_by_vg(
+        found
+            .iter()
+            .zip(&vgs)
+            .map(|((_, pv), vg)| (pv, vg.as_ref().ok())),
+    );
+    let groups: Vec<_> = groups
+        .into_iter()
+        .filter(|group| selected.is_none_or(|vg| group.matches(vg)))
+        .collect();
+    if let (Some(vg), true) = (selected, groups.is_empty()) {
+        fail(format_args!(
+            "No PV found belongs to a VG named or with UUID {}",
+            vg
+        ))
+    }
+
+    // the PVs of each VG together, then those of none
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Offset"),
+        Cell::new("PV UUID"),
+        Cell::new("Volume Group"),
+        Cell::new("VG UUID"),
+    ]));
+    for group in &groups {
+        for &i in &group.pvs {
+            let (offset, pv) = &found[i];
+            table.add_row(Row::new(vec![
+                Cell::new(&units.offset(*offset)),
+                Cell::new(&pv.uuid().to_string()),
+                Cell::new(&group.name),
+                Cell::new(&group.id),
+            ]));
+        }
+    }
+    if selected.is_none() {
+        for (i, ((offset, pv), vg)) in found.iter().zip(&vgs).enumerate() {
+            if groups.iter().any(|group| group.pvs.contains(&i)) {
+                continue;
+            }
+            let vg = match vg {
+                Ok(vg) => vg.name().to_string(),
+                Err(e) => format!("({})", e),
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&units.offset(*offset)),
+                Cell::new(&pv.uuid().to_string()),
+                Cell::new(&vg),
+                Cell::new(""),
+            ]));
+        }
+    }
+    table.printstd();
+    for group in &groups {
+        if !group.missing.is_empty() {
+            println!(
+                "VG {} ({}) is missing PVs: {}",
+                group.name,
+                group.id,
+                group.missing.join(", ")
+            );
+        }
+    }
+}
+
+// a metadata value for shell scripts: strings unquoted, array items one per line and
+// sections as config text
+fn metadata_value_text(value: &MetadataValue) -> String {
+    match value {
+        MetadataValue::String(s) => format!("{}\n", s),
+        MetadataValue::Number(n) => format!("{}\n", n),
+        MetadataValue::Array(items) => items.iter().map(metadata_value_text).collect(),
+        MetadataValue::Section(_) => value.to_pretty_text(),
+    }
+}
+
+fn parse_sector_size(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(size) if size.is_power_of_two() && (512..=65536).contains(&size) => Ok(size),
+        _ => Err(format!(
+            "'{}' is not a power of two from 512 to 65536",
+            value
+        )),
+    }
+}
+
+// warns if the PV header gives the PV a size that the -s sectors only make up at
+// another sector size, e.g. 4096-byte ones on a 4Kn disk
+fn check_sector_size(pv_size: u64, sectors: u64, sector_size: u64) {
+    if pv_size == 0 || pv_size == sectors * sector_size {
+        return;
+    }
+    if let Some(other) = [512, 4096]
+        .into_iter()
+        .find(|&other| other != sector_size && sectors * other == pv_size)
+    {
+        warn!(
+            "The PV header gives a size of {} bytes, {} sectors of {} bytes rather than {}: check --sector-size",
+            pv_size, sectors, other, sector_size
+        );
+    }
+}
+
+fn parse_mda(value: &str) -> Result<Mda, String> {
+    match value {
+        "primary" => Ok(Mda::Primary),
+        "secondary" => Ok(Mda::Secondary),
+        index => index
+            .parse()
+            .map(Mda::Index)
+            .map_err(|_| format!("'{}' is neither primary, secondary nor an index", index)),
+    }
+}
+
+fn print_metadata_comparison(comparison: &MetadataComparison, units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("MDA"),
+        Cell::new("Offset"),
+        Cell::new("Seqno"),
+        Cell::new("Checksum"),
+        Cell::new("Problems"),
+    ]));
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_owned());
+    for copy in &comparison.copies {
+        let mut problems: Vec<String> = copy
+            .checksum_mismatches
+            .iter()
+            .map(|mismatch| format!("bad {} checksum", mismatch.structure))
+            .collect();
+        problems.extend(copy.error.clone());
+        table.add_row(Row::new(vec![
+            Cell::new(&copy.mda.to_string()),
+            Cell::new(&units.offset(copy.offset)),
+            Cell::new(&or_dash(copy.seqno.map(|seqno| seqno.to_string()))),
+            Cell::new(&or_dash(copy.checksum.map(|crc| format!("{:#010x}", crc)))),
+            Cell::new(&problems.join("\n")),
+        ]));
+    }
+    table.printstd();
+    let verdict = |agrees| if agrees { "yes" } else { "NO" };
+    println!(
+        "Seqno agrees: {}\nChecksum agrees: {}\nContent agrees: {}",
+        verdict(comparison.seqno_agrees),
+        verdict(comparison.checksum_agrees),
+        verdict(comparison.content_agrees)
+    );
+}
+
+fn print_metadata_area_usage(usage: &[MetadataAreaUsage], units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("MDA"),
+        Cell::new("Offset"),
+        Cell::new("Size"),
+        Cell::new("Used"),
+        Cell::new("Free"),
+        Cell::new("Current metadata"),
+    ]));
+    let or_dash = |value: Option<u64>| value.map_or_else(|| "-".to_owned(), |v| units.size(v));
+    for area in usage {
+        let blob = match &area.error {
+            Some(e) => e.clone(),
+            None => area
+                .blob
+                .iter()
+                .map(|range| units.range(range))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&area.mda.to_string()),
+            Cell::new(&units.offset(area.offset)),
+            Cell::new(&units.size(area.size)),
+            Cell::new(&or_dash(area.used)),
+            Cell::new(&or_dash(area.free)),
+            Cell::new(&blob),
+        ]));
+    }
+    table.printstd();
+}
+
+fn carve_unallocated<T: Read + Seek>(lvm: &Lvm2, reader: &mut T, output: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(output)?;
+    let mut runs = lvm.unallocated_runs(reader);
+    while let Some(mut run) = runs.next_run() {
+        let extents = run.extents();
+        let path = output.join(format!("pe_{}-{}.raw", extents.start, extents.end - 1));
+        let mut file = File::create(&path)?;
+        let copied = io::copy(&mut run, &mut file)?;
+        info!(
+            "Carved extents {}-{} ({} bytes) to '{}'",
+            extents.start,
+            extents.end - 1,
+            copied,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn extract_lv<T: Read + Seek>(
+    lvm: &Lvm2,
+    reader: &mut T,
+    name: &str,
+    output: &Path,
+    expected_sha256: Option<&String>,
+    legs: LegCheck,
+    map: Option<&Path>,
+) -> Result<(), exhume_lvm::Error> {
+    let io = |source| exhume_lvm::Error::Io { source };
+    let lv = lvm
+        .vg()
+        .lv_by_name(name)
+        .or_else(|| lvm.vg().lv_by_id(name))
+        .ok_or_else(|| io(io::Error::other(format!("no LV named '{}'", name))))?;
+    // a cached LV as the kernel shows it, with the dirty blocks of its cache
+    let cache = match lvm.cache_metadata(lv, reader) {
+        Ok(cache) => {
+            info!(
+                "LV '{}' is cached: reading {} dirty blocks from '{}'",
+                name,
+                cache.dirty().count(),
+                cache.pool
+            );
+            Some(cache)
+        }
+        Err(exhume_lvm::Error::NotACachedVolume { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    // only once the LV is known to be readable
+    let create = || File::create(output).map_err(io);
+    let mirrored = lv
+        .segments()
+        .any(|(_, seg)| matches!(seg.kind, Segment::Raid1 { .. } | Segment::Mirror { .. }));
+    let copied = match (&cache, map) {
+        (Some(cache), _) => {
+            let mut lv = lvm.open_cached_lv(lv, cache, reader)?;
+            let copied = copy_lv(&mut lv, &mut create()?, expected_sha256)?;
+            if let Some(map) = map {
+                let ranges = [SalvagedRange {
+                    range: 0..copied,
+                    kind: SalvagedKind::Data,
+                }];
+                write_extraction_map(map, name, copied, &ranges)?;
+            }
+            copied
+        }
+        (None, Some(map)) => {
+            let mut salvaged = lvm.open_lv_salvaging(lv, reader);
+            let copied = copy_lv(&mut salvaged, &mut create()?, expected_sha256);
+            let size = salvaged.size();
+            write_extraction_map(map, name, size, salvaged.map())?;
+            if salvaged.zeroed_bytes() > 0 {
+                warn!(
+                    "{} bytes of LV '{}' were zero-filled, see '{}'",
+                    salvaged.zeroed_bytes(),
+                    name,
+                    map.display()
+                );
+            }
+            copied?
+        }
+        (None, None) if mirrored => {
+            let mut lv = lvm.open_mirrored_lv(lv, legs, reader)?;
+            let copied = copy_lv(&mut lv, &mut create()?, expected_sha256);
+            for failover in lv.failovers() {
+                warn!(
+                    "Bytes {}-{} read from leg {} as reading {} failed",
+                    failover.range.start,
+                    failover.range.end - 1,
+                    failover.leg,
+                    failover.failed.join(", ")
+                );
+            }
+            for mismatch in lv.mismatches() {
+                warn!(
+                    "Legs {} differ from the first leg in bytes {}-{}",
+                    mismatch.legs.join(", "),
+                    mismatch.range.start,
+                    mismatch.range.end - 1
+                );
+            }
+            copied?
+        }
+        (None, None) => {
+            let mut lv = lvm.try_open_lv(lv, reader)?;
+            copy_lv(&mut lv, &mut create()?, expected_sha256)?
+        }
+    };
+    info!(
+        "Extracted LV '{}' ({} bytes) to '{}'",
+        name,
+        copied,
+        output.display()
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ExtractionMap<'a> {
+    lv: &'a str,
+    size: u64,
+    ranges: &'a [SalvagedRange],
+}
+
+fn write_extraction_map(
+    path: &Path,
+    lv: &str,
+    size: u64,
+    ranges: &[SalvagedRange],
+) -> Result<(), exhume_lvm::Error> {
+    let io = |source| exhume_lvm::Error::Io { source };
+    let map = ExtractionMap { lv, size, ranges };
+    let json = serde_json::to_string_pretty(&map).map_err(|e| io(io::Error::other(e)))?;
+    std::fs::write(path, json + "\n").map_err(io)
+}
+
+fn copy_lv<R: Read>(
+    lv: &mut R,
+    file: &mut File,
+    expected_sha256: Option<&String>,
+) -> Result<u64, exhume_lvm::Error> {
+    Ok(match expected_sha256 {
+        Some(expected) => {
+            let copied = exhume_lvm::copy_verified(lv, file, expected)?;
+            info!("SHA-256 verified: {}", expected);
+            copied
+        }
+        None => {
+            let (copied, hash) = exhume_lvm::copy_sha256(lv, file)?;
+            info!("SHA-256: {}", exhume_lvm::to_hex(&hash));
+            copied
+        }
+    })
+}
+
+fn search_patterns(sub: &ArgMatches) -> Result<Vec<(String, Regex)>, String> {
+    let regexes = sub.get_many::<String>("regex").into_iter().flatten();
+    let hex = sub.get_many::<String>("hex").into_iter().flatten();
+    let mut patterns = Vec::new();
+    for re in regexes {
+        patterns.push((re.clone(), re.clone()));
+    }
+    for hex in hex {
+        let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+        if digits.is_empty()
+            || !digits.len().is_multiple_of(2)
+            || !digits.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(format!("'{}' is not a hex byte string", hex));
+        }
+        let escaped = digits
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| format!("\\x{}", std::str::from_utf8(pair).unwrap_or_default()))
+            .collect();
+        patterns.push((hex.clone(), escaped));
+    }
+    patterns
+        .into_iter()
+        .map(|(label, re)| {
+            RegexBuilder::new(&re)
+                .unicode(false)
+                .build()
+                .map(|re| (label, re))
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct SearchHit {
+    pattern: String,
+    /// LV name, or `unallocated` for extents no LV maps.
+    source: String,
+    /// Offset within the LV or extent run.
+    offset: u64,
+    /// Offset within the body, unless the data lives on another PV.
+    image_offset: Option<u64>,
+    len: usize,
+    /// The first bytes of the match, in hex.
+    preview: String,
+}
+
+fn search<T: Read + Seek>(
+    lvm: &Lvm2,
+    reader: &mut T,
+    partition_offset: u64,
+    patterns: &[(String, Regex)],
+    lv_names: &[&String],
+    all: bool,
+    unallocated: bool,
+) -> io::Result<Vec<SearchHit>> {
+    let mut hits = Vec::new();
+    let lvs = select_lvs(lvm, lv_names, all)?;
+
+    for lv in lvs {
+        let mut open = lvm.open_lv(lv, &mut *reader);
+        let searched = find_matches(&mut open, patterns, |pattern, offset, data| {
+            let image_offset = lv_image_offset(lvm, lv, offset, partition_offset);
+            hits.push(SearchHit::new(
+                pattern,
+                lv.name(),
+                offset,
+                image_offset,
+                data,
+            ));
+        });
+        // an LV this tool cannot map yet should not stop the others from being searched
+        if let Err(e) = searched {
+            warn!("Stopped searching LV '{}': {}", lv.name(), e);
+        }
+    }
+
+    if unallocated {
+        let mut runs = lvm.unallocated_runs(reader);
+        while let Some(mut run) = runs.next_run() {
+            let base = partition_offset + lvm.start_offset() + run.offset();
+            find_matches(&mut run, patterns, |pattern, offset, data| {
+                hits.push(SearchHit::new(
+                    pattern,
+                    "unallocated",
+                    offset,
+                    Some(base + offset),
+                    data,
+                ));
+            })?;
+        }
+    }
+    Ok(hits)
+}
+
+// the named LVs (by name or UUID), or all of them, hidden ones only with `all`
+fn select_lvs<'a>(lvm: &'a Lvm2, names: &[&String], all: bool) -> io::Result<Vec<LV<'a>>> {
+    if names.is_empty() {
+        return Ok(lvm.lvs_all().filter(|lv| all || !lv.is_hidden()).collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            lvm.vg()
+                .lv_by_name(name)
+                .or_else(|| lvm.vg().lv_by_id(name))
+                .ok_or_else(|| io::Error::other(format!("no LV named '{}'", name)))
+        })
+        .collect()
+}
+
+// where LV data lives in the body, if it is on the opened PV
+fn lv_image_offset(lvm: &Lvm2, lv: LV, offset: u64, partition_offset: u64) -> Option<u64> {
+    lv.locate(offset)
+        .ok()
+        .filter(|loc| loc.pv == lvm.pv_name())
+        .and_then(|loc| lvm.data_offset(loc.data_offset))
+        .map(|pv_offset| partition_offset + lvm.start_offset() + pv_offset)
+}
+
+impl SearchHit {
+    fn new(
+        pattern: &str,
+        source: &str,
+        offset: u64,
+        image_offset: Option<u64>,
+        data: &[u8],
+    ) -> Self {
+        SearchHit {
+            pattern: pattern.to_owned(),
+            source: source.to_owned(),
+            offset,
+            image_offset,
+            len: data.len(),
+            preview: data.iter().take(16).map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+// Matches are searched in 16 MiB chunks overlapping by 4 KiB, so a match longer
+// than that may be cut short where two chunks meet.
+fn find_matches<R: Read>(
+    source: &mut R,
+    patterns: &[(String, Regex)],
+    mut hit: impl FnMut(&str, u64, &[u8]),
+) -> io::Result<()> {
+    const CHUNK: u64 = 16 << 20;
+    const OVERLAP: usize = 4096;
+    let mut buf = Vec::new();
+    let mut base = 0;
+    loop {
+        let read = source.by_ref().take(CHUNK).read_to_end(&mut buf)?;
+        let eof = (read as u64) < CHUNK;
+        // matches starting past `limit` are found again, in full, in the next chunk
+        let limit = if eof {
+            buf.len()
+        } else {
+            buf.len().saturating_sub(OVERLAP)
+        };
+        for (label, re) in patterns {
+            for m in re.find_iter(&buf).take_while(|m| m.start() < limit) {
+                hit(label, base + m.start() as u64, m.as_bytes());
+            }
+        }
+        if eof {
+            return Ok(());
+        }
+        buf.drain(..limit);
+        base += limit as u64;
+    }
+}
+
+fn print_search_hits(hits: &[SearchHit], units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Pattern"),
+        Cell::new("Source"),
+        Cell::new("Offset"),
+        Cell::new("Image Offset"),
+        Cell::new("Match"),
+    ]));
+    for hit in hits {
+        table.add_row(Row::new(vec![
+            Cell::new(&hit.pattern),
+            Cell::new(&hit.source),
+            Cell::new(&units.offset(hit.offset)),
+            Cell::new(
+                &hit.image_offset
+                    .map(|offset| units.offset(offset))
+                    .unwrap_or_else(|| "-".to_owned()),
+            ),
+            Cell::new(&hit.preview),
+        ]));
+    }
+    table.printstd();
+}
+
+struct EntropyAnalysis {
+    chunk: u64,
+    threshold: f64,
+    unallocated: bool,
+}
+
+#[derive(Serialize)]
+struct EntropyChunk {
+    /// LV name, or `unallocated` for extents no LV maps.
+    source: String,
+    /// Offset within the LV or extent run.
+    offset: u64,
+    /// Offset within the body, unless the data lives on another PV.
+    image_offset: Option<u64>,
+    len: u64,
+    /// Shannon entropy in bits per byte, from 0 to 8.
+    entropy: f64,
+    /// Above the threshold: likely encrypted or compressed.
+    high: bool,
+}
+
+fn analyse_entropy<T: Read + Seek>(
+    lvm: &Lvm2,
+    reader: &mut T,
+    partition_offset: u64,
+    lv_names: &[&String],
+    all: bool,
+    analysis: &EntropyAnalysis,
+) -> io::Result<Vec<EntropyChunk>> {
+    let mut chunks = Vec::new();
+    for lv in select_lvs(lvm, lv_names, all)? {
+        let mut open = lvm.open_lv(lv, &mut *reader);
+        let analysed = chunk_entropy(&mut open, analysis.chunk, |offset, len, entropy| {
+            chunks.push(EntropyChunk {
+                source: lv.name().to_owned(),
+                offset,
+                image_offset: lv_image_offset(lvm, lv, offset, partition_offset),
+                len,
+                entropy,
+                high: entropy > analysis.threshold,
+            });
+        });
+        if let Err(e) = analysed {
+            warn!("Stopped analysing LV '{}': {}", lv.name(), e);
+        }
+    }
+
+    if analysis.unallocated {
+        let mut runs = lvm.unallocated_runs(reader);
+        while let Some(mut run) = runs.next_run() {
+            let base = partition_offset + lvm.start_offset() + run.offset();
+            chunk_entropy(&mut run, analysis.chunk, |offset, len, entropy| {
+                chunks.push(EntropyChunk {
+                    source: "unallocated".to_owned(),
+                    offset,
+                    image_offset: Some(base + offset),
+                    len,
+                    entropy,
+                    high: entropy > analysis.threshold,
+                });
+            })?;
+        }
+    }
+    Ok(chunks)
+}
+
+fn chunk_entropy<R: Read>(
+    source: &mut R,
+    chunk: u64,
+    mut result: impl FnMut(u64, u64, f64),
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    let mut offset = 0;
+    loop {
+        buf.clear();
+        let len = source.by_ref().take(chunk).read_to_end(&mut buf)? as u64;
+        if len == 0 {
+            return Ok(());
+        }
+        result(offset, len, shannon_entropy(&buf));
+        offset += len;
+    }
+}
+
+fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn print_entropy(chunks: &[EntropyChunk], units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Source"),
+        Cell::new("Offset"),
+        Cell::new("Image Offset"),
+        Cell::new("Length"),
+        Cell::new("Entropy"),
+        Cell::new("High"),
+    ]));
+    for chunk in chunks {
+        table.add_row(Row::new(vec![
+            Cell::new(&chunk.source),
+            Cell::new(&units.offset(chunk.offset)),
+            Cell::new(
+                &chunk
+                    .image_offset
+                    .map(|offset| units.offset(offset))
+                    .unwrap_or_else(|| "-".to_owned()),
+            ),
+            Cell::new(&units.size(chunk.len)),
+            Cell::new(&format!("{:.3}", chunk.entropy)),
+            Cell::new(if chunk.high { "yes" } else { "" }),
+        ]));
+    }
+    table.printstd();
+}
+
+fn dm_table(lvm: &Lvm2, name: &str, deleted: bool, devices: &[&String]) -> Result<DmTable, String> {
+    let mut paths = Vec::new();
+    for device in devices {
+        let (pv, path) = device
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not of the form PV=PATH", device))?;
+        paths.push((pv, path));
+    }
+    let device = |pv: PV| {
+        paths
+            .iter()
+            .find(|(name, _)| *name == pv.name() || LvmUuid::matches(name, pv.id()))
+            .map_or_else(
+                || pv.raw_metadata().device.clone(),
+                |(_, path)| path.to_string(),
+            )
+    };
+
+    if !deleted {
+        let lv = lvm
+            .vg()
+            .lv_by_name(name)
+            .or_else(|| lvm.vg().lv_by_id(name))
+            .ok_or_else(|| format!("no LV named '{}'", name))?;
+        return lvm.vg().dm_table(lv, device).map_err(|e| e.to_string());
+    }
+    let found = lvm
+        .deleted_lvs()
+        .into_iter()
+        .find(|d| d.lv.name() == name || LvmUuid::matches(d.lv.id(), name))
+        .ok_or_else(|| format!("no deleted LV named '{}' in older metadata", name))?;
+    let reallocated = lvm.reallocated_extents(&found);
+    if let Some(first) = reallocated.first() {
+        warn!(
+            "{} extents of LV '{}' now belong to other LVs, starting with extent {}",
+            reallocated
+                .iter()
+                .map(|run| run.end - run.start)
+                .sum::<u64>(),
+            name,
+            first.start
+        );
+    }
+    found
+        .generation
+        .dm_table(found.lv, device)
+        .map_err(|e| e.to_string())
+}
+
+// Offset in bytes and size in sectors of the LV within the body, the way the other
+// exhume tools take their partition. Only an LV stored in one piece on this PV has them.
+fn handoff(
+    lvm: &Lvm2,
+    partition: &BodySlice,
+    name: &String,
+    partition_offset: u64,
+    sector_size: u64,
+) -> Result<(u64, u64), exhume_lvm::Error> {
+    let lv = select_lvs(lvm, &[name], false).map_err(|source| exhume_lvm::Error::Io { source })?[0];
+    let lv = lvm.open_lv_as_body(lv, partition.clone())?;
+    match lv.physical_range(0) {
+        Some(range) if range.end - range.start >= lv.size() => {
+            Ok((partition_offset + range.start, lv.size() / sector_size))
+        }
+        _ => Err(exhume_lvm::Error::Io {
+            source: io::Error::other(
+                "the LV is not stored contiguously, extract it and hand over the file instead",
+            ),
+        }),
+    }
+}
+
+// a region of the body in mmls terms; only LVs stored contiguously get a slot,
+// since TSK can only read those at an offset
+#[derive(Serialize)]
+struct TskSlot<'a> {
+    slot: Option<usize>,
+    /// In sectors of the body.
+    start: u64,
+    length: u64,
+    description: String,
+    lv: Option<&'a str>,
+}
+
+fn tsk_layout(lvm: &Lvm2, partition_offset: u64, sector_size: u64) -> Vec<TskSlot<'_>> {
+    let sector_size = sector_size.max(1);
+    let pv_start = partition_offset + lvm.start_offset();
+    let mut slots = Vec::new();
+    let mut region = |start: u64, end: u64, description: String, lv, slot| {
+        if end > start {
+            slots.push(TskSlot {
+                slot,
+                start: start / sector_size,
+                length: (end - start).div_ceil(sector_size),
+                description,
+                lv,
+            });
+        }
+    };
+    let data_start = lvm.physical_extent_offset(0).unwrap_or(0);
+    region(0, pv_start, "Before the PV".to_owned(), None, None);
+    region(
+        pv_start,
+        pv_start + data_start,
+        "LVM label and metadata".to_owned(),
+        None,
+        None,
+    );
+    let Some(allocation) = lvm.pv_allocation() else {
+        return slots;
+    };
+    let mut next_slot = 0;
+    for run in allocation.runs() {
+        let (Some(start), Some(end)) = (
+            lvm.physical_extent_offset(run.extents.start),
+            lvm.physical_extent_offset(run.extents.end - 1),
+        ) else {
+            continue;
+        };
+        let (start, end) = (pv_start + start, pv_start + end + lvm.extent_size());
+        match run.lv {
+            None => region(start, end, "Unallocated".to_owned(), None, None),
+            Some(lv) if contiguous_extents(lvm, lv) == Some(run.extents.clone()) => {
+                region(
+                    start,
+                    end,
+                    lv.name().to_owned(),
+                    Some(lv.name()),
+                    Some(next_slot),
+                );
+                next_slot += 1;
+            }
+            Some(lv) => region(
+                start,
+                end,
+                format!(
+                    "{} extents {}-{} (not contiguous)",
+                    lv.name(),
+                    run.extents.start,
+                    run.extents.end - 1
+                ),
+                Some(lv.name()),
+                None,
+            ),
+        }
+    }
+    slots
+}
+
+// the physical extents of `lv` if they are all on the opened PV, in LV order and
+// without gaps, so that it can be read in place
+fn contiguous_extents(lvm: &Lvm2, lv: LV) -> Option<std::ops::Range<u64>> {
+    let mut extents: Option<std::ops::Range<u64>> = None;
+    for (_, seg) in lv.segments() {
+        let Segment::Linear { stripe } = &seg.kind else {
+            return None;
+        };
+        let start = stripe.start_extent;
+        extents = match extents {
+            None if stripe.pv == lvm.pv_name() && seg.start_extent == 0 => {
+                Some(start..start + seg.extent_count)
+            }
+            Some(extents)
+                if stripe.pv == lvm.pv_name()
+                    && seg.start_extent == extents.end - extents.start
+                    && start == extents.end =>
+            {
+                Some(extents.start..start + seg.extent_count)
+            }
+            _ => return None,
+        };
+    }
+    extents.filter(|extents| extents.end - extents.start == lv.size_in_extents())
+}
+
+fn print_tsk_layout(lvm: &Lvm2, slots: &[TskSlot], sector_size: u64) {
+    println!("LVM2 Volume Group {}, PV {}", lvm.vg_name(), lvm.pv_name());
+    println!("Offset Sector: 0");
+    println!("Units are in {}-byte sectors\n", sector_size);
+    println!("      Slot      Start        End          Length       Description");
+    for (i, slot) in slots.iter().enumerate() {
+        let number = slot
+            .slot
+            .map_or_else(|| "-------".to_owned(), |n| format!("{:03}", n));
+        println!(
+            "{:03}:  {:<7}   {:010}   {:010}   {:010}   {}",
+            i,
+            number,
+            slot.start,
+            slot.start + slot.length.max(1) - 1,
+            slot.length,
+            slot.description
+        );
+    }
+}
+
+#[derive(Serialize)]
+struct ThinReport {
+    lv: String,
+    #[serde(flatten)]
+    provisioning: ThinProvisioning,
+    /// Of the provisioned bytes, those no other device of the pool maps, and those
+    /// shared with e.g. snapshots.
+    unique_bytes: u64,
+    shared_bytes: u64,
+}
+
+// every thin LV when none are named (none are hidden); naming any other LV is an error
+fn thin_report<T: Read + Seek>(
+    lvm: &Lvm2,
+    reader: &mut T,
+    lv_names: &[&String],
+) -> Result<Vec<ThinReport>, exhume_lvm::Error> {
+    let lvs =
+        select_lvs(lvm, lv_names, false).map_err(|source| exhume_lvm::Error::Io { source })?;
+    let mut report = Vec::new();
+    let mut pools: Vec<ThinPoolUsage> = Vec::new();
+    for lv in lvs {
+        match lvm.thin_provisioning(lv, reader) {
+            Ok(provisioning) => {
+                // sharing is counted over the whole pool, read once for all its LVs
+                let pool = match pools.iter().position(|p| p.pool == provisioning.pool) {
+                    Some(i) => &pools[i],
+                    None => {
+                        let pool = lvm
+                            .vg()
+                            .lv_by_name(&provisioning.pool)
+                            .ok_or_else(|| io::Error::other("thin pool is missing"))
+                            .map_err(|source| exhume_lvm::Error::Io { source })?;
+                        pools.push(lvm.thin_pool_usage(pool, reader)?);
+                        &pools[pools.len() - 1]
+                    }
+                };
+                let usage = pool
+                    .devices
+                    .iter()
+                    .find(|device| device.device_id == provisioning.device_id);
+                report.push(ThinReport {
+                    lv: lv.name().to_owned(),
+                    unique_bytes: usage.map_or(0, |usage| usage.unique_bytes),
+                    shared_bytes: usage.map_or(0, |usage| usage.shared_bytes),
+                    provisioning,
+                })
+            }
+            Err(exhume_lvm::Error::NotAThinVolume { .. }) if lv_names.is_empty() => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(report)
+}
+
+// the VG generations in the LVM config files at `paths`, each a file or a directory
+// of them; files that cannot be read or parsed are skipped
+fn read_archives(paths: &[&String]) -> Vec<VolumeGroup> {
+    let mut files = Vec::new();
+    for path in paths {
+        match std::fs::read_dir(path) {
+            Ok(entries) => {
+                let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+                entries.sort();
+                files.extend(entries.into_iter().filter(|path| path.is_file()));
+            }
+            Err(_) => files.push(Path::new(path).to_path_buf()),
+        }
+    }
+    let mut generations = Vec::new();
+    for file in files {
+        match std::fs::read_to_string(&file) {
+            Ok(text) => match VolumeGroup::parse_all(&text) {
+                Ok(vgs) => generations.extend(vgs),
+                Err(e) => warn!("Skipping {}: {}", file.display(), e),
+            },
+            Err(e) => warn!("Skipping {}: {}", file.display(), e),
+        }
+    }
+    generations
+}
+
+fn print_history(history: &[MetadataGeneration], changes: bool) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Seqno"),
+        Cell::new("Written"),
+        Cell::new("Source"),
+        Cell::new("Description"),
+        Cell::new("Changes after"),
+    ]));
+    let value = |value: Option<&MetadataValue>| {
+        value.map_or_else(|| "-".to_owned(), |value| value.to_config_text())
+    };
+    for (i, generation) in history.iter().enumerate() {
+        let source = if generation.archived {
+            "archive"
+        } else if i + 1 == history.len() {
+            "current"
+        } else {
+            "metadata area"
+        };
+        let listed = match &generation.changes {
+            None => String::new(),
+            Some(list) if changes => list
+                .iter()
+                .map(|change| {
+                    format!(
+                        "{}: {} -> {}",
+                        change.path,
+                        value(change.old),
+                        value(change.new)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Some(list) => list.len().to_string(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&generation.seqno.to_string()),
+            Cell::new(
+                &generation
+                    .header
+                    .creation_time
+                    .map_or_else(|| "-".to_owned(), |time| time.to_string()),
+            ),
+            Cell::new(source),
+            Cell::new(generation.header.description.as_deref().unwrap_or_default()),
+            Cell::new(&listed),
+        ]));
+    }
+    table.printstd();
+}
+
+// the PV in `reader` described by the LVM config text in `path`, e.g. a file from
+// /etc/lvm/backup or /etc/lvm/archive recovered from the root filesystem
+fn open_with_metadata_file<T: Read + Seek>(
+    reader: &mut T,
+    options: &Lvm2Options,
+    path: &Path,
+) -> Result<Lvm2, exhume_lvm::Error> {
+    let text = std::fs::read_to_string(path).map_err(|source| exhume_lvm::Error::Io { source })?;
+    let vg = VolumeGroup::parse(&text)?;
+    Lvm2::from_parts(Pv::open_with(reader, options)?, vg)
+}
+
+#[derive(Serialize)]
+struct AllocationRun<'a> {
+    start_extent: u64,
+    extent_count: u64,
+    /// `None` if unallocated.
+    lv: Option<&'a str>,
+}
+
+// rows the map is kept to unless --cell says otherwise
+const MAP_ROWS: u64 = 32;
+// text map symbols, one per LV in order of their first extent; later LVs share '*'
+const MAP_SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const MAP_COLORS: &[&str] = &[
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#bcbd22",
+    "#17becf", "#393b79", "#637939", "#8c6d31", "#843c39", "#7b4173",
+];
+const MAP_FREE_COLOR: &str = "#d0d0d0";
+const MAP_MIXED_COLOR: &str = "#404040";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MapCell {
+    Free,
+    Lv(usize),
+    // several LVs, or an LV and unallocated extents
+    Mixed,
+}
+
+struct AllocationMap<'a> {
+    // LVs in order of their first extent, with how many extents each has
+    lvs: Vec<(&'a str, u64)>,
+    free: u64,
+    cells: Vec<MapCell>,
+    cell: u64,
+    columns: u64,
+}
+
+impl<'a> AllocationMap<'a> {
+    fn new(runs: &[ExtentRun<'a>], cell: u64, columns: u64) -> Self {
+        let mut lvs: Vec<(&str, u64)> = Vec::new();
+        let mut free = 0;
+        let mut cells: Vec<Option<MapCell>> = Vec::new();
+        for run in runs {
+            let len = run.extents.end - run.extents.start;
+            let owner = match run.lv {
+                Some(lv) => {
+                    let index = match lvs.iter().position(|&(name, _)| name == lv.name()) {
+                        Some(index) => index,
+                        None => {
+                            lvs.push((lv.name(), 0));
+                            lvs.len() - 1
+                        }
+                    };
+                    lvs[index].1 += len;
+                    MapCell::Lv(index)
+                }
+                None => {
+                    free += len;
+                    MapCell::Free
+                }
+            };
+            let last = (run.extents.end - 1) / cell;
+            cells.resize(last as usize + 1, None);
+            for c in &mut cells[(run.extents.start / cell) as usize..] {
+                *c = match c {
+                    Some(current) if *current != owner => Some(MapCell::Mixed),
+                    _ => Some(owner),
+                };
+            }
+        }
+        Self {
+            lvs,
+            free,
+            cells: cells
+                .into_iter()
+                .map(|c| c.unwrap_or(MapCell::Free))
+                .collect(),
+            cell,
+            columns,
+        }
+    }
+
+    fn extent_count(&self) -> u64 {
+        self.lvs.iter().map(|&(_, n)| n).sum::<u64>() + self.free
+    }
+
+    fn describe(&self, pv: &str, lvm: &Lvm2) -> String {
+        format!(
+            "{} extents of {} in {}, {} per cell",
+            self.extent_count(),
+            pv,
+            lvm.vg_name(),
+            self.cell
+        )
+    }
+
+    fn to_text(&self, pv: &str, lvm: &Lvm2) -> String {
+        let symbol = |index: usize| MAP_SYMBOLS.get(index).map_or('*', |&b| b as char);
+        let last_row = self.cells.len().saturating_sub(1) as u64 / self.columns;
+        let width = (last_row * self.columns * self.cell).to_string().len();
+        let mut out = format!("{}\n", self.describe(pv, lvm));
+        for (row, cells) in self.cells.chunks(self.columns as usize).enumerate() {
+            let line: String = cells
+                .iter()
+                .map(|cell| match cell {
+                    MapCell::Free => '.',
+                    MapCell::Lv(index) => symbol(*index),
+                    MapCell::Mixed => '#',
+                })
+                .collect();
+            let first = row as u64 * self.columns * self.cell;
+            out += &format!("{:>width$} {}\n", first, line);
+        }
+        out += "\n";
+        for (index, (name, extents)) in self.lvs.iter().enumerate() {
+            out += &format!("{} {} ({})\n", symbol(index), name, extents_label(*extents));
+        }
+        out += &format!(". unallocated ({})\n", extents_label(self.free));
+        if self.cells.contains(&MapCell::Mixed) {
+            out += "# cells shared by several LVs or partly unallocated\n";
+        }
+        out
+    }
+
+    fn to_svg(&self, pv: &str, lvm: &Lvm2) -> String {
+        const SIZE: u64 = 10;
+        let color = |cell: &MapCell| match cell {
+            MapCell::Free => MAP_FREE_COLOR,
+            MapCell::Lv(index) => MAP_COLORS[index % MAP_COLORS.len()],
+            MapCell::Mixed => MAP_MIXED_COLOR,
+        };
+        let rows = (self.cells.len() as u64).div_ceil(self.columns);
+        let legend = self.lvs.len() as u64 + 2;
+        let (width, map_height) = (self.columns * SIZE, rows * SIZE);
+        let height = map_height + (legend + 1) * 2 * SIZE;
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             font-family=\"monospace\" font-size=\"{}\">\n<title>{}</title>\n",
+            width.max(40 * SIZE),
+            height,
+            SIZE * 3 / 2,
+            xml_escape(&self.describe(pv, lvm))
+        );
+        for (i, cell) in self.cells.iter().enumerate() {
+            let (row, column) = (i as u64 / self.columns, i as u64 % self.columns);
+            let first = i as u64 * self.cell;
+            let owner = match cell {
+                MapCell::Free => "unallocated",
+                MapCell::Lv(index) => self.lvs[*index].0,
+                MapCell::Mixed => "several",
+            };
+            out += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\">\
+                 <title>{}-{}: {}</title></rect>\n",
+                column * SIZE,
+                row * SIZE,
+                SIZE,
+                SIZE,
+                color(cell),
+                first,
+                (first + self.cell).min(self.extent_count()) - 1,
+                xml_escape(owner)
+            );
+        }
+        let mut entries: Vec<(&str, String)> = self
+            .lvs
+            .iter()
+            .enumerate()
+            .map(|(index, (name, extents))| {
+                (
+                    color(&MapCell::Lv(index)),
+                    format!("{} ({})", name, extents_label(*extents)),
+                )
+            })
+            .collect();
+        entries.push((
+            MAP_FREE_COLOR,
+            format!("unallocated ({})", extents_label(self.free)),
+        ));
+        if self.cells.contains(&MapCell::Mixed) {
+            entries.push((
+                MAP_MIXED_COLOR,
+                "several LVs or partly unallocated".to_owned(),
+            ));
+        }
+        for (i, (color, label)) in entries.iter().enumerate() {
+            let y = map_height + (i as u64 + 1) * 2 * SIZE;
+            out += &format!(
+                "<rect x=\"0\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\
+                 <text x=\"{}\" y=\"{}\">{}</text>\n",
+                y,
+                SIZE,
+                SIZE,
+                color,
+                2 * SIZE,
+                y + SIZE,
+                xml_escape(label)
+            );
+        }
+        out += "</svg>\n";
+        out
+    }
+}
+
+fn extents_label(extents: u64) -> String {
+    match extents {
+        1 => "1 extent".to_owned(),
+        n => format!("{} extents", n),
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Serialize)]
+struct ValidationReport<'a> {
+    lv: &'a str,
+    #[serde(flatten)]
+    validation: LvValidation,
+}
+
+fn print_validation_report(report: &[ValidationReport]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("LV"),
+        Cell::new("Extents"),
+        Cell::new("Covered"),
+        Cell::new("Gaps"),
+        Cell::new("Overlaps"),
+        Cell::new("Unreadable"),
+        Cell::new("Complete"),
+    ]));
+    let runs = |runs: &[std::ops::Range<u64>]| {
+        runs.iter()
+            .map(|run| format!("{}-{}", run.start, run.end - 1))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    for entry in report {
+        let validation = &entry.validation;
+        let unreadable: Vec<String> = validation
+            .unreadable
+            .iter()
+            .map(|unreadable| {
+                let problem = match &unreadable.problem {
+                    SegmentProblem::Unsupported { segment_type } => segment_type.clone(),
+                    SegmentProblem::PvNotAvailable { pv } => format!("on {}", pv),
+                    SegmentProblem::PastEndOfPv { .. } => "past the PV end".to_owned(),
+                };
+                format!("{}: {}", unreadable.segment, problem)
+            })
+            .collect();
+        table.add_row(Row::new(vec![
+            Cell::new(entry.lv),
+            Cell::new(&validation.extent_count.to_string()),
+            Cell::new(&validation.covered_extents.to_string()),
+            Cell::new(&runs(&validation.gaps)),
+            Cell::new(&runs(&validation.overlaps)),
+            Cell::new(&unreadable.join("\n")),
+            Cell::new(if validation.is_complete() {
+                "yes"
+            } else {
+                "NO"
+            }),
+        ]));
+    }
+    table.printstd();
+}
+
+fn print_vg_validation(validation: &VgValidation) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Check"),
+        Cell::new("Result"),
+        Cell::new("Findings"),
+    ]));
+    for result in &validation.checks {
+        let findings: Vec<String> = result
+            .findings
+            .iter()
+            .map(|finding| serde_json::to_string(finding).unwrap_or_default())
+            .collect();
+        table.add_row(Row::new(vec![
+            Cell::new(match result.check {
+                VgCheck::Headers => "headers",
+                VgCheck::Checksums => "checksums",
+                VgCheck::MetadataText => "metadata text",
+                VgCheck::PvSizes => "PV sizes",
+                VgCheck::ExtentAccounting => "extent accounting",
+                VgCheck::SegmentReferences => "segment references",
+                VgCheck::History => "history",
+            }),
+            Cell::new(if result.passed() { "pass" } else { "FAIL" }),
+            Cell::new(&findings.join("\n")),
+        ]));
+    }
+    table.printstd();
+}
+
+#[derive(Serialize)]
+struct CacheReport {
+    lv: String,
+    #[serde(flatten)]
+    cache: CacheMetadata,
+}
+
+// every cached LV when none are named; naming any other LV is an error
+fn cache_report<T: Read + Seek>(
+    lvm: &Lvm2,
+    reader: &mut T,
+    lv_names: &[&String],
+) -> Result<Vec<CacheReport>, exhume_lvm::Error> {
+    let lvs =
+        select_lvs(lvm, lv_names, false).map_err(|source| exhume_lvm::Error::Io { source })?;
+    let mut report = Vec::new();
+    for lv in lvs {
+        match lvm.cache_metadata(lv, reader) {
+            Ok(cache) => report.push(CacheReport {
+                lv: lv.name().to_owned(),
+                cache,
+            }),
+            Err(exhume_lvm::Error::NotACachedVolume { .. }) if lv_names.is_empty() => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(report)
+}
+
+fn print_cache_report(report: &[CacheReport], blocks: bool, units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("LV"),
+        Cell::new("Pool"),
+        Cell::new("Format"),
+        Cell::new("Policy"),
+        Cell::new("Block Size"),
+        Cell::new("Cached"),
+        Cell::new("Dirty"),
+        Cell::new("Clean Shutdown"),
+    ]));
+    for entry in report {
+        let cache = &entry.cache;
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.lv),
+            Cell::new(&cache.pool),
+            Cell::new(&cache.format.to_string()),
+            Cell::new(&cache.policy),
+            Cell::new(&units.size(cache.block_size)),
+            Cell::new(&format!("{}/{}", cache.mappings.len(), cache.cache_blocks)),
+            Cell::new(&cache.dirty().count().to_string()),
+            Cell::new(if cache.clean_shutdown { "yes" } else { "no" }),
+        ]));
+    }
+    table.printstd();
+
+    if !blocks {
+        return;
+    }
+    for entry in report {
+        println!("\n{}:", entry.lv);
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Cache Block"),
+            Cell::new("Origin Offset"),
+            Cell::new("Dirty"),
+        ]));
+        for mapping in &entry.cache.mappings {
+            table.add_row(Row::new(vec![
+                Cell::new(&mapping.cache_block.to_string()),
+                Cell::new(
+                    &units.offset(mapping.origin_block.saturating_mul(entry.cache.block_size)),
+                ),
+                Cell::new(if mapping.dirty { "yes" } else { "" }),
+            ]));
+        }
+        table.printstd();
+    }
+}
+
+#[derive(Serialize)]
+struct DependsReport<'a> {
+    lv: &'a str,
+    requires: Vec<&'a str>,
+    required_by: Vec<&'a str>,
+    pvs: Vec<&'a str>,
+}
+
+fn depends_report<'a>(lvm: &'a Lvm2, lvs: &[LV<'a>]) -> Vec<DependsReport<'a>> {
+    let graph = lvm.vg().lv_graph();
+    lvs.iter()
+        .map(|lv| DependsReport {
+            lv: lv.name(),
+            requires: graph.requires(lv.name()),
+            required_by: graph.required_by(lv.name()),
+            pvs: graph.required_pvs(lv.name()),
+        })
+        .collect()
+}
+
+fn print_depends_report(report: &[DependsReport]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("LV"),
+        Cell::new("Requires"),
+        Cell::new("PVs"),
+        Cell::new("Required By"),
+    ]));
+    for entry in report {
+        table.add_row(Row::new(vec![
+            Cell::new(entry.lv),
+            Cell::new(&entry.requires.join("\n")),
+            Cell::new(&entry.pvs.join("\n")),
+            Cell::new(&entry.required_by.join("\n")),
+        ]));
+    }
+    table.printstd();
+}
+
+#[derive(Serialize)]
+struct TreeNode<'a> {
+    lv: &'a str,
+    /// The segment types, or `missing` for an LV the VG does not have.
+    #[serde(rename = "type")]
+    kind: String,
+    size: u64,
+    hidden: bool,
+    children: Vec<TreeNode<'a>>,
+}
+
+// each LV beneath the one it is part of or built on: components beneath their LV,
+// thin LVs beneath their pool, or their origin if a snapshot, and old-style snapshots
+// beneath their origin, leaving out the hidden snapshotN LVs that pair the two
+fn lv_tree(lvm: &Lvm2, all: bool) -> Vec<TreeNode<'_>> {
+    let graph = lvm.vg().lv_graph();
+    let relations = graph.relations();
+    let related = |lv: &str, role: LvRole| {
+        relations
+            .iter()
+            .find(|relation| relation.lv == lv && relation.role == role)
+            .map(|relation| relation.target)
+    };
+    let mut edges: Vec<(&str, &str)> = Vec::new();
+    for relation in relations {
+        match relation.role {
+            role if role.is_component() => edges.push((relation.lv, relation.target)),
+            LvRole::ThinPool => {
+                let parent = related(relation.lv, LvRole::ThinOrigin)
+                    .filter(|&origin| lvm.vg().lv_by_name(origin).is_some())
+                    .unwrap_or(relation.target);
+                edges.push((parent, relation.lv));
+            }
+            LvRole::SnapshotOrigin => {
+                if let Some(store) = related(relation.lv, LvRole::SnapshotStore) {
+                    edges.push((relation.target, store));
+                }
+            }
+            _ => (),
+        }
+    }
+    let pairing = |lv: &str| related(lv, LvRole::SnapshotOrigin).is_some();
+
+    fn node<'a>(
+        lvm: &'a Lvm2,
+        edges: &[(&'a str, &'a str)],
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> TreeNode<'a> {
+        let lv = lvm.vg().lv_by_name(name);
+        let mut kind: Vec<&str> = Vec::new();
+        for (_, seg) in lv.iter().flat_map(|lv| lv.segments()) {
+            if !kind.contains(&seg.kind.type_name()) {
+                kind.push(seg.kind.type_name());
+            }
+        }
+        path.push(name);
+        let mut children: Vec<TreeNode<'a>> = Vec::new();
+        for &(_, child) in edges.iter().filter(|&&(parent, _)| parent == name) {
+            // metadata naming an LV twice or in a loop is drawn only once
+            if !path.contains(&child) && !children.iter().any(|node| node.lv == child) {
+                children.push(node(lvm, edges, child, path));
+            }
+        }
+        path.pop();
+        TreeNode {
+            lv: name,
+            kind: match lv {
+                Some(_) => kind.join(","),
+                None => "missing".to_owned(),
+            },
+            size: lv.map_or(0, |lv| lv.size_bytes()),
+            hidden: lv.is_some_and(|lv| lv.is_hidden()),
+            children,
+        }
+    }
+    lvm.lvs_all()
+        .filter(|lv| all || !lv.is_hidden())
+        .filter(|lv| !pairing(lv.name()) && !edges.iter().any(|&(_, child)| child == lv.name()))
+        .map(|lv| node(lvm, &edges, lv.name(), &mut Vec::new()))
+        .collect()
+}
+
+fn print_lv_tree(vg: &str, tree: &[TreeNode], units: &Units) {
+    fn lines(node: &TreeNode, prefix: &str, last: bool, out: &mut Vec<[String; 3]>, units: &Units) {
+        let name = if node.hidden {
+            format!("[{}]", node.lv)
+        } else {
+            node.lv.to_owned()
+        };
+        out.push([
+            format!("{}{}{}", prefix, if last { "└─" } else { "├─" }, name),
+            node.kind.clone(),
+            units.size(node.size),
+        ]);
+        let prefix = format!("{}{}", prefix, if last { "  " } else { "│ " });
+        for (i, child) in node.children.iter().enumerate() {
+            lines(child, &prefix, i + 1 == node.children.len(), out, units);
+        }
+    }
+    let mut out = vec![["NAME".to_owned(), "TYPE".to_owned(), "SIZE".to_owned()]];
+    out.push([vg.to_owned(), "vg".to_owned(), String::new()]);
+    for (i, node) in tree.iter().enumerate() {
+        lines(node, "", i + 1 == tree.len(), &mut out, units);
+    }
+    let width = |column: usize| {
+        out.iter()
+            .map(|line| line[column].chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let (name_width, kind_width) = (width(0), width(1));
+    for [name, kind, size] in &out {
+        let pad = name_width - name.chars().count();
+        let line = format!("{}{} {:kind_width$} {}", name, " ".repeat(pad), kind, size);
+        println!("{}", line.trim_end());
+    }
+}
+
+#[derive(Serialize)]
+struct MirrorReport {
+    lv: String,
+    #[serde(flatten)]
+    log: MirrorLog,
+}
+
+// every mirror with a disk log when none are named; naming any other LV is an error
+fn mirror_report<T: Read + Seek>(
+    lvm: &Lvm2,
+    reader: &mut T,
+    lv_names: &[&String],
+) -> Result<Vec<MirrorReport>, exhume_lvm::Error> {
+    let lvs =
+        select_lvs(lvm, lv_names, false).map_err(|source| exhume_lvm::Error::Io { source })?;
+    let core_log = |lv: &LV| {
+        lv.segments()
+            .any(|(_, seg)| matches!(seg.kind, Segment::Mirror { log: None, .. }))
+    };
+    let mut report = Vec::new();
+    for lv in lvs {
+        if lv_names.is_empty() && core_log(&lv) {
+            continue;
+        }
+        match lvm.mirror_log(lv, reader) {
+            Ok(log) => report.push(MirrorReport {
+                lv: lv.name().to_owned(),
+                log,
+            }),
+            Err(exhume_lvm::Error::NotAMirror { .. }) if lv_names.is_empty() => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(report)
+}
+
+fn print_mirror_report(report: &[MirrorReport], regions: bool, units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("LV"),
+        Cell::new("Log"),
+        Cell::new("Region Size"),
+        Cell::new("Regions"),
+        Cell::new("Out of Sync"),
+    ]));
+    for entry in report {
+        let log = &entry.log;
+        let out_of_sync: u64 = log.out_of_sync.iter().map(|r| r.end - r.start).sum();
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.lv),
+            Cell::new(&log.log),
+            Cell::new(&units.size(log.region_size)),
+            Cell::new(&log.regions.to_string()),
+            Cell::new(&out_of_sync.to_string()),
+        ]));
+    }
+    table.printstd();
+
+    if !regions {
+        return;
+    }
+    for entry in report {
+        println!("\n{}:", entry.lv);
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Regions"),
+            Cell::new("Offset"),
+            Cell::new("Size"),
+        ]));
+        for (regions, bytes) in entry
+            .log
+            .out_of_sync
+            .iter()
+            .zip(entry.log.out_of_sync_bytes())
+        {
+            table.add_row(Row::new(vec![
+                Cell::new(&format!("{}-{}", regions.start, regions.end - 1)),
+                Cell::new(&units.offset(bytes.start)),
+                Cell::new(&units.size(bytes.end - bytes.start)),
+            ]));
+        }
+        table.printstd();
+    }
+}
+
+fn print_thin_report(report: &[ThinReport], ranges: bool, units: &Units) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("LV"),
+        Cell::new("Pool"),
+        Cell::new("Device ID"),
+        Cell::new("Virtual Size"),
+        Cell::new("Provisioned"),
+        Cell::new("Used"),
+        Cell::new("Unique"),
+        Cell::new("Shared"),
+    ]));
+    for thin in report {
+        let provisioning = &thin.provisioning;
+        let provisioned = provisioning.provisioned_bytes();
+        table.add_row(Row::new(vec![
+            Cell::new(&thin.lv),
+            Cell::new(&provisioning.pool),
+            Cell::new(&provisioning.device_id.to_string()),
+            Cell::new(&units.size(provisioning.virtual_size)),
+            Cell::new(&units.size(provisioned)),
+            Cell::new(&format!(
+                "{:.1}%",
+                100.0 * provisioned as f64 / provisioning.virtual_size.max(1) as f64
+            )),
+            Cell::new(&units.size(thin.unique_bytes)),
+            Cell::new(&units.size(thin.shared_bytes)),
+        ]));
+    }
+    table.printstd();
+
+    if !ranges {
+        return;
+    }
+    for thin in report {
+        println!("\n{}:", thin.lv);
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("LV Offset"),
+            Cell::new("Data Offset"),
+            Cell::new("Length"),
+        ]));
+        for run in &thin.provisioning.provisioned {
+            table.add_row(Row::new(vec![
+                Cell::new(&units.offset(run.lv_offset)),
+                Cell::new(&units.offset(run.data_offset)),
+                Cell::new(&units.size(run.len)),
+            ]));
+        }
+        table.printstd();
+    }
+}
+
+#[cfg(feature = "partitions")]
+const LVM_PARTITION_GUID: &str = "e6d6d379-f507-44c2-a23c-238f2a3df928";
+
+// offset and size in bytes of an LVM partition of the body's MBR, EBR or GPT
+#[cfg(feature = "partitions")]
+fn find_lvm_partition(body: &mut Body, index: Option<usize>) -> Result<(u64, u64), String> {
+    use exhume_partitions::Partitions;
+
+    let sector_size = body.get_sector_size() as u64;
+    let partitions = Partitions::new(body).map_err(|e| e.to_string())?;
+    let mut found: Vec<(u64, u64)> = partitions
+        .mbr
+        .iter()
+        .chain(partitions.ebr.iter().flatten())
+        .flat_map(|mbr| &mbr.partition_table)
+        .filter(|entry| entry.partition_type == 0x8e && entry.size_sectors > 0)
+        .map(|entry| {
+            (
+                u64::from(entry.start_lba) * sector_size,
+                u64::from(entry.size_sectors) * sector_size,
+            )
+        })
+        .collect();
+    if let Some(gpt) = &partitions.gpt {
+        found.extend(
+            gpt.partition_entries
+                .iter()
+                .filter(|entry| {
+                    entry
+                        .partition_type_guid_string
+                        .eq_ignore_ascii_case(LVM_PARTITION_GUID)
+                })
+                .map(|entry| {
+                    (
+                        entry.starting_lba * sector_size,
+                        entry.size_sectors * sector_size,
+                    )
+                }),
+        );
+    }
+    // a hybrid MBR lists GPT partitions a second time
+    found.sort_unstable();
+    found.dedup();
+
+    let describe = |(i, (offset, size)): (usize, &(u64, u64))| {
+        format!("{}: {} bytes at {:#x}", i + 1, size, offset)
+    };
+    match (index, found.as_slice()) {
+        (_, []) => Err("No LVM partition in the partition table, pass --offset and --size".into()),
+        (None, [partition]) => {
+            info!("Opening the LVM partition at {:#x}", partition.0);
+            Ok(*partition)
+        }
+        (None, _) => Err(format!(
+            "Several LVM partitions found, pick one with --partition: {}",
+            found
+                .iter()
+                .enumerate()
+                .map(describe)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        (Some(index), _) => index
+            .checked_sub(1)
+            .and_then(|i| found.get(i))
+            .copied()
+            .ok_or_else(|| format!("No LVM partition {}, found {}", index, found.len())),
+    }
+}
+
+fn print_snapshot_delta(delta: &SnapshotDelta, units: &Units) {
+    if !delta.valid {
+        warn!("The snapshot was invalidated, its COW store may be incomplete");
+    }
+    if delta.merging {
+        warn!("The snapshot is being merged into its origin, only the chunks not merged yet are listed");
+    }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Offset"),
+        Cell::new("Length"),
+        Cell::new("Chunks"),
+    ]));
+    for range in &delta.changed {
+        table.add_row(Row::new(vec![
+            Cell::new(&units.offset(range.start)),
+            Cell::new(&units.size(range.end - range.start)),
+            Cell::new(&format!(
+                "{}-{}",
+                range.start / delta.chunk_size,
+                range.end.div_ceil(delta.chunk_size) - 1
+            )),
+        ]));
+    }
+    table.printstd();
+}
+
+fn extract_slack<T: Read + Seek>(lvm: &Lvm2, reader: &mut T, output: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(output)?;
+    let pv = lvm.pv();
+
+    let ranges = pv.metadata_slack();
+    if let Some(mda) = pv.metadata_area_index().filter(|_| !ranges.is_empty()) {
+        let slack = pv.read_metadata_slack(reader).map_err(io::Error::other)?;
+        let path = output.join(format!("mda{}_slack.raw", mda));
+        std::fs::write(&path, &slack)?;
+        let ranges: Vec<String> = ranges
+            .iter()
+            .map(|range| format!("{:#x}-{:#x}", range.start, range.end))
+            .collect();
+        info!(
+            "Extracted metadata area slack {} ({} bytes) to '{}'",
+            ranges.join(", "),
+            slack.len(),
+            path.display()
+        );
+    }
+
+    match pv.pre_data_slack() {
+        Some(range) => {
+            let slack = pv.read_pre_data_slack(reader).map_err(io::Error::other)?;
+            let path = output.join("pre_data_slack.raw");
+            std::fs::write(&path, &slack)?;
+            info!(
+                "Extracted slack {:#x}-{:#x} ({} bytes) to '{}'",
+                range.start,
+                range.end,
+                slack.len(),
+                path.display()
+            );
+        }
+        None => info!("No slack between the metadata area and the first physical extent"),
+    }
+    Ok(())
+}
+
+// how the LV listing is sorted; LVs that compare equal stay in name order
+struct LvOrder<'a> {
+    key: &'a str,
+    descending: bool,
+}
+impl LvOrder<'_> {
+    // each side by its name, size in bytes and description
+    fn compare(&self, a: (&str, u64, &LVDesc), b: (&str, u64, &LVDesc)) -> Ordering {
+        let ordering = match self.key {
+            "size" => a.1.cmp(&b.1),
+            "created" => a.2.creation_time.cmp(&b.2.creation_time),
+            "segments" => a.2.segments.0.len().cmp(&b.2.segments.0.len()),
+            _ => a.0.cmp(b.0),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+// the names `--options` takes, each the key of the same field in its JSON output
+const LV_FIELDS: [&str; 18] = [
+    "lv_name",
+    "lv_uuid",
+    "lv_size",
+    "lv_extents",
+    "lv_time",
+    "lv_host",
+    "lv_status",
+    "lv_flags",
+    "lv_tags",
+    "lv_hidden",
+    "lv_allocation_policy",
+    "lv_read_ahead",
+    "lv_parent",
+    "seg_count",
+    "seg_type",
+    "devices",
+    "vg_name",
+    "vg_uuid",
+];
+
+fn lv_field(lvm: &Lvm2, lv: &LV, field: &str) -> serde_json::Value {
+    let desc = lv.raw_metadata();
+    match field {
+        "lv_name" => lv.name().into(),
+        "lv_uuid" => lv.id().into(),
+        "lv_size" => lv.size_bytes().into(),
+        "lv_extents" => lv.size_in_extents().into(),
+        "lv_time" => desc.creation_time.as_unix().into(),
+        "lv_host" => desc.creation_host.as_str().into(),
+        "lv_status" => desc.status.clone().into(),
+        "lv_flags" => desc.flags.clone().into(),
+        "lv_tags" => desc.tags.clone().into(),
+        "lv_hidden" => lv.is_hidden().into(),
+        "lv_allocation_policy" => desc
+            .allocation_policy
+            .as_ref()
+            .map_or("inherit", AllocationPolicy::as_str)
+            .into(),
+        "lv_read_ahead" => match desc.read_ahead {
+            None => "auto".into(),
+            Some(sheets) if sheets < 0 => "none".into(),
+            Some(sheets) => (sheets as u64 * 512).into(),
+        },
+        "lv_parent" => lvm
+            .vg()
+            .component_of(lv.name())
+            .map_or("", |relation| relation.lv)
+            .into(),
+        "seg_count" => desc.segments.0.len().into(),
+        "seg_type" => lv
+            .segments()
+            .map(|(_, seg)| seg.kind.type_name().to_owned())
+            .collect::<Vec<_>>()
+            .into(),
+        // as `lvs -o devices` shows them: each PV or LV mapped, at its first extent
+        "devices" => lv
+            .segments()
+            .flat_map(|(_, seg)| {
+                let stripes = seg
+                    .stripes()
+                    .iter()
+                    .map(|stripe| format!("{}({})", stripe.pv, stripe.start_extent));
+                // the LVs holding the data, not metadata or origins
+                let lvs = match &seg.kind {
+                    Segment::Thin { pool, .. } | Segment::ThinPool { pool, .. } => vec![(pool, 0)],
+                    Segment::Cache { origin, .. } => vec![(origin, 0)],
+                    Segment::CachePool { data, .. } => vec![(data, 0)],
+                    Segment::Snapshot { cow_store, .. } => vec![(cow_store, 0)],
+                    Segment::Mirror { images, .. } => images
+                        .iter()
+                        .map(|image| (&image.name, image.start_extent))
+                        .collect(),
+                    Segment::Raid0 { images, .. }
+                    | Segment::Raid1 { images, .. }
+                    | Segment::Raid { images, .. } => images.iter().map(|lv| (lv, 0)).collect(),
+                    _ => Vec::new(),
+                };
+                let lvs = lvs
+                    .into_iter()
+                    .map(|(lv, start)| format!("{}({})", lv, start));
+                stripes.chain(lvs).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+            .into(),
+        "vg_name" => lvm.vg_name().into(),
+        "vg_uuid" => lvm.vg_id().into(),
+        _ => serde_json::Value::Null,
+    }
+}
+
+// the fields of one LV as a JSON object, in the order they were asked for
+struct FieldRow<'a>(Vec<(&'a str, serde_json::Value)>);
+impl Serialize for FieldRow<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (field, value) in &self.0 {
+            map.serialize_entry(field, value)?;
+        }
+        map.end()
+    }
+}
+
+// the LVs listed, hidden ones only with `--all`, in `--sort` order
+fn sorted_lvs<'a>(lvm: &'a Lvm2, all: bool, order: &LvOrder) -> Vec<LV<'a>> {
+    let mut lvs: Vec<LV> = lvm.lvs_all().filter(|lv| all || !lv.is_hidden()).collect();
+    lvs.sort_by(|a, b| {
+        order.compare(
+            (a.name(), a.size_bytes(), a.raw_metadata()),
+            (b.name(), b.size_bytes(), b.raw_metadata()),
+        )
+    });
+    lvs
+}
+
+fn print_lv_fields(
+    lvm: &Lvm2,
+    all: bool,
+    order: &LvOrder,
+    fields: &[&String],
+    json: Option<JsonOutput>,
+    units: &Units,
+) {
+    let lvs = sorted_lvs(lvm, all, order);
+    let row = |lv: &LV| {
+        FieldRow(
+            fields
+                .iter()
+                .map(|&field| (field.as_str(), lv_field(lvm, lv, field)))
+                .collect(),
+        )
+    };
+    if json == Some(JsonOutput::Lines) {
+        or_exit(
+            write_json_lines(lvs.iter().map(row)),
+            "Error writing LV fields",
+        );
+        return;
+    }
+    if json.is_some() {
+        let rows: Vec<FieldRow> = lvs.iter().map(row).collect();
+        print_json(&rows, "LV fields");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.add_row(fields.iter().map(|field| Cell::new(field)).collect());
+    for lv in &lvs {
+        let cells = fields.iter().map(|&field| {
+            let text = match (field.as_str(), lv_field(lvm, lv, field)) {
+                ("lv_time", _) => lv.raw_metadata().creation_time.to_string(),
+                ("lv_size", _) => units.size(lv.size_bytes()),
+                ("lv_read_ahead", serde_json::Value::Number(bytes)) => {
+                    units.size(bytes.as_u64().unwrap_or_default())
+                }
+                (_, serde_json::Value::String(s)) => s,
+                (_, serde_json::Value::Array(items)) => items
+                    .iter()
+                    .map(|item| {
+                        item.as_str()
+                            .map_or_else(|| item.to_string(), str::to_owned)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+                (_, value) => value.to_string(),
+            };
+            Cell::new(&text)
+        });
+        table.add_row(cells.collect());
+    }
+    table.printstd()
+}
+
+// stripes on the opened PV also give where their data lies in the body
+fn print_lvm_info(lvm: &Lvm2, partition_offset: u64, all: bool, order: &LvOrder, units: &Units) {
+    let mut table = Table::new();
+
+    // Header row.
+    table.add_row(Row::new(vec![
+        Cell::new("Physical Volume"),
+        Cell::new("Volume Group"),
+        Cell::new("Logical Volume"),
+        Cell::new("Segment"),
+    ]));
+
+    let usage = lvm.vg().usage();
+    let mut pv_info = format!("Name: {}\nID: {}", lvm.pv_name(), lvm.pv_id());
+    if let Some(pv) = usage.pvs.iter().find(|pv| pv.pv == lvm.pv_name()) {
+        pv_info += &format!(
+            "\nSize: {}\nFree: {}",
+            units.size(pv.size_bytes),
+            units.size(pv.free_bytes)
+        );
+    }
+    if let Some(area) = lvm.bootloader_area() {
+        pv_info += &format!(
+            "\nBootloader Area: {} +{}",
+            units.offset(partition_offset + lvm.start_offset() + area.start),
+            units.size(area.end - area.start)
+        );
+    }
+    let config = lvm.vg().config();
+    let vg_info = format!(
+        "Name: {}\nID: {}\nExtent Size: {}\nPVs: {}\nSize: {}\nFree: {}\nAllocation: {}",
+        lvm.vg_name(),
+        lvm.vg_id(),
+        units.size(lvm.extent_size()),
+        usage.pvs.len(),
+        units.size(usage.size_bytes),
+        units.size(usage.free_bytes),
+        config
+            .allocation_policy
+            .as_ref()
+            .map_or("normal", AllocationPolicy::as_str)
+    );
+
+    let lvs = sorted_lvs(lvm, all, order);
+    let merges = lvm.vg().snapshot_merges();
+    for lv in lvs {
+        // in brackets, as `lvs -a` shows them
+        let name = if lv.is_hidden() {
+            format!("[{}]", lv.name())
+        } else {
+            lv.name().to_owned()
+        };
+        let desc = lv.raw_metadata();
+        let mut lv_info = format!(
+            "Name: {}\nID: {}\nSize (extents): {}\nSize{}: {}\nAllocation: {}\nRead Ahead: {}",
+            name,
+            lv.id(),
+            lv.size_in_extents(),
+            if units.unit.is_some() { "" } else { " (bytes)" },
+            units.size(lv.size_bytes()),
+            desc.allocation_policy
+                .as_ref()
+                .map_or("inherit", AllocationPolicy::as_str),
+            match desc.read_ahead {
+                None => "auto".to_owned(),
+                Some(sheets) if sheets < 0 => "none".to_owned(),
+                Some(sheets) => units.size(sheets as u64 * 512),
+            }
+        );
+        for merge in merges.iter().filter(|merge| merge.origin == lv.name()) {
+            lv_info.push_str(&format!("\nMerging: {}", merge.snapshot));
+        }
+        if let Some(relation) = lvm.vg().component_of(lv.name()) {
+            lv_info.push_str(&format!(
+                "\nPart of: {} ({})",
+                relation.lv,
+                relation.role.as_str()
+            ));
+        }
+        if lv.raw_metadata().segments.0.is_empty() {
+            table.add_row(Row::new(vec![
+                Cell::new(&pv_info),
+                Cell::new(&vg_info),
+                Cell::new(&lv_info),
+                Cell::new("No segments"),
+            ]));
+        } else {
+            for (seg_key, seg) in lv.segments() {
+                let stripe_size = match &seg.kind {
+                    Segment::Striped { stripe_size, .. }
+                    | Segment::Raid0 { stripe_size, .. }
+                    | Segment::Raid { stripe_size, .. } => *stripe_size,
+                    _ => None,
+                };
+                let mut seg_info = format!(
+                    "Key: {}\nStart: {}\nCount: {}\nType: {}\nStripe Count: {}\nStripe Size: {}",
+                    seg_key,
+                    seg.start_extent,
+                    seg.extent_count,
+                    seg.kind.type_name(),
+                    match seg.stripes().len() {
+                        0 => "-".to_owned(),
+                        n => n.to_string(),
+                    },
+                    // in sheets in the metadata
+                    stripe_size
+                        .map(|sheets| units.size(sheets.saturating_mul(512)))
+                        .unwrap_or_else(|| "-".to_owned()),
+                );
+                if seg.is_reshaping() {
+                    seg_info.push_str(&format!("\nReshaping: {}", seg.type_flags.join(", ")));
+                }
+                let stripe_len = seg.area_len() * lvm.extent_size();
+                for stripe in seg.stripes() {
+                    let image_offset = (stripe.pv == lvm.pv_name())
+                        .then(|| lvm.physical_extent_offset(stripe.start_extent))
+                        .flatten()
+                        .map(|offset| partition_offset + lvm.start_offset() + offset);
+                    seg_info.push_str(&format!(
+                        "\n{}({}): {} +{}",
+                        stripe.pv,
+                        stripe.start_extent,
+                        image_offset.map_or_else(|| "-".to_owned(), |o| units.offset(o)),
+                        units.size(stripe_len)
+                    ));
+                }
+                table.add_row(Row::new(vec![
+                    Cell::new(&pv_info),
+                    Cell::new(&vg_info),
+                    Cell::new(&lv_info),
+                    Cell::new(&seg_info),
+                ]));
+            }
+        }
+    }
+    table.printstd()
+}
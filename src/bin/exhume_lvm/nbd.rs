@@ -0,0 +1,236 @@
+// nbd.rs
+// The fixed newstyle handshake and simple replies of
+// https://github.com/NetworkBlockDevice/nbd/blob/master/doc/proto.md, for a single
+// read-only export.
+use exhume_lvm::{Lvm2, LV};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use tracing::{info, warn};
+
+const NBDMAGIC: u64 = 0x4e42_444d_4147_4943;
+const IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+const REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const REQUEST_MAGIC: u32 = 0x2560_9513;
+const SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const FLAG_NO_ZEROES: u16 = 1 << 1;
+const FLAG_HAS_FLAGS: u16 = 1 << 0;
+const FLAG_READ_ONLY: u16 = 1 << 1;
+
+const OPT_EXPORT_NAME: u32 = 1;
+const OPT_ABORT: u32 = 2;
+const OPT_LIST: u32 = 3;
+const OPT_INFO: u32 = 6;
+const OPT_GO: u32 = 7;
+const REP_ACK: u32 = 1;
+const REP_SERVER: u32 = 2;
+const REP_INFO: u32 = 3;
+const REP_ERR_UNSUP: u32 = (1 << 31) + 1;
+const REP_ERR_INVALID: u32 = (1 << 31) + 3;
+const REP_ERR_UNKNOWN: u32 = (1 << 31) + 6;
+const INFO_EXPORT: u16 = 0;
+
+const CMD_READ: u16 = 0;
+const CMD_WRITE: u16 = 1;
+const CMD_DISC: u16 = 2;
+const EPERM: u32 = 1;
+const EIO: u32 = 5;
+const EINVAL: u32 = 22;
+// larger requests are refused rather than buffered
+const MAX_REQUEST: u32 = 32 << 20;
+
+/// Serves each client on its own thread, through its own clone of `reader`, until
+/// the process is stopped.
+pub fn serve<T: Read + Seek + Clone + Send>(
+    lvm: &Lvm2,
+    lv: LV,
+    reader: &T,
+    listen: &str,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(listen)?;
+    info!(
+        "Exporting LV '{}' read-only on {}, e.g. `nbd-client -N {} <host> /dev/nbd0`",
+        lv.name(),
+        listener.local_addr()?,
+        lv.name()
+    );
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            // a client failing to connect does not stop the others
+            let (mut stream, peer) = match stream.and_then(|s| s.peer_addr().map(|p| (s, p))) {
+                Ok(connected) => connected,
+                Err(e) => {
+                    warn!("NBD connection failed: {}", e);
+                    continue;
+                }
+            };
+            let mut reader = reader.clone();
+            info!("NBD client {} connected", peer);
+            scope.spawn(move || match session(lvm, lv, &mut reader, &mut stream) {
+                Ok(()) => info!("NBD client {} disconnected", peer),
+                Err(e) => warn!("NBD client {} dropped: {}", peer, e),
+            });
+        }
+        Ok(())
+    })
+}
+
+fn session<T: Read + Seek>(
+    lvm: &Lvm2,
+    lv: LV,
+    reader: &mut T,
+    stream: &mut TcpStream,
+) -> io::Result<()> {
+    let size = lv.size_bytes();
+    let transmission_flags = FLAG_HAS_FLAGS | FLAG_READ_ONLY;
+
+    let mut greeting = NBDMAGIC.to_be_bytes().to_vec();
+    greeting.extend_from_slice(&IHAVEOPT.to_be_bytes());
+    greeting.extend_from_slice(&(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).to_be_bytes());
+    stream.write_all(&greeting)?;
+    let client_flags = read_u32(stream)?;
+    let no_zeroes = client_flags & u32::from(FLAG_NO_ZEROES) != 0;
+
+    loop {
+        if read_u64(stream)? != IHAVEOPT {
+            return Err(io::Error::other("bad option magic"));
+        }
+        let option = read_u32(stream)?;
+        let len = read_u32(stream)?;
+        if len > 4096 {
+            return Err(io::Error::other("option data too long"));
+        }
+        let mut data = vec![0; len as usize];
+        stream.read_exact(&mut data)?;
+        match option {
+            // the old way in: no reply header, and no way to refuse an unknown name
+            OPT_EXPORT_NAME => {
+                let mut export = size.to_be_bytes().to_vec();
+                export.extend_from_slice(&transmission_flags.to_be_bytes());
+                if !no_zeroes {
+                    export.extend_from_slice(&[0; 124]);
+                }
+                stream.write_all(&export)?;
+                break;
+            }
+            OPT_ABORT => {
+                option_reply(stream, option, REP_ACK, &[])?;
+                return Ok(());
+            }
+            OPT_LIST => {
+                let name = lv.name().as_bytes();
+                let mut server = (name.len() as u32).to_be_bytes().to_vec();
+                server.extend_from_slice(name);
+                option_reply(stream, option, REP_SERVER, &server)?;
+                option_reply(stream, option, REP_ACK, &[])?;
+            }
+            OPT_INFO | OPT_GO => {
+                let Some(name) = requested_export(&data) else {
+                    option_reply(stream, option, REP_ERR_INVALID, &[])?;
+                    continue;
+                };
+                // the empty name asks for the default export
+                if !name.is_empty() && name != lv.name().as_bytes() {
+                    option_reply(stream, option, REP_ERR_UNKNOWN, &[])?;
+                    continue;
+                }
+                let mut info = INFO_EXPORT.to_be_bytes().to_vec();
+                info.extend_from_slice(&size.to_be_bytes());
+                info.extend_from_slice(&transmission_flags.to_be_bytes());
+                option_reply(stream, option, REP_INFO, &info)?;
+                option_reply(stream, option, REP_ACK, &[])?;
+                if option == OPT_GO {
+                    break;
+                }
+            }
+            _ => option_reply(stream, option, REP_ERR_UNSUP, &[])?,
+        }
+    }
+
+    let mut open = lvm.open_lv(lv, reader);
+    let mut buf = Vec::new();
+    loop {
+        let mut request = [0; 28];
+        stream.read_exact(&mut request)?;
+        let field = |range: std::ops::Range<usize>| {
+            request[range]
+                .iter()
+                .fold(0u64, |n, &b| (n << 8) | u64::from(b))
+        };
+        if field(0..4) as u32 != REQUEST_MAGIC {
+            return Err(io::Error::other("bad request magic"));
+        }
+        let command = field(6..8) as u16;
+        let handle = field(8..16);
+        let offset = field(16..24);
+        let len = field(24..28) as u32;
+
+        let error = match command {
+            CMD_DISC => return Ok(()),
+            CMD_READ if len > MAX_REQUEST || offset.saturating_add(len.into()) > size => EINVAL,
+            CMD_READ => {
+                buf.resize(len as usize, 0);
+                match open
+                    .seek(SeekFrom::Start(offset))
+                    .and_then(|_| open.read_exact(&mut buf))
+                {
+                    Ok(()) => {
+                        simple_reply(stream, 0, handle, &buf)?;
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Error reading LV '{}' at {:#x}: {}", lv.name(), offset, e);
+                        EIO
+                    }
+                }
+            }
+            // the payload still has to be consumed to stay in sync
+            CMD_WRITE => {
+                io::copy(&mut (&mut *stream).take(len.into()), &mut io::sink())?;
+                EPERM
+            }
+            _ => EINVAL,
+        };
+        simple_reply(stream, error, handle, &[])?;
+    }
+}
+
+// the export name of an NBD_OPT_INFO or NBD_OPT_GO request
+fn requested_export(data: &[u8]) -> Option<&[u8]> {
+    let len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    data.get(4..4 + len)
+}
+
+// replies go out in one write each, so Nagle's algorithm does not hold them back
+fn option_reply(stream: &mut TcpStream, option: u32, reply: u32, data: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(20 + data.len());
+    message.extend_from_slice(&REPLY_MAGIC.to_be_bytes());
+    message.extend_from_slice(&option.to_be_bytes());
+    message.extend_from_slice(&reply.to_be_bytes());
+    message.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    message.extend_from_slice(data);
+    stream.write_all(&message)
+}
+
+fn simple_reply(stream: &mut TcpStream, error: u32, handle: u64, data: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(16 + data.len());
+    message.extend_from_slice(&SIMPLE_REPLY_MAGIC.to_be_bytes());
+    message.extend_from_slice(&error.to_be_bytes());
+    message.extend_from_slice(&handle.to_be_bytes());
+    message.extend_from_slice(data);
+    stream.write_all(&message)
+}
+
+fn read_u32(stream: &mut TcpStream) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(stream: &mut TcpStream) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    stream.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
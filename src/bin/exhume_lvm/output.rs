@@ -0,0 +1,91 @@
+// output.rs
+// how the subcommands print: JSON documents and lines, sizes and offsets in
+// tables, and the error that ends the run
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::process;
+use tracing::error;
+
+// `--json` prints one document, `--json-lines` one object per line as each is made
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum JsonOutput {
+    Document,
+    Lines,
+}
+
+pub(crate) fn write_json_lines<T: Serialize>(items: impl IntoIterator<Item = T>) -> io::Result<()> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for item in items {
+        serde_json::to_writer(&mut out, &item).map_err(io::Error::other)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+// `what` names the value in the error if it cannot be serialized
+pub(crate) fn print_json<T: Serialize + ?Sized>(value: &T, what: &str) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => fail(format_args!("Error serializing {}: {}", what, e)),
+    }
+}
+
+// logs `message` and exits with status 1
+pub(crate) fn fail(message: impl Display) -> ! {
+    error!("{}", message);
+    process::exit(1);
+}
+
+// the value, or the error after `what` and a colon, as `fail` reports it
+pub(crate) fn or_exit<T, E: Display>(result: Result<T, E>, what: &str) -> T {
+    result.unwrap_or_else(|e| fail(format_args!("{}: {}", what, e)))
+}
+
+// `--units`: how tables show sizes and offsets. Without it sizes are in bytes and
+// offsets in hex.
+#[derive(Clone, Copy)]
+pub(crate) struct Units<'a> {
+    pub(crate) unit: Option<&'a str>,
+    // bytes per extent, unknown until the VG is read; bytes are shown instead
+    pub(crate) extent_size: Option<u64>,
+}
+impl Units<'_> {
+    pub(crate) fn size(&self, bytes: u64) -> String {
+        match self.unit {
+            Some(unit) => self.convert(bytes, unit),
+            None => bytes.to_string(),
+        }
+    }
+
+    pub(crate) fn offset(&self, bytes: u64) -> String {
+        match self.unit {
+            Some(unit) => self.convert(bytes, unit),
+            None => format!("{:#x}", bytes),
+        }
+    }
+
+    pub(crate) fn range(&self, range: &std::ops::Range<u64>) -> String {
+        format!("{}-{}", self.offset(range.start), self.offset(range.end))
+    }
+
+    // whole amounts of B, S and e as integers, the rest with two decimals like lvs
+    fn convert(&self, bytes: u64, unit: &str) -> String {
+        let (divisor, suffix) = match (unit, self.extent_size) {
+            ("s", _) => (512, "S"),
+            ("k", _) => (1 << 10, "k"),
+            ("m", _) => (1 << 20, "m"),
+            ("g", _) => (1 << 30, "g"),
+            ("t", _) => (1 << 40, "t"),
+            ("e", Some(extent_size)) => (extent_size.max(1), "e"),
+            _ => (1, "B"),
+        };
+        if bytes == 0 {
+            "0".to_owned()
+        } else if matches!(suffix, "B" | "S" | "e") && bytes.is_multiple_of(divisor) {
+            format!("{}{}", bytes / divisor, suffix)
+        } else {
+            format!("{:.2}{}", bytes as f64 / divisor as f64, suffix)
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// uring.rs
+use io_uring::{opcode, types, IoUring};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+const CHUNK: usize = 256 << 10;
+// also the ring size, so a submission never finds the queue full
+const MAX_IN_FLIGHT: usize = 32;
+
+/// `size` bytes of a raw image from `start` on, read through io_uring. Reads that
+/// carry on where the last one stopped are served from a readahead window of
+/// chunks kept in flight, which doubles up to [`MAX_IN_FLIGHT`] while the access
+/// stays sequential. A read of a chunk or more elsewhere is split into chunks
+/// submitted together, straight into the caller's buffer.
+pub struct UringSlice {
+    ring: IoUring,
+    file: File,
+    start: u64,
+    size: u64,
+    position: u64,
+    // in slice order and contiguous, the first one holding `position`
+    chunks: VecDeque<Chunk>,
+    window: usize,
+    in_flight: usize,
+}
+
+struct Chunk {
+    offset: u64,
+    // the kernel writes here until the chunk's completion is reaped
+    buf: Box<[u8]>,
+    state: ChunkState,
+}
+
+enum ChunkState {
+    InFlight,
+    Done(usize),
+    Failed(i32),
+}
+
+impl UringSlice {
+    pub fn open(path: &Path, start: u64, size: u64) -> io::Result<Self> {
+        Ok(UringSlice {
+            ring: IoUring::new(MAX_IN_FLIGHT as u32)?,
+            file: File::open(path)?,
+            start,
+            size,
+            position: 0,
+            chunks: VecDeque::new(),
+            window: 1,
+            in_flight: 0,
+        })
+    }
+
+    // SAFETY: `buf` must stay valid and unused until the entry's completion is reaped
+    unsafe fn push(&mut self, offset: u64, buf: *mut u8, len: usize) -> io::Result<()> {
+        let entry = opcode::Read::new(types::Fd(self.file.as_raw_fd()), buf, len as u32)
+            .offset(self.start + offset)
+            .build()
+            .user_data(offset);
+        self.ring
+            .submission()
+            .push(&entry)
+            .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    fn submit_and_wait(&mut self, want: usize) -> io::Result<()> {
+        loop {
+            match self.ring.submit_and_wait(want) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result.map(|_| ()),
+            }
+        }
+    }
+
+    // keeps `window` chunks queued from the first one on
+    fn fill(&mut self) -> io::Result<()> {
+        let mut pushed = false;
+        while self.chunks.len() < self.window {
+            let offset = self
+                .chunks
+                .back()
+                .map_or(self.position, |chunk| chunk.offset + chunk.buf.len() as u64);
+            if offset >= self.size {
+                break;
+            }
+            let len = (self.size - offset).min(CHUNK as u64) as usize;
+            let mut buf = vec![0u8; len].into_boxed_slice();
+            // SAFETY: the chunk owns the buffer and is only dropped once reaped
+            unsafe { self.push(offset, buf.as_mut_ptr(), len)? };
+            self.chunks.push_back(Chunk {
+                offset,
+                buf,
+                state: ChunkState::InFlight,
+            });
+            pushed = true;
+        }
+        if pushed {
+            self.ring.submit()?;
+        }
+        Ok(())
+    }
+
+    // waits for at least `want` completions and files them under their chunks
+    fn reap(&mut self, want: usize) -> io::Result<()> {
+        self.submit_and_wait(want)?;
+        for entry in self.ring.completion() {
+            self.in_flight -= 1;
+            let state = match entry.result() {
+                errno if errno < 0 => ChunkState::Failed(-errno),
+                n => ChunkState::Done(n as usize),
+            };
+            if let Some(chunk) = self
+                .chunks
+                .iter_mut()
+                .find(|chunk| chunk.offset == entry.user_data())
+            {
+                chunk.state = state;
+            }
+        }
+        Ok(())
+    }
+
+    // drops the readahead window once the kernel is done with it
+    fn drain(&mut self) -> io::Result<()> {
+        while self.in_flight > 0 {
+            self.reap(self.in_flight)?;
+        }
+        self.chunks.clear();
+        self.window = 1;
+        Ok(())
+    }
+
+    // one batch of chunk-sized reads into `buf`, stopping at the first short one
+    fn read_direct(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pieces: Vec<(u64, usize)> = buf
+            .chunks(CHUNK)
+            .take(MAX_IN_FLIGHT)
+            .scan(self.position, |offset, piece| {
+                let read = (*offset, piece.len());
+                *offset += piece.len() as u64;
+                Some(read)
+            })
+            .collect();
+        let mut pushed = 0;
+        let mut pushing = Ok(());
+        for (i, &(offset, len)) in pieces.iter().enumerate() {
+            // SAFETY: `buf` outlives this call, which reaps every read it pushed
+            pushing = unsafe { self.push(offset, buf[i * CHUNK..].as_mut_ptr(), len) };
+            if pushing.is_err() {
+                break;
+            }
+            pushed += 1;
+        }
+        let mut done = vec![None; pushed];
+        while self.in_flight > 0 {
+            self.submit_and_wait(self.in_flight)?;
+            for entry in self.ring.completion() {
+                self.in_flight -= 1;
+                let i = ((entry.user_data() - self.position) / CHUNK as u64) as usize;
+                done[i] = Some(entry.result());
+            }
+        }
+        pushing?;
+        let mut read = 0;
+        for (result, &(_, len)) in done.into_iter().zip(&pieces) {
+            match result.unwrap_or(0) {
+                errno if errno < 0 && read == 0 => {
+                    return Err(io::Error::from_raw_os_error(-errno))
+                }
+                n if n >= 0 && n as usize == len => read += len,
+                n => {
+                    read += n.max(0) as usize;
+                    break;
+                }
+            }
+        }
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Read for UringSlice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let buf_len = buf.len().min((self.size - self.position) as usize);
+        let buf = &mut buf[..buf_len];
+        let hit = self.chunks.front().is_some_and(|chunk| {
+            (chunk.offset..chunk.offset + chunk.buf.len() as u64).contains(&self.position)
+        });
+        if !hit {
+            self.drain()?;
+            if buf.len() >= CHUNK {
+                return self.read_direct(buf);
+            }
+        }
+        self.fill()?;
+        while matches!(self.chunks[0].state, ChunkState::InFlight) {
+            self.reap(1)?;
+        }
+        let chunk = &self.chunks[0];
+        let at = (self.position - chunk.offset) as usize;
+        let n = match chunk.state {
+            ChunkState::Failed(errno) => {
+                self.drain()?;
+                return Err(io::Error::from_raw_os_error(errno));
+            }
+            // the image ends before the slice does
+            ChunkState::Done(0) => return Ok(0),
+            // a short read, taken up again from where it stopped
+            ChunkState::Done(n) if n <= at => {
+                self.drain()?;
+                return self.read(buf);
+            }
+            ChunkState::Done(n) => (n - at).min(buf.len()),
+            ChunkState::InFlight => unreachable!("reaped above"),
+        };
+        buf[..n].copy_from_slice(&chunk.buf[at..at + n]);
+        self.position += n as u64;
+        if self.position >= chunk.offset + chunk.buf.len() as u64 {
+            self.chunks.pop_front();
+            self.window = (self.window * 2).min(MAX_IN_FLIGHT);
+        }
+        Ok(n)
+    }
+}
+
+impl Seek for UringSlice {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size.checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+        // the readahead window is kept for the next read to decide on
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+impl Drop for UringSlice {
+    fn drop(&mut self) {
+        // buffers the kernel may still write to are leaked rather than freed
+        if self.drain().is_err() {
+            std::mem::forget(std::mem::take(&mut self.chunks));
+        }
+    }
+}
@@ -0,0 +1,392 @@
+// cache.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+use snafu::OptionExt;
+
+use crate::metadata::Segment;
+use crate::persistent_data::{le32, le64, MetadataDevice};
+use crate::{Error, InvalidCachePoolSnafu, Lvm2, NotACachedVolumeSnafu, LV};
+
+// dm-cache's metadata, see drivers/md/dm-cache-metadata.c
+const CACHE_MAGIC: u64 = 6142003;
+const CLEAN_SHUTDOWN: u32 = 1;
+// the low 16 bits of a mapping are flags, the rest the origin block
+const M_VALID: u64 = 1;
+const M_DIRTY: u64 = 2;
+
+/// The blocks of a cached LV its cache holds, as read from the cache metadata.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CacheMetadata {
+    /// Name of the cache pool or cachevol LV.
+    pub pool: String,
+    /// Name of the `_corig` LV holding the uncached data.
+    pub origin: String,
+    /// Name of the LV holding the cached blocks, and where they start in it.
+    pub data_lv: String,
+    pub data_offset: u64,
+    /// 1 or 2; format 2 keeps the dirty bits apart from the mappings.
+    pub format: u32,
+    /// The replacement policy, e.g. `smq`.
+    pub policy: String,
+    /// Cache block size in bytes.
+    pub block_size: u64,
+    /// How many blocks the cache has room for.
+    pub cache_blocks: u64,
+    /// Whether the cache was shut down cleanly. If not, its dirty bits may be stale
+    /// and, as the kernel does, every cached block is taken to be dirty.
+    pub clean_shutdown: bool,
+    /// The cache blocks holding a copy of an origin block, in cache block order.
+    pub mappings: Vec<CacheMapping>,
+}
+impl CacheMetadata {
+    /// The cached blocks newer than the origin's.
+    pub fn dirty(&self) -> impl Iterator<Item = &CacheMapping> + '_ {
+        self.mappings.iter().filter(|mapping| mapping.dirty)
+    }
+}
+
+/// A block of the origin that the cache holds a copy of.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheMapping {
+    pub cache_block: u64,
+    pub origin_block: u64,
+    /// Whether the copy was written to since it was last written back, so the
+    /// origin block is out of date.
+    pub dirty: bool,
+}
+
+// the fields of the cache metadata superblock
+#[derive(Debug, PartialEq, Eq)]
+struct Superblock {
+    format: u32,
+    clean_shutdown: bool,
+    policy: String,
+    mapping_root: u64,
+    // in bytes
+    block_size: u64,
+    cache_blocks: u64,
+    dirty_root: u64,
+}
+impl Superblock {
+    fn parse(block: &[u8]) -> Result<Self, &'static str> {
+        if le64(block, 32) != CACHE_MAGIC {
+            return Err("missing superblock magic");
+        }
+        let format = le32(block, 40);
+        if !matches!(format, 1 | 2) {
+            return Err("unknown metadata format");
+        }
+        let policy = block[44..60].split(|&b| b == 0).next().unwrap_or_default();
+        let superblock = Self {
+            format,
+            clean_shutdown: le32(block, 4) & CLEAN_SHUTDOWN != 0,
+            policy: String::from_utf8_lossy(policy).into_owned(),
+            mapping_root: le64(block, 192),
+            block_size: u64::from(le32(block, 232)) * 512,
+            cache_blocks: u64::from(le32(block, 240)),
+            dirty_root: le64(block, 284),
+        };
+        if superblock.block_size == 0 {
+            return Err("block size is zero");
+        }
+        Ok(superblock)
+    }
+}
+
+impl Lvm2 {
+    /// Reads the mappings and dirty bits of cached LV `lv` from its cache metadata,
+    /// in either metadata format. Checksums are not verified.
+    pub fn cache_metadata<T: Read + Seek>(
+        &self,
+        lv: LV<'_>,
+        reader: &mut T,
+    ) -> Result<CacheMetadata, Error> {
+        let (pool, origin, metadata_area, data_area) = lv
+            .segments()
+            .find_map(|(_, seg)| match &seg.kind {
+                Segment::Cache {
+                    pool,
+                    origin,
+                    metadata_area,
+                    data_area,
+                } => Some((pool.as_str(), origin, metadata_area, data_area)),
+                _ => None,
+            })
+            .context(NotACachedVolumeSnafu { lv: lv.name() })?;
+        let invalid = |reason| InvalidCachePoolSnafu { lv: pool, reason };
+        let pool_lv = self
+            .vg()
+            .lv_by_name(pool)
+            .context(invalid("pool LV is missing"))?;
+        // a cachevol holds both metadata and data, a cache pool has a sub-LV for each
+        let (metadata, metadata_range, data_lv, data_offset, data_len) =
+            match (metadata_area, data_area) {
+                (Some(metadata), Some(data)) => (
+                    pool_lv,
+                    metadata.start.saturating_mul(512)..metadata.end.saturating_mul(512),
+                    pool,
+                    data.start.saturating_mul(512),
+                    (data.end - data.start).saturating_mul(512),
+                ),
+                _ => {
+                    let (metadata, data) = pool_lv
+                        .segments()
+                        .find_map(|(_, seg)| match &seg.kind {
+                            Segment::CachePool { metadata, data, .. } => {
+                                Some((metadata.as_str(), data.as_str()))
+                            }
+                            _ => None,
+                        })
+                        .context(invalid("not a cache pool"))?;
+                    let metadata = self
+                        .vg()
+                        .lv_by_name(metadata)
+                        .context(invalid("metadata LV is missing"))?;
+                    let data_len = self
+                        .vg()
+                        .lv_by_name(data)
+                        .context(invalid("data LV is missing"))?
+                        .size_bytes();
+                    (metadata, 0..metadata.size_bytes(), data, 0, data_len)
+                }
+            };
+        let mut metadata = MetadataDevice::new(
+            pool,
+            |lv, reason| InvalidCachePoolSnafu { lv, reason }.build(),
+            self.open_lv(metadata, reader),
+            metadata_range.start,
+            metadata_range.end - metadata_range.start,
+        );
+
+        metadata.read_block(0)?;
+        let Superblock {
+            format,
+            clean_shutdown,
+            policy,
+            mapping_root,
+            block_size,
+            cache_blocks,
+            dirty_root,
+        } = Superblock::parse(&metadata.block)
+            .map_err(|reason| InvalidCachePoolSnafu { lv: pool, reason }.build())?;
+        snafu::ensure!(
+            cache_blocks.saturating_mul(block_size) <= data_len,
+            invalid("cache blocks overflow the data area")
+        );
+
+        let mut mappings = Vec::new();
+        metadata.walk_array(mapping_root, cache_blocks, |cache_block, value| {
+            if value & M_VALID != 0 {
+                mappings.push(CacheMapping {
+                    cache_block,
+                    origin_block: value >> 16,
+                    dirty: !clean_shutdown || (format == 1 && value & M_DIRTY != 0),
+                });
+            }
+        })?;
+        if format == 2 && clean_shutdown {
+            // one bit per cache block, 64 to a word
+            let mut words = Vec::new();
+            metadata.walk_array(dirty_root, cache_blocks.div_ceil(64), |index, word| {
+                if word != 0 {
+                    words.push((index, word));
+                }
+            })?;
+            for mapping in &mut mappings {
+                let (index, bit) = (mapping.cache_block / 64, mapping.cache_block % 64);
+                if let Ok(i) = words.binary_search_by_key(&index, |&(index, _)| index) {
+                    mapping.dirty = words[i].1 & (1 << bit) != 0;
+                }
+            }
+        }
+
+        Ok(CacheMetadata {
+            pool: pool.into(),
+            origin: origin.clone(),
+            data_lv: data_lv.into(),
+            data_offset,
+            format,
+            policy,
+            block_size,
+            cache_blocks,
+            clean_shutdown,
+            mappings,
+        })
+    }
+
+    /// Opens cached LV `lv` for reading the way the kernel presents it: from its
+    /// origin, except for the blocks `cache` says are dirty, which are read from the
+    /// cache. Reading the `_corig` LV instead gives the origin alone.
+    pub fn open_cached_lv<'a, 'r, T: Read + Seek>(
+        &'a self,
+        lv: LV<'a>,
+        cache: &CacheMetadata,
+        reader: &'r mut T,
+    ) -> Result<OpenCachedLV<'a, 'r, T>, Error> {
+        let invalid = |reason| InvalidCachePoolSnafu {
+            lv: cache.pool.as_str(),
+            reason,
+        };
+        let origin = self
+            .vg()
+            .lv_by_name(&cache.origin)
+            .context(invalid("origin LV is missing"))?;
+        let data = self
+            .vg()
+            .lv_by_name(&cache.data_lv)
+            .context(invalid("data LV is missing"))?;
+        // both are read through open_lv later on
+        self.try_open_lv(origin, &mut *reader)?;
+        self.try_open_lv(data, &mut *reader)?;
+        Ok(OpenCachedLV {
+            lvm: self,
+            lv,
+            origin,
+            data,
+            data_offset: cache.data_offset,
+            block_size: cache.block_size,
+            dirty: cache
+                .dirty()
+                .map(|mapping| (mapping.origin_block, mapping.cache_block))
+                .collect(),
+            reader,
+            position: 0,
+        })
+    }
+}
+
+/// A cached LV opened through [`Lvm2::open_cached_lv`].
+pub struct OpenCachedLV<'a, 'r, T> {
+    lvm: &'a Lvm2,
+    lv: LV<'a>,
+    origin: LV<'a>,
+    data: LV<'a>,
+    data_offset: u64,
+    block_size: u64,
+    // dirty origin blocks and the cache blocks holding them
+    dirty: BTreeMap<u64, u64>,
+    reader: &'r mut T,
+    position: u64,
+}
+impl<'a, T> OpenCachedLV<'a, '_, T> {
+    pub fn lv(&self) -> LV<'a> {
+        self.lv
+    }
+
+    pub fn size(&self) -> u64 {
+        self.lv.size_bytes()
+    }
+}
+impl<T: Read + Seek> Read for OpenCachedLV<'_, '_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        let size = self.size();
+        if self.position >= size {
+            return Ok(0);
+        }
+        let block = self.position / self.block_size;
+        let (lv, offset, len) = match self.dirty.get(&block) {
+            Some(&cache_block) => {
+                let within = self.position % self.block_size;
+                (
+                    self.data,
+                    self.data_offset + cache_block * self.block_size + within,
+                    self.block_size - within,
+                )
+            }
+            // the origin, up to the next dirty block
+            None => {
+                let end = self
+                    .dirty
+                    .range(block + 1..)
+                    .next()
+                    .map_or(size, |(&next, _)| {
+                        next.saturating_mul(self.block_size).min(size)
+                    });
+                (self.origin, self.position, end - self.position)
+            }
+        };
+        let len = buf
+            .len()
+            .min(usize::try_from(len).unwrap_or(usize::MAX))
+            .min(usize::try_from(size - self.position).unwrap_or(usize::MAX));
+        let mut open = self.lvm.open_lv(lv, &mut *self.reader);
+        open.seek(SeekFrom::Start(offset))?;
+        let n = open.read(&mut buf[..len])?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+impl<T> Seek for OpenCachedLV<'_, '_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size().checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or(crate::io_error("seek before the start of the LV"))?;
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistent_data::METADATA_BLOCK;
+
+    fn superblock_block(format: u32) -> Vec<u8> {
+        let mut block = alloc::vec![0u8; METADATA_BLOCK];
+        block[4..8].copy_from_slice(&CLEAN_SHUTDOWN.to_le_bytes());
+        block[32..40].copy_from_slice(&CACHE_MAGIC.to_le_bytes());
+        block[40..44].copy_from_slice(&format.to_le_bytes());
+        block[44..47].copy_from_slice(b"smq");
+        block[192..200].copy_from_slice(&5u64.to_le_bytes());
+        // 32 KiB blocks
+        block[232..236].copy_from_slice(&64u32.to_le_bytes());
+        block[240..244].copy_from_slice(&1000u32.to_le_bytes());
+        block[284..292].copy_from_slice(&6u64.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn superblock_fields() {
+        assert_eq!(
+            Superblock::parse(&superblock_block(2)),
+            Ok(Superblock {
+                format: 2,
+                clean_shutdown: true,
+                policy: "smq".into(),
+                mapping_root: 5,
+                block_size: 32768,
+                cache_blocks: 1000,
+                dirty_root: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn superblock_without_clean_shutdown() {
+        let mut block = superblock_block(1);
+        block[4] = 0;
+        let superblock = Superblock::parse(&block).unwrap();
+        assert!(!superblock.clean_shutdown);
+        assert_eq!(superblock.format, 1);
+    }
+
+    #[test]
+    fn superblock_rejects_what_it_cannot_read() {
+        let mut block = superblock_block(2);
+        block[32] ^= 1;
+        assert_eq!(Superblock::parse(&block), Err("missing superblock magic"));
+        assert_eq!(
+            Superblock::parse(&superblock_block(3)),
+            Err("unknown metadata format")
+        );
+        let mut block = superblock_block(2);
+        block[232..236].fill(0);
+        assert_eq!(Superblock::parse(&block), Err("block size is zero"));
+    }
+}
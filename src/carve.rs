@@ -0,0 +1,128 @@
+// carve.rs
+use acid_io::{Read, Seek, SeekFrom};
+use core::ops::Range;
+
+use crate::{LogicalToPhysical, Lvm2, Pv, ReadReason};
+
+/// A run of physical extents that no LV maps, readable like a file.
+pub struct UnallocatedRun<'a, 'r, T> {
+    pv: &'a Pv,
+    extents: Range<u64>,
+    offset: u64,
+    len: u64,
+    position: u64,
+    reader: &'r mut T,
+}
+
+impl<T> UnallocatedRun<'_, '_, T> {
+    /// The physical extents covered.
+    pub fn extents(&self) -> Range<u64> {
+        self.extents.clone()
+    }
+
+    /// Byte offset of the run within the PV.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> LogicalToPhysical for UnallocatedRun<'_, '_, T> {
+    fn physical_range(&self, offset: u64) -> Option<Range<u64>> {
+        let start = self.pv.start_offset() + self.offset + offset;
+        (offset < self.len).then(|| start..start + (self.len - offset))
+    }
+}
+
+impl<T: Read + Seek> Read for UnallocatedRun<'_, '_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        if self.position >= self.len {
+            return Ok(0);
+        }
+        let offset = self.offset + self.position;
+        self.reader
+            .seek(SeekFrom::Start(self.pv.start_offset() + offset))?;
+        let len = buf
+            .len()
+            .min(usize::try_from(self.len - self.position).unwrap_or(usize::MAX));
+        let n = self.reader.read(&mut buf[..len])?;
+        self.pv
+            .options()
+            .record(offset, n as u64, || ReadReason::Unallocated {
+                first_pe: self.extents.start,
+                last_pe: self.extents.end - 1,
+            });
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: Read + Seek> Seek for UnallocatedRun<'_, '_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.len.checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or(crate::io_error("seek before the start of the extent run"))?;
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+/// Hands out an [`UnallocatedRun`] for each of [`Lvm2::unallocated_extents`] in
+/// turn, see [`Lvm2::unallocated_runs`].
+pub struct UnallocatedRuns<'a, 'r, T> {
+    lvm: &'a Lvm2,
+    runs: alloc::vec::IntoIter<Range<u64>>,
+    reader: &'r mut T,
+}
+
+impl<'a, T: Read + Seek> UnallocatedRuns<'a, '_, T> {
+    /// The next run, borrowing the reader until it is dropped.
+    pub fn next_run(&mut self) -> Option<UnallocatedRun<'a, '_, T>> {
+        let extents = self.runs.next()?;
+        self.lvm.open_unallocated(extents, self.reader)
+    }
+}
+
+impl Lvm2 {
+    /// Opens a run of physical extents of this PV for reading, normally one of
+    /// [`Lvm2::unallocated_extents`]. `None` if it lies beyond the data area.
+    pub fn open_unallocated<'a, 'r, T: Read + Seek>(
+        &'a self,
+        extents: Range<u64>,
+        reader: &'r mut T,
+    ) -> Option<UnallocatedRun<'a, 'r, T>> {
+        let offset = self.physical_extent_offset(extents.start)?;
+        Some(UnallocatedRun {
+            pv: self.pv(),
+            offset,
+            len: (extents.end - extents.start) * self.extent_size(),
+            extents,
+            position: 0,
+            reader,
+        })
+    }
+
+    /// Readers over the space no live LV owns, one per run of unallocated extents, so
+    /// carving can skip the LVs. They share `reader`, so each has to be dropped before
+    /// [`UnallocatedRuns::next_run`] hands out the next one.
+    pub fn unallocated_runs<'a, 'r, T: Read + Seek>(
+        &'a self,
+        reader: &'r mut T,
+    ) -> UnallocatedRuns<'a, 'r, T> {
+        UnallocatedRuns {
+            lvm: self,
+            runs: self.unallocated_extents().into_iter(),
+            reader,
+        }
+    }
+}
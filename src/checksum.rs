@@ -0,0 +1,37 @@
+// checksum.rs
+// LVM uses a CRC-32 (reflected polynomial 0xedb88320) with a custom seed and
+// no final inversion for labels, metadata area headers and metadata text.
+
+pub const INITIAL_CRC: u32 = 0xf597a6cf;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continues an LVM checksum over `data`.
+pub fn calc_crc(initial: u32, data: &[u8]) -> u32 {
+    data.iter().fold(initial, |crc, &b| {
+        (crc >> 8) ^ TABLE[((crc ^ u32::from(b)) & 0xff) as usize]
+    })
+}
+
+/// The LVM checksum of `data`.
+pub fn lvm_crc(data: &[u8]) -> u32 {
+    calc_crc(INITIAL_CRC, data)
+}
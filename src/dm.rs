@@ -0,0 +1,110 @@
+// dm.rs
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use snafu::OptionExt;
+
+use crate::metadata::Segment;
+use crate::{Error, PVNotInVGSnafu, VolumeGroup, LV, PV};
+
+/// A device-mapper table, printed in the format `dmsetup create --table` reads.
+/// Built for an LV with [`VolumeGroup::dm_table`], or by hand from an extent map.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DmTable {
+    pub targets: Vec<DmTarget>,
+}
+
+/// `len` sheets of the mapped device, from sheet `start` on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DmTarget {
+    pub start: u64,
+    pub len: u64,
+    pub kind: DmTargetKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DmTargetKind {
+    /// Stored contiguously from sheet `offset` of `device`.
+    Linear { device: String, offset: u64 },
+    /// Spread over `stripes` (device and start sheet) in chunks of `chunk_size` sheets.
+    Striped {
+        chunk_size: u64,
+        stripes: Vec<(String, u64)>,
+    },
+}
+
+impl fmt::Display for DmTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for target in &self.targets {
+            write!(f, "{} {} ", target.start, target.len)?;
+            match &target.kind {
+                DmTargetKind::Linear { device, offset } => write!(f, "linear {device} {offset}")?,
+                DmTargetKind::Striped {
+                    chunk_size,
+                    stripes,
+                } => {
+                    write!(f, "striped {} {chunk_size}", stripes.len())?;
+                    for (device, offset) in stripes {
+                        write!(f, " {device} {offset}")?;
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl VolumeGroup {
+    /// The table mapping `lv` onto its PVs, where `device` names the block device
+    /// each PV is available as. `lv` has to be described by this VG, which can be a
+    /// historical generation, e.g. [`DeletedLv::generation`](crate::DeletedLv::generation).
+    ///
+    /// Only segments stored directly on PVs (linear and striped) can be mapped.
+    pub fn dm_table(
+        &self,
+        lv: LV<'_>,
+        mut device: impl FnMut(PV<'_>) -> String,
+    ) -> Result<DmTable, Error> {
+        let extent_size = self.config().extent_size;
+        let mut stripe_target = |pv_name: &str, start_extent: u64| {
+            let pv = self
+                .pv_by_name(pv_name)
+                .context(PVNotInVGSnafu { pv: pv_name })?;
+            Ok::<_, Error>((device(pv), pv.pe_start() + start_extent * extent_size))
+        };
+
+        let mut table = DmTable::default();
+        for (name, segment) in lv.segments() {
+            let kind = match &segment.kind {
+                Segment::Linear { stripe } => {
+                    let (device, offset) = stripe_target(&stripe.pv, stripe.start_extent)?;
+                    DmTargetKind::Linear { device, offset }
+                }
+                Segment::Striped {
+                    stripe_size,
+                    stripes,
+                } => DmTargetKind::Striped {
+                    chunk_size: stripe_size.unwrap_or(extent_size),
+                    stripes: stripes
+                        .iter()
+                        .map(|stripe| stripe_target(&stripe.pv, stripe.start_extent))
+                        .collect::<Result<_, _>>()?,
+                },
+                kind => {
+                    return Err(Error::UnsupportedSegment {
+                        lv: lv.name().to_string(),
+                        segment: name.to_string(),
+                        kind: kind.type_name().to_string(),
+                    })
+                }
+            };
+            table.targets.push(DmTarget {
+                start: segment.start_extent * extent_size,
+                len: segment.extent_count * extent_size,
+                kind,
+            });
+        }
+        Ok(table)
+    }
+}
@@ -0,0 +1,175 @@
+// dot.rs
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::metadata::Segment;
+use crate::VolumeGroup;
+
+impl VolumeGroup {
+    /// A Graphviz graph of the VG: its LVs, their segments and the PVs and LVs each
+    /// segment maps onto, such as a thin LV's pool or a snapshot's origin. Hidden LVs
+    /// are drawn dashed. Render it with e.g. `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let _ = self.write_dot(&mut out);
+        out
+    }
+
+    fn write_dot(&self, out: &mut String) -> core::fmt::Result {
+        writeln!(out, "digraph {} {{", quote(self.name()))?;
+        writeln!(out, "\trankdir=LR;")?;
+        writeln!(out, "\tnode [shape=box];")?;
+        let vg = format!("vg/{}", self.name());
+        writeln!(
+            out,
+            "\t{} [shape=folder, label={}];",
+            quote(&vg),
+            quote(&format!(
+                "VG {}\nextent size {}",
+                self.name(),
+                self.extent_size()
+            ))
+        )?;
+        for pv in self.pvs() {
+            let label = format!(
+                "PV {}\n{}\n{} extents",
+                pv.name(),
+                pv.raw_metadata().device,
+                pv.pe_count()
+            );
+            writeln!(
+                out,
+                "\t{} [shape=cylinder, label={}];",
+                quote(&format!("pv/{}", pv.name())),
+                quote(&label)
+            )?;
+        }
+        for lv in self.lvs_all() {
+            let node = format!("lv/{}", lv.name());
+            writeln!(
+                out,
+                "\t{} [label={}{}];",
+                quote(&node),
+                quote(&format!(
+                    "LV {}\n{} extents",
+                    lv.name(),
+                    lv.size_in_extents()
+                )),
+                if lv.is_hidden() { ", style=dashed" } else { "" }
+            )?;
+            writeln!(out, "\t{} -> {};", quote(&vg), quote(&node))?;
+            for (key, seg) in lv.segments() {
+                let segment = format!("seg/{}/{}", lv.name(), key);
+                writeln!(
+                    out,
+                    "\t{} [shape=ellipse, label={}];",
+                    quote(&segment),
+                    quote(&format!(
+                        "{}\n{}\nextents {}-{}",
+                        key,
+                        seg.kind.type_name(),
+                        seg.start_extent,
+                        seg.extents().end.saturating_sub(1)
+                    ))
+                )?;
+                writeln!(out, "\t{} -> {};", quote(&node), quote(&segment))?;
+                for (pv, extents) in seg.pv_extents() {
+                    writeln!(
+                        out,
+                        "\t{} -> {} [label={}];",
+                        quote(&segment),
+                        quote(&format!("pv/{}", pv)),
+                        quote(&format!(
+                            "PE {}-{}",
+                            extents.start,
+                            extents.end.saturating_sub(1)
+                        ))
+                    )?;
+                }
+                for (target, role) in lv_references(&seg.kind) {
+                    writeln!(
+                        out,
+                        "\t{} -> {} [style=dashed, label={}];",
+                        quote(&segment),
+                        quote(&format!("lv/{}", target)),
+                        quote(&role)
+                    )?;
+                }
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+// the LVs a segment maps onto or depends on, with what each is to it
+fn lv_references<'a>(segment: &'a Segment) -> Vec<(&'a str, String)> {
+    let role = |lv: &'a String, role: &str| (lv.as_str(), String::from(role));
+    let numbered = |lvs: &'a [String], role: &str| {
+        lvs.iter()
+            .enumerate()
+            .map(|(i, lv)| (lv.as_str(), format!("{} {}", role, i)))
+            .collect::<Vec<_>>()
+    };
+    match segment {
+        Segment::Linear { .. } | Segment::Striped { .. } | Segment::Unknown { .. } => Vec::new(),
+        Segment::Thin { pool, origin, .. } => core::iter::once(role(pool, "pool"))
+            .chain(origin.iter().map(|origin| role(origin, "origin")))
+            .collect(),
+        Segment::ThinPool { metadata, pool, .. } => {
+            vec![role(pool, "data"), role(metadata, "metadata")]
+        }
+        Segment::Cache { pool, origin, .. } => {
+            vec![role(origin, "origin"), role(pool, "cache")]
+        }
+        Segment::CachePool { data, metadata, .. } => {
+            vec![role(data, "data"), role(metadata, "metadata")]
+        }
+        Segment::Snapshot {
+            origin, cow_store, ..
+        } => vec![role(origin, "origin"), role(cow_store, "COW store")],
+        Segment::Mirror { images, log, .. } => {
+            let mut references: Vec<_> = images
+                .iter()
+                .enumerate()
+                .map(|(i, image)| (image.name.as_str(), format!("image {}", i)))
+                .collect();
+            references.extend(log.iter().map(|log| role(log, "log")));
+            references
+        }
+        Segment::Raid0 { images, .. } => numbered(images, "image"),
+        Segment::Raid1 {
+            images,
+            meta_images,
+            ..
+        }
+        | Segment::Raid {
+            images,
+            meta_images,
+            ..
+        } => {
+            let mut references = numbered(images, "image");
+            references.extend(numbered(meta_images, "metadata"));
+            references
+        }
+    }
+}
+
+// a DOT string literal; Graphviz reads `\n` in labels as a centred line break
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,241 @@
+// export.rs
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use serde::Serialize;
+
+use crate::metadata::LVSegmentDesc;
+use crate::{DeletedLv, Finding, Lvm2, Timestamp, VolumeGroup, LV};
+
+/// One record of the line-per-record export made by [`Lvm2::export_records`], for
+/// ingestion by timeline and case tools. `record_type` tells the kinds apart. Every
+/// time is in seconds since the Unix epoch; records that have one carry it as
+/// `timestamp`, next to a `timestamp_desc` saying what it is.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "record_type", rename_all = "snake_case")]
+pub enum ExportRecord<'a> {
+    /// The VG, as of the metadata in use.
+    VolumeGroup {
+        name: &'a str,
+        id: &'a str,
+        seqno: u64,
+        /// In bytes.
+        extent_size: u64,
+        pv_count: usize,
+        lv_count: usize,
+        /// When the metadata was written.
+        timestamp: Option<Timestamp>,
+        timestamp_desc: &'static str,
+        /// `creation_host` of the metadata, i.e. the host that last changed the VG.
+        host: Option<&'a str>,
+        /// The LVM command that wrote the metadata.
+        description: Option<&'a str>,
+    },
+    /// A PV the VG lists. Only the opened one has a `label_offset`.
+    PhysicalVolume {
+        vg: &'a str,
+        name: &'a str,
+        id: &'a str,
+        /// The device path the PV had when the metadata was written.
+        device: &'a str,
+        status: &'a [String],
+        /// In sheets.
+        dev_size: Option<u64>,
+        /// Start of the first physical extent, in sheets.
+        pe_start: u64,
+        pe_count: u64,
+        opened: bool,
+        label_offset: Option<u64>,
+    },
+    /// An LV, current or deleted.
+    LogicalVolume {
+        vg: &'a str,
+        name: &'a str,
+        id: &'a str,
+        status: &'a [String],
+        flags: &'a [String],
+        tags: &'a [String],
+        size_bytes: u64,
+        segment_count: usize,
+        /// See [`LV::is_hidden`].
+        hidden: bool,
+        timestamp: Timestamp,
+        timestamp_desc: &'static str,
+        host: &'a str,
+        /// Only listed by older metadata left in the metadata area.
+        deleted: bool,
+        /// For a deleted LV, when the first metadata without it was written, see
+        /// [`Lvm2::removal_time`].
+        removed_timestamp: Option<Timestamp>,
+        /// Seqno of the metadata this description comes from.
+        seqno: u64,
+        /// For a deleted LV, how many of its physical extents on the opened PV
+        /// another LV maps now, so their old contents are likely gone.
+        reallocated_extents: Option<u64>,
+    },
+    /// A segment of an LV record.
+    Segment {
+        vg: &'a str,
+        lv: &'a str,
+        /// Key of the segment in the LV section, e.g. `segment1`.
+        key: &'a str,
+        /// As `lvs -o segtype` prints it.
+        segment_type: &'a str,
+        start_extent: u64,
+        extent_count: u64,
+        deleted: bool,
+        seqno: u64,
+        stripes: Vec<ExportStripe<'a>>,
+    },
+    /// An anomaly, see [`Lvm2::findings`]. `kind` tells them apart.
+    Finding(Finding),
+}
+
+/// Where a stripe of an exported segment lives.
+#[derive(Serialize, Clone, Debug)]
+pub struct ExportStripe<'a> {
+    pub pv: &'a str,
+    pub start_extent: u64,
+    /// Offset of the stripe's first byte within the reader the PV was opened from,
+    /// if it lies on the opened PV.
+    pub image_offset: Option<u64>,
+}
+
+impl Lvm2 {
+    /// Everything known about the VG as a flat list of records: the VG, its PVs, its
+    /// current and deleted LVs each followed by their segments, then the findings.
+    /// Serialize each to one line for a JSONL export.
+    pub fn export_records(&self) -> Vec<ExportRecord<'_>> {
+        let mut records = Vec::new();
+        self.try_for_each_export_record(|record| {
+            records.push(record);
+            Ok::<(), Infallible>(())
+        })
+        .unwrap_or_else(|never| match never {});
+        records
+    }
+
+    /// Hands the records of [`Lvm2::export_records`] to `emit` one at a time and in
+    /// the same order as they are made, rather than collecting them first, so writing
+    /// out a VG with thousands of LVs takes no more memory than a small one. Stops at
+    /// the first error `emit` returns.
+    pub fn try_for_each_export_record<'a, E>(
+        &'a self,
+        mut emit: impl FnMut(ExportRecord<'a>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let vg = self.vg();
+        let config = vg.config();
+        let header = vg.header();
+        emit(ExportRecord::VolumeGroup {
+            name: vg.name(),
+            id: vg.id(),
+            seqno: config.seqno,
+            extent_size: vg.extent_size(),
+            pv_count: config.physical_volumes.len(),
+            lv_count: config.logical_volumes.len(),
+            timestamp: header.creation_time,
+            timestamp_desc: "Metadata Written",
+            host: header.creation_host.as_deref(),
+            description: header.description.as_deref(),
+        })?;
+        for pv in vg.pvs() {
+            let opened = pv.name() == self.pv_name();
+            emit(ExportRecord::PhysicalVolume {
+                vg: vg.name(),
+                name: pv.name(),
+                id: pv.id(),
+                device: &pv.raw_metadata().device,
+                status: pv.status(),
+                dev_size: pv.dev_size(),
+                pe_start: pv.pe_start(),
+                pe_count: pv.pe_count(),
+                opened,
+                label_offset: opened.then(|| self.pv().label_offset()),
+            })?;
+        }
+        for lv in vg.lvs_all() {
+            self.export_lv(&mut emit, vg, lv, None)?;
+        }
+        for deleted in self.deleted_lvs() {
+            let reallocated = self
+                .reallocated_extents(&deleted)
+                .iter()
+                .map(|run| run.end - run.start)
+                .sum();
+            self.export_lv(&mut emit, deleted.generation, deleted.lv, Some(reallocated))?;
+        }
+        self.findings()
+            .into_iter()
+            .try_for_each(|finding| emit(ExportRecord::Finding(finding)))
+    }
+
+    // the LV and its segments, as described by `generation`
+    fn export_lv<'a, E>(
+        &'a self,
+        emit: &mut impl FnMut(ExportRecord<'a>) -> Result<(), E>,
+        generation: &'a VolumeGroup,
+        lv: LV<'a>,
+        reallocated_extents: Option<u64>,
+    ) -> Result<(), E> {
+        let desc = lv.raw_metadata();
+        let seqno = generation.config().seqno;
+        let deleted = reallocated_extents.is_some();
+        emit(ExportRecord::LogicalVolume {
+            vg: generation.name(),
+            name: lv.name(),
+            id: lv.id(),
+            status: &desc.status,
+            flags: &desc.flags,
+            tags: &desc.tags,
+            size_bytes: lv.size_bytes(),
+            segment_count: desc.segment_count,
+            hidden: lv.is_hidden(),
+            timestamp: desc.creation_time,
+            timestamp_desc: "LV Created",
+            host: &desc.creation_host,
+            deleted,
+            removed_timestamp: deleted
+                .then(|| self.removal_time(&DeletedLv { generation, lv }))
+                .flatten(),
+            seqno,
+            reallocated_extents,
+        })?;
+        // the generation may know this PV under another name
+        let opened = generation
+            .pv_by_id(self.pv_id())
+            .map_or(self.pv_name(), |pv| pv.name());
+        for (key, segment) in lv.segments() {
+            emit(ExportRecord::Segment {
+                vg: generation.name(),
+                lv: lv.name(),
+                key,
+                segment_type: segment.kind.type_name(),
+                start_extent: segment.start_extent,
+                extent_count: segment.extent_count,
+                deleted,
+                seqno,
+                stripes: self.export_stripes(segment, opened),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn export_stripes<'a>(
+        &self,
+        segment: &'a LVSegmentDesc,
+        opened: &str,
+    ) -> Vec<ExportStripe<'a>> {
+        segment
+            .stripes()
+            .iter()
+            .map(|stripe| ExportStripe {
+                pv: &stripe.pv,
+                start_extent: stripe.start_extent,
+                image_offset: (stripe.pv == opened)
+                    .then(|| self.physical_extent_offset(stripe.start_extent))
+                    .flatten()
+                    .map(|offset| self.start_offset() + offset),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,509 @@
+// findings.rs
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+
+use crate::{ChecksumMismatch, Lvm2, LvmUuid, Timestamp, VolumeGroup};
+
+/// A suspicious state of the on-disk structures, which may point at tampering or at
+/// a PV that was swapped or restored.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// A structure whose checksum does not match, tolerated by
+    /// [`ChecksumMode::Warn`](crate::ChecksumMode::Warn).
+    ChecksumMismatch(ChecksumMismatch),
+    /// A label in the first four sectors that names another sector as its own, as
+    /// left by a PV that started elsewhere on the disk.
+    MisplacedLabel { offset: u64, sector_number: u64 },
+    /// Bytes of the metadata text that are not UTF-8, as left by a partial overwrite.
+    /// They read as U+FFFD in the parsed metadata.
+    InvalidMetadataText { offset: u64, len: u64 },
+    /// More than one valid label in the first four sectors.
+    MultipleLabels { offsets: Vec<u64> },
+    /// A metadata area whose header could not be read, at byte `offset` of the PV.
+    /// The PV opens without metadata when none of its areas can be read.
+    UnusableMetadataArea {
+        mda: usize,
+        offset: u64,
+        reason: String,
+    },
+    /// The metadata read from the PV does not list the PV's own UUID.
+    PvNotInMetadata { pv_id: String },
+    /// An older copy left in the metadata area carries a higher seqno than the
+    /// metadata in use, which LVM would never have written over it.
+    HigherSeqnoInHistory { current: u64, found: u64 },
+    /// A generation claims to have been written before the one preceding it.
+    TimestampOutOfOrder {
+        seqno: u64,
+        creation_time: Timestamp,
+        previous_seqno: u64,
+        previous_creation_time: Timestamp,
+    },
+    /// An LV claims to have been created after the metadata listing it was written.
+    LvCreatedAfterMetadata {
+        seqno: u64,
+        lv: String,
+        lv_creation_time: Timestamp,
+        metadata_creation_time: Timestamp,
+    },
+    /// An LV section the typed model cannot read, which [`VolumeGroup::lvs`] leaves
+    /// out.
+    InvalidLvMetadata {
+        seqno: u64,
+        lv: String,
+        reason: String,
+    },
+    /// The metadata lists this PV's name with a UUID other than the label's.
+    PvUuidMismatch {
+        seqno: u64,
+        pv: String,
+        label_id: String,
+        metadata_id: String,
+    },
+    /// Physical extents claimed by two segments at once, see
+    /// [`VolumeGroup::overlapping_extents`].
+    OverlappingExtents(ExtentOverlap),
+    /// The first physical extent in the metadata is not where the PV header puts
+    /// the data area. Both in bytes.
+    PeStartMismatch { header: u64, metadata: u64 },
+    /// A segment stripe on a PV the metadata does not list, or past its last
+    /// extent, see [`VolumeGroup::invalid_stripes`].
+    InvalidStripe(InvalidStripe),
+    /// The PV header and the metadata disagree on the size of the PV. Both in bytes.
+    PvSizeMismatch { header: u64, metadata: u64 },
+    /// A PV whose extents reach past the device size the metadata gives it. Both in
+    /// sheets.
+    ExtentsPastDeviceEnd {
+        seqno: u64,
+        pv: String,
+        data_end: u64,
+        dev_size: u64,
+    },
+    /// An LV whose `segment_count` is not the number of segments it holds, e.g.
+    /// because a segment section does not parse.
+    SegmentCountMismatch {
+        seqno: u64,
+        lv: String,
+        declared: usize,
+        found: usize,
+    },
+    /// An LV whose segments leave runs of its extents unmapped or map them twice,
+    /// see [`LV::validate`](crate::LV::validate).
+    InvalidLvMapping {
+        seqno: u64,
+        lv: String,
+        gaps: Vec<Range<u64>>,
+        overlaps: Vec<Range<u64>>,
+    },
+    /// A segment naming an LV, e.g. its thin pool or snapshot origin, that the
+    /// metadata does not list.
+    MissingLvReference {
+        seqno: u64,
+        lv: String,
+        segment: String,
+        target: String,
+    },
+    /// A RAID LV caught in the middle of a reshape, whose segments carry these
+    /// type flags, see [`LV::is_reshaping`](crate::LV::is_reshaping). Its data is
+    /// partly in the old layout and partly in the new, so reading it either way is
+    /// ambiguous.
+    RaidReshapeInProgress {
+        seqno: u64,
+        lv: String,
+        flags: Vec<String>,
+    },
+}
+impl Finding {
+    /// The check of [`Lvm2::validate`] this finding fails.
+    pub fn check(&self) -> VgCheck {
+        match self {
+            Finding::MisplacedLabel { .. }
+            | Finding::MultipleLabels { .. }
+            | Finding::UnusableMetadataArea { .. }
+            | Finding::PvNotInMetadata { .. }
+            | Finding::PvUuidMismatch { .. }
+            | Finding::PeStartMismatch { .. } => VgCheck::Headers,
+            Finding::ChecksumMismatch(_) => VgCheck::Checksums,
+            Finding::InvalidMetadataText { .. }
+            | Finding::InvalidLvMetadata { .. }
+            | Finding::SegmentCountMismatch { .. }
+            | Finding::RaidReshapeInProgress { .. } => VgCheck::MetadataText,
+            Finding::PvSizeMismatch { .. } | Finding::ExtentsPastDeviceEnd { .. } => {
+                VgCheck::PvSizes
+            }
+            Finding::OverlappingExtents(_) | Finding::InvalidLvMapping { .. } => {
+                VgCheck::ExtentAccounting
+            }
+            Finding::InvalidStripe(stripe) if stripe.pe_count.is_some() => {
+                VgCheck::ExtentAccounting
+            }
+            Finding::InvalidStripe(_) | Finding::MissingLvReference { .. } => {
+                VgCheck::SegmentReferences
+            }
+            Finding::HigherSeqnoInHistory { .. }
+            | Finding::TimestampOutOfOrder { .. }
+            | Finding::LvCreatedAfterMetadata { .. } => VgCheck::History,
+        }
+    }
+}
+
+/// The groups of structural checks [`Lvm2::validate`] runs, in the order it reports
+/// them.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum VgCheck {
+    /// The label and PV header agree with each other and with the metadata.
+    Headers,
+    /// Every checksummed structure matches its checksum.
+    Checksums,
+    /// The metadata text decodes and describes every LV in full.
+    MetadataText,
+    /// The PVs hold the extents the metadata gives them.
+    PvSizes,
+    /// Every extent is mapped by at most one segment, and each LV's extents by
+    /// exactly one.
+    ExtentAccounting,
+    /// Segments only name PVs and LVs the metadata lists.
+    SegmentReferences,
+    /// The metadata generations follow each other in order. Needs
+    /// [`Lvm2Options::load_history`](crate::Lvm2Options::load_history).
+    History,
+}
+impl VgCheck {
+    pub const ALL: [VgCheck; 7] = [
+        VgCheck::Headers,
+        VgCheck::Checksums,
+        VgCheck::MetadataText,
+        VgCheck::PvSizes,
+        VgCheck::ExtentAccounting,
+        VgCheck::SegmentReferences,
+        VgCheck::History,
+    ];
+}
+
+/// The outcome of every check of [`Lvm2::validate`].
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct VgValidation {
+    /// One result per check, in [`VgCheck::ALL`] order.
+    pub checks: Vec<CheckResult>,
+}
+impl VgValidation {
+    /// Whether every check passed.
+    pub fn is_consistent(&self) -> bool {
+        self.checks.iter().all(CheckResult::passed)
+    }
+}
+
+/// What one check of [`Lvm2::validate`] found.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: VgCheck,
+    pub findings: Vec<Finding>,
+}
+impl CheckResult {
+    pub fn passed(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Physical extents `first_pe..=last_pe` of a PV that two segments both map.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ExtentOverlap {
+    pub pv: String,
+    pub first_pe: u64,
+    pub last_pe: u64,
+    pub lv: String,
+    pub segment: String,
+    pub other_lv: String,
+    pub other_segment: String,
+}
+
+/// A stripe of segment `segment` of `lv` mapping physical extents
+/// `first_pe..=last_pe` of `pv`, which the VG either does not list (`pe_count` is
+/// `None`) or which only has `pe_count` extents. Reads there fail or return another
+/// PV's data.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidStripe {
+    /// Seqno of the metadata the segment is from.
+    pub seqno: u64,
+    pub lv: String,
+    pub segment: String,
+    pub pv: String,
+    pub first_pe: u64,
+    pub last_pe: u64,
+    pub pe_count: Option<u64>,
+}
+
+impl VolumeGroup {
+    /// Segment stripes referring to PVs missing from `physical_volumes`, or to
+    /// extents beyond a PV's `pe_count`.
+    pub fn invalid_stripes(&self) -> Vec<InvalidStripe> {
+        let mut invalid = Vec::new();
+        for lv in self.lvs_all() {
+            for (segment, desc) in lv.segments() {
+                for (pv, extents) in desc.pv_extents() {
+                    let pe_count = self.pv_by_name(pv).map(|pv| pv.pe_count());
+                    if extents.is_empty() || pe_count.is_some_and(|count| extents.end <= count) {
+                        continue;
+                    }
+                    invalid.push(InvalidStripe {
+                        seqno: self.config().seqno,
+                        lv: lv.name().into(),
+                        segment: segment.into(),
+                        pv: pv.into(),
+                        first_pe: extents.start,
+                        last_pe: extents.end - 1,
+                        pe_count,
+                    });
+                }
+            }
+        }
+        invalid
+    }
+
+    /// Physical extents that more than one segment maps. Only segments stored on PVs
+    /// directly are considered, so LVs stacked on others (thin volumes, snapshots,
+    /// RAID images) never count as overlaps; a real one means corruption or tampering.
+    pub fn overlapping_extents(&self) -> Vec<ExtentOverlap> {
+        let mut areas: Vec<(&str, u64, u64, &str, &str)> = Vec::new();
+        for lv in self.lvs_all() {
+            for (segment, desc) in lv.segments() {
+                for (pv, extents) in desc.pv_extents() {
+                    if !extents.is_empty() {
+                        areas.push((pv, extents.start, extents.end, lv.name(), segment));
+                    }
+                }
+            }
+        }
+        areas.sort_unstable();
+
+        let mut overlaps = Vec::new();
+        for (i, &(pv, _, end, lv, segment)) in areas.iter().enumerate() {
+            // areas on the same PV starting before this one ends
+            for &(other_pv, other_start, other_end, other_lv, other_segment) in &areas[i + 1..] {
+                if other_pv != pv || other_start >= end {
+                    break;
+                }
+                overlaps.push(ExtentOverlap {
+                    pv: pv.into(),
+                    first_pe: other_start,
+                    last_pe: end.min(other_end) - 1,
+                    lv: lv.into(),
+                    segment: segment.into(),
+                    other_lv: other_lv.into(),
+                    other_segment: other_segment.into(),
+                });
+            }
+        }
+        overlaps
+    }
+}
+
+impl Lvm2 {
+    /// Runs every structural check on the headers, checksums, PV sizes, extent
+    /// accounting and segment references, and groups what [`Lvm2::findings`] reports
+    /// by check.
+    pub fn validate(&self) -> VgValidation {
+        let mut checks: Vec<CheckResult> = VgCheck::ALL
+            .iter()
+            .map(|&check| CheckResult {
+                check,
+                findings: Vec::new(),
+            })
+            .collect();
+        for finding in self.findings() {
+            checks[finding.check() as usize].findings.push(finding);
+        }
+        VgValidation { checks }
+    }
+
+    /// Checks the label (see [`Pv::findings`](crate::Pv::findings)), headers and every known metadata generation for states a
+    /// normal LVM history does not produce. Timestamp and history checks need
+    /// [`Lvm2Options::load_history`](crate::Lvm2Options::load_history).
+    pub fn findings(&self) -> Vec<Finding> {
+        let generations: Vec<&VolumeGroup> = self
+            .historical_metadata()
+            .iter()
+            .chain(core::iter::once(self.vg()))
+            .collect();
+
+        let mut findings = self.pv().findings();
+        let current = self.vg().config().seqno;
+        for old in self.historical_metadata() {
+            if old.config().seqno > current {
+                findings.push(Finding::HigherSeqnoInHistory {
+                    current,
+                    found: old.config().seqno,
+                });
+            }
+        }
+        // each generation's timestamp is checked against the last one before it
+        let mut previous = None;
+        for generation in generations {
+            findings.extend(self.generation_findings(generation, previous));
+            if let Some(creation_time) = generation.header().creation_time {
+                previous = Some((generation.config().seqno, creation_time));
+            }
+        }
+        findings.extend(
+            self.vg()
+                .overlapping_extents()
+                .into_iter()
+                .map(Finding::OverlappingExtents),
+        );
+        let header = self
+            .pv_header()
+            .data_descriptors
+            .first()
+            .map(|dd| dd.offset);
+        let metadata = self
+            .vg()
+            .pv_by_name(self.pv_name())
+            .map(|pv| pv.pe_start() * 512);
+        if let (Some(header), Some(metadata)) = (header, metadata) {
+            if header != metadata {
+                findings.push(Finding::PeStartMismatch { header, metadata });
+            }
+        }
+        let header = self.pv_header().pv_size;
+        let metadata = self
+            .vg()
+            .pv_by_name(self.pv_name())
+            .and_then(|pv| pv.dev_size())
+            .map(|size| size.saturating_mul(512));
+        if let Some(metadata) = metadata {
+            // LVM leaves the header's size at 0 when it does not know it
+            if header != 0 && header != metadata {
+                findings.push(Finding::PvSizeMismatch { header, metadata });
+            }
+        }
+        findings
+    }
+
+    // `previous` is the seqno and creation time of the last generation before with one
+    fn generation_findings(
+        &self,
+        generation: &VolumeGroup,
+        previous: Option<(u64, Timestamp)>,
+    ) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let seqno = generation.config().seqno;
+        if let Some(creation_time) = generation.header().creation_time {
+            if let Some((previous_seqno, previous_creation_time)) = previous {
+                if creation_time < previous_creation_time {
+                    findings.push(Finding::TimestampOutOfOrder {
+                        seqno,
+                        creation_time,
+                        previous_seqno,
+                        previous_creation_time,
+                    });
+                }
+            }
+
+            for lv in generation.lvs_all() {
+                let lv_creation_time = lv.raw_metadata().creation_time;
+                if lv_creation_time > creation_time {
+                    findings.push(Finding::LvCreatedAfterMetadata {
+                        seqno,
+                        lv: lv.name().into(),
+                        lv_creation_time,
+                        metadata_creation_time: creation_time,
+                    });
+                }
+            }
+        }
+
+        findings.extend(
+            generation
+                .invalid_stripes()
+                .into_iter()
+                .map(Finding::InvalidStripe),
+        );
+
+        let extent_size = generation.config().extent_size;
+        for pv in generation.pvs() {
+            let data_end = pv
+                .pe_start()
+                .saturating_add(pv.pe_count().saturating_mul(extent_size));
+            if let Some(dev_size) = pv.dev_size().filter(|&size| data_end > size) {
+                findings.push(Finding::ExtentsPastDeviceEnd {
+                    seqno,
+                    pv: pv.name().into(),
+                    data_end,
+                    dev_size,
+                });
+            }
+        }
+
+        for lv in generation.lvs_all() {
+            let declared = lv.raw_metadata().segment_count;
+            let found = lv.segments().count();
+            if declared != found {
+                findings.push(Finding::SegmentCountMismatch {
+                    seqno,
+                    lv: lv.name().into(),
+                    declared,
+                    found,
+                });
+            }
+            if lv.is_reshaping() {
+                let mut flags: Vec<String> = Vec::new();
+                for (_, segment) in lv.segments() {
+                    for flag in &segment.type_flags {
+                        if !flags.contains(flag) {
+                            flags.push(flag.clone());
+                        }
+                    }
+                }
+                findings.push(Finding::RaidReshapeInProgress {
+                    seqno,
+                    lv: lv.name().into(),
+                    flags,
+                });
+            }
+            let validation = lv.validate(self);
+            if !validation.gaps.is_empty() || !validation.overlaps.is_empty() {
+                findings.push(Finding::InvalidLvMapping {
+                    seqno,
+                    lv: lv.name().into(),
+                    gaps: validation.gaps,
+                    overlaps: validation.overlaps,
+                });
+            }
+            for (segment, desc) in lv.segments() {
+                for target in desc.kind.referenced_lvs() {
+                    if !generation.config().logical_volumes.contains_key(target) {
+                        findings.push(Finding::MissingLvReference {
+                            seqno,
+                            lv: lv.name().into(),
+                            segment: segment.into(),
+                            target: target.into(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (lv, error) in generation.invalid_lvs() {
+            findings.push(Finding::InvalidLvMetadata {
+                seqno,
+                lv: lv.into(),
+                reason: error.to_string(),
+            });
+        }
+
+        if let Some(pv) = generation.pv_by_name(self.pv_name()) {
+            if !LvmUuid::matches(pv.id(), self.pv().id()) {
+                findings.push(Finding::PvUuidMismatch {
+                    seqno,
+                    pv: pv.name().into(),
+                    label_id: self.pv().uuid().to_string(),
+                    metadata_id: pv.id().into(),
+                });
+            }
+        }
+        findings
+    }
+}
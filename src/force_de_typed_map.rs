@@ -1,7 +1,7 @@
 use core::marker::PhantomData;
 
 use alloc::collections::BTreeMap;
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, Debug)]
 pub struct ForceDeTypedMap<K, V>(pub BTreeMap<K, V>);
@@ -47,3 +47,12 @@ impl<'de, K: Deserialize<'de> + Ord, V: Deserialize<'de>> Deserialize<'de>
         deserializer.deserialize_map(MyVisitor::<K, V>(PhantomData, PhantomData))
     }
 }
+
+impl<K: Serialize, V: Serialize> Serialize for ForceDeTypedMap<K, V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
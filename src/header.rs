@@ -1,7 +1,6 @@
 use alloc::borrow::ToOwned;
 use alloc::string::String;
 use alloc::vec::Vec;
-use log::debug;
 use nom::bytes::complete::take;
 use nom::bytes::streaming::tag;
 use nom::error::ParseError;
@@ -9,6 +8,7 @@ use nom::multi::many_till;
 use nom::number::complete::le_u32;
 use nom::number::streaming::le_u64;
 use nom::IResult;
+use tracing::debug;
 
 #[derive(Debug)]
 pub struct PhysicalVolumeLabelHeader {
@@ -41,6 +41,36 @@ pub struct PhysicalVolumeHeader {
     pub pv_size: u64,
     pub data_descriptors: Vec<DataDescriptor>,
     pub metadata_descriptors: Vec<DataDescriptor>,
+    /// Written by LVM 2.02.110 and later, after the metadata area descriptors.
+    pub extension: Option<PvHeaderExtension>,
+}
+
+/// The extension of the PV header following its descriptor lists.
+#[derive(Debug)]
+pub struct PvHeaderExtension {
+    pub version: u32,
+    /// `PV_EXT_USED` (1) once the PV belongs to a VG.
+    pub flags: u32,
+    /// Areas reserved for a bootloader with `pvcreate --bootloaderareasize`, in bytes
+    /// of the PV. LVM allocates at most one.
+    pub bootloader_descriptors: Vec<DataDescriptor>,
+}
+
+impl PvHeaderExtension {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, version) = le_u32(input)?;
+        let (input, flags) = le_u32(input)?;
+        let (input, (bootloader_descriptors, _)) =
+            many_till(DataDescriptor::parse, tag(&[0u8; 16]))(input)?;
+        Ok((
+            input,
+            Self {
+                version,
+                flags,
+                bootloader_descriptors,
+            },
+        ))
+    }
 }
 
 impl PhysicalVolumeHeader {
@@ -59,6 +89,12 @@ impl PhysicalVolumeHeader {
             many_till(DataDescriptor::parse, tag(&[0u8; 16]))(input)?;
         let (input, (metadata_descriptors, _)) =
             many_till(DataDescriptor::parse, tag(&[0u8; 16]))(input)?;
+        // older PVs leave zeroes here, and a damaged extension is no reason to reject
+        // the header
+        let (input, extension) = match PvHeaderExtension::parse(input) {
+            Ok((rest, extension)) if extension.version != 0 => (rest, Some(extension)),
+            _ => (input, None),
+        };
         Ok((
             input,
             Self {
@@ -66,6 +102,7 @@ impl PhysicalVolumeHeader {
                 pv_size,
                 data_descriptors,
                 metadata_descriptors,
+                extension,
             },
         ))
     }
@@ -106,8 +143,11 @@ impl MetadataAreaHeader {
 
         for loc in &location_descriptors {
             debug!(
-                "LocationDescriptor: data_area_offset: {}, data_area_size: {}, checksum: {}, flags: {}",
-                loc.data_area_offset, loc.data_area_size, loc.checksum, loc.flags
+                data_area_offset = loc.data_area_offset,
+                data_area_size = loc.data_area_size,
+                checksum = loc.checksum,
+                flags = loc.flags,
+                "LocationDescriptor"
             );
         }
         Ok((
@@ -0,0 +1,47 @@
+// history.rs
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use crate::metadata::MetadataChange;
+use crate::{Lvm2, MetadataHeader, VolumeGroup};
+
+/// One generation of the VG metadata and what changed in the generation after it.
+#[derive(Serialize, Clone)]
+pub struct MetadataGeneration<'a> {
+    pub seqno: u64,
+    #[serde(flatten)]
+    pub header: &'a MetadataHeader,
+    /// Whether the generation came from an archive file, see
+    /// [`Lvm2::add_history`].
+    pub archived: bool,
+    /// Differences to the next recovered generation, `None` for the current one.
+    pub changes: Option<Vec<MetadataChange<'a>>>,
+    #[serde(skip)]
+    pub vg: &'a VolumeGroup,
+}
+
+impl Lvm2 {
+    /// Every known generation of the VG metadata, the recovered ones (see
+    /// [`Lvm2Options::load_history`](crate::Lvm2Options::load_history) and
+    /// [`Lvm2::add_history`]) followed by the current one, ordered by seqno.
+    pub fn history(&self) -> Vec<MetadataGeneration<'_>> {
+        let generations: Vec<&VolumeGroup> = self
+            .historical_metadata()
+            .iter()
+            .chain(core::iter::once(self.vg()))
+            .collect();
+        generations
+            .iter()
+            .enumerate()
+            .map(|(i, vg)| MetadataGeneration {
+                seqno: vg.config().seqno,
+                header: vg.header(),
+                archived: self.is_archived(vg.config().seqno),
+                changes: generations
+                    .get(i + 1)
+                    .map(|next| vg.metadata().diff(next.metadata())),
+                vg,
+            })
+            .collect()
+    }
+}
@@ -1,26 +1,32 @@
 // lvm2.rs
-//#![no_std]
+//! Without the default `std` feature the crate builds on `alloc` and reads through
+//! `acid_io`'s traits. That only works on unix and windows targets, the ones `acid_io`
+//! has its no_std I/O types for; `wasm32-unknown-unknown` builds keep `std`.
+#![cfg_attr(not(feature = "std"), no_std)]
 extern crate alloc;
 
 use acid_io::{Read, Seek, SeekFrom};
 use alloc::string::{String, ToString};
-use header::PhysicalVolumeHeader;
-use log::debug;
-use serde::Deserialize;
-use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use alloc::vec::Vec;
+use core::ops::Range;
+use snafu::{ensure, OptionExt, Snafu};
 
-use crate::header::{MetadataAreaHeader, PhysicalVolumeLabelHeader};
-use crate::metadata::{deserialize::MetadataElements, MetadataRoot};
+use crate::header::{MetadataAreaHeader, PhysicalVolumeHeader, PhysicalVolumeLabelHeader};
+use crate::metadata::Segment;
 
 // Vocabulary: in this crate we use the term "sheet" to describe a block of exactly 512 bytes
 // (to avoid confusion around the word "sector")
 // // spec: https://github.com/libyal/libvslvm/blob/ab09a380072448d9c84c886d487d8c3dfa2d1527/documentation/Logical%20Volume%20Manager%20(LVM)%20format.asciidoc#2-physical-volume-label
 
+/// An opened PV together with the VG it belongs to.
 pub struct Lvm2 {
-    pvh: PhysicalVolumeHeader,
+    pv: Pv,
+    vg: VolumeGroup,
     pv_name: String,
-    vg_name: String,
-    vg_config: MetadataRoot,
+    pv_id: String,
+    history: Vec<VolumeGroup>,
+    // seqnos of the generations in `history` that came from `add_history`
+    archived: Vec<u64>,
 }
 
 #[derive(Debug, Snafu)]
@@ -33,8 +39,34 @@ pub enum Error {
         source: acid_io::Error,
     },
     WrongMagic,
-    ParseError {
-        error: String,
+    #[snafu(display("no PV label in bytes {}..{}", scanned.start, scanned.end))]
+    LabelNotFound {
+        scanned: Range<u64>,
+    },
+    #[snafu(display("invalid PV label at byte {offset}: expected {expected}"))]
+    LabelParse {
+        offset: u64,
+        expected: &'static str,
+    },
+    #[snafu(display("invalid PV header at byte {offset}: expected {expected}"))]
+    PvHeaderParse {
+        offset: u64,
+        expected: &'static str,
+    },
+    #[snafu(display("invalid metadata area header at byte {offset}: expected {expected}"))]
+    MdaHeaderParse {
+        offset: u64,
+        expected: &'static str,
+    },
+    #[snafu(display(
+        "invalid metadata text at line {line}, column {column}: expected {expected}"
+    ))]
+    MetadataParse {
+        /// Byte offset within the metadata text.
+        offset: usize,
+        line: usize,
+        column: usize,
+        expected: &'static str,
     },
     MultipleVGsError,
     PVDoesntContainItself,
@@ -46,6 +78,154 @@ pub enum Error {
         source: serde::de::value::Error,
     },
     MissingMetadata,
+    #[snafu(display("metadata at byte {offset} is {size} bytes, over the {limit} byte limit"))]
+    MetadataTooLarge {
+        offset: u64,
+        size: u64,
+        limit: u64,
+    },
+    #[snafu(display(
+        "{structure} at byte {offset}: checksum mismatch (stored {expected:#010x}, computed {computed:#010x})"
+    ))]
+    ChecksumMismatch {
+        structure: &'static str,
+        offset: u64,
+        expected: u32,
+        computed: u32,
+    },
+    #[snafu(display("PV {pv} belongs to VG {found}, expected {expected}"))]
+    VGMismatch {
+        pv: String,
+        expected: String,
+        found: String,
+    },
+    #[snafu(display("PV {pv} carries metadata seqno {found}, expected {expected}"))]
+    SeqnoMismatch {
+        pv: String,
+        expected: u64,
+        found: u64,
+    },
+    #[snafu(display("PV {pv} is not listed in the VG metadata"))]
+    PVNotInVG {
+        pv: String,
+    },
+    #[snafu(display(
+        "PV {pv} was supplied more than once, e.g. as a disk and its clone; keep one or pick it with VolumeGroupSet::replace"
+    ))]
+    DuplicatePv {
+        pv: String,
+    },
+    #[snafu(display("no PV supplied belongs to a VG named or with UUID {vg}"))]
+    VgNotFound {
+        vg: String,
+    },
+    #[snafu(display("several VGs are named {vg}, select one by UUID: {ids}"))]
+    AmbiguousVg {
+        vg: String,
+        ids: String,
+    },
+    #[snafu(display("LV {lv}: physical extent {pe} has been reallocated since it was deleted"))]
+    ExtentsReallocated {
+        lv: String,
+        pe: u64,
+    },
+    #[snafu(display("LV {lv} {segment}: {kind} segments cannot be mapped"))]
+    UnsupportedSegment {
+        lv: String,
+        segment: String,
+        kind: String,
+    },
+    #[snafu(display("LV {lv}: extent {extent} is {problem}"))]
+    InvalidMapping {
+        lv: String,
+        extent: u64,
+        problem: &'static str,
+    },
+    #[snafu(display("LV {lv}: its data is on PV {pv}, which is not open"))]
+    PVNotAvailable {
+        lv: String,
+        pv: String,
+    },
+    #[snafu(display("LV {lv} is not a snapshot"))]
+    NotASnapshot {
+        lv: String,
+    },
+    #[snafu(display("COW store {lv}: {reason}"))]
+    InvalidCowStore {
+        lv: String,
+        reason: &'static str,
+    },
+    #[snafu(display("LV {lv} is not an old-style mirror"))]
+    NotAMirror {
+        lv: String,
+    },
+    #[snafu(display("LV {lv} is neither a RAID1 LV nor an old-style mirror"))]
+    NotMirrored {
+        lv: String,
+    },
+    #[snafu(display("LV {lv}: leg {leg} is missing"))]
+    MissingLeg {
+        lv: String,
+        leg: String,
+    },
+    #[snafu(display("mirror {lv}: {reason}"))]
+    InvalidMirrorLog {
+        lv: String,
+        reason: &'static str,
+    },
+    #[snafu(display("LV {lv} is not a thin volume"))]
+    NotAThinVolume {
+        lv: String,
+    },
+    #[snafu(display("thin pool {lv}: {reason}"))]
+    InvalidThinPool {
+        lv: String,
+        reason: &'static str,
+    },
+    #[snafu(display("LV {lv} is not a cached volume"))]
+    NotACachedVolume {
+        lv: String,
+    },
+    #[snafu(display("cache pool {lv}: {reason}"))]
+    InvalidCachePool {
+        lv: String,
+        reason: &'static str,
+    },
+    #[snafu(display("SHA-256 mismatch: expected {expected}, computed {computed}"))]
+    HashMismatch {
+        expected: String,
+        computed: String,
+    },
+}
+
+// Where in `input` a nom parser gave up, and what it was looking for there.
+pub(crate) fn nom_failure(
+    input: &[u8],
+    e: nom::Err<nom::error::Error<&[u8]>>,
+) -> (u64, &'static str) {
+    match e {
+        nom::Err::Incomplete(_) => (input.len() as u64, "more data"),
+        nom::Err::Error(e) | nom::Err::Failure(e) => (
+            (input.len() - e.input.len()) as u64,
+            match e.code {
+                nom::error::ErrorKind::Tag => "magic value",
+                nom::error::ErrorKind::Char => "valid UTF-8",
+                nom::error::ErrorKind::Eof => "more data",
+                nom::error::ErrorKind::ManyTill => "descriptor list terminator",
+                _ => "valid structure",
+            },
+        ),
+    }
+}
+
+// acid_io's own error type has no `Error::other`, and clippy wants it under std
+#[cfg(feature = "std")]
+pub(crate) fn io_error(msg: &'static str) -> acid_io::Error {
+    acid_io::Error::other(msg)
+}
+#[cfg(not(feature = "std"))]
+pub(crate) fn io_error(msg: &'static str) -> acid_io::Error {
+    acid_io::Error::new(acid_io::ErrorKind::Other, msg)
 }
 
 #[cfg(not(feature = "std"))]
@@ -77,11 +257,70 @@ mod no_std {
     }
 }
 
+mod allocation;
+mod audit;
+mod cache;
+mod carve;
+mod checksum;
+mod dm;
+mod dot;
+mod export;
+mod findings;
 mod force_de_typed_map;
-mod header;
+pub mod header;
+mod history;
 mod lv;
+mod lv_body;
 pub mod metadata;
+mod mirror;
+mod mirrored;
+mod options;
+mod persistent_data;
+mod pv;
+#[cfg(feature = "python")]
+mod python;
+mod relations;
+mod salvage;
+mod shared_reader;
+mod snapshot;
+mod summary;
+#[cfg(test)]
+mod test_image;
+mod thin;
+mod timestamp;
+mod uuid;
+#[cfg(feature = "hash")]
+mod verify;
+mod vg;
+mod vg_set;
+pub use allocation::*;
+#[cfg(feature = "std")]
+pub use audit::AccessLog;
+pub use audit::{PhysicalRead, ReadReason, ReadRecorder};
+pub use cache::*;
+pub use carve::*;
+pub use dm::*;
+pub use export::*;
+pub use findings::*;
+pub use history::*;
 pub use lv::*;
+pub use lv_body::*;
+pub use mirror::*;
+pub use mirrored::*;
+pub use options::*;
+pub use pv::*;
+pub use relations::*;
+pub use salvage::*;
+pub use shared_reader::*;
+pub use snapshot::*;
+pub use summary::*;
+pub use thin::*;
+pub use timestamp::*;
+pub use uuid::*;
+#[cfg(feature = "hash")]
+pub use verify::*;
+pub use vg::*;
+pub use vg_set::*;
 
 impl Lvm2 {
     // Public getters to expose pv_name and lvs for external use.
@@ -89,123 +328,217 @@ impl Lvm2 {
         &self.pv_name
     }
 
+    /// The on-disk PV this instance was opened from.
+    pub fn pv(&self) -> &Pv {
+        &self.pv
+    }
+
+    /// The VG described by the metadata.
+    pub fn vg(&self) -> &VolumeGroup {
+        &self.vg
+    }
+
+    /// The label header found in the second sheet of the PV.
+    pub fn label_header(&self) -> &PhysicalVolumeLabelHeader {
+        self.pv.label_header()
+    }
+
+    /// The PV header, including its data area and metadata area descriptors.
+    pub fn pv_header(&self) -> &PhysicalVolumeHeader {
+        self.pv.pv_header()
+    }
+
+    /// The bootloader area of the opened PV, in bytes of the PV: as the metadata
+    /// declares it, or else as the PV header extension does.
+    pub fn bootloader_area(&self) -> Option<Range<u64>> {
+        self.vg
+            .pv_by_name(&self.pv_name)
+            .and_then(|pv| pv.bootloader_area())
+            .or_else(|| self.pv.bootloader_area())
+    }
+
+    /// Reads the [`Lvm2::bootloader_area`], empty if there is none. A bootloader
+    /// embedded there is evidence in its own right.
+    pub fn read_bootloader_area<T: Read + Seek>(&self, reader: &mut T) -> Result<Vec<u8>, Error> {
+        match self.bootloader_area() {
+            Some(area) => self
+                .pv
+                .read_range(reader, area, || ReadReason::BootloaderArea),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The header of the metadata area the VG config was read from. Offsets in its
+    /// location descriptors are relative to `metadata_area_offset`.
+    pub fn metadata_area_header(&self) -> Option<&MetadataAreaHeader> {
+        self.pv.metadata_area_header()
+    }
+
+    /// The VG config text exactly as it was read from the metadata area.
+    pub fn raw_metadata_text(&self) -> &str {
+        self.vg.raw_metadata_text()
+    }
+
+    /// Looks up a value by its `/`-separated path within the VG section, for fields
+    /// the typed model does not cover, e.g. `logical_volumes/root/segment1/type`.
+    pub fn metadata_get(&self, path: &str) -> Option<&metadata::MetadataValue> {
+        self.vg.metadata_get(path)
+    }
+
+    /// The LVs users see, leaving out hidden ones like [`VolumeGroup::lvs`].
     pub fn lvs(&self) -> impl Iterator<Item = LV<'_>> + '_ {
-        self.vg_config
-            .logical_volumes
-            .iter()
-            .map(|(name, desc)| LV { name, desc })
+        self.vg.lvs()
+    }
+
+    /// All LVs, hidden ones included.
+    pub fn lvs_all(&self) -> impl Iterator<Item = LV<'_>> + '_ {
+        self.vg.lvs_all()
+    }
+
+    pub fn pvs(&self) -> impl Iterator<Item = PV<'_>> + '_ {
+        self.vg.pvs()
     }
 
     // Modified to take a mutable reference for the reader.
     pub fn open<T: Read + Seek>(reader: &mut T) -> Result<Self, Error> {
-        reader.seek(SeekFrom::Start(512)).context(IoSnafu)?; // skip zero sheet
-
-        let mut buf = [0u8; 512];
-        reader.read_exact(&mut buf).context(IoSnafu)?; // read header
-        tracing::trace!(?buf);
-
-        let (_, vhl) = PhysicalVolumeLabelHeader::parse(&buf).map_err(|e| Error::ParseError {
-            error: e.to_string(),
-        })?;
-        debug!(
-            "PhysicalVolumeLabelHeader: sector_number: {}, checksum: {}, data_offset: {}",
-            vhl.sector_number, vhl.checksum, vhl.data_offset
-        );
-        let (_, pvh) =
-            PhysicalVolumeHeader::parse(&buf[(vhl.data_offset as usize)..]).map_err(|e| {
-                Error::ParseError {
-                    error: e.to_string(),
-                }
-            })?;
-        tracing::trace!(?pvh);
+        Self::open_with(reader, &Lvm2Options::default())
+    }
 
-        debug!(
-            "PhysicalVolumeHeader: pv_ident: {}, pv_size: {}",
-            pvh.pv_ident, pvh.pv_size
-        );
+    /// Opens a PV starting `start_offset` bytes into `reader`, e.g. a partition on a
+    /// whole-disk image. LVs opened later are read from the same coordinates.
+    pub fn open_at<T: Read + Seek>(reader: &mut T, start_offset: u64) -> Result<Self, Error> {
+        Self::open_with(reader, &Lvm2Options::default().start_offset(start_offset))
+    }
 
-        let metadata_descriptor = pvh
-            .metadata_descriptors
-            .first()
-            .context(MissingMetadataSnafu)?;
-
-        reader
-            .seek(acid_io::SeekFrom::Start(metadata_descriptor.offset))
-            .context(IoSnafu)?; // skip zero sheet
-        reader.read_exact(&mut buf).context(IoSnafu)?;
-        let (_, mah) = MetadataAreaHeader::parse(&buf).map_err(|e| Error::ParseError {
-            error: e.to_string(),
-        })?;
-        tracing::trace!(?mah);
-        debug!(
-            "MetadataAreaHeader: checksum: {}, version: {}, metadata_area_offset: {}, metadata_area_size: {}",
-            mah.checksum, mah.version, mah.metadata_area_offset, mah.metadata_area_size
-        );
+    pub fn open_with<T: Read + Seek>(reader: &mut T, options: &Lvm2Options) -> Result<Self, Error> {
+        let span = tracing::debug_span!(
+            "open",
+            pv = tracing::field::Empty,
+            vg = tracing::field::Empty
+        )
+        .entered();
+        let mut pv = Pv::open_with(reader, options)?;
+        span.record("pv", pv.id());
+        let vg = if options.allow_multiple_vgs {
+            let mut vgs = VolumeGroup::parse_all(pv.load_metadata(reader)?)?;
+            let index = vgs
+                .iter()
+                .position(|vg| vg.pv_by_id(pv.id()).is_some())
+                .context(PVDoesntContainItselfSnafu)?;
+            vgs.swap_remove(index)
+        } else {
+            pv.volume_group(reader)?
+        };
+        span.record("vg", vg.name());
 
-        let mut metadata = String::new();
-        for locdesc in &mah.location_descriptors {
-            reader
-                .seek(acid_io::SeekFrom::Start(
-                    metadata_descriptor.offset + locdesc.data_area_offset,
-                ))
-                .context(IoSnafu)?; // skip zero sheet
-            reader
-                .by_ref()
-                .take(locdesc.data_area_size)
-                .read_to_string(&mut metadata)
-                .context(IoSnafu)?;
-        }
-        tracing::debug!(%metadata);
+        let mut history: Vec<VolumeGroup> = pv
+            .historical_metadata_texts()
+            .iter()
+            .filter_map(|text| VolumeGroup::parse_all(text).ok())
+            .flatten()
+            .filter(|old| old.id() == vg.id() && old.config().seqno != vg.config().seqno)
+            .collect();
+        history.sort_by_key(|old| old.config().seqno);
+        history.dedup_by_key(|old| old.config().seqno);
+
+        let mut lvm = Self::from_parts(pv, vg)?;
+        lvm.history = history;
+        Ok(lvm)
+    }
 
-        let (trailing_garbage, metadata) =
-            MetadataElements::parse(&metadata).map_err(|e| Error::ParseError {
-                error: e.to_string(),
-            })?;
-        tracing::debug!(?trailing_garbage, ?metadata);
+    /// Looks for PVs starting at every `step` bytes within `range` of `reader`, and
+    /// returns each one found with its start offset. Candidates that fail to open,
+    /// including on IO errors, are skipped.
+    pub fn scan<T: Read + Seek>(reader: &mut T, range: Range<u64>, step: u64) -> Vec<(u64, Pv)> {
+        Self::scan_with(reader, range, step, &Lvm2Options::default())
+    }
 
-        let meta_root =
-            force_de_typed_map::ForceDeTypedMap::<String, MetadataRoot>::deserialize(&metadata)
-                .context(SerdeSnafu)?;
-        tracing::debug!(?meta_root);
+    pub fn scan_with<T: Read + Seek>(
+        reader: &mut T,
+        range: Range<u64>,
+        step: u64,
+        options: &Lvm2Options,
+    ) -> Vec<(u64, Pv)> {
+        let _span = tracing::debug_span!("scan", start = range.start, end = range.end).entered();
+        let mut found = Vec::new();
+        let mut start = range.start;
+        while start < range.end {
+            let options = options.clone().start_offset(start);
+            // a label is only accepted in the sector it names, so a PV is not also
+            // found from the candidates just before it
+            if let Ok(pv) = Pv::open_with(reader, &options) {
+                tracing::debug!(offset = start, pv = pv.id(), "found PV");
+                found.push((start, pv));
+            }
+            start = start.saturating_add(step.max(1));
+        }
+        found
+    }
 
-        ensure!(meta_root.0.len() == 1, MultipleVGsSnafu);
-        let (vg_name, vg_config) = meta_root.0.into_iter().next().unwrap();
+    /// Older generations of this VG's metadata recovered from the metadata area,
+    /// ordered by seqno. Only populated when opened with [`Lvm2Options::load_history`]
+    /// or through [`Lvm2::add_history`].
+    pub fn historical_metadata(&self) -> &[VolumeGroup] {
+        &self.history
+    }
 
-        let pv_name = vg_config
-            .physical_volumes
-            .iter()
-            .find(|(_, v)| v.id.replace('-', "") == pvh.pv_ident)
-            .context(PVDoesntContainItselfSnafu)?
-            .0
-            .clone();
+    /// Pairs a PV with a VG, which may have been parsed from another PV's metadata.
+    pub fn from_parts(pv: Pv, vg: VolumeGroup) -> Result<Self, Error> {
+        let entry = vg
+            .pvs()
+            .find(|v| LvmUuid::matches(v.id(), pv.id()))
+            .context(PVDoesntContainItselfSnafu)?;
+        let (pv_name, pv_id) = (entry.name().to_string(), entry.id().to_string());
 
         Ok(Self {
-            pvh,
+            pv,
+            vg,
             pv_name,
-            vg_name,
-            vg_config,
+            pv_id,
+            history: Vec::new(),
+            archived: Vec::new(),
         })
     }
 
+    /// Adds generations recovered elsewhere, e.g. parsed by [`VolumeGroup::parse`]
+    /// from `/etc/lvm/archive` files, to [`Lvm2::historical_metadata`]. Generations
+    /// of other VGs, of the current seqno or of one already known are left out, so
+    /// a copy in the metadata area is kept over an archived one.
+    pub fn add_history(&mut self, generations: impl IntoIterator<Item = VolumeGroup>) {
+        for vg in generations {
+            let seqno = vg.config().seqno;
+            let known = seqno == self.vg.config().seqno
+                || self.history.iter().any(|old| old.config().seqno == seqno);
+            if vg.id() == self.vg.id() && !known {
+                self.archived.push(seqno);
+                self.history.push(vg);
+            }
+        }
+        self.history.sort_by_key(|old| old.config().seqno);
+    }
+
+    /// Whether the generation of this seqno was added by [`Lvm2::add_history`]
+    /// rather than recovered from the metadata area.
+    pub fn is_archived(&self, seqno: u64) -> bool {
+        self.archived.contains(&seqno)
+    }
+
     // Modified LV open functions: they now take a mutable reference for the reader.
     pub fn open_lv_by_name<'a, 'r, T: Read + Seek>(
         &'a self,
         name: &str,
         reader: &'r mut T,
     ) -> Option<OpenLV<'a, 'r, T>> {
-        self.vg_config
-            .logical_volumes
-            .get_key_value(name)
-            .map(move |(name, desc)| self.open_lv(LV { name, desc }, reader))
+        self.vg
+            .lv_by_name(name)
+            .map(move |lv| self.open_lv(lv, reader))
     }
     pub fn open_lv_by_id<'a, 'r, T: Read + Seek>(
         &'a self,
         id: &str,
         reader: &'r mut T,
     ) -> Option<OpenLV<'a, 'r, T>> {
-        self.lvs()
-            .find(|lv| lv.id() == id)
-            .map(move |lv| self.open_lv(lv, reader))
+        self.vg.lv_by_id(id).map(move |lv| self.open_lv(lv, reader))
     }
     pub fn open_lv<'a, 'r, T: Read + Seek>(
         &'a self,
@@ -214,25 +547,328 @@ impl Lvm2 {
     ) -> OpenLV<'a, 'r, T> {
         OpenLV {
             lv,
-            lvm: self,
-            reader,
+            pvs: alloc::vec![PvHandle {
+                name: &self.pv_name,
+                pv: &self.pv,
+                reader,
+            }],
             position: 0,
-            current_segment_end: 0,
         }
     }
 
+    /// As [`Lvm2::open_lv`], but checks the whole mapping first rather than failing on
+    /// the first read that hits a problem: every extent of the LV has to be mapped by
+    /// exactly one linear or striped segment, onto this PV and within its extents.
+    pub fn try_open_lv<'a, 'r, T: Read + Seek>(
+        &'a self,
+        lv: LV<'a>,
+        reader: &'r mut T,
+    ) -> Result<OpenLV<'a, 'r, T>, Error> {
+        let pe_count = self
+            .vg
+            .pv_by_name(&self.pv_name)
+            .map_or(0, |pv| pv.pe_count());
+        let mut next = 0;
+        for (key, segment) in lv.segments() {
+            let invalid = |extent, problem| InvalidMappingSnafu {
+                lv: lv.name(),
+                extent,
+                problem,
+            };
+            ensure!(
+                segment.start_extent <= next,
+                invalid(next, "mapped by no segment")
+            );
+            ensure!(
+                segment.start_extent == next,
+                invalid(segment.start_extent, "mapped by more than one segment")
+            );
+            ensure!(
+                matches!(
+                    segment.kind,
+                    Segment::Linear { .. } | Segment::Striped { .. }
+                ) && !segment.stripes().is_empty(),
+                UnsupportedSegmentSnafu {
+                    lv: lv.name(),
+                    segment: key,
+                    kind: segment.kind.type_name(),
+                }
+            );
+            let stripes = segment.stripes().len() as u64;
+            for stripe in segment.stripes() {
+                ensure!(
+                    stripe.pv == self.pv_name,
+                    PVNotAvailableSnafu {
+                        lv: lv.name(),
+                        pv: &stripe.pv,
+                    }
+                );
+                // reported at about the LV extent the stripe leaves the PV at
+                let fits = pe_count.saturating_sub(stripe.start_extent);
+                ensure!(
+                    segment.area_len() <= fits,
+                    invalid(
+                        segment.start_extent + fits.saturating_mul(stripes),
+                        "mapped past the last extent of its PV"
+                    )
+                );
+            }
+            next = segment.extents().end;
+        }
+        Ok(self.open_lv(lv, reader))
+    }
+
     pub fn pv_id(&self) -> &str {
-        &self.vg_config.physical_volumes[&self.pv_name].id
+        &self.pv_id
     }
 
     pub fn vg_name(&self) -> &str {
-        &self.vg_name
+        self.vg.name()
     }
     pub fn vg_id(&self) -> &str {
-        &self.vg_config.id
+        self.vg.id()
     }
 
     pub fn extent_size(&self) -> u64 {
-        self.vg_config.extent_size * 512
+        self.vg.extent_size()
+    }
+
+    /// Where the PV starts in the reader it was opened from.
+    pub fn start_offset(&self) -> u64 {
+        self.pv.start_offset()
+    }
+
+    /// Translates an offset within this PV's data area into an offset within the PV.
+    pub fn data_offset(&self, offset: u64) -> Option<u64> {
+        self.pv.data_offset(offset)
+    }
+
+    // reads from byte `offset` of this PV's data area into `buf` for segment `segment`
+    // of `lv`, as an OpenLV reads the segments it maps
+    pub(crate) fn read_data_area<T: Read + Seek>(
+        &self,
+        offset: u64,
+        buf: &mut [u8],
+        reader: &mut T,
+        lv: &str,
+        segment: &str,
+    ) -> acid_io::Result<usize> {
+        let seek_target = self
+            .data_offset(offset)
+            .ok_or(io_error("data is beyond the end of this PV"))?;
+        reader.seek(SeekFrom::Start(self.start_offset() + seek_target))?;
+        let n = reader.read(buf)?;
+        self.pv
+            .options()
+            .record(seek_target, n as u64, || ReadReason::LvData {
+                lv: lv.into(),
+                segment: segment.into(),
+            });
+        Ok(n)
+    }
+
+    /// Byte offset within this PV of the physical extent `pe`.
+    pub fn physical_extent_offset(&self, pe: u64) -> Option<u64> {
+        self.data_offset(pe.checked_mul(self.extent_size())?)
+    }
+
+    /// Runs of physical extents on this PV that are not mapped by any LV segment.
+    pub fn unallocated_extents(&self) -> Vec<Range<u64>> {
+        self.pv_allocation()
+            .map_or_else(Vec::new, |allocation| allocation.unallocated().collect())
+    }
+
+    fn pe_count(&self) -> u64 {
+        self.vg
+            .pv_by_name(&self.pv_name)
+            .map_or(0, |pv| pv.pe_count())
+    }
+
+    // the runs of this PV's physical extents the segments of `lvs` map, sorted and
+    // merged, where `pv_name` is what their metadata calls this PV
+    fn allocated_runs<'b>(
+        &self,
+        lvs: impl Iterator<Item = LV<'b>>,
+        pv_name: &str,
+    ) -> Vec<Range<u64>> {
+        let pe_count = self.pe_count();
+        let mut areas: Vec<Range<u64>> = lvs
+            .flat_map(|lv| lv.segments())
+            .flat_map(|(_, seg)| seg.pv_extents())
+            .filter(|&(pv, _)| pv == pv_name)
+            .map(|(_, extents)| extents.start..extents.end.min(pe_count))
+            .filter(|extents| !extents.is_empty())
+            .collect();
+        areas.sort_unstable_by_key(|extents| extents.start);
+        let mut runs: Vec<Range<u64>> = Vec::new();
+        for extents in areas {
+            match runs.last_mut() {
+                Some(run) if extents.start <= run.end => run.end = run.end.max(extents.end),
+                _ => runs.push(extents),
+            }
+        }
+        runs
+    }
+
+    /// LVs that only appear in [`Lvm2::historical_metadata`], each taken from the
+    /// newest generation that still lists it.
+    pub fn deleted_lvs(&self) -> Vec<DeletedLv<'_>> {
+        let mut deleted: Vec<DeletedLv<'_>> = Vec::new();
+        for generation in self.history.iter().rev() {
+            for lv in generation.lvs_all() {
+                let known = self.vg.lv_by_id(lv.id()).is_some()
+                    || deleted.iter().any(|d| LvmUuid::matches(d.lv.id(), lv.id()));
+                if !known {
+                    deleted.push(DeletedLv { generation, lv });
+                }
+            }
+        }
+        deleted
+    }
+
+    /// When `deleted` was removed, as far as the metadata tells: the time the first
+    /// generation after [`DeletedLv::generation`] was written.
+    pub fn removal_time(&self, deleted: &DeletedLv<'_>) -> Option<Timestamp> {
+        let seqno = deleted.generation.config().seqno;
+        self.history
+            .iter()
+            .chain(core::iter::once(&self.vg))
+            .find(|vg| vg.config().seqno > seqno)?
+            .header()
+            .creation_time
+    }
+
+    /// Runs of physical extents of this PV that a deleted LV used to map and a
+    /// current LV maps now, in extent order. Their old contents are most likely gone.
+    pub fn reallocated_extents(&self, deleted: &DeletedLv<'_>) -> Vec<Range<u64>> {
+        let Some(old_name) = deleted.generation.pv_by_id(&self.pv_id).map(|pv| pv.name()) else {
+            return Vec::new();
+        };
+        let then = self.allocated_runs(core::iter::once(deleted.lv), old_name);
+        let now = self.allocated_runs(self.lvs_all(), &self.pv_name);
+        let mut reallocated = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while let (Some(old), Some(new)) = (then.get(i), now.get(j)) {
+            let both = old.start.max(new.start)..old.end.min(new.end);
+            if !both.is_empty() {
+                reallocated.push(both);
+            }
+            // move past whichever run ends first
+            if old.end <= new.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        reallocated
+    }
+
+    /// Opens a deleted LV with the mappings it had in its last generation, provided
+    /// none of its extents on this PV have been handed to another LV since.
+    pub fn open_deleted_lv<'a, 'r, T: Read + Seek>(
+        &'a self,
+        deleted: &DeletedLv<'a>,
+        reader: &'r mut T,
+    ) -> Result<OpenLV<'a, 'r, T>, Error> {
+        if let Some(pe) = self
+            .reallocated_extents(deleted)
+            .first()
+            .map(|run| run.start)
+        {
+            return ExtentsReallocatedSnafu {
+                lv: deleted.lv.name(),
+                pe,
+            }
+            .fail();
+        }
+        // the old metadata may know this PV under another name
+        let name = deleted
+            .generation
+            .pv_by_id(&self.pv_id)
+            .map_or(self.pv_name.as_str(), |pv| pv.name());
+        Ok(OpenLV {
+            lv: deleted.lv,
+            pvs: alloc::vec![PvHandle {
+                name,
+                pv: &self.pv,
+                reader,
+            }],
+            position: 0,
+        })
+    }
+}
+
+/// An LV missing from the current metadata, as described by an older generation.
+#[derive(Clone, Copy)]
+pub struct DeletedLv<'a> {
+    /// The newest generation that lists the LV.
+    pub generation: &'a VolumeGroup,
+    pub lv: LV<'a>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_image::{self, EXTENT};
+    use acid_io::Read;
+
+    const STRIPED: &str = "start_extent = 0\nextent_count = 8\ntype = \"striped\"\n\
+                           stripe_count = 2\nstripe_size = 2\nstripes = [\"pv0\", 0, \"pv0\", 10]";
+
+    // the data area byte [`STRIPED`] keeps LV byte `offset` at, in chunks of 1 KiB
+    fn striped_offset(offset: u64) -> u64 {
+        let chunk = offset / 1024;
+        let start = [0, 10 * EXTENT][(chunk % 2) as usize];
+        start + chunk / 2 * 1024 + offset % 1024
+    }
+
+    fn open(lvs: &str) -> (Lvm2, acid_io::Cursor<Vec<u8>>) {
+        let mut reader = test_image::reader(test_image::image(lvs, 20));
+        let lvm = Lvm2::open(&mut reader).unwrap();
+        (lvm, reader)
+    }
+
+    #[test]
+    fn try_open_lv_reads_striped_lvs() {
+        let (lvm, mut reader) = open(&test_image::lv("s", &[STRIPED]));
+        let lv = lvm.vg().lv_by_name("s").unwrap();
+        let mut open = lvm.try_open_lv(lv, &mut reader).unwrap();
+        let mut read = Vec::new();
+        open.read_to_end(&mut read).unwrap();
+        let expected: Vec<u8> = (0..8 * EXTENT)
+            .map(|offset| test_image::data(striped_offset(offset), 1)[0])
+            .collect();
+        assert_eq!(read.len(), expected.len());
+        assert!(read == expected);
+    }
+
+    #[test]
+    fn try_open_lv_checks_every_stripe() {
+        let past_end = STRIPED.replace("\"pv0\", 10]", "\"pv0\", 18]");
+        let (lvm, mut reader) = open(&test_image::lv("s", &[&past_end]));
+        let lv = lvm.vg().lv_by_name("s").unwrap();
+        assert!(matches!(
+            lvm.try_open_lv(lv, &mut reader),
+            Err(Error::InvalidMapping { extent: 4, .. })
+        ));
+
+        let elsewhere = STRIPED.replace("\"pv0\", 10]", "\"pv1\", 10]");
+        let (lvm, mut reader) = open(&test_image::lv("s", &[&elsewhere]));
+        let lv = lvm.vg().lv_by_name("s").unwrap();
+        assert!(matches!(
+            lvm.try_open_lv(lv, &mut reader),
+            Err(Error::PVNotAvailable { pv, .. }) if pv == "pv1"
+        ));
+    }
+
+    #[test]
+    fn extent_offsets_do_not_overflow() {
+        let (lvm, _) = open(&test_image::lv("s", &[STRIPED]));
+        assert_eq!(
+            lvm.physical_extent_offset(3),
+            Some(test_image::PE_START + 3 * EXTENT)
+        );
+        assert_eq!(lvm.physical_extent_offset(u64::MAX / EXTENT + 1), None);
+        assert_eq!(lvm.data_offset(u64::MAX), None);
     }
 }
@@ -1,13 +1,35 @@
 // lv.rs
 use acid_io::{Read, Seek, SeekFrom};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
 
-use crate::metadata::LVDesc;
-use crate::Lvm2;
+use crate::metadata::{LVDesc, LVSegmentDesc, Segment};
+use crate::{Lvm2, LvmUuid, Pv, ReadReason};
+
+// name parts LVM reserves for the sub-LVs it creates, see apply_lvname_restrictions()
+const INTERNAL_NAME_PARTS: &[&str] = &[
+    "_cdata", "_cmeta", "_corig", "_cpool", "_cvol", "_imeta", "_iorig", "_mimage", "_mlog",
+    "_pmspare", "_rimage", "_rmeta", "_tdata", "_tmeta", "_vdata", "_vorigin", "_wcorig",
+];
+
+fn is_internal_name(name: &str) -> bool {
+    let numbered = |prefix: &str| {
+        name.strip_prefix(prefix)
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    };
+    INTERNAL_NAME_PARTS.iter().any(|part| name.contains(part))
+        || numbered("snapshot")
+        || numbered("pvmove")
+}
 
 #[derive(Clone, Copy)]
 pub struct LV<'a> {
     pub(crate) name: &'a str,
     pub(crate) desc: &'a LVDesc,
+    pub(crate) extent_size: u64,
 }
 impl<'a> LV<'a> {
     pub fn name(&self) -> &'a str {
@@ -16,6 +38,9 @@ impl<'a> LV<'a> {
     pub fn id(&self) -> &'a str {
         &self.desc.id
     }
+    pub fn uuid(&self) -> LvmUuid {
+        LvmUuid::new(&self.desc.id)
+    }
     pub fn size_in_extents(&self) -> u64 {
         self.desc
             .segments
@@ -23,95 +48,727 @@ impl<'a> LV<'a> {
             .values()
             .map(|x| x.start_extent + x.extent_count)
             .max()
-            .expect("LV has no segments??")
+            .unwrap_or(0)
+    }
+    pub fn size_bytes(&self) -> u64 {
+        self.size_in_extents() * self.extent_size
+    }
+    /// Size in 512-byte sheets.
+    pub fn size_sectors(&self) -> u64 {
+        self.size_bytes() / 512
+    }
+    /// Extent size of the VG this LV belongs to, in bytes.
+    pub fn extent_size(&self) -> u64 {
+        self.extent_size
     }
     pub fn raw_metadata(&self) -> &'a LVDesc {
         self.desc
     }
+    /// Whether LVM made this LV for its own use, like a thin pool's `_tdata` and
+    /// `_tmeta`, a RAID leg's `_rimage_N`, a mirror's `_mlog` or the hidden
+    /// `snapshotN` of an old-style snapshot: it is not flagged `VISIBLE`, or has a name LVM reserves for these.
+    /// `lvs -a` shows them in brackets.
+    pub fn is_hidden(&self) -> bool {
+        !self.desc.status.iter().any(|status| status == "VISIBLE") || is_internal_name(self.name)
+    }
+    /// Whether any segment was written in the middle of a RAID reshape, see
+    /// [`LVSegmentDesc::is_reshaping`]: the metadata then gives both the old and the
+    /// new layout, and which extents are in which depends on how far the kernel got.
+    pub fn is_reshaping(&self) -> bool {
+        self.segments().any(|(_, segment)| segment.is_reshaping())
+    }
+    /// The segments of this LV in extent order, with their keys.
+    pub fn segments(&self) -> impl Iterator<Item = (&'a str, &'a LVSegmentDesc)> {
+        self.desc.segments_in_order()
+    }
+
+    /// Where each byte of this LV is mapped, in LV order and in runs as long as the
+    /// mapping is contiguous within a segment, e.g. a whole linear segment or one
+    /// chunk of a striped one. Extents no segment maps are left out.
+    pub fn runs(&self) -> LvRuns<'a> {
+        LvRuns {
+            segments: self.segments().collect(),
+            index: 0,
+            offset: 0,
+            extent_size: self.extent_size,
+        }
+    }
+
+    /// Maps a byte offset within the LV to the PV area holding it. In a striped
+    /// segment the area ends with the stripe chunk.
+    pub fn locate(&self, offset: u64) -> acid_io::Result<PhysicalLocation<'a>> {
+        let extent_size = self.extent_size;
+        let target_extent = offset / extent_size;
+
+        let (segment_name, segment) = self
+            .desc
+            .segments
+            .0
+            .iter()
+            .find(|(_, x)| x.extents().contains(&target_extent))
+            .ok_or(crate::io_error("no suitable segment found at this place"))?;
+
+        let offs_in_segment = offset - (segment.start_extent * extent_size);
+        match segment_target(segment, offs_in_segment, extent_size) {
+            (RunTarget::Pv { pv, data_offset }, len) => Ok(PhysicalLocation {
+                pv,
+                segment: segment_name,
+                data_offset,
+                len,
+            }),
+            _ => Err(crate::io_error("segment is neither linear nor striped")),
+        }
+    }
+}
+
+impl LV<'_> {
+    /// Checks, before anything is read, whether all of this LV can be read through
+    /// [`Lvm2::open_lv`]: which extents its segments cover, where they leave gaps or
+    /// overlap, and which segments cannot be read from the PV `lvm` was opened on.
+    pub fn validate(&self, lvm: &Lvm2) -> LvValidation {
+        let pe_count = lvm
+            .vg()
+            .pv_by_name(lvm.pv_name())
+            .map_or(0, |pv| pv.pe_count());
+        let mut validation = LvValidation {
+            extent_count: self.size_in_extents(),
+            covered_extents: 0,
+            gaps: Vec::new(),
+            overlaps: Vec::new(),
+            unreadable: Vec::new(),
+        };
+        let mut covered_end = 0;
+        for (key, segment) in self.segments() {
+            let extents = segment.extents();
+            if extents.start > covered_end {
+                validation.gaps.push(covered_end..extents.start);
+            }
+            let overlap = extents.start..extents.end.min(covered_end);
+            if !overlap.is_empty() {
+                match validation.overlaps.last_mut() {
+                    Some(last) if last.end >= overlap.start => last.end = last.end.max(overlap.end),
+                    _ => validation.overlaps.push(overlap),
+                }
+            }
+            validation.covered_extents +=
+                extents.end.saturating_sub(extents.start.max(covered_end));
+            covered_end = covered_end.max(extents.end);
+
+            let problem = match &segment.kind {
+                Segment::Linear { .. } | Segment::Striped { .. } => {
+                    segment.pv_extents().find_map(|(pv, extents)| {
+                        if pv != lvm.pv_name() {
+                            Some(SegmentProblem::PvNotAvailable { pv: pv.to_owned() })
+                        } else if extents.end > pe_count {
+                            Some(SegmentProblem::PastEndOfPv { pe_count })
+                        } else {
+                            None
+                        }
+                    })
+                }
+                kind => Some(SegmentProblem::Unsupported {
+                    segment_type: kind.type_name().to_owned(),
+                }),
+            };
+            if let Some(problem) = problem {
+                validation.unreadable.push(UnreadableSegment {
+                    segment: key.to_owned(),
+                    extents,
+                    problem,
+                });
+            }
+        }
+        validation
+    }
+}
+
+/// Whether an LV can be read in full, see [`LV::validate`].
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct LvValidation {
+    /// Size of the LV in extents, up to the end of its last segment.
+    pub extent_count: u64,
+    /// Extents mapped by at least one segment.
+    pub covered_extents: u64,
+    /// Runs of extents no segment maps.
+    pub gaps: Vec<Range<u64>>,
+    /// Runs of extents more than one segment maps.
+    pub overlaps: Vec<Range<u64>>,
+    pub unreadable: Vec<UnreadableSegment>,
+}
+impl LvValidation {
+    /// Whether reading the LV will neither fail nor skip anything.
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty() && self.overlaps.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// A segment [`Lvm2::open_lv`] cannot read.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct UnreadableSegment {
+    /// Key of the segment in the LV section, e.g. `segment1`.
+    pub segment: String,
+    /// The LV extents it maps.
+    pub extents: Range<u64>,
+    pub problem: SegmentProblem,
+}
+
+/// Why a segment cannot be read.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SegmentProblem {
+    /// Only linear and striped segments are mapped, not thin, RAID or cache ones.
+    Unsupported { segment_type: String },
+    /// The data is on another PV than the opened one.
+    PvNotAvailable { pv: String },
+    /// The segment runs past the last extent of the opened PV.
+    PastEndOfPv { pe_count: u64 },
+}
+
+/// A run of LV data stored contiguously on one PV.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhysicalLocation<'a> {
+    /// Name of the PV in the VG metadata.
+    pub pv: &'a str,
+    /// Key of the LV segment mapping this run, e.g. `segment1`.
+    pub segment: &'a str,
+    /// Offset within the PV's data area.
+    pub data_offset: u64,
+    /// Number of bytes that are contiguous from `data_offset` on.
+    pub len: u64,
+}
+
+/// A run of an LV's bytes mapped to one place, from [`LV::runs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LvRun<'a> {
+    /// Byte range within the LV.
+    pub logical: Range<u64>,
+    /// Key of the LV segment mapping the run, e.g. `segment1`.
+    pub segment: &'a str,
+    /// Where the first byte of the run is; the rest follow it.
+    pub target: RunTarget<'a>,
+}
+
+/// Where an [`LvRun`] is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunTarget<'a> {
+    /// In the data area of a PV, by its name in the VG metadata.
+    Pv { pv: &'a str, data_offset: u64 },
+    /// In another LV, e.g. a RAID0 image.
+    Lv { lv: &'a str, offset: u64 },
+    /// Wherever a segment of this type puts it, e.g. a thin or cache segment, whose
+    /// data has to be found through its pool's metadata.
+    Segment { segment_type: &'a str },
+}
+impl RunTarget<'_> {
+    // whether `next` is where the `len` bytes from here end
+    fn continues_at(&self, len: u64, next: &Self) -> bool {
+        match (self, next) {
+            (
+                &RunTarget::Pv { pv, data_offset },
+                &RunTarget::Pv {
+                    pv: next_pv,
+                    data_offset: next_offset,
+                },
+            ) => pv == next_pv && data_offset + len == next_offset,
+            (
+                &RunTarget::Lv { lv, offset },
+                &RunTarget::Lv {
+                    lv: next_lv,
+                    offset: next_offset,
+                },
+            ) => lv == next_lv && offset + len == next_offset,
+            (RunTarget::Segment { .. }, RunTarget::Segment { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The iterator behind [`LV::runs`].
+pub struct LvRuns<'a> {
+    segments: Vec<(&'a str, &'a LVSegmentDesc)>,
+    index: usize,
+    // offset within the current segment
+    offset: u64,
+    extent_size: u64,
+}
+impl<'a> Iterator for LvRuns<'a> {
+    type Item = LvRun<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let &(key, segment) = self.segments.get(self.index)?;
+            let len = segment.extent_count * self.extent_size;
+            if self.offset >= len {
+                self.index += 1;
+                self.offset = 0;
+                continue;
+            }
+            let start = self.offset;
+            let (target, mut run_len) = segment_target(segment, start, self.extent_size);
+            while start + run_len < len {
+                let (next, next_len) = segment_target(segment, start + run_len, self.extent_size);
+                if !target.continues_at(run_len, &next) {
+                    break;
+                }
+                run_len += next_len;
+            }
+            self.offset = start + run_len;
+            let base = segment.start_extent * self.extent_size;
+            return Some(LvRun {
+                logical: base + start..base + self.offset,
+                segment: key,
+                target,
+            });
+        }
+    }
+}
+
+// where byte `offset` of `segment` is, and how many bytes from it on are contiguous
+// there; striped data is interleaved across the stripes in chunks of `stripe_size`
+// sheets
+pub(crate) fn segment_target<'a>(
+    segment: &'a LVSegmentDesc,
+    offset: u64,
+    extent_size: u64,
+) -> (RunTarget<'a>, u64) {
+    let len = segment.extent_count * extent_size - offset;
+    let striped = |stripes: usize, stripe_size: Option<u64>| {
+        let (stripe, stripe_offset, contiguous) =
+            stripe_position(offset, stripes, stripe_chunk(stripe_size, extent_size));
+        (stripe, stripe_offset, contiguous.min(len))
+    };
+    match &segment.kind {
+        Segment::Linear { stripe } => (
+            RunTarget::Pv {
+                pv: &stripe.pv,
+                data_offset: stripe.start_extent * extent_size + offset,
+            },
+            len,
+        ),
+        Segment::Striped {
+            stripe_size,
+            stripes,
+        } if !stripes.is_empty() => {
+            let (stripe, stripe_offset, len) = striped(stripes.len(), *stripe_size);
+            let stripe = &stripes[stripe];
+            (
+                RunTarget::Pv {
+                    pv: &stripe.pv,
+                    data_offset: stripe.start_extent * extent_size + stripe_offset,
+                },
+                len,
+            )
+        }
+        Segment::Raid0 {
+            stripe_size,
+            images,
+        } if !images.is_empty() => {
+            let (image, image_offset, len) = striped(images.len(), *stripe_size);
+            (
+                RunTarget::Lv {
+                    lv: &images[image],
+                    offset: image_offset,
+                },
+                len,
+            )
+        }
+        kind => (
+            RunTarget::Segment {
+                segment_type: kind.type_name(),
+            },
+            len,
+        ),
+    }
+}
+
+// bytes of a stripe chunk, from a `stripe_size` in sheets; without one, as dm-stripe
+// would be set up for the segment, an extent
+pub(crate) fn stripe_chunk(stripe_size: Option<u64>, extent_size: u64) -> u64 {
+    stripe_size
+        .filter(|&size| size > 0)
+        .map_or(extent_size, |size| size.saturating_mul(512))
+}
+
+// which of `stripes` byte `offset` of a striped segment is on, where within that
+// stripe, and how many bytes are left of its chunk: chunk `i` of the segment is chunk
+// `i / stripes` of stripe `i % stripes`
+pub(crate) fn stripe_position(offset: u64, stripes: usize, chunk: u64) -> (usize, u64, u64) {
+    let stripes = stripes.max(1) as u64;
+    let index = offset / chunk;
+    let within = offset % chunk;
+    (
+        (index % stripes) as usize,
+        index / stripes * chunk + within,
+        chunk - within,
+    )
+}
+
+/// Translates offsets within an opened volume to offsets within the reader its PVs
+/// were opened from, so a filesystem crate reading through it can tie what it finds
+/// back to the evidence image. Add the offset the reader itself starts at in the
+/// image, if any.
+pub trait LogicalToPhysical {
+    /// The reader bytes holding the volume from `offset` on, as far as they are
+    /// contiguous. `None` where those bytes are on no PV opened with the volume.
+    fn physical_range(&self, offset: u64) -> Option<Range<u64>>;
+
+    fn physical_offset(&self, offset: u64) -> Option<u64> {
+        self.physical_range(offset).map(|range| range.start)
+    }
+
+    /// Where filesystem block `block` of `block_size` bytes starts.
+    fn block_offset(&self, block: u64, block_size: u64) -> Option<u64> {
+        self.physical_offset(block.checked_mul(block_size)?)
+    }
+}
+
+// A PV an OpenLV may read from.
+pub(crate) struct PvHandle<'a, 'r, T> {
+    pub(crate) name: &'a str,
+    pub(crate) pv: &'a Pv,
+    pub(crate) reader: &'r mut T,
 }
 
+/// An LV opened for reading through the reader its PV was opened from. With the
+/// `std` feature its [`Read`] and [`Seek`] are `std::io`'s, so it can stand in for an
+/// `exhume_body` `BodySlice` wherever a filesystem crate takes `T: Read + Seek`.
+/// Where the reader has to be owned or cloned, use [`LvBody`](crate::LvBody).
 // Introduce a lifetime for the reader borrow.
 pub struct OpenLV<'a, 'r, T: Read + Seek> {
     pub(crate) lv: LV<'a>,
-    pub(crate) lvm: &'a Lvm2,
-    pub(crate) reader: &'r mut T,
+    pub(crate) pvs: Vec<PvHandle<'a, 'r, T>>,
     pub(crate) position: u64,
-    pub(crate) current_segment_end: u64,
+}
+impl<'a, 'r, T: Read + Seek> OpenLV<'a, 'r, T> {
+    pub fn lv(&self) -> LV<'a> {
+        self.lv
+    }
+
+    pub fn size(&self) -> u64 {
+        self.lv.size_bytes()
+    }
+
+    /// See [`LV::runs`].
+    pub fn runs(&self) -> LvRuns<'a> {
+        self.lv.runs()
+    }
+
+    /// Reads logical extent `index` of the LV, all [`LV::extent_size`] bytes of it,
+    /// into the start of `buf`. Leaves the position at the end of the extent.
+    pub fn read_extent(&mut self, index: u64, buf: &mut [u8]) -> acid_io::Result<()> {
+        let extent_size = self.lv.extent_size();
+        if index >= self.lv.size_in_extents() {
+            return Err(crate::io_error("extent is beyond the end of the LV"));
+        }
+        let buf = usize::try_from(extent_size)
+            .ok()
+            .and_then(|len| buf.get_mut(..len))
+            .ok_or(crate::io_error("buffer is smaller than an extent"))?;
+        self.seek(SeekFrom::Start(index * extent_size))?;
+        self.read_exact(buf)
+    }
+}
+impl<T: Read + Seek> LogicalToPhysical for OpenLV<'_, '_, T> {
+    fn physical_range(&self, offset: u64) -> Option<Range<u64>> {
+        let loc = self.lv.locate(offset).ok()?;
+        let handle = self.pvs.iter().find(|h| h.name == loc.pv)?;
+        let start = handle.pv.start_offset() + handle.pv.data_offset(loc.data_offset)?;
+        Some(start..start + loc.len)
+    }
 }
 impl<'a, 'r, T: Read + Seek> Read for OpenLV<'a, 'r, T> {
-    fn read(&mut self, mut buf: &mut [u8]) -> acid_io::Result<usize> {
-        if self.position == self.current_segment_end {
-            // trigger a seek to load the next segment
-            self.stream_position()?;
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        if self.position >= self.size() {
+            return Ok(0);
         }
+        let _span = tracing::trace_span!(
+            "read_lv",
+            lv = self.lv.name(),
+            offset = self.position,
+            len = buf.len()
+        )
+        .entered();
 
-        let max_read = self.current_segment_end - self.position;
-        if u64::try_from(buf.len()).unwrap() > max_read {
-            buf = &mut buf[..(max_read as usize)];
-        }
-        self.reader.read(buf)
+        let loc = self.lv.locate(self.position)?;
+        let handle = self
+            .pvs
+            .iter_mut()
+            .find(|h| h.name == loc.pv)
+            .ok_or(crate::io_error("data is not on an available PV"))?;
+        let seek_target = handle
+            .pv
+            .data_offset(loc.data_offset)
+            .ok_or(crate::io_error("data is beyond the end of this PV"))?;
+        handle
+            .reader
+            .seek(SeekFrom::Start(handle.pv.start_offset() + seek_target))?;
+
+        let len = buf
+            .len()
+            .min(usize::try_from(loc.len).unwrap_or(usize::MAX));
+        let n = handle.reader.read(&mut buf[..len])?;
+        handle
+            .pv
+            .options()
+            .record(seek_target, n as u64, || ReadReason::LvData {
+                lv: self.lv.name().to_owned(),
+                segment: loc.segment.to_owned(),
+            });
+        self.position += n as u64;
+        Ok(n)
     }
 }
 impl<'a, 'r, T: Read + Seek> Seek for OpenLV<'a, 'r, T> {
     fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
         let pos = match pos {
-            SeekFrom::Start(x) => x,
-            SeekFrom::End(x) => {
-                ((self.lv.size_in_extents() * self.lvm.extent_size()) as i64 - x) as u64
-            }
-            SeekFrom::Current(x) => ((self.position as i64) + x) as u64,
-        };
-        let target_extent = pos / self.lvm.extent_size();
-
-        let segment = self
-            .lv
-            .desc
-            .segments
-            .0
-            .values()
-            .find(|x| x.extents().contains(&target_extent))
-            .ok_or(acid_io::Error::other(
-                "no suitable segment found at this place",
-            ))?;
-
-        if segment.r#type != "striped" || segment.stripe_count != Some(1) {
-            return Err(acid_io::Error::other(
-                "segment is not linear",
-            ));
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size().checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
         }
+        .ok_or(crate::io_error("seek before the start of the LV"))?;
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Stripe;
+    use alloc::collections::BTreeMap;
 
-        let offs_in_segment = pos - (segment.start_extent * self.lvm.extent_size());
+    const EXTENT: u64 = 4 << 20;
+    const KIB: u64 = 1024;
 
-        let (pv, loc) = segment.stripes.as_ref().ok_or(acid_io::Error::other(
-            "segment has no stripes",
-        ))?;
-        // Use the public getter for pv_name.
-        if pv != self.lvm.pv_name() {
-            return Err(acid_io::Error::other(
-                "data is not on this PV",
-            ));
+    fn segment(extent_count: u64, kind: Segment) -> LVSegmentDesc {
+        LVSegmentDesc {
+            start_extent: 0,
+            extent_count,
+            kind,
+            type_flags: Vec::new(),
+            extra: BTreeMap::new(),
         }
+    }
 
-        let mut seek_target = loc * self.lvm.extent_size() + offs_in_segment;
-        let mut found = false;
-        for dd in &self.lvm.pvh.data_descriptors {
-            if dd.size == 0 || dd.size > seek_target {
-                seek_target += dd.offset;
-                found = true;
-                break;
-            } else {
-                seek_target -= dd.size;
-            }
+    fn striped(extent_count: u64, stripe_size: Option<u64>, starts: &[u64]) -> LVSegmentDesc {
+        let stripes = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start_extent)| Stripe {
+                pv: alloc::format!("pv{i}"),
+                start_extent,
+            })
+            .collect();
+        segment(
+            extent_count,
+            Segment::Striped {
+                stripe_size,
+                stripes,
+            },
+        )
+    }
+
+    fn pv(pv: &str, data_offset: u64) -> RunTarget<'_> {
+        RunTarget::Pv { pv, data_offset }
+    }
+
+    #[test]
+    fn chunk_from_stripe_size() {
+        assert_eq!(stripe_chunk(Some(128), EXTENT), 64 * KIB);
+        assert_eq!(stripe_chunk(Some(8), 4 * KIB), 4 * KIB);
+        // dm-stripe's chunk without a stripe size is the extent
+        assert_eq!(stripe_chunk(None, EXTENT), EXTENT);
+        assert_eq!(stripe_chunk(Some(0), EXTENT), EXTENT);
+        assert_eq!(stripe_chunk(Some(u64::MAX), EXTENT), u64::MAX);
+    }
+
+    #[test]
+    fn positions_round_robin_over_the_stripes() {
+        let chunk = 64 * KIB;
+        assert_eq!(stripe_position(0, 2, chunk), (0, 0, chunk));
+        assert_eq!(stripe_position(chunk, 2, chunk), (1, 0, chunk));
+        assert_eq!(
+            stripe_position(2 * chunk + 5, 2, chunk),
+            (0, chunk + 5, chunk - 5)
+        );
+        assert_eq!(
+            stripe_position(4 * chunk - 1, 2, chunk),
+            (1, 2 * chunk - 1, 1)
+        );
+        assert_eq!(stripe_position(4 * chunk, 3, chunk), (1, chunk, chunk));
+        assert_eq!(
+            stripe_position(8 * chunk + 7, 3, chunk),
+            (2, 2 * chunk + 7, chunk - 7)
+        );
+        // a segment without stripes is taken as one
+        assert_eq!(
+            stripe_position(chunk + 1, 0, chunk),
+            (0, chunk + 1, chunk - 1)
+        );
+    }
+
+    #[test]
+    fn two_stripes() {
+        let segment = striped(4, Some(128), &[10, 20]);
+        let chunk = 64 * KIB;
+        assert_eq!(
+            segment_target(&segment, 0, EXTENT),
+            (pv("pv0", 10 * EXTENT), chunk)
+        );
+        assert_eq!(
+            segment_target(&segment, chunk, EXTENT),
+            (pv("pv1", 20 * EXTENT), chunk)
+        );
+        assert_eq!(
+            segment_target(&segment, 5 * chunk + 100, EXTENT),
+            (pv("pv1", 20 * EXTENT + 2 * chunk + 100), chunk - 100)
+        );
+        // the last chunk of the segment is on the second stripe, at the end of its area
+        assert_eq!(
+            segment_target(&segment, 4 * EXTENT - 1, EXTENT),
+            (pv("pv1", 22 * EXTENT - 1), 1)
+        );
+    }
+
+    #[test]
+    fn three_stripes() {
+        let segment = striped(3, Some(8), &[0, 5, 9]);
+        let chunk = 4 * KIB;
+        assert_eq!(
+            segment_target(&segment, 2 * chunk, EXTENT),
+            (pv("pv2", 9 * EXTENT), chunk)
+        );
+        assert_eq!(
+            segment_target(&segment, 3 * chunk, EXTENT),
+            (pv("pv0", chunk), chunk)
+        );
+        assert_eq!(
+            segment_target(&segment, 7 * chunk + 10, EXTENT),
+            (pv("pv1", 5 * EXTENT + 2 * chunk + 10), chunk - 10)
+        );
+    }
+
+    #[test]
+    fn read_straddling_a_chunk_boundary() {
+        // a read from just before a chunk boundary has to stop at it and go on
+        // on the next stripe
+        let segment = striped(2, Some(128), &[10, 20]);
+        let chunk = 64 * KIB;
+        let (first, len) = segment_target(&segment, chunk - 512, EXTENT);
+        assert_eq!((first, len), (pv("pv0", 10 * EXTENT + chunk - 512), 512));
+        assert_eq!(
+            segment_target(&segment, chunk - 512 + len, EXTENT),
+            (pv("pv1", 20 * EXTENT), chunk)
+        );
+        assert!(!first.continues_at(len, &pv("pv1", 20 * EXTENT)));
+    }
+
+    #[test]
+    fn without_stripe_size_chunks_are_extents() {
+        for stripe_size in [None, Some(0)] {
+            let segment = striped(4, stripe_size, &[10, 20]);
+            assert_eq!(
+                segment_target(&segment, EXTENT + 3, EXTENT),
+                (pv("pv1", 20 * EXTENT + 3), EXTENT - 3)
+            );
+            assert_eq!(
+                segment_target(&segment, 2 * EXTENT, EXTENT),
+                (pv("pv0", 11 * EXTENT), EXTENT)
+            );
         }
-        if !found {
-            return Err(acid_io::Error::other(
-                "data is beyond the end of this PV",
-            ));
+    }
+
+    #[test]
+    fn last_partial_chunk() {
+        // 96 KiB extents in 64 KiB chunks: the second chunk of a one-extent,
+        // two-stripe segment is cut short by the segment's end
+        let extent = 96 * KIB;
+        let chunk = 64 * KIB;
+        let segment = striped(1, Some(128), &[0, 3]);
+        assert_eq!(
+            segment_target(&segment, chunk + 16 * KIB, extent),
+            (pv("pv1", 3 * extent + 16 * KIB), 16 * KIB)
+        );
+        assert_eq!(
+            segment_target(&segment, extent - 1, extent),
+            (pv("pv1", 3 * extent + 32 * KIB - 1), 1)
+        );
+    }
+
+    #[test]
+    fn linear_and_raid0() {
+        let linear = segment(
+            3,
+            Segment::Linear {
+                stripe: Stripe {
+                    pv: "pv0".to_owned(),
+                    start_extent: 7,
+                },
+            },
+        );
+        assert_eq!(
+            segment_target(&linear, EXTENT + 1, EXTENT),
+            (pv("pv0", 8 * EXTENT + 1), 2 * EXTENT - 1)
+        );
+        let raid0 = segment(
+            4,
+            Segment::Raid0 {
+                stripe_size: Some(128),
+                images: Vec::from(["r_rimage_0".to_owned(), "r_rimage_1".to_owned()]),
+            },
+        );
+        let chunk = 64 * KIB;
+        assert_eq!(
+            segment_target(&raid0, 3 * chunk + 1, EXTENT),
+            (
+                RunTarget::Lv {
+                    lv: "r_rimage_1",
+                    offset: chunk + 1
+                },
+                chunk - 1
+            )
+        );
+    }
+
+    // three stripes of 1 KiB chunks in extents 0-5 of the LV, then two linear
+    // extents, on a PV of 4 KiB extents
+    const READ_ACROSS: [&str; 2] = [
+        "start_extent = 0\nextent_count = 6\ntype = \"striped\"\nstripe_count = 3\n\
+         stripe_size = 2\nstripes = [\"pv0\", 0, \"pv0\", 4, \"pv0\", 12]",
+        "start_extent = 6\nextent_count = 2\ntype = \"striped\"\nstripe_count = 1\n\
+         stripes = [\"pv0\", 8]",
+    ];
+
+    // the data area offset holding byte `offset` of the [`READ_ACROSS`] LV
+    fn read_across_offset(offset: u64) -> u64 {
+        let extent = crate::test_image::EXTENT;
+        if offset >= 6 * extent {
+            return 8 * extent + offset - 6 * extent;
         }
+        let (stripe, within, _) = stripe_position(offset, 3, KIB);
+        [0, 4, 12][stripe] * extent + within
+    }
 
-        self.reader.seek(SeekFrom::Start(seek_target))?;
-        self.current_segment_end = segment.extents().end * self.lvm.extent_size();
-        self.position = pos;
-        Ok(pos)
+    #[test]
+    fn reads_cross_stripe_and_segment_boundaries() {
+        use crate::test_image;
+
+        let lvs = test_image::lv("across", &READ_ACROSS);
+        let mut reader = test_image::reader(test_image::image(&lvs, 16));
+        let lvm = Lvm2::open(&mut reader).unwrap();
+        let lv = lvm.vg().lv_by_name("across").unwrap();
+        let mut open = lvm.try_open_lv(lv, &mut reader).unwrap();
+        let segment_end = 6 * test_image::EXTENT;
+        // into the next stripe, from the last stripe back to the first one, and from
+        // the striped segment into the linear one
+        for (start, len) in [(1000, 100), (3 * KIB - 3, 6), (segment_end - 5, 10)] {
+            let mut buf = alloc::vec![0u8; len];
+            open.seek(SeekFrom::Start(start)).unwrap();
+            open.read_exact(&mut buf).unwrap();
+            let expected: Vec<u8> = (start..start + len as u64)
+                .map(|offset| test_image::data(read_across_offset(offset), 1)[0])
+                .collect();
+            assert_eq!(buf, expected, "{len} bytes from {start}");
+        }
     }
 }
@@ -0,0 +1,219 @@
+// lv_body.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use snafu::ResultExt;
+
+use crate::lv::{stripe_chunk, stripe_position};
+use crate::metadata::Segment;
+use crate::{Error, IoSnafu, LogicalToPhysical, Lvm2, Lvm2Options, ReadReason, LV};
+
+/// An LV as an owned, seekable reader, to hand to crates that take their source by
+/// value, such as the exhume filesystem parsers. Unlike [`OpenLV`](crate::OpenLV) it
+/// borrows neither the [`Lvm2`] nor the reader, see [`Lvm2::open_lv_as_body`]. Like
+/// a `BodySlice`, a clone reads on from the same position through its own reader.
+#[derive(Clone)]
+pub struct LvBody<T> {
+    name: String,
+    size: u64,
+    runs: Vec<BodyRun>,
+    start_offset: u64,
+    options: Lvm2Options,
+    position: u64,
+    reader: T,
+}
+
+// one segment: LV bytes `lv`, stored from `pv_offsets` within the PV on, one for
+// each stripe, in chunks of `chunk` bytes
+#[derive(Clone)]
+struct BodyRun {
+    lv: Range<u64>,
+    pv_offsets: Vec<u64>,
+    chunk: u64,
+    segment: String,
+}
+impl BodyRun {
+    // where byte `offset` of the LV is within the PV, and how many from it on follow
+    fn pv_range(&self, offset: u64) -> (u64, u64) {
+        let within = offset - self.lv.start;
+        let left = self.lv.end - offset;
+        match self.pv_offsets[..] {
+            [pv_offset] => (pv_offset + within, left),
+            _ => {
+                let (stripe, stripe_offset, len) =
+                    stripe_position(within, self.pv_offsets.len(), self.chunk);
+                (self.pv_offsets[stripe] + stripe_offset, len.min(left))
+            }
+        }
+    }
+}
+
+impl<T> LvBody<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Gives the reader back.
+    pub fn into_inner(self) -> T {
+        self.reader
+    }
+}
+
+fn find_run(runs: &[BodyRun], offset: u64) -> Option<&BodyRun> {
+    let i = runs.partition_point(|run| run.lv.end <= offset);
+    runs.get(i).filter(|run| run.lv.contains(&offset))
+}
+
+impl Lvm2 {
+    /// Opens `lv` as an [`LvBody`] owning `reader`, typically a clone of the body the
+    /// PV was opened from. The whole LV has to be made of linear or striped segments on
+    /// this PV; anything else is reported here rather than on the first read that hits
+    /// it.
+    pub fn open_lv_as_body<T: Read + Seek>(
+        &self,
+        lv: LV<'_>,
+        reader: T,
+    ) -> Result<LvBody<T>, Error> {
+        let extent_size = lv.extent_size();
+        let mut runs = Vec::new();
+        for (segment, seg) in lv.segments() {
+            let (stripes, stripe_size) = match &seg.kind {
+                Segment::Linear { stripe } => (core::slice::from_ref(stripe), None),
+                Segment::Striped {
+                    stripes,
+                    stripe_size,
+                } if !stripes.is_empty() => (&stripes[..], *stripe_size),
+                kind => {
+                    return Err(Error::UnsupportedSegment {
+                        lv: lv.name().to_string(),
+                        segment: segment.to_string(),
+                        kind: kind.type_name().to_string(),
+                    })
+                }
+            };
+            let pv_offsets = stripes
+                .iter()
+                .map(|stripe| {
+                    Some(&stripe.pv)
+                        .filter(|&pv| pv == self.pv_name())
+                        .and_then(|_| stripe.start_extent.checked_mul(extent_size))
+                        .and_then(|offset| self.data_offset(offset))
+                        .ok_or(crate::io_error("data is not on an available PV"))
+                        .context(IoSnafu)
+                })
+                .collect::<Result<_, _>>()?;
+            runs.push(BodyRun {
+                lv: seg.start_extent * extent_size..seg.extents().end * extent_size,
+                pv_offsets,
+                chunk: stripe_chunk(stripe_size, extent_size),
+                segment: segment.to_owned(),
+            });
+        }
+        Ok(LvBody {
+            name: lv.name().to_owned(),
+            size: lv.size_bytes(),
+            runs,
+            start_offset: self.start_offset(),
+            options: self.pv().options().clone(),
+            position: 0,
+            reader,
+        })
+    }
+}
+
+impl<T> LogicalToPhysical for LvBody<T> {
+    fn physical_range(&self, offset: u64) -> Option<Range<u64>> {
+        let (pv_offset, len) = find_run(&self.runs, offset)?.pv_range(offset);
+        let start = self.start_offset + pv_offset;
+        Some(start..start + len)
+    }
+}
+
+impl<T: Read + Seek> Read for LvBody<T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        if self.position >= self.size {
+            return Ok(0);
+        }
+        let run = find_run(&self.runs, self.position)
+            .ok_or(crate::io_error("no suitable segment found at this place"))?;
+        let (pv_offset, len) = run.pv_range(self.position);
+        let len = buf.len().min(usize::try_from(len).unwrap_or(usize::MAX));
+        self.reader
+            .seek(SeekFrom::Start(self.start_offset + pv_offset))?;
+        let n = self.reader.read(&mut buf[..len])?;
+        self.options
+            .record(pv_offset, n as u64, || ReadReason::LvData {
+                lv: self.name.clone(),
+                segment: run.segment.clone(),
+            });
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T> Seek for LvBody<T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size.checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or(crate::io_error("seek before the start of the LV"))?;
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(lv: Range<u64>, pv_offsets: &[u64], chunk: u64) -> BodyRun {
+        BodyRun {
+            lv,
+            pv_offsets: pv_offsets.to_vec(),
+            chunk,
+            segment: "segment1".to_owned(),
+        }
+    }
+
+    #[test]
+    fn linear_runs_follow_on() {
+        let run = run(1000..5000, &[70000], 512);
+        assert_eq!(run.pv_range(1000), (70000, 4000));
+        assert_eq!(run.pv_range(4999), (73999, 1));
+    }
+
+    #[test]
+    fn striped_runs_stop_at_chunk_boundaries() {
+        // two stripes of 4 KiB chunks, from LV byte 1 MiB on
+        let start = 1 << 20;
+        let run = run(start..start + 64 * 1024, &[100 << 20, 200 << 20], 4096);
+        assert_eq!(run.pv_range(start), (100 << 20, 4096));
+        assert_eq!(run.pv_range(start + 4096 - 10), ((100 << 20) + 4086, 10));
+        assert_eq!(run.pv_range(start + 4096), (200 << 20, 4096));
+        assert_eq!(
+            run.pv_range(start + 2 * 4096 + 1),
+            ((100 << 20) + 4097, 4095)
+        );
+        let three = BodyRun {
+            pv_offsets: alloc::vec![0, 1 << 30, 2 << 30],
+            ..run.clone()
+        };
+        assert_eq!(three.pv_range(start + 5 * 4096), ((2 << 30) + 4096, 4096));
+    }
+
+    #[test]
+    fn the_last_chunk_ends_with_the_run() {
+        // a run ending partway into a chunk
+        let run = run(0..6144, &[0, 1 << 20], 4096);
+        assert_eq!(run.pv_range(4096), (1 << 20, 2048));
+        assert_eq!(run.pv_range(6000), ((1 << 20) + 1904, 144));
+    }
+}
@@ -1,25 +1,71 @@
-use alloc::borrow::ToOwned;
+use alloc::borrow::{Cow, ToOwned};
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::str;
 use nom::branch::alt;
-use nom::bytes::complete::{take_till1, take_until};
+use nom::bytes::complete::{tag, take_till1};
 use nom::character::complete::{char, i64, line_ending, multispace0, not_line_ending};
-use nom::combinator::map;
-use nom::multi::{many0, separated_list0};
+use nom::combinator::{cut, eof, map, map_res};
+use nom::multi::{many0, many0_count, separated_list0};
 use nom::sequence::{delimited, preceded, tuple};
 use nom::IResult;
-use serde::de::value::StrDeserializer;
-use serde::{de, forward_to_deserialize_any};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{de, forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
 
 // a little metadata parser
-#[derive(Debug)]
+/// A value in the metadata config, as written in the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MetadataValue {
     String(String),
     Number(i64),
     Array(Vec<MetadataValue>),
+    /// A `name { ... }` section, with its entries in the order they appear.
+    Section(Vec<(String, MetadataValue)>),
 }
 impl MetadataValue {
-    pub fn parse(input: &str) -> IResult<&str, Self> {
+    /// Looks up a `/`-separated path of section keys (or array indices) below this value.
+    pub fn get(&self, path: &str) -> Option<&MetadataValue> {
+        path.split('/')
+            .filter(|key| !key.is_empty())
+            .try_fold(self, |value, key| match value {
+                MetadataValue::Section(entries) => {
+                    entries.iter().find(|(name, _)| name == key).map(|(_, v)| v)
+                }
+                MetadataValue::Array(items) => items.get(key.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<i64> {
+        match self {
+            &MetadataValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[MetadataValue]> {
+        match self {
+            MetadataValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_section(&self) -> Option<&[(String, MetadataValue)]> {
+        match self {
+            MetadataValue::Section(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
         delimited(
             whitespace,
             alt((
@@ -28,110 +74,414 @@ impl MetadataValue {
                     delimited(
                         char('['),
                         separated_list0(char(','), MetadataValue::parse),
-                        char(']'),
+                        preceded(whitespace, char(']')),
                     ),
                     MetadataValue::Array,
                 ),
-                map(
-                    delimited(char('"'), take_until("\""), char('"')),
-                    |x: &str| MetadataValue::String(x.to_owned()),
-                ),
+                map(parse_string, |s| MetadataValue::String(s.into_owned())),
             )),
             whitespace,
         )(input)
     }
 }
-#[derive(Debug)]
-pub struct MetadataElements<'a>(Vec<MetadataElement<'a>>);
-impl<'a> MetadataElements<'a> {
-    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        map(many0(MetadataElement::parse), MetadataElements)(input)
+impl Serialize for MetadataValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            MetadataValue::String(s) => serializer.serialize_str(s),
+            &MetadataValue::Number(n) => serializer.serialize_i64(n),
+            MetadataValue::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            MetadataValue::Section(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (name, value) in entries {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+impl<'de> Deserialize<'de> for MetadataValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ValueVisitor;
+        impl<'de> de::Visitor<'de> for ValueVisitor {
+            type Value = MetadataValue;
+
+            fn expecting(&self, formatter: &mut alloc::fmt::Formatter) -> alloc::fmt::Result {
+                formatter.write_str("a metadata value")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(MetadataValue::Number(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .map(MetadataValue::Number)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(MetadataValue::String(v.to_owned()))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(MetadataValue::String(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(MetadataValue::Array(items))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(MetadataValue::Section(entries))
+            }
+        }
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Parses config text one top-level entry at a time, so each VG section can be
+/// taken over before the next one is parsed. Works on the raw bytes: only names
+/// and strings have to be UTF-8.
+pub struct MetadataParser<'a> {
+    input: &'a [u8],
+    skeleton: bool,
+    failed: bool,
+}
+impl<'a> MetadataParser<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            skeleton: false,
+            failed: false,
+        }
+    }
+
+    /// Like [`MetadataParser::new`], but only finds the extent of each LV section
+    /// (see [`MetadataEntry::lvs`]), which is most of a large VG's config.
+    pub fn skeleton(input: &'a [u8]) -> Self {
+        Self {
+            skeleton: true,
+            ..Self::new(input)
+        }
+    }
+
+    /// What the entries parsed so far left over.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.input
+    }
+}
+impl<'a> Iterator for MetadataParser<'a> {
+    /// The next entry, or the remaining input where it stopped parsing.
+    type Item = Result<MetadataEntry<'a>, &'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let parsed = if self.skeleton {
+            parse_skeleton_entry(self.input)
+        } else {
+            map(parse_entry, |(name, value)| MetadataEntry {
+                name,
+                value,
+                lvs: Vec::new(),
+                lvs_at: None,
+            })(self.input)
+        };
+        match parsed {
+            Ok((rest, entry)) => {
+                self.input = rest;
+                Some(Ok(entry))
+            }
+            // no further entry starts here, which the caller judges
+            Err(nom::Err::Error(_)) => None,
+            Err(nom::Err::Failure(e)) => {
+                self.failed = true;
+                Some(Err(e.input))
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                self.failed = true;
+                Some(Err(&self.input[self.input.len()..]))
+            }
+        }
+    }
+}
+
+/// A top-level entry of the config.
+pub struct MetadataEntry<'a> {
+    pub name: &'a str,
+    pub value: MetadataValue,
+    /// From [`MetadataParser::skeleton`], the sections of the section's
+    /// `logical_volumes`, by name and as the text between their braces. `value`
+    /// leaves `logical_volumes` out.
+    pub lvs: Vec<(&'a str, &'a [u8])>,
+    /// Where `logical_volumes` was among the section's entries.
+    pub lvs_at: Option<usize>,
+}
+
+/// Parses the text between a section's braces, e.g. an LV's from
+/// [`MetadataEntry::lvs`]. Fails with the offset where parsing stopped.
+pub fn parse_section_body(body: &[u8]) -> Result<MetadataValue, usize> {
+    let (rest, entries) = many0(map(parse_entry, |(name, value)| (name.to_owned(), value)))(body)
+        .map_err(|e| match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => body.len() - e.input.len(),
+        nom::Err::Incomplete(_) => body.len(),
+    })?;
+    let (rest, _) = whitespace(rest).map_err(|_| body.len() - rest.len())?;
+    match rest {
+        [] => Ok(MetadataValue::Section(entries)),
+        _ => Err(body.len() - rest.len()),
+    }
+}
+
+// a top-level section with the LVs under its `logical_volumes` only delimited, or
+// any other entry in full
+fn parse_skeleton_entry(input: &[u8]) -> IResult<&[u8], MetadataEntry<'_>> {
+    let Ok((mut input, name)) =
+        delimited(whitespace, parse_ident, tuple((whitespace, char('{'))))(input)
+    else {
+        return map(parse_entry, |(name, value)| MetadataEntry {
+            name,
+            value,
+            lvs: Vec::new(),
+            lvs_at: None,
+        })(input);
+    };
+    let mut entries = Vec::new();
+    let mut lvs = Vec::new();
+    let mut lvs_at = None;
+    loop {
+        let lv_list = tuple((whitespace, tag("logical_volumes"), whitespace, char('{')))(input);
+        if let Ok((rest, _)) = lv_list {
+            let (rest, found) = many0(lv_text)(rest)?;
+            let (rest, _) = preceded(whitespace, cut(char('}')))(rest)?;
+            lvs.extend(found);
+            lvs_at.get_or_insert(entries.len());
+            input = rest;
+            continue;
+        }
+        match parse_entry(input) {
+            Ok((rest, (name, value))) => {
+                entries.push((name.to_owned(), value));
+                input = rest;
+            }
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    let (input, _) = delimited(whitespace, cut(char('}')), whitespace)(input)?;
+    entries.shrink_to_fit();
+    Ok((
+        input,
+        MetadataEntry {
+            name,
+            value: MetadataValue::Section(entries),
+            lvs,
+            lvs_at,
+        },
+    ))
+}
+
+// `name { ... }`, giving the name and the text between the braces
+fn lv_text(input: &[u8]) -> IResult<&[u8], (&str, &[u8])> {
+    delimited(
+        whitespace,
+        tuple((
+            parse_ident,
+            preceded(tuple((whitespace, char('{'))), cut(section_body)),
+        )),
+        whitespace,
+    )(input)
+}
+
+// the text up to the brace closing the section just opened, skipping over strings
+// and comments rather than parsing anything
+fn section_body(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let mut depth = 0usize;
+    let mut bytes = input.iter().enumerate();
+    while let Some((i, &b)) = bytes.next() {
+        match b {
+            b'"' => {
+                while let Some((_, &b)) = bytes.next() {
+                    match b {
+                        b'"' => break,
+                        b'\\' => {
+                            bytes.next();
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            b'#' => {
+                bytes.by_ref().find(|&(_, &b)| b == b'\n');
+            }
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Ok((&input[i + 1..], &input[..i])),
+            b'}' => depth -= 1,
+            _ => (),
+        }
     }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::TakeUntil,
+    )))
 }
 
-#[derive(Debug)]
-pub enum MetadataElement<'a> {
-    Group {
-        name: &'a str,
-        contents: MetadataElements<'a>,
-    },
-    Value {
-        name: &'a str,
-        value: MetadataValue,
-    },
+// LVM escapes `"` and `\` within strings with a backslash. Bytes that are not
+// UTF-8 are replaced rather than failing the whole config. Borrows the input unless
+// either had to be done.
+fn parse_string(input: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    let (input, _) = char('"')(input)?;
+    let mut escaped = false;
+    let mut bytes = input.iter().enumerate();
+    while let Some((i, &b)) = bytes.next() {
+        match b {
+            b'"' if escaped => {
+                let mut value = Vec::with_capacity(i);
+                let mut bytes = input[..i].iter();
+                while let Some(&b) = bytes.next() {
+                    value.extend(if b == b'\\' { bytes.next() } else { Some(&b) });
+                }
+                let value = String::from_utf8(value)
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+                return Ok((&input[i + 1..], Cow::Owned(value)));
+            }
+            b'"' => return Ok((&input[i + 1..], String::from_utf8_lossy(&input[..i]))),
+            b'\\' => {
+                escaped = true;
+                bytes.next();
+            }
+            _ => (),
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::TakeUntil,
+    )))
 }
-fn parse_ident(input: &str) -> IResult<&str, &str> {
-    take_till1(|x| " \r\n\t{}=[]".contains(x))(input)
+fn parse_ident(input: &[u8]) -> IResult<&[u8], &str> {
+    map_res(take_till1(|x| b" \r\n\t{}=[]".contains(&x)), str::from_utf8)(input)
 }
-fn comment(input: &str) -> IResult<&str, &str> {
+fn comment(input: &[u8]) -> IResult<&[u8], &[u8]> {
     preceded(
         multispace0,
-        delimited(char('#'), not_line_ending, line_ending),
+        // the last line of a hand-edited file may not end in a newline
+        delimited(char('#'), not_line_ending, alt((line_ending, eof))),
     )(input)
 }
-fn whitespace(input: &str) -> IResult<&str, ()> {
-    let (input, _) = many0(comment)(input)?;
+// counted rather than collected: this runs around every token
+fn whitespace(input: &[u8]) -> IResult<&[u8], ()> {
+    let (input, _) = many0_count(comment)(input)?;
     let (input, _) = multispace0(input)?;
     Ok((input, ()))
 }
-impl<'a> MetadataElement<'a> {
-    pub fn name(&self) -> &'a str {
-        match self {
-            MetadataElement::Group { name, .. } => name,
-            MetadataElement::Value { name, .. } => name,
-        }
-    }
-    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        delimited(
-            whitespace,
-            alt((
-                map(
-                    tuple((
-                        parse_ident,
-                        whitespace,
-                        char('='),
-                        whitespace,
-                        MetadataValue::parse,
-                    )),
-                    |(name, _, _, _, v)| MetadataElement::Value { name, value: v },
-                ),
-                map(
-                    tuple((
-                        parse_ident,
-                        whitespace,
-                        char('{'),
-                        MetadataElements::parse,
-                        char('}'),
-                    )),
-                    |(name, _, _, contents, _)| MetadataElement::Group { name, contents },
-                ),
-            )),
-            whitespace,
-        )(input)
-    }
+// once past the `=` or `{` the entry has to complete, so errors are reported where
+// they happen instead of backtracking to the start of the enclosing section
+fn parse_entry(input: &[u8]) -> IResult<&[u8], (&str, MetadataValue)> {
+    delimited(
+        whitespace,
+        alt((
+            map(
+                tuple((
+                    parse_ident,
+                    whitespace,
+                    char('='),
+                    whitespace,
+                    cut(MetadataValue::parse),
+                )),
+                |(name, _, _, _, value)| (name, value),
+            ),
+            map(
+                tuple((
+                    parse_ident,
+                    whitespace,
+                    char('{'),
+                    many0(map(parse_entry, |(name, value)| (name.to_owned(), value))),
+                    whitespace,
+                    cut(char('}')),
+                )),
+                // sections are kept for the VG's lifetime, so drop the spare capacity
+                |(name, _, _, mut entries, _, _)| {
+                    entries.shrink_to_fit();
+                    (name, MetadataValue::Section(entries))
+                },
+            ),
+        )),
+        whitespace,
+    )(input)
 }
 
-impl<'de> de::Deserializer<'de> for &MetadataElements<'de> {
+// strings and keys are lent out for as long as the value lives, so anything that
+// buffers them (serde's `flatten`, for one) need not copy
+impl<'de> de::Deserializer<'de> for &'de MetadataValue {
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
         tuple_struct map struct enum identifier ignored_any
     }
 
     type Error = serde::de::value::Error;
 
+    // a value that is present at all is always Some
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_map(MetadataElementsAccess(self.0.iter().peekable()))
+        match self {
+            MetadataValue::String(s) => visitor.visit_borrowed_str(s),
+            &MetadataValue::Number(n) => visitor.visit_i64(n),
+            MetadataValue::Array(a) => visitor.visit_seq(MetadataValuesAccess(a.iter())),
+            MetadataValue::Section(entries) => {
+                visitor.visit_map(MetadataSectionAccess::new(entries.iter()))
+            }
+        }
     }
 }
-pub struct MetadataElementsAccess<'a, 'de>(
-    core::iter::Peekable<alloc::slice::Iter<'a, MetadataElement<'de>>>,
-);
-
-impl<'de, 'a> de::MapAccess<'de> for MetadataElementsAccess<'a, 'de> {
+/// Section entries as a map, e.g. for a [`de::value::MapAccessDeserializer`].
+pub struct MetadataSectionAccess<I: Iterator>(core::iter::Peekable<I>);
+impl<I: Iterator> MetadataSectionAccess<I> {
+    pub fn new(entries: I) -> Self {
+        Self(entries.peekable())
+    }
+}
+impl<'de, I> de::MapAccess<'de> for MetadataSectionAccess<I>
+where
+    I: Iterator<Item = &'de (String, MetadataValue)>,
+{
     type Error = serde::de::value::Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
@@ -140,7 +490,9 @@ impl<'de, 'a> de::MapAccess<'de> for MetadataElementsAccess<'a, 'de> {
     {
         match self.0.peek() {
             None => Ok(None),
-            Some(x) => seed.deserialize(StrDeserializer::new(x.name())).map(Some),
+            Some((name, _)) => seed
+                .deserialize(BorrowedStrDeserializer::new(name))
+                .map(Some),
         }
     }
 
@@ -150,33 +502,12 @@ impl<'de, 'a> de::MapAccess<'de> for MetadataElementsAccess<'a, 'de> {
     {
         match self.0.next() {
             None => panic!("deserializing value before key"),
-            Some(MetadataElement::Group { contents, .. }) => seed.deserialize(contents),
-            Some(MetadataElement::Value { value, .. }) => seed.deserialize(value),
-        }
-    }
-}
-impl<'de> de::Deserializer<'de> for &MetadataValue {
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
-    }
-
-    type Error = serde::de::value::Error;
-
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        match self {
-            MetadataValue::String(s) => visitor.visit_str(s),
-            &MetadataValue::Number(n) => visitor.visit_i64(n),
-            MetadataValue::Array(a) => visitor.visit_seq(MetadataValuesAccess(a.iter())),
+            Some((_, value)) => seed.deserialize(value),
         }
     }
 }
 pub struct MetadataValuesAccess<'a>(alloc::slice::Iter<'a, MetadataValue>);
-impl<'de, 'a> de::SeqAccess<'de> for MetadataValuesAccess<'a> {
+impl<'de> de::SeqAccess<'de> for MetadataValuesAccess<'de> {
     type Error = serde::de::value::Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
@@ -189,3 +520,169 @@ impl<'de, 'a> de::SeqAccess<'de> for MetadataValuesAccess<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn string(s: &str) -> MetadataValue {
+        MetadataValue::String(s.to_owned())
+    }
+
+    #[test]
+    fn parse_string_borrows_plain_strings() {
+        let (rest, value) = parse_string(b"\"/dev/sda\" rest").unwrap();
+        assert_eq!(rest, b" rest");
+        assert!(matches!(value, Cow::Borrowed("/dev/sda")));
+    }
+
+    #[test]
+    fn parse_string_unescapes() {
+        let (rest, value) = parse_string(br#""a \"quoted\" C:\\path" rest"#).unwrap();
+        assert_eq!(rest, b" rest");
+        assert_eq!(value, r#"a "quoted" C:\path"#);
+        let (_, value) = parse_string(br#""\\""#).unwrap();
+        assert_eq!(value, "\\");
+    }
+
+    #[test]
+    fn parse_string_replaces_invalid_utf8() {
+        let (_, value) = parse_string(b"\"host\xff\"").unwrap();
+        assert_eq!(value, "host\u{fffd}");
+        let (_, value) = parse_string(b"\"\\\"\xff\"").unwrap();
+        assert_eq!(value, "\"\u{fffd}");
+    }
+
+    #[test]
+    fn parse_string_needs_the_closing_quote() {
+        assert!(parse_string(b"\"open").is_err());
+        assert!(parse_string(b"\"escaped\\\"").is_err());
+    }
+
+    #[test]
+    fn section_body_stops_at_the_matching_brace() {
+        let input = b"a { b = 1 } c = \"}\" # }\n d = 2 } after";
+        let (rest, body) = section_body(input).unwrap();
+        assert_eq!(rest, b" after");
+        assert_eq!(body, b"a { b = 1 } c = \"}\" # }\n d = 2 ");
+        // an escaped quote does not end the string
+        let (rest, body) = section_body(b"s = \"\\\"}\" } x").unwrap();
+        assert_eq!(rest, b" x");
+        assert_eq!(body, b"s = \"\\\"}\" ");
+    }
+
+    #[test]
+    fn section_body_needs_the_closing_brace() {
+        assert!(section_body(b"a { b = 1 }").is_err());
+        assert!(section_body(b"a = \"}").is_err());
+        assert!(section_body(b"# }").is_err());
+    }
+
+    #[test]
+    fn comment_at_end_of_input() {
+        assert_eq!(
+            parse_section_body(b"a = 1\n# no newline after this"),
+            Ok(MetadataValue::Section(vec![(
+                "a".to_owned(),
+                MetadataValue::Number(1)
+            )]))
+        );
+        let entries: Vec<_> = MetadataParser::new(b"a = 1 # trailing")
+            .map(|entry| entry.unwrap().name)
+            .collect();
+        assert_eq!(entries, ["a"]);
+    }
+
+    #[test]
+    fn nested_sections() {
+        let text = b"vg {\n\tid = \"x\"\n\tpvs {\n\t\tpv0 {\n\t\t\tpe_count = 4\n\t\t}\n\t}\n\tl = [\"a\", 1, []]\n}\n";
+        let mut parser = MetadataParser::new(text);
+        let entry = parser.next().unwrap().unwrap();
+        assert!(parser.next().is_none());
+        assert!(parser.remaining().is_empty());
+        assert_eq!(entry.name, "vg");
+        assert_eq!(
+            entry.value,
+            MetadataValue::Section(vec![
+                ("id".to_owned(), string("x")),
+                (
+                    "pvs".to_owned(),
+                    MetadataValue::Section(vec![(
+                        "pv0".to_owned(),
+                        MetadataValue::Section(vec![(
+                            "pe_count".to_owned(),
+                            MetadataValue::Number(4)
+                        )])
+                    )])
+                ),
+                (
+                    "l".to_owned(),
+                    MetadataValue::Array(vec![
+                        string("a"),
+                        MetadataValue::Number(1),
+                        MetadataValue::Array(Vec::new())
+                    ])
+                ),
+            ])
+        );
+        assert_eq!(
+            entry.value.get("pvs/pv0/pe_count"),
+            Some(&MetadataValue::Number(4))
+        );
+        assert_eq!(entry.value.get("l/0"), Some(&string("a")));
+    }
+
+    #[test]
+    fn skeleton_delimits_lvs() {
+        let text = b"vg {\n\tid = \"x\"\n\tlogical_volumes {\n\t\tlv0 {\n\t\t\tname = \"}\"\n\t\t\tseg { a = 1 }\n\t\t}\n\t}\n\tseqno = 2\n}\n";
+        let entry = MetadataParser::skeleton(text).next().unwrap().unwrap();
+        assert_eq!(entry.lvs_at, Some(1));
+        assert_eq!(entry.lvs.len(), 1);
+        let (name, body) = entry.lvs[0];
+        assert_eq!(name, "lv0");
+        assert_eq!(
+            parse_section_body(body),
+            Ok(MetadataValue::Section(vec![
+                ("name".to_owned(), string("}")),
+                (
+                    "seg".to_owned(),
+                    MetadataValue::Section(vec![("a".to_owned(), MetadataValue::Number(1))])
+                ),
+            ]))
+        );
+        assert_eq!(
+            entry.value,
+            MetadataValue::Section(vec![
+                ("id".to_owned(), string("x")),
+                ("seqno".to_owned(), MetadataValue::Number(2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        // in a string the bytes are replaced
+        let entry = MetadataParser::new(b"host = \"a\xc3\x28\"\n")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.value, string("a\u{fffd}("));
+        // a name has to be UTF-8
+        let mut parser = MetadataParser::new(b"ok = 1\nn\xff = 1\n");
+        assert!(parser.next().unwrap().is_ok());
+        assert!(parser.next().is_none());
+        assert_eq!(parser.remaining(), b"n\xff = 1\n");
+    }
+
+    #[test]
+    fn errors_after_the_equals_sign_are_reported_there() {
+        let text = b"vg {\n\ta = 1\n\tb = ]\n}\n";
+        let mut parser = MetadataParser::new(text);
+        assert!(matches!(parser.next(), Some(Err(b"]\n}\n"))));
+        assert!(parser.next().is_none());
+        // at the unterminated string's contents
+        assert_eq!(parse_section_body(b"a = 1\nb = \"open"), Err(11));
+        assert_eq!(parse_section_body(b"a = 1\n}"), Err(6));
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::MetadataValue;
+
+/// A value that differs between two configs, by its `/`-separated path.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetadataChange<'a> {
+    pub path: String,
+    /// `None` when the key was added.
+    pub old: Option<&'a MetadataValue>,
+    /// `None` when the key was removed.
+    pub new: Option<&'a MetadataValue>,
+}
+
+impl MetadataValue {
+    /// The values that differ from `self` to `newer`. Sections are compared key by
+    /// key, anything else as a whole.
+    pub fn diff<'a>(&'a self, newer: &'a MetadataValue) -> Vec<MetadataChange<'a>> {
+        let mut changes = Vec::new();
+        diff_into(&mut changes, "", self, newer);
+        changes
+    }
+}
+
+fn diff_into<'a>(
+    changes: &mut Vec<MetadataChange<'a>>,
+    path: &str,
+    old: &'a MetadataValue,
+    new: &'a MetadataValue,
+) {
+    let (MetadataValue::Section(old_entries), MetadataValue::Section(new_entries)) = (old, new)
+    else {
+        if old != new {
+            changes.push(MetadataChange {
+                path: path.to_owned(),
+                old: Some(old),
+                new: Some(new),
+            });
+        }
+        return;
+    };
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{path}/{key}")
+        }
+    };
+    for (key, old_value) in old_entries {
+        match new_entries.iter().find(|(k, _)| k == key) {
+            Some((_, new_value)) => diff_into(changes, &join(key), old_value, new_value),
+            None => changes.push(MetadataChange {
+                path: join(key),
+                old: Some(old_value),
+                new: None,
+            }),
+        }
+    }
+    for (key, new_value) in new_entries {
+        if !old_entries.iter().any(|(k, _)| k == key) {
+            changes.push(MetadataChange {
+                path: join(key),
+                old: None,
+                new: Some(new_value),
+            });
+        }
+    }
+}
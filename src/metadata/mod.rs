@@ -1,53 +1,976 @@
 use core::ops::Range;
 
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
-use serde::Deserialize;
+use once_cell::race::OnceBox;
+use serde::{de, Deserialize, Deserializer, Serialize};
 
 use crate::force_de_typed_map::ForceDeTypedMap;
+use crate::{Error, Timestamp};
 
 pub(crate) mod deserialize;
+use deserialize::{parse_section_body, MetadataSectionAccess};
+pub use deserialize::{MetadataEntry, MetadataParser, MetadataValue};
+mod diff;
+pub use diff::MetadataChange;
+mod write;
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct MetadataRoot {
     pub id: String,
+    pub seqno: u64,
     pub extent_size: u64,
+    /// Where new extents go by default; `normal` when not written.
+    pub allocation_policy: Option<AllocationPolicy>,
+    /// The most LVs and PVs the VG may hold, 0 for no limit.
+    pub max_lv: Option<u64>,
+    pub max_pv: Option<u64>,
+    /// How many metadata areas LVM keeps in use across the PVs, 0 for all of them.
+    pub metadata_copies: Option<u64>,
     pub physical_volumes: BTreeMap<String, PVDesc>,
-    pub logical_volumes: BTreeMap<String, LVDesc>,
+    /// Empty in a VG section without LVs, which LVM may leave out.
+    #[serde(default)]
+    pub logical_volumes: BTreeMap<String, LazyLVDesc>,
+    /// Keys this model does not recognize, kept as written.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, MetadataValue>,
 }
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct PVDesc {
     pub id: String,
     pub device: String,
+    #[serde(default)]
+    pub status: Vec<String>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    pub dev_size: Option<u64>,
     pub pe_start: u64,
     pub pe_count: u64,
+    /// Start and size of the bootloader area, in sheets.
+    pub ba_start: Option<u64>,
+    pub ba_size: Option<u64>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, MetadataValue>,
+}
+/// How LVM picks the extents to allocate to an LV, see `lvm(8)`. It only applies
+/// when extents are allocated, but explains the layout found: a `contiguous` LV's
+/// segments follow each other on one PV, a `cling` one's stay on the PVs it
+/// already uses, while `anywhere` may even put two stripes on the same PV.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum AllocationPolicy {
+    /// Stripes and mirror legs on different PVs, anything else anywhere free.
+    Normal,
+    /// Each new segment right after the one before, on the same PVs.
+    Contiguous,
+    /// On the PVs the LV already uses.
+    Cling,
+    /// On PVs sharing a tag with those the LV already uses.
+    ClingByTags,
+    /// Anywhere free, even on a PV already holding another stripe or leg.
+    Anywhere,
+    /// The VG's policy, for an LV.
+    Inherit,
+    /// A policy this crate does not know, as written.
+    Other(String),
+}
+impl AllocationPolicy {
+    /// The policy as written in the metadata, e.g. `cling_by_tags`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AllocationPolicy::Normal => "normal",
+            AllocationPolicy::Contiguous => "contiguous",
+            AllocationPolicy::Cling => "cling",
+            AllocationPolicy::ClingByTags => "cling_by_tags",
+            AllocationPolicy::Anywhere => "anywhere",
+            AllocationPolicy::Inherit => "inherit",
+            AllocationPolicy::Other(policy) => policy,
+        }
+    }
+}
+impl From<String> for AllocationPolicy {
+    fn from(policy: String) -> Self {
+        match policy.as_str() {
+            "normal" => AllocationPolicy::Normal,
+            "contiguous" => AllocationPolicy::Contiguous,
+            "cling" => AllocationPolicy::Cling,
+            "cling_by_tags" => AllocationPolicy::ClingByTags,
+            "anywhere" => AllocationPolicy::Anywhere,
+            "inherit" => AllocationPolicy::Inherit,
+            _ => AllocationPolicy::Other(policy),
+        }
+    }
+}
+impl From<AllocationPolicy> for String {
+    fn from(policy: AllocationPolicy) -> Self {
+        policy.as_str().to_owned()
+    }
+}
+
+/// An LV section, only parsed and deserialized into an [`LVDesc`] when first used, so
+/// opening a VG with thousands of LVs to read one of them stays cheap.
+#[derive(Clone, Debug)]
+pub struct LazyLVDesc {
+    text: Vec<u8>,
+    raw: OnceBox<Result<MetadataValue, de::value::Error>>,
+    desc: OnceBox<Result<LVDesc, de::value::Error>>,
+}
+impl LazyLVDesc {
+    // the text between the section's braces
+    pub(crate) fn from_text(text: &[u8]) -> Self {
+        Self {
+            text: text.to_vec(),
+            raw: OnceBox::new(),
+            desc: OnceBox::new(),
+        }
+    }
+
+    /// The section as written, or why it does not parse.
+    pub fn raw(&self) -> Result<&MetadataValue, &de::value::Error> {
+        self.raw
+            .get_or_init(|| {
+                Box::new(parse_section_body(&self.text).map_err(|offset| {
+                    de::Error::custom(format_args!(
+                        "invalid config syntax {} bytes into the LV section",
+                        offset
+                    ))
+                }))
+            })
+            .as_ref()
+    }
+
+    /// The typed LV, or why the section does not describe one.
+    pub fn desc(&self) -> Result<&LVDesc, &de::value::Error> {
+        self.desc
+            .get_or_init(|| {
+                Box::new(
+                    self.raw()
+                        .map_err(Clone::clone)
+                        .and_then(LVDesc::from_section),
+                )
+            })
+            .as_ref()
+    }
+}
+impl From<LVDesc> for LazyLVDesc {
+    fn from(desc: LVDesc) -> Self {
+        Self {
+            text: Vec::new(),
+            raw: OnceBox::with_value(Box::new(Ok(desc.to_metadata_value()))),
+            desc: OnceBox::with_value(Box::new(Ok(desc))),
+        }
+    }
+}
+impl<'de> Deserialize<'de> for LazyLVDesc {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self {
+            text: Vec::new(),
+            raw: OnceBox::with_value(Box::new(Ok(MetadataValue::deserialize(deserializer)?))),
+            desc: OnceBox::new(),
+        })
+    }
+}
+impl Serialize for LazyLVDesc {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match (self.desc(), self.raw()) {
+            (Ok(desc), _) => desc.serialize(serializer),
+            (_, Ok(raw)) => raw.serialize(serializer),
+            (_, Err(e)) => serializer.collect_str(e),
+        }
+    }
 }
-#[derive(Deserialize, Clone, Debug)]
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(from = "RawLVDesc")]
 pub struct LVDesc {
     pub id: String,
     pub status: Vec<String>,
     pub flags: Vec<String>,
     #[serde(default)]
     pub tags: Vec<String>,
-    pub creation_time: u64,
+    pub creation_time: Timestamp,
     pub creation_host: String,
+    /// Where extents go when the LV is extended; the VG's policy when not written.
+    pub allocation_policy: Option<AllocationPolicy>,
+    /// Read-ahead in sheets, -1 for none. Not written when the kernel picks it.
+    pub read_ahead: Option<i64>,
     pub segment_count: usize,
     #[serde(flatten)]
     pub segments: ForceDeTypedMap<String, LVSegmentDesc>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, MetadataValue>,
+}
+impl LVDesc {
+    /// Segments ordered by the LV extent they start at rather than by key, which
+    /// would put `segment10` before `segment2`.
+    pub fn segments_in_order(&self) -> impl Iterator<Item = (&str, &LVSegmentDesc)> {
+        let mut segments: Vec<_> = self
+            .segments
+            .0
+            .iter()
+            .map(|(name, segment)| (name.as_str(), segment))
+            .collect();
+        segments.sort_by_key(|&(name, segment)| (segment.start_extent, name));
+        segments.into_iter()
+    }
+
+    // as `LVDesc::deserialize`, but types the sections straight from `section`
+    // instead of from the copies `flatten` would buffer
+    fn from_section(section: &MetadataValue) -> Result<Self, de::value::Error> {
+        let Some(entries) = section.as_section() else {
+            return LVDesc::deserialize(section);
+        };
+        let is_section = |entry: &&(String, MetadataValue)| entry.1.as_section().is_some();
+        let values = entries.iter().filter(|entry| !is_section(entry));
+        let mut desc = LVDesc::from(RawLVDesc::deserialize(
+            de::value::MapAccessDeserializer::new(MetadataSectionAccess::new(values)),
+        )?);
+        for (key, value) in entries.iter().filter(is_section) {
+            match LVSegmentDesc::deserialize(value) {
+                Ok(segment) => {
+                    desc.segments.0.insert(key.clone(), segment);
+                }
+                Err(_) => {
+                    desc.extra.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(desc)
+    }
 }
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(from = "RawSegmentDesc")]
 pub struct LVSegmentDesc {
     pub start_extent: u64,
     pub extent_count: u64,
-    pub r#type: String,
-    pub stripe_count: Option<usize>,
-    pub stripe_size: Option<usize>,
-    pub stripes: Option<(String, u64)>,
-    pub raid0_lvs: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub kind: Segment,
+    /// Flags LVM appends to the segment type, e.g. `RESHAPE` in `raid5_ls+RESHAPE`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub type_flags: Vec<String>,
+    /// Keys not consumed by `kind`, kept as written.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, MetadataValue>,
 }
 impl LVSegmentDesc {
     pub fn extents(&self) -> Range<u64> {
         self.start_extent..(self.start_extent + self.extent_count)
     }
+
+    /// Whether the segment was written in the middle of a RAID reshape, i.e. carries
+    /// one of [`RESHAPE_FLAGS`]. Its images are then partly in the old layout and
+    /// partly in the new one, so where a given extent is cannot be told from the
+    /// metadata alone.
+    pub fn is_reshaping(&self) -> bool {
+        self.type_flags
+            .iter()
+            .any(|flag| RESHAPE_FLAGS.contains(&flag.as_str()))
+    }
+
+    /// Extents of this segment set aside as reshape space, `reshape_count` in the
+    /// metadata: room at the start or end of a `_rimage` LV for rewriting its data
+    /// out of place.
+    pub fn reshape_extents(&self) -> Option<u64> {
+        match self.extra.get("reshape_count")? {
+            MetadataValue::Number(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    /// The PV areas this segment maps directly. Empty for segments stacked on other LVs.
+    pub fn stripes(&self) -> &[Stripe] {
+        match &self.kind {
+            Segment::Linear { stripe } => core::slice::from_ref(stripe),
+            Segment::Striped { stripes, .. } => stripes,
+            _ => &[],
+        }
+    }
+
+    /// Number of physical extents each stripe of this segment occupies on its PV.
+    pub fn area_len(&self) -> u64 {
+        match self.stripes().len() {
+            0 => 0,
+            n => self.extent_count / n as u64,
+        }
+    }
+
+    /// The physical extents each stripe occupies, by the PV's name in the metadata,
+    /// in stripe order.
+    pub fn pv_extents(&self) -> impl Iterator<Item = (&str, Range<u64>)> + '_ {
+        let area_len = self.area_len();
+        self.stripes().iter().map(move |stripe| {
+            let start = stripe.start_extent;
+            (stripe.pv.as_str(), start..start.saturating_add(area_len))
+        })
+    }
+}
+
+/// What a segment maps its extents onto, decoded from its `type` and the
+/// fields that go with it.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Segment {
+    /// A `striped` segment with a single stripe.
+    Linear { stripe: Stripe },
+    Striped {
+        /// Size of a stripe chunk in sheets.
+        stripe_size: Option<u64>,
+        stripes: Vec<Stripe>,
+    },
+    Thin {
+        pool: String,
+        device_id: u64,
+        origin: Option<String>,
+        /// A thin snapshot of this LV that `lvconvert --merge` is merging back into
+        /// it, `merge` in the metadata.
+        merge: Option<String>,
+    },
+    ThinPool {
+        metadata: String,
+        pool: String,
+        /// Size of a pool block in sheets.
+        chunk_size: Option<u64>,
+        /// The pool metadata transaction LVM last committed.
+        transaction_id: Option<u64>,
+        /// Whether newly provisioned blocks are zeroed before use.
+        zero_new_blocks: Option<bool>,
+        /// How discards are handled: `ignore`, `nopassdown` or `passdown`.
+        discards: Option<String>,
+    },
+    /// An LV cached by a cache pool or cachevol `pool`, in front of the hidden
+    /// `_corig` LV `origin` holding its uncached data.
+    Cache {
+        pool: String,
+        origin: String,
+        /// With a cachevol, the sheets of `pool` holding the cache metadata.
+        metadata_area: Option<Range<u64>>,
+        /// With a cachevol, the sheets of `pool` holding the cached blocks.
+        data_area: Option<Range<u64>>,
+    },
+    /// A cache pool, made of the `_cdata` and `_cmeta` LVs `data` and `metadata`.
+    CachePool {
+        data: String,
+        metadata: String,
+        /// Size of a cache block in sheets.
+        chunk_size: Option<u64>,
+        /// `writethrough`, `writeback` or `passthrough`. Only with `writeback` can
+        /// the cache hold data the origin lacks.
+        cache_mode: Option<String>,
+    },
+    /// An old-style (non-thin) snapshot of `origin`, whose copied-out chunks are kept
+    /// in the `cow_store` LV.
+    Snapshot {
+        origin: String,
+        cow_store: String,
+        /// Size of a copied chunk in sheets.
+        chunk_size: u64,
+        /// Whether `lvconvert --merge` is copying the chunks back into the origin;
+        /// the COW store is then written as `merging_store`.
+        merging: bool,
+    },
+    /// An old-style (non-RAID) mirror of the `_mimage` LVs `images`, which keeps
+    /// track of the regions in sync in the `_mlog` LV `log`. Without a log, as with
+    /// `--mirrorlog core`, the kernel kept that in memory only.
+    Mirror {
+        images: Vec<MirrorImage>,
+        log: Option<String>,
+        /// Size of a region the log tracks, in sheets.
+        region_size: Option<u64>,
+    },
+    Raid0 {
+        stripe_size: Option<u64>,
+        images: Vec<String>,
+    },
+    Raid1 {
+        images: Vec<String>,
+        meta_images: Vec<String>,
+        #[serde(flatten)]
+        layout: RaidLayout,
+    },
+    /// Another RAID level with `_rmeta` LVs, e.g. `raid5_ls`, `raid6_zr` or `raid10`,
+    /// or `raid0_meta`; `lvconvert` passes through these while converting between
+    /// levels.
+    Raid {
+        /// The segment type, e.g. `raid5_ls`.
+        level: String,
+        stripe_size: Option<u64>,
+        images: Vec<String>,
+        meta_images: Vec<String>,
+        #[serde(flatten)]
+        layout: RaidLayout,
+    },
+    /// A segment type this crate does not model (or one missing its required fields).
+    Unknown { raw: String },
+}
+impl Segment {
+    /// The segment type as `lvs -o segtype` would print it.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Segment::Linear { .. } => "linear",
+            Segment::Striped { .. } => "striped",
+            Segment::Thin { .. } => "thin",
+            Segment::ThinPool { .. } => "thin-pool",
+            Segment::Cache { .. } => "cache",
+            Segment::CachePool { .. } => "cache-pool",
+            Segment::Snapshot { .. } => "snapshot",
+            Segment::Mirror { .. } => "mirror",
+            Segment::Raid0 { .. } => "raid0",
+            Segment::Raid1 { .. } => "raid1",
+            Segment::Raid { level, .. } => level,
+            Segment::Unknown { raw } => raw,
+        }
+    }
+
+    /// Names of the other LVs this segment maps onto or depends on.
+    pub fn referenced_lvs(&self) -> Vec<&str> {
+        match self {
+            Segment::Linear { .. } | Segment::Striped { .. } | Segment::Unknown { .. } => {
+                Vec::new()
+            }
+            Segment::Thin { pool, origin, .. } => core::iter::once(pool)
+                .chain(origin)
+                .map(String::as_str)
+                .collect(),
+            Segment::ThinPool { metadata, pool, .. } => vec![metadata, pool],
+            Segment::Cache { pool, origin, .. } => vec![pool, origin],
+            Segment::CachePool { data, metadata, .. } => vec![data, metadata],
+            Segment::Snapshot {
+                origin, cow_store, ..
+            } => vec![origin, cow_store],
+            Segment::Mirror { images, log, .. } => images
+                .iter()
+                .map(|image| &image.name)
+                .chain(log)
+                .map(String::as_str)
+                .collect(),
+            Segment::Raid0 { images, .. } => images.iter().map(String::as_str).collect(),
+            Segment::Raid1 {
+                images,
+                meta_images,
+                ..
+            }
+            | Segment::Raid {
+                images,
+                meta_images,
+                ..
+            } => images
+                .iter()
+                .chain(meta_images)
+                .map(String::as_str)
+                .collect(),
+        }
+    }
+
+    /// The region, copy and data offset fields of a RAID segment with `_rmeta` LVs.
+    pub fn raid_layout(&self) -> Option<&RaidLayout> {
+        match self {
+            Segment::Raid1 { layout, .. } | Segment::Raid { layout, .. } => Some(layout),
+            _ => None,
+        }
+    }
+}
+
+/// The fields of a RAID segment that describe how its images are laid out, beyond
+/// the images themselves. `data_offset` in particular moves while a reshape is under
+/// way, see [`LVSegmentDesc::is_reshaping`].
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RaidLayout {
+    /// Size of a region the `_rmeta` bitmaps track, in sheets.
+    pub region_size: Option<u64>,
+    /// How many copies of the data the images hold, e.g. 2 for `raid10`.
+    pub data_copies: Option<u64>,
+    /// Where the data starts within each `_rimage` LV, in sheets.
+    pub data_offset: Option<u64>,
+}
+
+/// The segment type flags `lvconvert` sets while reshaping a RAID LV, e.g. the
+/// `RESHAPE` of `raid5_ls+RESHAPE`.
+pub const RESHAPE_FLAGS: [&str; 5] = [
+    "RESHAPE",
+    "RESHAPE_DATA_OFFSET",
+    "RESHAPE_DELTA_DISKS_PLUS",
+    "RESHAPE_DELTA_DISKS_MINUS",
+    "REMOVE_AFTER_RESHAPE",
+];
+
+/// One entry of a segment's `stripes`, e.g. `"pv0", 128`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Stripe {
+    /// Name of the PV in the VG metadata.
+    pub pv: String,
+    /// First physical extent of the stripe on that PV.
+    pub start_extent: u64,
+}
+
+/// One entry of a mirror segment's `mirrors`, e.g. `"lv_mimage_0", 0`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MirrorImage {
+    /// Name of the `_mimage` LV holding the leg or, in a `pvmove` LV, of the PV.
+    pub name: String,
+    /// Extent of that LV or PV the segment's copy starts at: 0 but in a segment
+    /// added when the mirror was extended, or a `pvmove` LV's.
+    pub start_extent: u64,
+}
+
+// the LV exactly as it is written in the metadata text; segments and unknown keys
+// share the same namespace, so anything that parses as a segment is taken as one
+#[derive(Deserialize)]
+struct RawLVDesc {
+    id: String,
+    status: Vec<String>,
+    flags: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    creation_time: Timestamp,
+    creation_host: String,
+    allocation_policy: Option<AllocationPolicy>,
+    read_ahead: Option<i64>,
+    segment_count: usize,
+    #[serde(flatten)]
+    rest: BTreeMap<String, MetadataValue>,
+}
+impl From<RawLVDesc> for LVDesc {
+    fn from(raw: RawLVDesc) -> Self {
+        let mut segments = BTreeMap::new();
+        let mut extra = BTreeMap::new();
+        for (key, value) in raw.rest {
+            match value {
+                MetadataValue::Section(_) => match LVSegmentDesc::deserialize(&value) {
+                    Ok(segment) => {
+                        segments.insert(key, segment);
+                    }
+                    Err(_) => {
+                        extra.insert(key, value);
+                    }
+                },
+                _ => {
+                    extra.insert(key, value);
+                }
+            }
+        }
+        LVDesc {
+            id: raw.id,
+            status: raw.status,
+            flags: raw.flags,
+            tags: raw.tags,
+            creation_time: raw.creation_time,
+            creation_host: raw.creation_host,
+            allocation_policy: raw.allocation_policy,
+            read_ahead: raw.read_ahead,
+            segment_count: raw.segment_count,
+            segments: ForceDeTypedMap(segments),
+            extra,
+        }
+    }
+}
+
+// the segment exactly as it is written in the metadata text
+#[derive(Deserialize)]
+struct RawSegmentDesc {
+    start_extent: u64,
+    extent_count: u64,
+    r#type: String,
+    stripe_size: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_stripes")]
+    stripes: Option<Vec<(String, u64)>>,
+    raid0_lvs: Option<Vec<String>>,
+    raids: Option<Vec<String>>,
+    thin_pool: Option<String>,
+    device_id: Option<u64>,
+    origin: Option<String>,
+    metadata: Option<String>,
+    pool: Option<String>,
+    cow_store: Option<String>,
+    chunk_size: Option<u64>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, MetadataValue>,
+}
+impl From<RawSegmentDesc> for LVSegmentDesc {
+    fn from(mut raw: RawSegmentDesc) -> Self {
+        let (start_extent, extent_count) = (raw.start_extent, raw.extent_count);
+        let mut type_flags: Vec<String> = raw.r#type.split('+').map(str::to_owned).collect();
+        let r#type = type_flags.remove(0);
+        let mut extra = core::mem::take(&mut raw.extra);
+        let kind = match (r#type.as_str(), raw) {
+            (
+                "striped",
+                RawSegmentDesc {
+                    stripes: Some(stripes),
+                    stripe_size,
+                    ..
+                },
+            ) => {
+                let mut stripes: Vec<Stripe> = stripes
+                    .into_iter()
+                    .map(|(pv, start_extent)| Stripe { pv, start_extent })
+                    .collect();
+                if stripes.len() == 1 {
+                    Segment::Linear {
+                        stripe: stripes.remove(0),
+                    }
+                } else {
+                    Segment::Striped {
+                        stripe_size,
+                        stripes,
+                    }
+                }
+            }
+            (
+                "thin",
+                RawSegmentDesc {
+                    thin_pool: Some(pool),
+                    device_id: Some(device_id),
+                    origin,
+                    ..
+                },
+            ) => Segment::Thin {
+                pool,
+                device_id,
+                origin,
+                merge: take_string(&mut extra, "merge"),
+            },
+            (
+                "thin-pool",
+                RawSegmentDesc {
+                    metadata: Some(metadata),
+                    pool: Some(pool),
+                    chunk_size,
+                    ..
+                },
+            ) => Segment::ThinPool {
+                metadata,
+                pool,
+                chunk_size,
+                transaction_id: take_number(&mut extra, "transaction_id"),
+                zero_new_blocks: take_number(&mut extra, "zero_new_blocks").map(|zero| zero != 0),
+                discards: take_string(&mut extra, "discards"),
+            },
+            (
+                "cache",
+                RawSegmentDesc {
+                    origin: Some(origin),
+                    ..
+                },
+            ) if matches!(extra.get("cache_pool"), Some(MetadataValue::String(_))) => {
+                Segment::Cache {
+                    pool: take_string(&mut extra, "cache_pool").unwrap_or_default(),
+                    origin,
+                    metadata_area: take_area(&mut extra, "metadata"),
+                    data_area: take_area(&mut extra, "data"),
+                }
+            }
+            (
+                "cache-pool",
+                RawSegmentDesc {
+                    metadata: Some(metadata),
+                    chunk_size,
+                    ..
+                },
+            ) if matches!(extra.get("data"), Some(MetadataValue::String(_))) => {
+                Segment::CachePool {
+                    data: take_string(&mut extra, "data").unwrap_or_default(),
+                    metadata,
+                    chunk_size,
+                    cache_mode: take_string(&mut extra, "cache_mode"),
+                }
+            }
+            (
+                "snapshot",
+                RawSegmentDesc {
+                    origin: Some(origin),
+                    cow_store: Some(cow_store),
+                    chunk_size: Some(chunk_size),
+                    ..
+                },
+            ) => Segment::Snapshot {
+                origin,
+                cow_store,
+                chunk_size,
+                merging: false,
+            },
+            (
+                "snapshot",
+                RawSegmentDesc {
+                    origin: Some(origin),
+                    chunk_size: Some(chunk_size),
+                    ..
+                },
+            ) if matches!(extra.get("merging_store"), Some(MetadataValue::String(_))) => {
+                Segment::Snapshot {
+                    origin,
+                    cow_store: take_string(&mut extra, "merging_store").unwrap_or_default(),
+                    chunk_size,
+                    merging: true,
+                }
+            }
+            ("mirror", _) if extra.get("mirrors").and_then(mirror_images).is_some() => {
+                Segment::Mirror {
+                    images: take_mirrors(&mut extra),
+                    log: take_string(&mut extra, "mirror_log"),
+                    region_size: take_number(&mut extra, "region_size"),
+                }
+            }
+            (
+                "raid0",
+                RawSegmentDesc {
+                    raid0_lvs: Some(images),
+                    stripe_size,
+                    ..
+                },
+            ) => Segment::Raid0 {
+                stripe_size,
+                images,
+            },
+            (
+                "raid1",
+                RawSegmentDesc {
+                    raids: Some(raids), ..
+                },
+            ) => {
+                let (meta_images, images) = raid_legs(&raids);
+                Segment::Raid1 {
+                    images,
+                    meta_images,
+                    layout: take_raid_layout(&mut extra),
+                }
+            }
+            (
+                level,
+                RawSegmentDesc {
+                    raids: Some(raids),
+                    stripe_size,
+                    ..
+                },
+            ) if level.starts_with("raid") => {
+                let (meta_images, images) = raid_legs(&raids);
+                Segment::Raid {
+                    level: level.to_owned(),
+                    stripe_size,
+                    images,
+                    meta_images,
+                    layout: take_raid_layout(&mut extra),
+                }
+            }
+            (other, raw) => {
+                // an unknown type, or a known one missing what it needs: whatever the
+                // fields above took goes back, so the segment keeps all it was written with
+                restore_fields(&mut extra, raw);
+                Segment::Unknown {
+                    raw: other.to_owned(),
+                }
+            }
+        };
+        LVSegmentDesc {
+            start_extent,
+            extent_count,
+            kind,
+            type_flags,
+            extra,
+        }
+    }
+}
+
+// puts the fields `RawSegmentDesc` takes out of the segment back into `extra`
+fn restore_fields(extra: &mut BTreeMap<String, MetadataValue>, raw: RawSegmentDesc) {
+    let strings = |names: Vec<String>| {
+        MetadataValue::Array(names.into_iter().map(MetadataValue::String).collect())
+    };
+    let mut insert = |key: &str, value: Option<MetadataValue>| {
+        if let Some(value) = value {
+            extra.insert(key.to_owned(), value);
+        }
+    };
+    let number = |n: Option<u64>| n.map(|n| MetadataValue::Number(n as i64));
+    insert("stripe_size", number(raw.stripe_size));
+    insert(
+        "stripes",
+        raw.stripes.map(|stripes| {
+            MetadataValue::Array(
+                stripes
+                    .into_iter()
+                    .flat_map(|(pv, start)| {
+                        [
+                            MetadataValue::String(pv),
+                            MetadataValue::Number(start as i64),
+                        ]
+                    })
+                    .collect(),
+            )
+        }),
+    );
+    insert("raid0_lvs", raw.raid0_lvs.map(strings));
+    insert("raids", raw.raids.map(strings));
+    insert("thin_pool", raw.thin_pool.map(MetadataValue::String));
+    insert("device_id", number(raw.device_id));
+    insert("origin", raw.origin.map(MetadataValue::String));
+    insert("metadata", raw.metadata.map(MetadataValue::String));
+    insert("pool", raw.pool.map(MetadataValue::String));
+    insert("cow_store", raw.cow_store.map(MetadataValue::String));
+    insert("chunk_size", number(raw.chunk_size));
+}
+
+// raids alternate between the rmeta and rimage sub-LV of each leg
+fn raid_legs(raids: &[String]) -> (Vec<String>, Vec<String>) {
+    raids
+        .chunks(2)
+        .fold((Vec::new(), Vec::new()), |(mut metas, mut images), leg| {
+            metas.push(leg[0].clone());
+            images.extend(leg.get(1).cloned());
+            (metas, images)
+        })
+}
+
+fn take_raid_layout(extra: &mut BTreeMap<String, MetadataValue>) -> RaidLayout {
+    RaidLayout {
+        region_size: take_number(extra, "region_size"),
+        data_copies: take_number(extra, "data_copies"),
+        data_offset: take_number(extra, "data_offset"),
+    }
+}
+
+/// Parses any text in LVM's config syntax into a section of its top-level entries,
+/// in order and with duplicates kept: VG metadata, but also `lvm.conf`, profiles
+/// and other config files found on evidence. Use [`MetadataValue::get`] to look up
+/// settings, e.g. `devices/filter`, and [`MetadataParser`] to take the entries one
+/// at a time instead.
+pub fn parse_config(text: &str) -> Result<MetadataValue, Error> {
+    let mut parser = MetadataParser::new(text.as_bytes());
+    let mut entries = Vec::new();
+    for entry in parser.by_ref() {
+        let entry = entry.map_err(|rest| {
+            metadata_parse_error(text, text.len() - rest.len(), "valid config syntax")
+        })?;
+        entries.push((entry.name.to_owned(), entry.value));
+    }
+    ensure_fully_parsed(text, parser.remaining())?;
+    Ok(MetadataValue::Section(entries))
+}
+
+// metadata text is NUL-terminated; anything before that the grammar did not consume
+// would otherwise be silently dropped
+pub(crate) fn ensure_fully_parsed(text: &str, remaining: &[u8]) -> Result<(), Error> {
+    tracing::debug!(trailing_garbage = %String::from_utf8_lossy(remaining));
+    let unparsed = remaining.split(|&b| b == 0).next().unwrap_or_default();
+    match unparsed.iter().position(|b| !b.is_ascii_whitespace()) {
+        Some(start) => {
+            let offset = text.len() - remaining.len() + start;
+            Err(metadata_parse_error(text, offset, "a key or section"))
+        }
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn metadata_parse_error(text: &str, offset: usize, expected: &'static str) -> Error {
+    let before = &text.as_bytes()[..offset];
+    Error::MetadataParse {
+        offset,
+        line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+        column: before.len()
+            - before
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1)
+            + 1,
+        expected,
+    }
+}
+
+// the optional fields only some segment types have are taken out of `extra`, so
+// that other types keep them as written
+fn take_number(extra: &mut BTreeMap<String, MetadataValue>, key: &str) -> Option<u64> {
+    let number = match extra.get(key)? {
+        MetadataValue::Number(n) => u64::try_from(*n).ok()?,
+        _ => return None,
+    };
+    extra.remove(key);
+    Some(number)
+}
+
+fn take_string(extra: &mut BTreeMap<String, MetadataValue>, key: &str) -> Option<String> {
+    match extra.remove(key)? {
+        MetadataValue::String(s) => Some(s),
+        other => {
+            extra.insert(key.to_owned(), other);
+            None
+        }
+    }
+}
+
+// mirrors are stored like stripes, ["lv_mimage_0", 0, "lv_mimage_1", 0]
+fn mirror_images(mirrors: &MetadataValue) -> Option<Vec<MirrorImage>> {
+    let mirrors = mirrors.as_array()?;
+    if mirrors.len() % 2 != 0 {
+        return None;
+    }
+    mirrors
+        .chunks(2)
+        .map(|image| {
+            Some(MirrorImage {
+                name: image[0].as_str()?.to_owned(),
+                start_extent: u64::try_from(image[1].as_number()?).ok()?,
+            })
+        })
+        .collect()
+}
+
+fn take_mirrors(extra: &mut BTreeMap<String, MetadataValue>) -> Vec<MirrorImage> {
+    extra
+        .remove("mirrors")
+        .as_ref()
+        .and_then(mirror_images)
+        .unwrap_or_default()
+}
+
+// a cachevol area, as `{prefix}_start` and `{prefix}_len` in sheets
+fn take_area(extra: &mut BTreeMap<String, MetadataValue>, prefix: &str) -> Option<Range<u64>> {
+    let (start_key, len_key) = (format!("{prefix}_start"), format!("{prefix}_len"));
+    let number = |key: &str| match extra.get(key) {
+        Some(MetadataValue::Number(n)) => u64::try_from(*n).ok(),
+        _ => None,
+    };
+    let (start, len) = (number(&start_key)?, number(&len_key)?);
+    extra.remove(&start_key);
+    extra.remove(&len_key);
+    Some(start..start.saturating_add(len))
+}
+
+// stripes are stored flat: ["pv0", 0, "pv1", 0]
+fn deserialize_stripes<'de, D>(deserializer: D) -> Result<Option<Vec<(String, u64)>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct StripesVisitor;
+    impl<'de> de::Visitor<'de> for StripesVisitor {
+        type Value = Vec<(String, u64)>;
+
+        fn expecting(&self, formatter: &mut alloc::fmt::Formatter) -> alloc::fmt::Result {
+            formatter.write_str("a list of alternating PV names and extents")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            let mut stripes = Vec::new();
+            while let Some(pv) = seq.next_element::<String>()? {
+                let extent = seq
+                    .next_element::<u64>()?
+                    .ok_or_else(|| de::Error::invalid_length(stripes.len() * 2 + 1, &self))?;
+                stripes.push((pv, extent));
+            }
+            Ok(stripes)
+        }
+    }
+    deserializer.deserialize_seq(StripesVisitor).map(Some)
 }
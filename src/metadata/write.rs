@@ -0,0 +1,1048 @@
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+
+use super::{
+    LVDesc, LVSegmentDesc, MetadataRoot, MetadataValue, PVDesc, RaidLayout, Segment, Stripe,
+};
+
+// builds a section in the order LVM writes it; `extra` keys already present are skipped
+struct SectionBuilder(Vec<(String, MetadataValue)>);
+impl SectionBuilder {
+    fn new() -> Self {
+        SectionBuilder(Vec::new())
+    }
+    fn str(mut self, key: &str, value: &str) -> Self {
+        self.0
+            .push((key.to_owned(), MetadataValue::String(value.to_owned())));
+        self
+    }
+    fn num(mut self, key: &str, value: u64) -> Self {
+        self.0
+            .push((key.to_owned(), MetadataValue::Number(value as i64)));
+        self
+    }
+    fn strs(mut self, key: &str, values: &[String]) -> Self {
+        let values = values
+            .iter()
+            .map(|v| MetadataValue::String(v.clone()))
+            .collect();
+        self.0.push((key.to_owned(), MetadataValue::Array(values)));
+        self
+    }
+    fn value(mut self, key: &str, value: MetadataValue) -> Self {
+        self.0.push((key.to_owned(), value));
+        self
+    }
+    // plain values go before the sections LVM nests below them
+    fn extra(mut self, extra: &alloc::collections::BTreeMap<String, MetadataValue>) -> Self {
+        let first_section = self
+            .0
+            .iter()
+            .position(|(_, v)| matches!(v, MetadataValue::Section(_)))
+            .unwrap_or(self.0.len());
+        let mut values = Vec::new();
+        let mut sections = Vec::new();
+        for (key, value) in extra {
+            if self.0.iter().any(|(k, _)| k == key) {
+                continue;
+            }
+            match value {
+                MetadataValue::Section(_) => sections.push((key.clone(), value.clone())),
+                _ => values.push((key.clone(), value.clone())),
+            }
+        }
+        self.0.splice(first_section..first_section, values);
+        self.0.extend(sections);
+        self
+    }
+    fn build(self) -> MetadataValue {
+        MetadataValue::Section(self.0)
+    }
+}
+
+fn stripes_value<'a>(stripes: impl IntoIterator<Item = &'a Stripe>) -> MetadataValue {
+    MetadataValue::Array(
+        stripes
+            .into_iter()
+            .flat_map(|s| {
+                [
+                    MetadataValue::String(s.pv.clone()),
+                    MetadataValue::Number(s.start_extent as i64),
+                ]
+            })
+            .collect(),
+    )
+}
+
+impl MetadataRoot {
+    /// This VG's section as it would appear in the metadata text.
+    pub fn to_metadata_value(&self) -> MetadataValue {
+        let pvs = self
+            .physical_volumes
+            .iter()
+            .map(|(name, pv)| (name.clone(), pv.to_metadata_value()))
+            .collect();
+        let lvs = self
+            .logical_volumes
+            .iter()
+            .map(|(name, lv)| {
+                // an LV the model cannot read is written back as it was found
+                let value = match (lv.desc(), lv.raw()) {
+                    (Ok(desc), _) => desc.to_metadata_value(),
+                    (_, Ok(raw)) => raw.clone(),
+                    (_, Err(_)) => MetadataValue::Section(Vec::new()),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        let mut vg = SectionBuilder::new()
+            .str("id", &self.id)
+            .num("seqno", self.seqno);
+        if let Some(policy) = &self.allocation_policy {
+            vg = vg.str("allocation_policy", policy.as_str());
+        }
+        vg = vg.num("extent_size", self.extent_size);
+        // in the order lvm writes them
+        for (key, value) in [
+            ("max_lv", self.max_lv),
+            ("max_pv", self.max_pv),
+            ("metadata_copies", self.metadata_copies),
+        ] {
+            if let Some(value) = value {
+                vg = vg.num(key, value);
+            }
+        }
+        vg.value("physical_volumes", MetadataValue::Section(pvs))
+            .value("logical_volumes", MetadataValue::Section(lvs))
+            .extra(&self.extra)
+            .build()
+    }
+
+    /// Writes this VG back out as LVM config text, in the layout used by
+    /// `vgcfgbackup` (without the trailing description keys).
+    pub fn to_config_text(&self, vg_name: &str) -> String {
+        MetadataValue::Section(alloc::vec![(vg_name.to_owned(), self.to_metadata_value())])
+            .to_config_text()
+    }
+}
+
+impl PVDesc {
+    pub fn to_metadata_value(&self) -> MetadataValue {
+        let mut pv = SectionBuilder::new()
+            .str("id", &self.id)
+            .str("device", &self.device)
+            .strs("status", &self.status)
+            .strs("flags", &self.flags);
+        if let Some(dev_size) = self.dev_size {
+            pv = pv.num("dev_size", dev_size);
+        }
+        pv = pv
+            .num("pe_start", self.pe_start)
+            .num("pe_count", self.pe_count);
+        for (key, value) in [("ba_start", self.ba_start), ("ba_size", self.ba_size)] {
+            if let Some(value) = value {
+                pv = pv.num(key, value);
+            }
+        }
+        pv.extra(&self.extra).build()
+    }
+}
+
+impl LVDesc {
+    pub fn to_metadata_value(&self) -> MetadataValue {
+        let mut lv = SectionBuilder::new()
+            .str("id", &self.id)
+            .strs("status", &self.status)
+            .strs("flags", &self.flags);
+        if !self.tags.is_empty() {
+            lv = lv.strs("tags", &self.tags);
+        }
+        lv = lv
+            .num("creation_time", self.creation_time.as_unix())
+            .str("creation_host", &self.creation_host);
+        if let Some(policy) = &self.allocation_policy {
+            lv = lv.str("allocation_policy", policy.as_str());
+        }
+        if let Some(read_ahead) = self.read_ahead {
+            lv = lv.value("read_ahead", MetadataValue::Number(read_ahead));
+        }
+        lv = lv.num("segment_count", self.segment_count as u64);
+        for (name, segment) in self.segments_in_order() {
+            lv = lv.value(name, segment.to_metadata_value());
+        }
+        lv.extra(&self.extra).build()
+    }
+}
+
+impl LVSegmentDesc {
+    pub fn to_metadata_value(&self) -> MetadataValue {
+        // a linear segment is a striped one with a single stripe on disk
+        let mut r#type = String::from(match self.kind {
+            Segment::Linear { .. } => "striped",
+            ref kind => kind.type_name(),
+        });
+        for flag in &self.type_flags {
+            r#type.push('+');
+            r#type.push_str(flag);
+        }
+        let seg = SectionBuilder::new()
+            .num("start_extent", self.start_extent)
+            .num("extent_count", self.extent_count)
+            .str("type", &r#type);
+        let seg = match &self.kind {
+            Segment::Linear { stripe } => seg
+                .num("stripe_count", 1)
+                .value("stripes", stripes_value([stripe])),
+            Segment::Striped {
+                stripe_size,
+                stripes,
+            } => {
+                let mut seg = seg.num("stripe_count", stripes.len() as u64);
+                if let Some(stripe_size) = stripe_size {
+                    seg = seg.num("stripe_size", *stripe_size);
+                }
+                seg.value("stripes", stripes_value(stripes))
+            }
+            Segment::Thin {
+                pool,
+                device_id,
+                origin,
+                merge,
+            } => {
+                let mut seg = seg.str("thin_pool", pool).num("device_id", *device_id);
+                if let Some(origin) = origin {
+                    seg = seg.str("origin", origin);
+                }
+                match merge {
+                    Some(merge) => seg.str("merge", merge),
+                    None => seg,
+                }
+            }
+            Segment::ThinPool {
+                metadata,
+                pool,
+                chunk_size,
+                transaction_id,
+                zero_new_blocks,
+                discards,
+            } => {
+                // in the order lvm writes them
+                let mut seg = seg.str("metadata", metadata).str("pool", pool);
+                if let Some(transaction_id) = transaction_id {
+                    seg = seg.num("transaction_id", *transaction_id);
+                }
+                if let Some(chunk_size) = chunk_size {
+                    seg = seg.num("chunk_size", *chunk_size);
+                }
+                if let Some(discards) = discards {
+                    seg = seg.str("discards", discards);
+                }
+                match zero_new_blocks {
+                    Some(zero) => seg.num("zero_new_blocks", u64::from(*zero)),
+                    None => seg,
+                }
+            }
+            Segment::Cache {
+                pool,
+                origin,
+                metadata_area,
+                data_area,
+            } => {
+                let mut seg = seg.str("cache_pool", pool).str("origin", origin);
+                for (prefix, area) in [("metadata", metadata_area), ("data", data_area)] {
+                    if let Some(area) = area {
+                        seg = seg
+                            .num(&format!("{prefix}_start"), area.start)
+                            .num(&format!("{prefix}_len"), area.end - area.start);
+                    }
+                }
+                seg
+            }
+            Segment::CachePool {
+                data,
+                metadata,
+                chunk_size,
+                cache_mode,
+            } => {
+                let mut seg = seg.str("data", data).str("metadata", metadata);
+                if let Some(chunk_size) = chunk_size {
+                    seg = seg.num("chunk_size", *chunk_size);
+                }
+                match cache_mode {
+                    Some(cache_mode) => seg.str("cache_mode", cache_mode),
+                    None => seg,
+                }
+            }
+            Segment::Snapshot {
+                origin,
+                cow_store,
+                chunk_size,
+                merging,
+            } => seg
+                .num("chunk_size", *chunk_size)
+                .str("origin", origin)
+                .str(
+                    if *merging {
+                        "merging_store"
+                    } else {
+                        "cow_store"
+                    },
+                    cow_store,
+                ),
+            Segment::Mirror {
+                images,
+                log,
+                region_size,
+            } => {
+                let mut seg = seg.num("mirror_count", images.len() as u64);
+                if let Some(log) = log {
+                    seg = seg.str("mirror_log", log);
+                }
+                if let Some(region_size) = region_size {
+                    seg = seg.num("region_size", *region_size);
+                }
+                let mirrors = images
+                    .iter()
+                    .flat_map(|image| {
+                        [
+                            MetadataValue::String(image.name.clone()),
+                            MetadataValue::Number(image.start_extent as i64),
+                        ]
+                    })
+                    .collect();
+                seg.value("mirrors", MetadataValue::Array(mirrors))
+            }
+            Segment::Raid0 {
+                stripe_size,
+                images,
+            } => {
+                let seg = seg.num("stripe_count", images.len() as u64);
+                let seg = match stripe_size {
+                    Some(stripe_size) => seg.num("stripe_size", *stripe_size),
+                    None => seg,
+                };
+                seg.strs("raid0_lvs", images)
+            }
+            Segment::Raid1 {
+                images,
+                meta_images,
+                layout,
+            } => raid_layout(seg.num("device_count", images.len() as u64), layout, None)
+                .strs("raids", &raid_legs(meta_images, images)),
+            Segment::Raid {
+                stripe_size,
+                images,
+                meta_images,
+                layout,
+                ..
+            } => raid_layout(
+                seg.num("device_count", images.len() as u64),
+                layout,
+                *stripe_size,
+            )
+            .strs("raids", &raid_legs(meta_images, images)),
+            Segment::Unknown { .. } => seg,
+        };
+        seg.extra(&self.extra).build()
+    }
+}
+
+// in the order lvm writes them, before the legs
+fn raid_layout(
+    mut seg: SectionBuilder,
+    layout: &RaidLayout,
+    stripe_size: Option<u64>,
+) -> SectionBuilder {
+    if let Some(data_copies) = layout.data_copies {
+        seg = seg.num("data_copies", data_copies);
+    }
+    if let Some(region_size) = layout.region_size {
+        seg = seg.num("region_size", region_size);
+    }
+    if let Some(stripe_size) = stripe_size {
+        seg = seg.num("stripe_size", stripe_size);
+    }
+    match layout.data_offset {
+        Some(data_offset) => seg.num("data_offset", data_offset),
+        None => seg,
+    }
+}
+
+fn raid_legs(meta_images: &[String], images: &[String]) -> Vec<String> {
+    meta_images
+        .iter()
+        .zip(images)
+        .flat_map(|(meta, image)| [meta.clone(), image.clone()])
+        .collect()
+}
+
+impl MetadataValue {
+    /// Formats this value as LVM config text. A section is written as its entries,
+    /// so the result of `to_metadata_value` can be written out as a whole file.
+    pub fn to_config_text(&self) -> String {
+        let mut out = String::new();
+        // writing into a String cannot fail
+        let _ = match self {
+            MetadataValue::Section(entries) => write_entries(&mut out, entries, 0),
+            value => write_value(&mut out, value),
+        };
+        out
+    }
+
+    /// Formats this value as LVM config text for reading rather than for LVM: keys
+    /// in natural order (`segment2` before `segment10`), plain values before the
+    /// sections nested below them and their `=` aligned. The same metadata always
+    /// gives the same text, however it was laid out on disk.
+    pub fn to_pretty_text(&self) -> String {
+        let mut out = String::new();
+        let _ = match self {
+            MetadataValue::Section(entries) => write_pretty_entries(&mut out, entries, 0),
+            value => write_value(&mut out, value),
+        };
+        out
+    }
+
+    /// Formats this value as JSON: sections become objects with their keys in the
+    /// order written, duplicates included, arrays become arrays.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write_json(&mut out, self);
+        out
+    }
+}
+
+fn write_json(out: &mut String, value: &MetadataValue) -> fmt::Result {
+    match value {
+        MetadataValue::String(s) => write_json_string(out, s)?,
+        MetadataValue::Number(n) => write!(out, "{n}")?,
+        MetadataValue::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(out, item)?;
+            }
+            out.push(']');
+        }
+        MetadataValue::Section(entries) => {
+            out.push('{');
+            for (i, (name, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json_string(out, name)?;
+                out.push(':');
+                write_json(out, value)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn write_json_string(out: &mut String, s: &str) -> fmt::Result {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => write!(out, "\\u{:04x}", u32::from(c))?,
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
+
+fn write_pretty_entries(
+    out: &mut String,
+    entries: &[(String, MetadataValue)],
+    depth: usize,
+) -> fmt::Result {
+    let (mut sections, mut values): (Vec<_>, Vec<_>) = entries
+        .iter()
+        .partition(|(_, value)| matches!(value, MetadataValue::Section(_)));
+    values.sort_by(|a, b| natural_cmp(&a.0, &b.0));
+    sections.sort_by(|a, b| natural_cmp(&a.0, &b.0));
+    let width = values.iter().map(|(name, _)| name.chars().count()).max();
+    for (name, value) in &values {
+        indent(out, depth);
+        write!(out, "{name:width$} = ", width = width.unwrap_or_default())?;
+        write_value(out, value)?;
+        out.push('\n');
+    }
+    for (i, (name, value)) in sections.iter().enumerate() {
+        if i > 0 || !values.is_empty() {
+            out.push('\n');
+        }
+        indent(out, depth);
+        writeln!(out, "{name} {{")?;
+        if let MetadataValue::Section(children) = value {
+            write_pretty_entries(out, children, depth + 1)?;
+        }
+        indent(out, depth);
+        out.push_str("}\n");
+    }
+    Ok(())
+}
+
+// compares runs of digits by their value, so `pv2` sorts before `pv10`
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (na, nb) = (digits(a), digits(b));
+            let (da, db) = (
+                a[..na].trim_start_matches('0'),
+                b[..nb].trim_start_matches('0'),
+            );
+            let ordering = da.len().cmp(&db.len()).then_with(|| da.cmp(db));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            (a, b) = (&a[na..], &b[nb..]);
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+fn write_entries(
+    out: &mut String,
+    entries: &[(String, MetadataValue)],
+    depth: usize,
+) -> fmt::Result {
+    for (i, (name, value)) in entries.iter().enumerate() {
+        match value {
+            MetadataValue::Section(children) => {
+                // LVM separates nested sections from what precedes them with a blank line
+                if i > 0 {
+                    out.push('\n');
+                }
+                indent(out, depth);
+                writeln!(out, "{name} {{")?;
+                write_entries(out, children, depth + 1)?;
+                indent(out, depth);
+                out.push_str("}\n");
+            }
+            value => {
+                indent(out, depth);
+                write!(out, "{name} = ")?;
+                write_value(out, value)?;
+                out.push('\n');
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_value(out: &mut String, value: &MetadataValue) -> fmt::Result {
+    match value {
+        MetadataValue::String(s) => {
+            out.push('"');
+            for c in s.chars() {
+                if c == '"' || c == '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        }
+        MetadataValue::Number(n) => write!(out, "{n}")?,
+        MetadataValue::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, item)?;
+            }
+            out.push(']');
+        }
+        MetadataValue::Section(entries) => {
+            out.push_str("{\n");
+            write_entries(out, entries, 1)?;
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push('\t');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VolumeGroup;
+
+    // one LV of each segment type, with keys this crate does not model and a name
+    // and strings that need escaping
+    const VG: &str = r#"# Generated by LVM2: a comment
+contents = "Text Format Volume Group"
+version = 1
+
+description = "Created *after* executing 'vgcfgbackup \"vg0\"'"
+
+vg0 {
+	id = "Ax1DaE-93a2-4ZsY-Zkbm-Bg6o-lMrG-zqTGqF"
+	seqno = 42
+	format = "lvm2" # a comment after a value
+	status = ["RESIZEABLE", "READ", "WRITE"]
+	flags = []
+	extent_size = 8192
+	max_lv = 0
+	max_pv = 0
+	metadata_copies = 0
+
+	physical_volumes {
+
+		pv0 {
+			id = "Rk3S1M-cg6l-G3Ym-4Wso-B1K9-3x8o-22VaZ3"
+			device = "/dev/sdb"
+			status = ["ALLOCATABLE"]
+			flags = []
+			dev_size = 20971520
+			pe_start = 2048
+			pe_count = 2559
+		}
+
+		pv1 {
+			id = "uGbfbk-JZoJ-1JmT-rbSl-NB8F-bJ2q-2OJz3d"
+			device = "/dev/sdc"
+			status = ["ALLOCATABLE"]
+			flags = ["MISSING"]
+			pe_start = 2048
+			pe_count = 2559
+			ba_start = 2048
+			ba_size = 0
+		}
+	}
+
+	logical_volumes {
+
+		lin {
+			id = "cbRHVu-6IJI-zYl1-B7Jw-n8Vo-zT8x-vpXN1S"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			tags = ["a \"tag\"", "c:\\tag"]
+			creation_time = 1700000000
+			creation_host = "host"
+			allocation_policy = "cling"
+			read_ahead = -1
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "striped"
+				stripe_count = 1
+				stripes = ["pv0", 0]
+			}
+		}
+
+		striped {
+			id = "yK1bLm-0cBd-5yPh-0oVY-m4Mi-16j7-4Csark"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000001
+			creation_host = "host"
+			segment_count = 2
+
+			segment1 {
+				start_extent = 0
+				extent_count = 20
+				type = "striped"
+				stripe_count = 2
+				stripe_size = 128
+				stripes = ["pv0", 10, "pv1", 0]
+			}
+
+			segment2 {
+				start_extent = 20
+				extent_count = 4
+				type = "striped"
+				stripe_count = 1
+				stripes = ["pv1", 100]
+			}
+		}
+
+		pool {
+			id = "jtXT0m-7EIB-lebc-wAb5-bbox-5oJu-BH4VfV"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000002
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "thin-pool"
+				metadata = "pool_tmeta"
+				pool = "pool_tdata"
+				transaction_id = 2
+				chunk_size = 128
+				discards = "passdown"
+				zero_new_blocks = 1
+			}
+		}
+
+		thin1 {
+			id = "oHf1ti-8S1k-eaNt-CZqB-Ywhq-cjZT-sRGUYp"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000003
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 20
+				type = "thin"
+				thin_pool = "pool"
+				transaction_id = 0
+				device_id = 1
+			}
+		}
+
+		thin2 {
+			id = "ST9tQ1-ZtYd-JPE8-hTeV-ITwl-ab2H-1i4ULT"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000004
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 20
+				type = "thin"
+				thin_pool = "pool"
+				transaction_id = 1
+				device_id = 2
+				origin = "thin1"
+			}
+		}
+
+		snap {
+			id = "dYVnmq-oSst-hnK2-7hkC-4hWN-Fyb4-z0MJfD"
+			status = ["READ", "VISIBLE"]
+			flags = []
+			creation_time = 1700000005
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 2
+				type = "snapshot"
+				chunk_size = 8
+				origin = "lin"
+				cow_store = "snap_cow"
+			}
+		}
+
+		mir {
+			id = "3pgTwR-PDILy-7itV-9SYd-yJk9-r2Dd-bNkOud"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000006
+			creation_host = "host"
+			segment_count = 2
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "mirror"
+				mirror_count = 2
+				mirror_log = "mir_mlog"
+				region_size = 1024
+				mirrors = ["mir_mimage_0", 0, "mir_mimage_1", 0]
+			}
+
+			segment2 {
+				start_extent = 10
+				extent_count = 5
+				type = "mirror"
+				mirror_count = 2
+				mirror_log = "mir_mlog"
+				region_size = 1024
+				mirrors = ["mir_mimage_0", 10, "mir_mimage_1", 10]
+			}
+		}
+
+		pvmove0 {
+			id = "VZQ2Yh-b1hD-IbJx-RShG-aTP6-Mzu9-6Ow72K"
+			status = ["READ", "WRITE", "PVMOVE", "LOCKED"]
+			flags = []
+			creation_time = 1700000013
+			creation_host = "host"
+			allocation_policy = "contiguous"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "mirror"
+				mirror_count = 2
+				mirrors = ["pv0", 100, "pv1", 200]
+			}
+		}
+
+		r1 {
+			id = "1lJNpS-u4ae-tdyP-8Lbu-M71x-LZQ0-lf4FZ3"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000007
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "raid1"
+				device_count = 2
+				region_size = 4096
+				data_offset = 0
+				raids = ["r1_rmeta_0", "r1_rimage_0", "r1_rmeta_1", "r1_rimage_1"]
+			}
+		}
+
+		r5 {
+			id = "i45Jz5-VCnd-NCuQ-cJZJ-tdFH-uVbG-9UCLuh"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000008
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 20
+				type = "raid5_ls+RESHAPE"
+				device_count = 3
+				region_size = 4096
+				stripe_size = 128
+				reshape_count = 1
+				raids = ["r5_rmeta_0", "r5_rimage_0", "r5_rmeta_1", "r5_rimage_1", "r5_rmeta_2", "r5_rimage_2"]
+			}
+		}
+
+		r0 {
+			id = "ePd0mG-hljM-C8VO-Ax0k-NRJb-vZ68-rk36qw"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000009
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 20
+				type = "raid0"
+				stripe_count = 2
+				stripe_size = 128
+				raid0_lvs = ["r0_rimage_0", "r0_rimage_1"]
+			}
+		}
+
+		cached {
+			id = "yJM7bP-FL7c-lSDa-EzEJ-SF6W-3iBq-lDeiCL"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000010
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "cache"
+				cache_pool = "cvol"
+				origin = "cached_corig"
+				metadata_start = 0
+				metadata_len = 8192
+				data_start = 8192
+				data_len = 73728
+				metadata_format = 2
+				policy = "smq"
+
+				policy_settings {
+					migration_threshold = 2048
+				}
+			}
+		}
+
+		cpool {
+			id = "w1JlnE-hG3R-F7ow-a0Sl-dQM9-xel8-WNYAIf"
+			status = ["READ", "WRITE"]
+			flags = []
+			creation_time = 1700000011
+			creation_host = "host"
+			segment_count = 1
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "cache-pool"
+				data = "cpool_cdata"
+				metadata = "cpool_cmeta"
+				chunk_size = 128
+				cache_mode = "writeback"
+			}
+		}
+
+		odd {
+			id = "lPJabj-qMp2-1Sg4-zGNp-8zsF-ZtNI-oY7Most"
+			status = ["READ", "WRITE", "VISIBLE"]
+			flags = []
+			creation_time = 1700000012
+			creation_host = "host"
+			segment_count = 2
+			unknown_key = "kept"
+
+			segment1 {
+				start_extent = 0
+				extent_count = 10
+				type = "vdo"
+				vdo_pool = "vpool"
+				vdo_offset = 0
+			}
+
+			segment2 {
+				start_extent = 10
+				extent_count = 10
+				type = "striped"
+				stripe_size = 128
+				origin = "elsewhere"
+			}
+		}
+	}
+}
+"#;
+
+    // sections with their entries sorted by key, as the order they are written in
+    // does not matter
+    fn sorted(value: &MetadataValue) -> MetadataValue {
+        match value {
+            MetadataValue::Section(entries) => {
+                let mut entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), sorted(value)))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                MetadataValue::Section(entries)
+            }
+            MetadataValue::Array(items) => MetadataValue::Array(items.iter().map(sorted).collect()),
+            value => value.clone(),
+        }
+    }
+
+    #[test]
+    fn every_segment_type_is_modeled() {
+        let vg = VolumeGroup::parse(VG).unwrap();
+        let types: Vec<(&str, &str)> = vg
+            .config()
+            .logical_volumes
+            .iter()
+            .flat_map(|(name, lv)| {
+                lv.desc()
+                    .unwrap()
+                    .segments_in_order()
+                    .map(move |(_, segment)| (name.as_str(), segment.kind.type_name()))
+            })
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("cached", "cache"),
+                ("cpool", "cache-pool"),
+                ("lin", "linear"),
+                ("mir", "mirror"),
+                ("mir", "mirror"),
+                ("odd", "vdo"),
+                ("odd", "striped"),
+                ("pool", "thin-pool"),
+                ("pvmove0", "mirror"),
+                ("r0", "raid0"),
+                ("r1", "raid1"),
+                ("r5", "raid5_ls"),
+                ("snap", "snapshot"),
+                ("striped", "striped"),
+                ("striped", "linear"),
+                ("thin1", "thin"),
+                ("thin2", "thin"),
+            ]
+        );
+        let pvmove = vg.config().logical_volumes["pvmove0"].desc().unwrap();
+        let Segment::Mirror { images, .. } = &pvmove.segments.0["segment1"].kind else {
+            panic!("pvmove0 is not a mirror");
+        };
+        assert_eq!(
+            images
+                .iter()
+                .map(|image| (image.name.as_str(), image.start_extent))
+                .collect::<Vec<_>>(),
+            [("pv0", 100), ("pv1", 200)]
+        );
+        let odd = vg.config().logical_volumes["odd"].desc().unwrap();
+        assert!(matches!(
+            odd.segments.0["segment2"].kind,
+            Segment::Unknown { .. }
+        ));
+    }
+
+    #[test]
+    fn config_text_round_trips() {
+        let vg = VolumeGroup::parse(VG).unwrap();
+        let text = vg.to_config_text();
+        let written = VolumeGroup::parse(&text).unwrap();
+        assert_eq!(written.name(), "vg0");
+        // every key and value of the original, whatever the model made of it
+        assert_eq!(sorted(written.metadata()), sorted(vg.metadata()));
+        assert_eq!(
+            written.config().to_metadata_value(),
+            vg.config().to_metadata_value()
+        );
+        assert_eq!(written.to_config_text(), text);
+    }
+
+    #[test]
+    fn unreadable_lvs_are_written_as_found() {
+        let text = VG.replace("creation_time = 1700000012", "creation_time = \"never\"");
+        let vg = VolumeGroup::parse(&text).unwrap();
+        assert!(vg.config().logical_volumes["odd"].desc().is_err());
+        let written = VolumeGroup::parse(&vg.to_config_text()).unwrap();
+        assert_eq!(sorted(written.metadata()), sorted(vg.metadata()));
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let value = MetadataValue::Section(alloc::vec![(
+            "s".to_owned(),
+            MetadataValue::String(r#"a "b" \c"#.to_owned()),
+        )]);
+        let text = value.to_config_text();
+        assert_eq!(text, "s = \"a \\\"b\\\" \\\\c\"\n");
+        assert_eq!(crate::metadata::parse_config(&text).unwrap(), value);
+    }
+}
@@ -0,0 +1,172 @@
+// mirror.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+use snafu::{OptionExt, ResultExt};
+
+use crate::metadata::Segment;
+use crate::{Error, InvalidMirrorLogSnafu, IoSnafu, Lvm2, NotAMirrorSnafu, LV};
+
+// dm-log's disk log, see drivers/md/dm-log.c
+const MIRROR_MAGIC: u32 = 0x4d69_5272;
+// the bitmap of clean regions starts two sectors in
+const LOG_OFFSET: u64 = 1024;
+
+/// Which regions of an old-style mirror its disk log marks as in sync, i.e. the same
+/// on every image.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MirrorLog {
+    /// Name of the `_mlog` LV.
+    pub log: String,
+    /// Region size in bytes.
+    pub region_size: u64,
+    /// Number of regions the log tracks.
+    pub regions: u64,
+    /// Runs of regions not marked in sync, in order: being written or resynced when
+    /// the log was last written, so the images may differ there. Elsewhere any image
+    /// reads the same.
+    pub out_of_sync: Vec<Range<u64>>,
+}
+impl MirrorLog {
+    /// The byte ranges of the mirror in the regions not in sync.
+    pub fn out_of_sync_bytes(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.out_of_sync.iter().map(|regions| {
+            regions.start.saturating_mul(self.region_size)
+                ..regions.end.saturating_mul(self.region_size)
+        })
+    }
+}
+
+impl Lvm2 {
+    /// Reads the disk log of old-style mirror `lv` (see [`Segment::Mirror`]) for the
+    /// regions it marks as out of sync.
+    pub fn mirror_log<T: Read + Seek>(
+        &self,
+        lv: LV<'_>,
+        reader: &mut T,
+    ) -> Result<MirrorLog, Error> {
+        let (log, region_size) = lv
+            .segments()
+            .find_map(|(_, seg)| match &seg.kind {
+                Segment::Mirror {
+                    log, region_size, ..
+                } => Some((log, region_size)),
+                _ => None,
+            })
+            .context(NotAMirrorSnafu { lv: lv.name() })?;
+        let invalid = |reason| InvalidMirrorLogSnafu {
+            lv: lv.name(),
+            reason,
+        };
+        let log = log
+            .as_deref()
+            .context(invalid("the log is kept in memory"))?;
+        let region_size = region_size
+            .filter(|&size| size > 0)
+            .context(invalid("missing region size"))?
+            .saturating_mul(512);
+        let log_lv = self
+            .vg()
+            .lv_by_name(log)
+            .context(invalid("log LV is missing"))?;
+        let mut open = self.open_lv(log_lv, reader);
+
+        let mut header = [0u8; 16];
+        open.read_exact(&mut header).context(IoSnafu)?;
+        let regions = lv.size_bytes().div_ceil(region_size);
+        // a log written before the mirror grew covers fewer regions; as the kernel
+        // does, the ones past its end are taken to be out of sync
+        let logged = log_header(&header)
+            .map_err(|reason| {
+                InvalidMirrorLogSnafu {
+                    lv: lv.name(),
+                    reason,
+                }
+                .build()
+            })?
+            .min(regions);
+        snafu::ensure!(
+            LOG_OFFSET.saturating_add(logged.div_ceil(8)) <= log_lv.size_bytes(),
+            invalid("bitmap overflows the log LV")
+        );
+
+        let mut bits = vec![0u8; logged.div_ceil(8) as usize];
+        open.seek(SeekFrom::Start(LOG_OFFSET)).context(IoSnafu)?;
+        open.read_exact(&mut bits).context(IoSnafu)?;
+        let out_of_sync = out_of_sync(&bits, logged, regions);
+        Ok(MirrorLog {
+            log: log.into(),
+            region_size,
+            regions,
+            out_of_sync,
+        })
+    }
+}
+
+// the number of regions the disk log header says the log covers
+fn log_header(header: &[u8; 16]) -> Result<u64, &'static str> {
+    let le32 = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap_or_default());
+    if le32(0) != MIRROR_MAGIC {
+        return Err("missing log magic");
+    }
+    if !matches!(le32(4), 1 | 2) {
+        return Err("unknown log version");
+    }
+    Ok(u64::from_le_bytes(
+        header[8..].try_into().unwrap_or_default(),
+    ))
+}
+
+// the runs of `regions` not marked in sync in `bits`, one bit per region, set once the
+// region is in sync. Only the first `logged` regions have a bit.
+fn out_of_sync(bits: &[u8], logged: u64, regions: u64) -> Vec<Range<u64>> {
+    let in_sync = |r: u64| r < logged && bits[(r / 8) as usize] & (1 << (r % 8)) != 0;
+    let mut out_of_sync: Vec<Range<u64>> = Vec::new();
+    for region in (0..regions).filter(|&r| !in_sync(r)) {
+        match out_of_sync.last_mut() {
+            Some(last) if last.end == region => last.end += 1,
+            _ => out_of_sync.push(region..region + 1),
+        }
+    }
+    out_of_sync
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(version: u32, regions: u64) -> [u8; 16] {
+        let mut header = [0u8; 16];
+        header[..4].copy_from_slice(&MIRROR_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&version.to_le_bytes());
+        header[8..].copy_from_slice(&regions.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn log_header_gives_the_logged_regions() {
+        assert_eq!(log_header(&header(2, 100)), Ok(100));
+        assert_eq!(log_header(&header(1, 0)), Ok(0));
+        assert_eq!(log_header(&header(3, 100)), Err("unknown log version"));
+        let mut header = header(2, 100);
+        header[3] ^= 1;
+        assert_eq!(log_header(&header), Err("missing log magic"));
+    }
+
+    #[test]
+    fn regions_without_their_bit_are_out_of_sync() {
+        // regions 0-3 and 9 in sync, 4-8 and 10-11 not
+        let bits = [0b0000_1111, 0b0000_0010];
+        assert_eq!(out_of_sync(&bits, 12, 12), [4..9, 10..12]);
+    }
+
+    #[test]
+    fn regions_past_the_log_are_out_of_sync() {
+        let bits = [0xff];
+        assert_eq!(out_of_sync(&bits, 8, 8), []);
+        assert_eq!(out_of_sync(&bits, 8, 20), alloc::vec![8..20]);
+    }
+}
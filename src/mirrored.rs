@@ -0,0 +1,378 @@
+// mirrored.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+use snafu::ensure;
+
+use crate::metadata::Segment;
+use crate::{
+    Error, InvalidMappingSnafu, Lvm2, MissingLegSnafu, NotMirroredSnafu, PVNotAvailableSnafu, LV,
+};
+
+/// What an [`OpenMirroredLV`] does with the legs it does not read the data from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LegCheck {
+    /// Reads one leg only, as the kernel may read from any of them.
+    #[default]
+    Off,
+    /// Reads every leg and compares them; where they differ, logs a warning, records
+    /// it in [`OpenMirroredLV::mismatches`] and goes on with the data read.
+    Warn,
+    /// As `Warn`, but the read fails.
+    Fail,
+}
+
+/// Bytes of a RAID1 or mirror LV where its legs differ, found by an
+/// [`OpenMirroredLV`] checking them.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct LegMismatch {
+    /// The LV bytes from the first to the last that differ, within one read.
+    pub range: Range<u64>,
+    /// The legs differing there from the leg the data was read from.
+    pub legs: Vec<String>,
+}
+
+/// Bytes of a RAID1 or mirror LV an [`OpenMirroredLV`] read from another leg than
+/// the first, as reading them from the legs before it failed.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct LegFailover {
+    pub range: Range<u64>,
+    /// The leg the bytes were read from.
+    pub leg: String,
+    /// The legs reading them failed on, in leg order.
+    pub failed: Vec<String>,
+}
+
+// a raid1 or mirror segment: its byte range within the LV, and where each leg keeps
+// its copy
+struct MirroredSegment<'a> {
+    range: Range<u64>,
+    name: &'a str,
+    legs: Vec<Leg<'a>>,
+}
+
+// where a leg's copy of a segment starts
+#[derive(Clone, Copy)]
+enum Leg<'a> {
+    // at this byte of an `_rimage` or `_mimage` LV
+    Lv(LV<'a>, u64),
+    // at this byte of the opened PV's data area, as a `pvmove` LV maps its legs
+    Pv(&'a str, u64),
+}
+impl Leg<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Leg::Lv(lv, _) => lv.name(),
+            Leg::Pv(pv, _) => pv,
+        }
+    }
+}
+
+impl Lvm2 {
+    /// Opens RAID1 or old-style mirror LV `lv` for reading from its first leg, the
+    /// `_rimage_0` or `_mimage_0` LV. Where reading a leg fails, e.g. on bad sectors,
+    /// the same bytes are read from the next one instead, and recorded in
+    /// [`OpenMirroredLV::failovers`]; legs that are missing or not on the opened PV are
+    /// left out. With [`LegCheck::Warn`] or [`LegCheck::Fail`] every read also reads
+    /// the other legs and compares them, so data taken from a stale leg does not go
+    /// unnoticed; every leg then has to be there.
+    pub fn open_mirrored_lv<'a, 'r, T: Read + Seek>(
+        &'a self,
+        lv: LV<'a>,
+        check: LegCheck,
+        reader: &'r mut T,
+    ) -> Result<OpenMirroredLV<'a, 'r, T>, Error> {
+        let extent_size = lv.extent_size();
+        let mut segments = Vec::new();
+        for (name, segment) in lv.segments() {
+            // the images of raid1 start `data_offset` sheets in, mirror images at the
+            // extent given with each
+            let images: Vec<(&str, u64)> = match &segment.kind {
+                Segment::Raid1 { images, layout, .. } => {
+                    let data_offset = layout.data_offset.unwrap_or(0).saturating_mul(512);
+                    images
+                        .iter()
+                        .map(|image| (image.as_str(), data_offset))
+                        .collect()
+                }
+                Segment::Mirror { images, .. } => images
+                    .iter()
+                    .map(|image| {
+                        let offset = image.start_extent.saturating_mul(extent_size);
+                        (image.name.as_str(), offset)
+                    })
+                    .collect(),
+                _ => return NotMirroredSnafu { lv: lv.name() }.fail(),
+            };
+            ensure!(!images.is_empty(), NotMirroredSnafu { lv: lv.name() });
+            let len = segment.extent_count.saturating_mul(extent_size);
+            let mut legs = Vec::new();
+            let mut unusable = None;
+            for (image, offset) in images {
+                match self.mirror_leg(lv, segment.start_extent, len, image, offset, reader) {
+                    Ok(leg) => legs.push(leg),
+                    Err(e) if check == LegCheck::Off => unusable = unusable.or(Some(e)),
+                    Err(e) => return Err(e),
+                }
+            }
+            if let (true, Some(e)) = (legs.is_empty(), unusable) {
+                return Err(e);
+            }
+            let extents = segment.extents();
+            segments.push(MirroredSegment {
+                range: extents.start.saturating_mul(extent_size)
+                    ..extents.end.saturating_mul(extent_size),
+                name,
+                legs,
+            });
+        }
+        Ok(OpenMirroredLV {
+            lvm: self,
+            lv,
+            check,
+            segments,
+            mismatches: Vec::new(),
+            failovers: Vec::new(),
+            scratch: Vec::new(),
+            reader,
+            position: 0,
+        })
+    }
+
+    // leg `image` of `lv`'s segment at extent `start`, `len` bytes from `offset` on,
+    // checked to be readable in full
+    fn mirror_leg<'a, T: Read + Seek>(
+        &'a self,
+        lv: LV<'a>,
+        start: u64,
+        len: u64,
+        image: &'a str,
+        offset: u64,
+        reader: &mut T,
+    ) -> Result<Leg<'a>, Error> {
+        let past_end = |problem| InvalidMappingSnafu {
+            lv: lv.name(),
+            extent: start,
+            problem,
+        };
+        let end = offset.saturating_add(len);
+        if let Some(leg) = self.vg().lv_by_name(image) {
+            self.try_open_lv(leg, reader)?;
+            ensure!(
+                end <= leg.size_bytes(),
+                past_end("mapped past the end of its leg")
+            );
+            return Ok(Leg::Lv(leg, offset));
+        }
+        ensure!(
+            self.vg().pv_by_name(image).is_some(),
+            MissingLegSnafu {
+                lv: lv.name(),
+                leg: image,
+            }
+        );
+        ensure!(
+            image == self.pv_name(),
+            PVNotAvailableSnafu {
+                lv: lv.name(),
+                pv: image,
+            }
+        );
+        ensure!(
+            end <= self.pe_count().saturating_mul(self.extent_size()),
+            past_end("mapped past the last extent of its PV")
+        );
+        Ok(Leg::Pv(image, offset))
+    }
+}
+
+// reads `leg`'s copy of `segment` of `lv` from `within` bytes into it
+fn read_leg<T: Read + Seek>(
+    lvm: &Lvm2,
+    lv: LV<'_>,
+    segment: &str,
+    leg: Leg<'_>,
+    within: u64,
+    buf: &mut [u8],
+    reader: &mut T,
+) -> acid_io::Result<usize> {
+    match leg {
+        Leg::Lv(leg, offset) => {
+            let mut open = lvm.open_lv(leg, reader);
+            open.seek(SeekFrom::Start(offset + within))?;
+            open.read(buf)
+        }
+        Leg::Pv(_, offset) => lvm.read_data_area(offset + within, buf, reader, lv.name(), segment),
+    }
+}
+
+/// A RAID1 or mirror LV opened through [`Lvm2::open_mirrored_lv`].
+pub struct OpenMirroredLV<'a, 'r, T> {
+    lvm: &'a Lvm2,
+    lv: LV<'a>,
+    check: LegCheck,
+    segments: Vec<MirroredSegment<'a>>,
+    mismatches: Vec<LegMismatch>,
+    failovers: Vec<LegFailover>,
+    // the other legs' bytes, to compare with
+    scratch: Vec<u8>,
+    reader: &'r mut T,
+    position: u64,
+}
+impl<'a, T> OpenMirroredLV<'a, '_, T> {
+    pub fn lv(&self) -> LV<'a> {
+        self.lv
+    }
+
+    pub fn size(&self) -> u64 {
+        self.lv.size_bytes()
+    }
+
+    /// Where the legs were found to differ so far, in the order read; adjacent
+    /// ranges differing in the same legs are merged.
+    pub fn mismatches(&self) -> &[LegMismatch] {
+        &self.mismatches
+    }
+
+    /// Where the data was read from another leg than the first so far, in the order
+    /// read; adjacent ranges read the same way are merged.
+    pub fn failovers(&self) -> &[LegFailover] {
+        &self.failovers
+    }
+}
+impl<T: Read + Seek> Read for OpenMirroredLV<'_, '_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        let position = self.position;
+        if position >= self.size() {
+            return Ok(0);
+        }
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.range.contains(&position))
+            .ok_or(crate::io_error("no suitable segment found at this place"))?;
+        let within = position - segment.range.start;
+        let len = buf
+            .len()
+            .min(usize::try_from(segment.range.end - position).unwrap_or(usize::MAX));
+        // from the first leg that reads, failing over to the next on errors
+        let mut failed = Vec::new();
+        let mut read = Err(crate::io_error("no leg of the LV is readable"));
+        for (i, &leg) in segment.legs.iter().enumerate() {
+            read = read_leg(
+                self.lvm,
+                self.lv,
+                segment.name,
+                leg,
+                within,
+                &mut buf[..len],
+                self.reader,
+            )
+            .map(|n| (i, n));
+            match &read {
+                Ok(_) => break,
+                Err(e) => {
+                    tracing::warn!(lv = self.lv.name(), leg = leg.name(), offset = position, error = %e, "reading leg failed");
+                    failed.push(leg.name().to_string());
+                }
+            }
+        }
+        let (source, n) = read?;
+        let source_leg = segment.legs[source];
+        let range = position..position + n as u64;
+        if !failed.is_empty() && n > 0 {
+            match self.failovers.last_mut() {
+                Some(last)
+                    if last.range.end == range.start
+                        && last.leg == source_leg.name()
+                        && last.failed == failed =>
+                {
+                    last.range.end = range.end;
+                }
+                _ => self.failovers.push(LegFailover {
+                    range: range.clone(),
+                    leg: source_leg.name().to_string(),
+                    failed,
+                }),
+            }
+        }
+
+        if self.check != LegCheck::Off && n > 0 {
+            let mut differing: Option<Range<usize>> = None;
+            let mut legs = Vec::new();
+            for (i, &leg) in segment.legs.iter().enumerate() {
+                if i == source {
+                    continue;
+                }
+                self.scratch.resize(n, 0);
+                // a leg that failed to read above cannot be compared either
+                let mut compared = 0;
+                while compared < n {
+                    match read_leg(
+                        self.lvm,
+                        self.lv,
+                        segment.name,
+                        leg,
+                        within + compared as u64,
+                        &mut self.scratch[compared..],
+                        self.reader,
+                    ) {
+                        Ok(read) if read > 0 => compared += read,
+                        _ => break,
+                    }
+                }
+                if compared < n {
+                    continue;
+                }
+                let ours = &buf[..n];
+                let Some(start) = (0..n).find(|&i| ours[i] != self.scratch[i]) else {
+                    continue;
+                };
+                let end = (0..n)
+                    .rposition(|i| ours[i] != self.scratch[i])
+                    .unwrap_or(start)
+                    + 1;
+                differing = Some(match differing {
+                    Some(range) => range.start.min(start)..range.end.max(end),
+                    None => start..end,
+                });
+                legs.push(leg.name().to_string());
+            }
+            if let Some(differing) = differing {
+                let range = position + differing.start as u64..position + differing.end as u64;
+                tracing::warn!(
+                    lv = self.lv.name(),
+                    start = range.start,
+                    end = range.end,
+                    legs = ?legs,
+                    "legs differ"
+                );
+                match self.mismatches.last_mut() {
+                    Some(last) if last.range.end >= range.start && last.legs == legs => {
+                        last.range.end = last.range.end.max(range.end);
+                    }
+                    _ => self.mismatches.push(LegMismatch { range, legs }),
+                }
+                if self.check == LegCheck::Fail {
+                    return Err(crate::io_error("the legs of the LV differ"));
+                }
+            }
+        }
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+impl<T> Seek for OpenMirroredLV<'_, '_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size().checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or(crate::io_error("seek before the start of the LV"))?;
+        self.position = pos;
+        Ok(pos)
+    }
+}
@@ -0,0 +1,224 @@
+// options.rs
+use serde::Serialize;
+use snafu::ensure;
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+use alloc::sync::Arc;
+
+use crate::audit::RecorderHandle;
+use crate::{ChecksumMismatchSnafu, Error, PhysicalRead, ReadReason, ReadRecorder};
+
+/// How checksum mismatches in on-disk structures are handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumMode {
+    /// Don't verify checksums at all.
+    Ignore,
+    /// Log a warning and carry on.
+    Warn,
+    /// Fail the open.
+    Strict,
+}
+
+/// A bad checksum tolerated under [`ChecksumMode::Warn`].
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub structure: &'static str,
+    pub offset: u64,
+    pub stored: u32,
+    pub computed: u32,
+}
+
+/// One of a PV's metadata areas, by its place among the PV header's metadata area
+/// descriptors. LVM puts the first at the start of the PV and, with
+/// `--pvmetadatacopies 2`, a second one at its end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mda {
+    Primary,
+    Secondary,
+    Index(usize),
+}
+impl Mda {
+    pub fn index(self) -> usize {
+        match self {
+            Mda::Primary => 0,
+            Mda::Secondary => 1,
+            Mda::Index(index) => index,
+        }
+    }
+}
+impl From<usize> for Mda {
+    fn from(index: usize) -> Self {
+        Mda::Index(index)
+    }
+}
+
+/// Open-time configuration for [`Lvm2::open_with`](crate::Lvm2::open_with) and
+/// [`Pv::open_with`](crate::Pv::open_with).
+#[derive(Clone, Debug)]
+pub struct Lvm2Options {
+    pub(crate) checksums: ChecksumMode,
+    pub(crate) allow_multiple_vgs: bool,
+    pub(crate) preferred_mda: usize,
+    pub(crate) mda_fallback: bool,
+    pub(crate) load_history: bool,
+    pub(crate) max_metadata_size: u64,
+    pub(crate) log_level: LevelFilter,
+    pub(crate) recorder: Option<RecorderHandle>,
+    pub(crate) start_offset: u64,
+}
+
+impl Default for Lvm2Options {
+    fn default() -> Self {
+        Self {
+            checksums: ChecksumMode::Warn,
+            allow_multiple_vgs: false,
+            preferred_mda: 0,
+            mda_fallback: true,
+            load_history: false,
+            max_metadata_size: 16 << 20,
+            log_level: LevelFilter::TRACE,
+            recorder: None,
+            start_offset: 0,
+        }
+    }
+}
+
+impl Lvm2Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How to handle label, metadata area header and metadata text checksum mismatches.
+    /// Defaults to [`ChecksumMode::Warn`].
+    pub fn checksums(mut self, mode: ChecksumMode) -> Self {
+        self.checksums = mode;
+        self
+    }
+
+    /// Accept metadata text describing more than one VG, picking the one that lists
+    /// the opened PV. Off by default.
+    pub fn allow_multiple_vgs(mut self, allow: bool) -> Self {
+        self.allow_multiple_vgs = allow;
+        self
+    }
+
+    /// Has no effect: LVM writes the label in one of the first four 512-byte sectors
+    /// whatever the device's sector size, and that is where it is searched for.
+    #[deprecated(note = "the label is always searched for in the first 2048 bytes")]
+    pub fn sector_size(self, _sector_size: u64) -> Self {
+        self
+    }
+
+    /// The metadata area to read the VG config from. The others are tried in order
+    /// if it is missing or unreadable. Defaults to [`Mda::Primary`].
+    pub fn prefer_mda(mut self, mda: impl Into<Mda>) -> Self {
+        self.preferred_mda = mda.into().index();
+        self.mda_fallback = true;
+        self
+    }
+
+    /// Like [`Lvm2Options::prefer_mda`], but never falls back to another area: the
+    /// open fails if this one is unreadable, and loading the metadata if the PV has
+    /// no such area.
+    pub fn only_mda(mut self, mda: impl Into<Mda>) -> Self {
+        self.preferred_mda = mda.into().index();
+        self.mda_fallback = false;
+        self
+    }
+
+    /// Also recover older metadata generations left in the metadata area.
+    /// Off by default.
+    pub fn load_history(mut self, load: bool) -> Self {
+        self.load_history = load;
+        self
+    }
+
+    /// Upper bound on the bytes read from a metadata area, both for the current text
+    /// and for the area scanned by [`Lvm2Options::load_history`]. Keeps a corrupt
+    /// size field from triggering a huge allocation. Defaults to 16 MiB.
+    pub fn max_metadata_size(mut self, bytes: u64) -> Self {
+        self.max_metadata_size = bytes;
+        self
+    }
+
+    /// Caps the verbosity of the log messages emitted while opening.
+    pub fn log_level(mut self, level: LevelFilter) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Byte offset of the PV within the reader, for readers covering a whole disk.
+    /// Every read made for the PV, including LV data, is shifted by it. Defaults to 0.
+    pub fn start_offset(mut self, offset: u64) -> Self {
+        self.start_offset = offset;
+        self
+    }
+
+    /// Reports every byte range read from the source (labels, metadata areas and LV
+    /// data read through [`OpenLV`](crate::OpenLV)) to `recorder`, e.g. an
+    /// [`AccessLog`](crate::AccessLog) to attach to the case file.
+    pub fn record_reads(mut self, recorder: Arc<dyn ReadRecorder>) -> Self {
+        self.recorder = Some(RecorderHandle(recorder));
+        self
+    }
+
+    pub(crate) fn record(&self, offset: u64, len: u64, reason: impl FnOnce() -> ReadReason) {
+        if let Some(RecorderHandle(recorder)) = &self.recorder {
+            recorder.record(PhysicalRead {
+                offset: self.start_offset + offset,
+                len,
+                reason: reason(),
+            });
+        }
+    }
+
+    pub(crate) fn logs(&self, level: Level) -> bool {
+        level <= self.log_level
+    }
+
+    // Ok(Some(..)) for a mismatch that is only warned about
+    pub(crate) fn check_checksum(
+        &self,
+        structure: &'static str,
+        offset: u64,
+        expected: u32,
+        computed: u32,
+    ) -> Result<Option<ChecksumMismatch>, Error> {
+        match self.checksums {
+            ChecksumMode::Ignore => Ok(None),
+            ChecksumMode::Warn => {
+                if expected == computed {
+                    return Ok(None);
+                }
+                if self.logs(Level::WARN) {
+                    tracing::warn!(
+                        structure,
+                        offset,
+                        "checksum mismatch (stored {:#010x}, computed {:#010x})",
+                        expected,
+                        computed
+                    );
+                }
+                Ok(Some(ChecksumMismatch {
+                    structure,
+                    offset,
+                    stored: expected,
+                    computed,
+                }))
+            }
+            ChecksumMode::Strict => {
+                ensure!(
+                    expected == computed,
+                    ChecksumMismatchSnafu {
+                        structure,
+                        offset,
+                        expected,
+                        computed,
+                    }
+                );
+                Ok(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+// persistent_data.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::vec;
+use alloc::vec::Vec;
+use snafu::ResultExt;
+
+use crate::{Error, IoSnafu};
+
+// the device-mapper persistent-data structures the thin and cache targets keep their
+// metadata in, see drivers/md/persistent-data/
+pub(crate) const METADATA_BLOCK: usize = 4096;
+const NODE_HEADER: usize = 32;
+const INTERNAL_NODE: u32 = 1;
+const LEAF_NODE: u32 = 2;
+// deeper than any btree that fits in the largest pool metadata device
+const MAX_DEPTH: usize = 16;
+// csum, max_entries, nr_entries, value_size, blocknr
+const ARRAY_BLOCK_HEADER: usize = 24;
+
+// A pool's metadata, read block by block. Malformed structures are reported through
+// `invalid`, which tells what kind of pool `pool` is.
+pub(crate) struct MetadataDevice<'p, T> {
+    pool: &'p str,
+    invalid: fn(&str, &'static str) -> Error,
+    reader: T,
+    // where the metadata starts within `reader`
+    start: u64,
+    pub(crate) block: Vec<u8>,
+    blocks: u64,
+}
+impl<'p, T: Read + Seek> MetadataDevice<'p, T> {
+    pub(crate) fn new(
+        pool: &'p str,
+        invalid: fn(&str, &'static str) -> Error,
+        reader: T,
+        start: u64,
+        len: u64,
+    ) -> Self {
+        Self {
+            pool,
+            invalid,
+            reader,
+            start,
+            block: vec![0u8; METADATA_BLOCK],
+            blocks: len / METADATA_BLOCK as u64,
+        }
+    }
+
+    pub(crate) fn invalid(&self, reason: &'static str) -> Error {
+        (self.invalid)(self.pool, reason)
+    }
+
+    pub(crate) fn read_block(&mut self, index: u64) -> Result<(), Error> {
+        if index >= self.blocks {
+            return Err(self.invalid("block is past the end of the metadata"));
+        }
+        self.reader
+            .seek(SeekFrom::Start(self.start + index * METADATA_BLOCK as u64))
+            .context(IoSnafu)?;
+        self.reader.read_exact(&mut self.block).context(IoSnafu)
+    }
+
+    // Visits the leaf entries of the btree rooted at `root` in key order, or only
+    // the one keyed `only`. Values are taken to be 64 bits, which holds for both
+    // levels of the thin data mapping tree and for the trees behind arrays.
+    pub(crate) fn walk(
+        &mut self,
+        root: u64,
+        only: Option<u64>,
+        mut visit: impl FnMut(u64, u64),
+    ) -> Result<(), Error> {
+        // a well-formed tree visits each block at most once; trees may share subtrees,
+        // as thin snapshots do, so each walk gets its own budget
+        let mut nodes_left = self.blocks;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            if depth > MAX_DEPTH {
+                return Err(self.invalid("btree is too deep"));
+            }
+            if nodes_left == 0 {
+                return Err(self.invalid("btree revisits its nodes"));
+            }
+            // block numbers in a damaged node can be anything
+            if node >= self.blocks {
+                return Err(self.invalid("btree node is past the end of the metadata"));
+            }
+            nodes_left -= 1;
+            self.read_block(node)?;
+            let block = &self.block;
+            let flags = le32(block, 4);
+            let entries = le32(block, 16) as usize;
+            let max_entries = le32(block, 20) as usize;
+            if le32(block, 24) != 8 {
+                return Err(self.invalid("unexpected btree value size"));
+            }
+            if entries > max_entries || NODE_HEADER + max_entries * 16 > METADATA_BLOCK {
+                return Err(self.invalid("btree node overflows its block"));
+            }
+            let key = |i: usize| le64(block, NODE_HEADER + i * 8);
+            let value = |i: usize| le64(block, NODE_HEADER + (max_entries + i) * 8);
+
+            match (flags & (INTERNAL_NODE | LEAF_NODE), only) {
+                (LEAF_NODE, Some(only)) => {
+                    if let Some(i) = (0..entries).find(|&i| key(i) == only) {
+                        visit(only, value(i));
+                    }
+                }
+                (LEAF_NODE, None) => (0..entries).for_each(|i| visit(key(i), value(i))),
+                // each child holds the keys from its own up to the next child's
+                (INTERNAL_NODE, Some(only)) => {
+                    if let Some(i) = (0..entries).rev().find(|&i| key(i) <= only) {
+                        stack.push((value(i), depth + 1));
+                    }
+                }
+                (INTERNAL_NODE, None) => {
+                    stack.extend((0..entries).rev().map(|i| (value(i), depth + 1)));
+                }
+                _ => return Err(self.invalid("btree node is neither internal nor a leaf")),
+            }
+        }
+        Ok(())
+    }
+
+    // Visits the first `len` entries of the array rooted at `root`, which have to be
+    // 64 bits each. Entries missing from the array are skipped.
+    pub(crate) fn walk_array(
+        &mut self,
+        root: u64,
+        len: u64,
+        mut visit: impl FnMut(u64, u64),
+    ) -> Result<(), Error> {
+        // the btree maps the index of each array block to where it is stored
+        let mut blocks = Vec::new();
+        self.walk(root, None, |index, block| blocks.push((index, block)))?;
+        let per_block = ((METADATA_BLOCK - ARRAY_BLOCK_HEADER) / 8) as u64;
+        for (index, location) in blocks {
+            self.read_block(location)?;
+            let block = &self.block;
+            let max_entries = u64::from(le32(block, 4));
+            let entries = u64::from(le32(block, 8));
+            if le32(block, 12) != 8 {
+                return Err(self.invalid("unexpected array value size"));
+            }
+            if max_entries != per_block || entries > max_entries {
+                return Err(self.invalid("array block overflows its block"));
+            }
+            let first = index.saturating_mul(per_block);
+            for i in 0..entries.min(len.saturating_sub(first)) {
+                visit(first + i, le64(block, ARRAY_BLOCK_HEADER + i as usize * 8));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn le32(block: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+pub(crate) fn le64(block: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(block[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_image;
+    use acid_io::Cursor;
+
+    fn invalid(lv: &str, reason: &'static str) -> Error {
+        crate::InvalidThinPoolSnafu { lv, reason }.build()
+    }
+
+    // a btree node of 64-bit values with room for `max_entries`
+    fn node(flags: u32, max_entries: u32, entries: &[(u64, u64)]) -> Vec<u8> {
+        let mut block = alloc::vec![0u8; METADATA_BLOCK];
+        block[4..8].copy_from_slice(&flags.to_le_bytes());
+        block[16..20].copy_from_slice(&(entries.len() as u32).to_le_bytes());
+        block[20..24].copy_from_slice(&max_entries.to_le_bytes());
+        block[24..28].copy_from_slice(&8u32.to_le_bytes());
+        for (i, &(key, value)) in entries.iter().enumerate() {
+            let key_at = NODE_HEADER + i * 8;
+            let value_at = NODE_HEADER + (max_entries as usize + i) * 8;
+            block[key_at..key_at + 8].copy_from_slice(&key.to_le_bytes());
+            block[value_at..value_at + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        block
+    }
+
+    fn array_block(entries: &[u64]) -> Vec<u8> {
+        let mut block = alloc::vec![0u8; METADATA_BLOCK];
+        let max_entries = ((METADATA_BLOCK - ARRAY_BLOCK_HEADER) / 8) as u32;
+        block[4..8].copy_from_slice(&max_entries.to_le_bytes());
+        block[8..12].copy_from_slice(&(entries.len() as u32).to_le_bytes());
+        block[12..16].copy_from_slice(&8u32.to_le_bytes());
+        for (i, entry) in entries.iter().enumerate() {
+            let at = ARRAY_BLOCK_HEADER + i * 8;
+            block[at..at + 8].copy_from_slice(&entry.to_le_bytes());
+        }
+        block
+    }
+
+    fn device(blocks: &[Vec<u8>]) -> MetadataDevice<'static, Cursor<Vec<u8>>> {
+        let bytes = blocks.concat();
+        let len = bytes.len() as u64;
+        MetadataDevice::new("pool", invalid, test_image::reader(bytes), 0, len)
+    }
+
+    fn reason(error: Error) -> &'static str {
+        match error {
+            Error::InvalidThinPool { reason, .. } => reason,
+            other => panic!("unexpected error {other}"),
+        }
+    }
+
+    // an internal node in block 0 over leaves in blocks 1 and 2
+    fn tree() -> Vec<Vec<u8>> {
+        alloc::vec![
+            node(INTERNAL_NODE, 126, &[(0, 1), (10, 2)]),
+            node(LEAF_NODE, 126, &[(1, 100), (4, 400)]),
+            node(LEAF_NODE, 126, &[(10, 1000), (12, 1200)]),
+        ]
+    }
+
+    #[test]
+    fn walk_visits_leaves_in_key_order() {
+        let mut visited = Vec::new();
+        device(&tree())
+            .walk(0, None, |key, value| visited.push((key, value)))
+            .unwrap();
+        assert_eq!(visited, [(1, 100), (4, 400), (10, 1000), (12, 1200)]);
+    }
+
+    #[test]
+    fn walk_finds_one_key() {
+        let mut visited = Vec::new();
+        let mut metadata = device(&tree());
+        for key in [4, 12, 5, 0] {
+            metadata
+                .walk(0, Some(key), |key, value| visited.push((key, value)))
+                .unwrap();
+        }
+        assert_eq!(visited, [(4, 400), (12, 1200)]);
+    }
+
+    #[test]
+    fn walk_rejects_malformed_trees() {
+        let walk =
+            |blocks: &[Vec<u8>]| reason(device(blocks).walk(0, None, |_, _| ()).unwrap_err());
+        // a node pointing at itself
+        let cycle = [node(INTERNAL_NODE, 126, &[(0, 0)])];
+        assert_eq!(walk(&cycle), "btree revisits its nodes");
+        let past_end = [
+            node(INTERNAL_NODE, 126, &[(0, 7)]),
+            node(LEAF_NODE, 126, &[]),
+        ];
+        assert_eq!(
+            walk(&past_end),
+            "btree node is past the end of the metadata"
+        );
+        let overflowing = [node(LEAF_NODE, 300, &[])];
+        assert_eq!(walk(&overflowing), "btree node overflows its block");
+        let mut wide = node(LEAF_NODE, 126, &[]);
+        wide[24] = 16;
+        assert_eq!(walk(&[wide]), "unexpected btree value size");
+        assert_eq!(
+            walk(&[node(0, 126, &[])]),
+            "btree node is neither internal nor a leaf"
+        );
+    }
+
+    #[test]
+    fn walk_array_follows_the_block_tree() {
+        let per_block = ((METADATA_BLOCK - ARRAY_BLOCK_HEADER) / 8) as u64;
+        // array blocks 0 and 1 are stored in metadata blocks 2 and 1
+        let blocks = [
+            node(LEAF_NODE, 126, &[(0, 2), (1, 1)]),
+            array_block(&[7, 8, 9]),
+            array_block(&[5, 6]),
+        ];
+        let mut visited = Vec::new();
+        device(&blocks)
+            .walk_array(0, per_block + 2, |index, value| {
+                visited.push((index, value))
+            })
+            .unwrap();
+        assert_eq!(
+            visited,
+            [(0, 5), (1, 6), (per_block, 7), (per_block + 1, 8)]
+        );
+    }
+}
@@ -0,0 +1,1043 @@
+// pv.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+use snafu::{ensure, OptionExt, ResultExt};
+use tracing::{debug, trace, warn, Level};
+
+use crate::checksum::lvm_crc;
+use crate::header::{
+    LocationDescriptor, MetadataAreaHeader, PhysicalVolumeHeader, PhysicalVolumeLabelHeader,
+};
+use crate::metadata::PVDesc;
+use crate::ReadReason;
+use crate::{
+    nom_failure, ChecksumMismatch, Error, Finding, IoSnafu, LabelNotFoundSnafu, Lvm2Options,
+    LvmUuid, MetadataTooLargeSnafu, MissingMetadataSnafu, VolumeGroup,
+};
+
+/// A physical volume as found on disk: its label, header and metadata area.
+/// Unlike [`PV`], this does not depend on any VG metadata being present or valid.
+pub struct Pv {
+    label_offset: u64,
+    labels: Vec<LabelSighting>,
+    vhl: PhysicalVolumeLabelHeader,
+    pvh: PhysicalVolumeHeader,
+    mda_index: Option<usize>,
+    mah: Option<MetadataAreaHeader>,
+    metadata_text: Option<String>,
+    history_texts: Vec<String>,
+    checksum_mismatches: Vec<ChecksumMismatch>,
+    invalid_text: Vec<Range<u64>>,
+    // metadata areas whose header could not be read, by index, with the reason
+    unusable_mdas: Vec<(usize, String)>,
+    options: Lvm2Options,
+}
+
+impl Pv {
+    pub fn open<T: Read + Seek>(reader: &mut T) -> Result<Self, Error> {
+        Self::open_with(reader, &Lvm2Options::default())
+    }
+
+    /// Reads the label, PV header and metadata area header, which takes a few sheets
+    /// of IO. The metadata text is only read by [`Pv::load_metadata`]. A PV none of
+    /// whose metadata area headers can be read opens without metadata, and
+    /// [`Pv::findings`] reports why.
+    pub fn open_with<T: Read + Seek>(reader: &mut T, options: &Lvm2Options) -> Result<Self, Error> {
+        let span = tracing::debug_span!("open_pv", pv = tracing::field::Empty).entered();
+        let reader = &mut OffsetReader::new(reader, options.start_offset);
+        let (label_offset, buf, labels) = find_label(reader, options)?;
+        if options.logs(Level::TRACE) {
+            trace!(?buf);
+        }
+
+        let (_, vhl) = PhysicalVolumeLabelHeader::parse(&buf).map_err(|e| {
+            let (offset, expected) = nom_failure(&buf, e);
+            Error::LabelParse {
+                offset: label_offset + offset,
+                expected,
+            }
+        })?;
+        if options.logs(Level::DEBUG) {
+            debug!(
+                sector_number = vhl.sector_number,
+                checksum = vhl.checksum,
+                data_offset = vhl.data_offset,
+                "PhysicalVolumeLabelHeader"
+            );
+        }
+        // the label checksum covers everything after the checksum field
+        let mut checksum_mismatches = Vec::new();
+        checksum_mismatches.extend(options.check_checksum(
+            "PV label",
+            label_offset,
+            vhl.checksum,
+            lvm_crc(&buf[20..]),
+        )?);
+
+        let pvh_buf = buf.get(vhl.data_offset as usize..).unwrap_or_default();
+        let (_, pvh) = PhysicalVolumeHeader::parse(pvh_buf).map_err(|e| {
+            let (offset, expected) = nom_failure(pvh_buf, e);
+            Error::PvHeaderParse {
+                offset: label_offset + u64::from(vhl.data_offset) + offset,
+                expected,
+            }
+        })?;
+        span.record("pv", pvh.pv_ident.as_str());
+        if options.logs(Level::TRACE) {
+            trace!(?pvh);
+        }
+        if options.logs(Level::DEBUG) {
+            debug!(
+                pv_ident = pvh.pv_ident.as_str(),
+                pv_size = pvh.pv_size,
+                "PhysicalVolumeHeader"
+            );
+        }
+
+        // PVs created with --metadatacopies 0 carry no metadata area
+        let mut pv = Self {
+            label_offset,
+            labels,
+            vhl,
+            pvh,
+            mda_index: None,
+            mah: None,
+            metadata_text: None,
+            history_texts: Vec::new(),
+            checksum_mismatches,
+            invalid_text: Vec::new(),
+            unusable_mdas: Vec::new(),
+            options: options.clone(),
+        };
+
+        // try the preferred metadata area first, then the others in order
+        let count = pv.pvh.metadata_descriptors.len();
+        let others = (0..count).filter(|&i| options.mda_fallback && i != options.preferred_mda);
+        let order = core::iter::once(options.preferred_mda)
+            .filter(|&i| i < count)
+            .chain(others);
+        // under ChecksumMode::Strict a wrong checksum still fails the open
+        let mut strict_error = None;
+        for index in order {
+            match read_mda_header(
+                reader,
+                index,
+                pv.pvh.metadata_descriptors[index].offset,
+                options,
+                &mut pv.checksum_mismatches,
+            ) {
+                Ok(mah) => {
+                    pv.mda_index = Some(index);
+                    pv.mah = Some(mah);
+                    return Ok(pv);
+                }
+                Err(e) => {
+                    if options.logs(Level::WARN) {
+                        warn!(mda = index, "metadata area is unusable: {}", e);
+                    }
+                    if matches!(e, Error::ChecksumMismatch { .. }) {
+                        strict_error.get_or_insert(e);
+                    } else {
+                        pv.unusable_mdas.push((index, e.to_string()));
+                    }
+                }
+            }
+        }
+        match strict_error {
+            Some(e) => Err(e),
+            None => Ok(pv),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &Lvm2Options {
+        &self.options
+    }
+
+    /// Where this PV starts in the reader it was opened from, see
+    /// [`Lvm2Options::start_offset`]. All other offsets are relative to it.
+    pub fn start_offset(&self) -> u64 {
+        self.options.start_offset
+    }
+
+    /// Byte offset of the sector the label was found in.
+    pub fn label_offset(&self) -> u64 {
+        self.label_offset
+    }
+
+    /// Index into the PV header's metadata descriptors of the metadata area that was read.
+    pub fn metadata_area_index(&self) -> Option<usize> {
+        self.mda_index
+    }
+
+    /// Older metadata texts recovered from the metadata area, if requested with
+    /// [`Lvm2Options::load_history`] and loaded. Not necessarily valid or ordered.
+    pub fn historical_metadata_texts(&self) -> &[String] {
+        &self.history_texts
+    }
+
+    /// Checksum mismatches that were let through under [`ChecksumMode::Warn`] so far,
+    /// including those in the metadata text once loaded.
+    ///
+    /// [`ChecksumMode::Warn`]: crate::ChecksumMode::Warn
+    pub fn checksum_mismatches(&self) -> &[ChecksumMismatch] {
+        &self.checksum_mismatches
+    }
+
+    /// Byte ranges of the PV holding metadata text that is not UTF-8, replaced by
+    /// U+FFFD in [`Pv::raw_metadata_text`]. Empty until the metadata is loaded.
+    pub fn invalid_metadata_bytes(&self) -> &[Range<u64>] {
+        &self.invalid_text
+    }
+
+    /// Label anomalies, unreadable metadata area headers and tolerated checksum
+    /// mismatches found while opening, and
+    /// once the metadata is loaded, whether it lists this PV at all and where its
+    /// text is not UTF-8.
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = self
+            .checksum_mismatches
+            .iter()
+            .copied()
+            .map(Finding::ChecksumMismatch)
+            .collect();
+
+        let mut valid = Vec::new();
+        for &(offset, sector_number) in &self.labels {
+            if sector_number.checked_mul(512) == Some(offset) {
+                valid.push(offset);
+            } else {
+                findings.push(Finding::MisplacedLabel {
+                    offset,
+                    sector_number,
+                });
+            }
+        }
+        if valid.len() > 1 {
+            findings.push(Finding::MultipleLabels { offsets: valid });
+        }
+        findings.extend(self.unusable_mdas.iter().map(|(mda, reason)| {
+            Finding::UnusableMetadataArea {
+                mda: *mda,
+                offset: self.pvh.metadata_descriptors[*mda].offset,
+                reason: reason.clone(),
+            }
+        }));
+        findings.extend(
+            self.invalid_text
+                .iter()
+                .map(|bytes| Finding::InvalidMetadataText {
+                    offset: bytes.start,
+                    len: bytes.end - bytes.start,
+                }),
+        );
+
+        if let Some(text) = &self.metadata_text {
+            let listed = VolumeGroup::parse_all(text)
+                .is_ok_and(|vgs| vgs.iter().any(|vg| vg.pv_by_id(self.id()).is_some()));
+            if !listed {
+                findings.push(Finding::PvNotInMetadata {
+                    pv_id: self.uuid().to_string(),
+                });
+            }
+        }
+        findings
+    }
+
+    /// The PV UUID from the header, without dashes.
+    pub fn id(&self) -> &str {
+        &self.pvh.pv_ident
+    }
+    pub fn uuid(&self) -> LvmUuid {
+        LvmUuid::new(&self.pvh.pv_ident)
+    }
+
+    /// The label header, normally found in the second sheet of the PV.
+    pub fn label_header(&self) -> &PhysicalVolumeLabelHeader {
+        &self.vhl
+    }
+
+    /// The PV header, including its data area and metadata area descriptors.
+    pub fn pv_header(&self) -> &PhysicalVolumeHeader {
+        &self.pvh
+    }
+
+    /// The header of the metadata area that was read, if the PV has one. Offsets in its
+    /// location descriptors are relative to `metadata_area_offset`.
+    pub fn metadata_area_header(&self) -> Option<&MetadataAreaHeader> {
+        self.mah.as_ref()
+    }
+
+    /// The VG config text as it was read from the metadata area, once loaded. Bytes
+    /// that are not UTF-8 are replaced, see [`Pv::invalid_metadata_bytes`].
+    pub fn raw_metadata_text(&self) -> Option<&str> {
+        self.metadata_text.as_deref()
+    }
+
+    /// Reads the metadata text from `reader` on first use, along with older
+    /// generations if requested. Later calls return the cached text. If the text of
+    /// the metadata area in use cannot be read, the others are tried in order, as
+    /// when opening.
+    pub fn load_metadata<T: Read + Seek>(&mut self, reader: &mut T) -> Result<&str, Error> {
+        if self.metadata_text.is_none() {
+            let _span =
+                tracing::debug_span!("load_metadata", pv = self.pvh.pv_ident.as_str()).entered();
+            let first = self.mda_index.context(MissingMetadataSnafu)?;
+            let reader = &mut OffsetReader::new(reader, self.options.start_offset);
+            let others = (0..self.pvh.metadata_descriptors.len())
+                .filter(|&i| self.options.mda_fallback && i != first)
+                .collect::<Vec<_>>();
+            let mut first_error = None;
+            for mda in core::iter::once(first).chain(others) {
+                match self.load_mda_text(reader, mda) {
+                    Ok(()) => break,
+                    Err(e) => {
+                        if self.options.logs(Level::WARN) {
+                            warn!(mda, "metadata area text is unusable: {}", e);
+                        }
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            if let (None, Some(e)) = (&self.metadata_text, first_error) {
+                return Err(e);
+            }
+        }
+        Ok(self.metadata_text.as_deref().unwrap_or_default())
+    }
+
+    // reads the text of metadata area `mda`, and its header unless it is the one in
+    // use, making it the one in use
+    fn load_mda_text<T: Read + Seek>(&mut self, reader: &mut T, mda: usize) -> Result<(), Error> {
+        let mda_offset = self.pvh.metadata_descriptors[mda].offset;
+        let invalid_before = self.invalid_text.len();
+        let header = if self.mda_index == Some(mda) {
+            None
+        } else {
+            Some(read_mda_header(
+                reader,
+                mda,
+                mda_offset,
+                &self.options,
+                &mut self.checksum_mismatches,
+            )?)
+        };
+        let mah = match &header {
+            Some(mah) => mah,
+            None => self.mah.as_ref().context(MissingMetadataSnafu)?,
+        };
+        let loaded = read_mda_text(
+            reader,
+            mda,
+            mda_offset,
+            mah,
+            &self.options,
+            &mut self.checksum_mismatches,
+            &mut self.invalid_text,
+        )
+        .and_then(|text| {
+            let history = if self.options.load_history {
+                scan_history(reader, mda, mda_offset, mah, &text, &self.options)?
+            } else {
+                Vec::new()
+            };
+            Ok((text, history))
+        });
+        let (text, history) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                // what a copy that failed to read turned up is not about the text in use
+                self.invalid_text.truncate(invalid_before);
+                return Err(e);
+            }
+        };
+        if let Some(mah) = header {
+            self.mah = Some(mah);
+        }
+        self.mda_index = Some(mda);
+        self.history_texts = history;
+        self.metadata_text = Some(text);
+        Ok(())
+    }
+
+    /// Reads the metadata text of every metadata area, not only the one in use, to
+    /// tell whether they agree. LVM writes the same text to each, so a copy that
+    /// differs is left over from an interrupted write, or was edited.
+    pub fn compare_metadata_areas<T: Read + Seek>(&self, reader: &mut T) -> MetadataComparison {
+        let reader = &mut OffsetReader::new(reader, self.options.start_offset);
+        let copies = (0..self.pvh.metadata_descriptors.len())
+            .map(|mda| self.read_metadata_copy(reader, mda))
+            .collect();
+        MetadataComparison::new(copies)
+    }
+
+    fn read_metadata_copy<T: Read + Seek>(&self, reader: &mut T, mda: usize) -> MetadataCopy {
+        let offset = self.pvh.metadata_descriptors[mda].offset;
+        let mut copy = MetadataCopy {
+            mda,
+            offset,
+            checksum: None,
+            seqno: None,
+            checksum_mismatches: Vec::new(),
+            error: None,
+            text: None,
+        };
+        let read = read_mda_header(
+            reader,
+            mda,
+            offset,
+            &self.options,
+            &mut copy.checksum_mismatches,
+        )
+        .and_then(|mah| {
+            copy.checksum = mah
+                .location_descriptors
+                .first()
+                .map(|locdesc| locdesc.checksum);
+            read_mda_text(
+                reader,
+                mda,
+                offset,
+                &mah,
+                &self.options,
+                &mut copy.checksum_mismatches,
+                &mut Vec::new(),
+            )
+        });
+        match read {
+            Ok(text) => {
+                match VolumeGroup::parse_all(&text) {
+                    Ok(vgs) => {
+                        copy.seqno = vgs
+                            .iter()
+                            .find(|vg| vg.pv_by_id(self.id()).is_some())
+                            .or(vgs.first())
+                            .map(|vg| vg.config().seqno)
+                    }
+                    Err(e) => copy.error = Some(e.to_string()),
+                }
+                copy.text = Some(text);
+            }
+            Err(e) => copy.error = Some(e.to_string()),
+        }
+        copy
+    }
+
+    /// How full each metadata area is, from its header alone. Older generations
+    /// survive only in the free part of the ring, so it bounds how much history
+    /// [`Lvm2Options::load_history`] can recover.
+    pub fn metadata_area_usage<T: Read + Seek>(&self, reader: &mut T) -> Vec<MetadataAreaUsage> {
+        let reader = &mut OffsetReader::new(reader, self.options.start_offset);
+        self.pvh
+            .metadata_descriptors
+            .iter()
+            .enumerate()
+            .map(|(mda, md)| {
+                let mut usage = MetadataAreaUsage {
+                    mda,
+                    offset: md.offset,
+                    size: md.size,
+                    used: None,
+                    free: None,
+                    blob_offset: None,
+                    blob: Vec::new(),
+                    error: None,
+                };
+                match read_mda_header(reader, mda, md.offset, &self.options, &mut Vec::new()) {
+                    Ok(mah) => {
+                        let used = mah
+                            .location_descriptors
+                            .iter()
+                            .map(|loc| loc.data_area_size)
+                            .fold(0u64, u64::saturating_add);
+                        usage.size = mah.metadata_area_size;
+                        usage.used = Some(used);
+                        usage.free = Some(
+                            mah.metadata_area_size
+                                .saturating_sub(512)
+                                .saturating_sub(used),
+                        );
+                        usage.blob_offset = mah
+                            .location_descriptors
+                            .first()
+                            .map(|loc| loc.data_area_offset);
+                        for loc in &mah.location_descriptors {
+                            usage
+                                .blob
+                                .extend(ring_ranges(mah.metadata_area_size, loc).map(|range| {
+                                    md.offset.saturating_add(range.start)
+                                        ..md.offset.saturating_add(range.end)
+                                }));
+                        }
+                    }
+                    Err(e) => usage.error = Some(e.to_string()),
+                }
+                usage
+            })
+            .collect()
+    }
+
+    /// Parses the VG config stored in this PV's metadata area, reading it first if needed.
+    pub fn volume_group<T: Read + Seek>(&mut self, reader: &mut T) -> Result<VolumeGroup, Error> {
+        VolumeGroup::parse(self.load_metadata(reader)?)
+    }
+
+    /// The bootloader area the PV header extension declares, in bytes of the PV.
+    pub fn bootloader_area(&self) -> Option<Range<u64>> {
+        self.pvh
+            .extension
+            .as_ref()?
+            .bootloader_descriptors
+            .iter()
+            .find(|ba| ba.size > 0)
+            .map(|ba| ba.offset..ba.offset.saturating_add(ba.size))
+    }
+
+    // reads `range` of the PV, recording it as read for `reason`
+    pub(crate) fn read_range<T: Read + Seek>(
+        &self,
+        reader: &mut T,
+        range: Range<u64>,
+        reason: impl FnOnce() -> ReadReason,
+    ) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        let reader = &mut OffsetReader::new(reader, self.options.start_offset);
+        let len = range.end.saturating_sub(range.start);
+        read_exact_at(reader, range.start, len, &mut data)?;
+        self.options.record(range.start, len, reason);
+        Ok(data)
+    }
+
+    /// The bytes between the end of the metadata area (or of the label, without one)
+    /// and the start of the data area. LVM never writes there, so it tends to keep
+    /// whatever the disk held before, except in a bootloader area.
+    /// `None` when the two are adjacent.
+    pub fn pre_data_slack(&self) -> Option<Range<u64>> {
+        let end = self.pvh.data_descriptors.first()?.offset;
+        let start = self
+            .pvh
+            .metadata_descriptors
+            .iter()
+            .map(|md| md.offset.saturating_add(md.size))
+            .filter(|&md_end| md_end <= end)
+            .max()
+            .unwrap_or(self.label_offset + 512);
+        (start < end).then_some(start..end)
+    }
+
+    /// Reads the [`Pv::pre_data_slack`] region, empty if there is none.
+    pub fn read_pre_data_slack<T: Read + Seek>(&self, reader: &mut T) -> Result<Vec<u8>, Error> {
+        let mut slack = Vec::new();
+        if let Some(range) = self.pre_data_slack() {
+            let reader = &mut OffsetReader::new(reader, self.options.start_offset);
+            let len = range.end - range.start;
+            read_exact_at(reader, range.start, len, &mut slack)?;
+            self.options
+                .record(range.start, len, || ReadReason::PreDataSlack);
+        }
+        Ok(slack)
+    }
+
+    /// The parts of the metadata area's text ring not taken by the current metadata,
+    /// starting right after it and wrapping around: older configs not yet overwritten
+    /// and whatever else was left there. Empty without a metadata area.
+    pub fn metadata_slack(&self) -> Vec<Range<u64>> {
+        let (Some(mda), Some(mah)) = (self.mda_index, &self.mah) else {
+            return Vec::new();
+        };
+        let mda_offset = self.pvh.metadata_descriptors[mda].offset;
+        let size = mah.metadata_area_size;
+        let mut ring = match mah.location_descriptors.first() {
+            Some(loc) => {
+                // a blob running past the end of the ring continues after the header
+                let start = loc.data_area_offset.min(size);
+                let end = loc.data_area_offset.saturating_add(loc.data_area_size);
+                match end.checked_sub(size) {
+                    Some(wrapped) if wrapped > 0 => {
+                        alloc::vec![wrapped.saturating_add(512).min(start)..start]
+                    }
+                    _ => alloc::vec![end..size, 512..start],
+                }
+            }
+            None => alloc::vec![512..size],
+        };
+        ring.retain(|range| range.start < range.end);
+        ring.into_iter()
+            .map(|range| {
+                mda_offset.saturating_add(range.start)..mda_offset.saturating_add(range.end)
+            })
+            .collect()
+    }
+
+    /// Reads the [`Pv::metadata_slack`] ranges, one after the other.
+    pub fn read_metadata_slack<T: Read + Seek>(&self, reader: &mut T) -> Result<Vec<u8>, Error> {
+        let mut slack = Vec::new();
+        let reader = &mut OffsetReader::new(reader, self.options.start_offset);
+        for range in self.metadata_slack() {
+            let len = range.end - range.start;
+            read_exact_at(reader, range.start, len, &mut slack)?;
+            self.options
+                .record(range.start, len, || ReadReason::MetadataSlack {
+                    mda: self.mda_index.unwrap_or_default(),
+                });
+        }
+        Ok(slack)
+    }
+
+    /// Translates an offset within this PV's data area into an offset within the PV.
+    pub fn data_offset(&self, mut offset: u64) -> Option<u64> {
+        for dd in &self.pvh.data_descriptors {
+            if dd.size == 0 || dd.size > offset {
+                return offset.checked_add(dd.offset);
+            }
+            offset -= dd.size;
+        }
+        None
+    }
+}
+
+/// The metadata one metadata area holds, see [`Pv::compare_metadata_areas`].
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetadataCopy {
+    /// Index among the PV header's metadata area descriptors, see [`Mda`](crate::Mda).
+    pub mda: usize,
+    /// Byte offset of the area within the PV.
+    pub offset: u64,
+    /// Checksum of the text as the area header stores it.
+    pub checksum: Option<u32>,
+    /// Seqno of the VG config in the text.
+    pub seqno: Option<u64>,
+    /// Bad checksums of the area header or text that the checksum mode let through.
+    pub checksum_mismatches: Vec<ChecksumMismatch>,
+    /// Why the area could not be read or its text parsed.
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub text: Option<String>,
+}
+
+/// Whether the metadata areas of a PV agree, see [`Pv::compare_metadata_areas`].
+/// Each check fails if any area could not be read.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetadataComparison {
+    pub copies: Vec<MetadataCopy>,
+    pub seqno_agrees: bool,
+    pub checksum_agrees: bool,
+    /// Whether the texts are byte for byte the same.
+    pub content_agrees: bool,
+}
+impl MetadataComparison {
+    fn new(copies: Vec<MetadataCopy>) -> Self {
+        let readable = copies.iter().all(|copy| copy.error.is_none());
+        let agree = |same: fn(&MetadataCopy, &MetadataCopy) -> bool| {
+            readable && copies.windows(2).all(|pair| same(&pair[0], &pair[1]))
+        };
+        Self {
+            seqno_agrees: agree(|a, b| a.seqno == b.seqno),
+            checksum_agrees: agree(|a, b| a.checksum == b.checksum),
+            content_agrees: agree(|a, b| a.text == b.text),
+            copies,
+        }
+    }
+
+    pub fn agrees(&self) -> bool {
+        self.seqno_agrees && self.checksum_agrees && self.content_agrees
+    }
+}
+
+/// How a metadata area is used, see [`Pv::metadata_area_usage`]. Sizes are in bytes.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetadataAreaUsage {
+    /// Index among the PV header's metadata area descriptors, see [`Mda`](crate::Mda).
+    pub mda: usize,
+    /// Byte offset of the area within the PV.
+    pub offset: u64,
+    /// The whole area, including its header sheet.
+    pub size: u64,
+    /// Taken by the current metadata text.
+    pub used: Option<u64>,
+    /// The rest of the text ring, where older generations may linger.
+    pub free: Option<u64>,
+    /// Where the current text starts, relative to the area.
+    pub blob_offset: Option<u64>,
+    /// PV byte ranges of the current text, two when it wraps around the ring.
+    pub blob: Vec<Range<u64>>,
+    /// Why the area header could not be read.
+    pub error: Option<String>,
+}
+
+/// A physical volume as described by the VG metadata.
+#[derive(Clone, Copy)]
+pub struct PV<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) desc: &'a PVDesc,
+}
+impl<'a> PV<'a> {
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+    pub fn id(&self) -> &'a str {
+        &self.desc.id
+    }
+    pub fn uuid(&self) -> LvmUuid {
+        LvmUuid::new(&self.desc.id)
+    }
+    /// Device size in sheets, if the metadata records it.
+    pub fn dev_size(&self) -> Option<u64> {
+        self.desc.dev_size
+    }
+    /// Start of the first physical extent, in sheets.
+    pub fn pe_start(&self) -> u64 {
+        self.desc.pe_start
+    }
+    pub fn pe_count(&self) -> u64 {
+        self.desc.pe_count
+    }
+    pub fn status(&self) -> &'a [String] {
+        &self.desc.status
+    }
+    pub fn raw_metadata(&self) -> &'a PVDesc {
+        self.desc
+    }
+    /// The bootloader area `ba_start` and `ba_size` declare, in bytes of the PV.
+    pub fn bootloader_area(&self) -> Option<Range<u64>> {
+        let start = self.desc.ba_start?.saturating_mul(512);
+        let size = self
+            .desc
+            .ba_size
+            .filter(|&size| size > 0)?
+            .saturating_mul(512);
+        Some(start..start.saturating_add(size))
+    }
+}
+
+// Views a reader as if it started `start` bytes in.
+struct OffsetReader<'r, T> {
+    inner: &'r mut T,
+    start: u64,
+}
+impl<'r, T> OffsetReader<'r, T> {
+    fn new(inner: &'r mut T, start: u64) -> Self {
+        Self { inner, start }
+    }
+}
+impl<T: Read> Read for OffsetReader<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+impl<T: Seek> Seek for OffsetReader<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => SeekFrom::Start(self.start + x),
+            pos => pos,
+        };
+        self.inner
+            .seek(pos)?
+            .checked_sub(self.start)
+            .ok_or(crate::io_error("seek before the start of the PV"))
+    }
+}
+
+// offset and claimed sector number of a sector starting with LABELONE
+type LabelSighting = (u64, u64);
+
+// LVM looks for its label in the first four sectors, at each 512-byte boundary as it
+// addresses them in 512-byte units whatever the device's sector size. All of them are
+// read so that further labels can be reported.
+// LVM looks for the label in the first four 512-byte sectors, whatever the device's
+const LABEL_SCAN_SIZE: u64 = 4 * 512;
+
+fn find_label<T: Read + Seek>(
+    reader: &mut T,
+    options: &Lvm2Options,
+) -> Result<(u64, [u8; 512], Vec<LabelSighting>), Error> {
+    let mut found = None;
+    let mut labels = Vec::new();
+    let mut buf = [0u8; 512];
+    for offset in (0..LABEL_SCAN_SIZE).step_by(512) {
+        let read = reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| reader.read_exact(&mut buf));
+        match read {
+            Ok(()) => options.record(offset, 512, || ReadReason::Label),
+            // past the end of a tiny image, which matters only without a label so far
+            Err(_) if found.is_some() => break,
+            Err(e) => return Err(e).context(IoSnafu),
+        }
+        if !buf.starts_with(b"LABELONE") {
+            continue;
+        }
+        // like LVM, ignore a label that is not in the sector it claims to be in. It
+        // belongs to a PV starting elsewhere (in 512-byte units regardless of sector size)
+        let sector_number = u64::from_le_bytes(buf[8..16].try_into().unwrap_or_default());
+        labels.push((offset, sector_number));
+        if found.is_none() && sector_number.checked_mul(512) == Some(offset) {
+            found = Some((offset, buf));
+        }
+    }
+    match found {
+        Some((offset, buf)) => Ok((offset, buf, labels)),
+        None => LabelNotFoundSnafu {
+            scanned: 0..LABEL_SCAN_SIZE,
+        }
+        .fail(),
+    }
+}
+
+fn read_mda_header<T: Read + Seek>(
+    reader: &mut T,
+    mda: usize,
+    mda_offset: u64,
+    options: &Lvm2Options,
+    mismatches: &mut Vec<ChecksumMismatch>,
+) -> Result<MetadataAreaHeader, Error> {
+    let mut buf = [0u8; 512];
+    reader.seek(SeekFrom::Start(mda_offset)).context(IoSnafu)?;
+    reader.read_exact(&mut buf).context(IoSnafu)?;
+    options.record(mda_offset, 512, || ReadReason::MetadataAreaHeader { mda });
+    let (_, mah) = MetadataAreaHeader::parse(&buf).map_err(|e| {
+        let (offset, expected) = nom_failure(&buf, e);
+        Error::MdaHeaderParse {
+            offset: mda_offset + offset,
+            expected,
+        }
+    })?;
+    if options.logs(Level::TRACE) {
+        trace!(?mah);
+    }
+    if options.logs(Level::DEBUG) {
+        debug!(
+            checksum = mah.checksum,
+            version = mah.version,
+            metadata_area_offset = mah.metadata_area_offset,
+            metadata_area_size = mah.metadata_area_size,
+            "MetadataAreaHeader"
+        );
+    }
+    mismatches.extend(options.check_checksum(
+        "metadata area header",
+        mda_offset,
+        mah.checksum,
+        lvm_crc(&buf[4..]),
+    )?);
+    Ok(mah)
+}
+
+fn read_mda_text<T: Read + Seek>(
+    reader: &mut T,
+    mda: usize,
+    mda_offset: u64,
+    mah: &MetadataAreaHeader,
+    options: &Lvm2Options,
+    mismatches: &mut Vec<ChecksumMismatch>,
+    invalid: &mut Vec<Range<u64>>,
+) -> Result<String, Error> {
+    let size = mah.location_descriptors.iter().fold(0u64, |size, locdesc| {
+        size.saturating_add(locdesc.data_area_size)
+    });
+    let limit = options
+        .max_metadata_size
+        .min(mah.metadata_area_size.saturating_sub(512));
+    ensure!(
+        size <= limit,
+        MetadataTooLargeSnafu {
+            offset: mda_offset,
+            size,
+            limit,
+        }
+    );
+
+    let mut metadata = Vec::with_capacity(size as usize);
+    // where each piece of the text came from, to locate bytes that are not UTF-8
+    let mut pieces = Vec::new();
+    for locdesc in &mah.location_descriptors {
+        let start = metadata.len();
+        read_ring(
+            reader,
+            mda_offset,
+            mah.metadata_area_size,
+            locdesc,
+            &mut metadata,
+            &mut |offset, len| {
+                pieces.push((offset, len));
+                options.record(offset, len, || ReadReason::MetadataText { mda })
+            },
+        )?;
+        mismatches.extend(options.check_checksum(
+            "metadata text",
+            mda_offset + locdesc.data_area_offset,
+            locdesc.checksum,
+            lvm_crc(&metadata[start..]),
+        )?);
+    }
+    // partially overwritten areas keep stray bytes in otherwise readable text
+    let text = String::from_utf8(metadata).unwrap_or_else(|e| {
+        let metadata = e.into_bytes();
+        let mut pos = 0;
+        for chunk in metadata.utf8_chunks() {
+            pos += chunk.valid().len();
+            let bad = pos..pos + chunk.invalid().len();
+            pos = bad.end;
+            if bad.is_empty() {
+                continue;
+            }
+            for bytes in text_to_pv(&pieces, bad) {
+                if options.logs(Level::WARN) {
+                    warn!(
+                        offset = bytes.start,
+                        len = bytes.end - bytes.start,
+                        "metadata text is not UTF-8"
+                    );
+                }
+                invalid.push(bytes);
+            }
+        }
+        String::from_utf8_lossy(&metadata).into_owned()
+    });
+    if options.logs(Level::DEBUG) {
+        debug!(metadata = %text);
+    }
+    Ok(text)
+}
+
+// maps a range of the text assembled from `pieces` (PV offset and length, in order)
+// back onto the PV, in one range per piece it touches
+fn text_to_pv(pieces: &[(u64, u64)], range: Range<usize>) -> Vec<Range<u64>> {
+    let (start, end) = (range.start as u64, range.end as u64);
+    let mut text_offset = 0;
+    let mut ranges = Vec::new();
+    for &(offset, len) in pieces {
+        let (from, to) = (start.max(text_offset), end.min(text_offset + len));
+        if from < to {
+            ranges.push(offset + from - text_offset..offset + to - text_offset);
+        }
+        text_offset += len;
+    }
+    ranges
+}
+
+// The text area is a ring buffer following the header sheet: a blob running past
+// its end continues right after the header. The ranges are relative to the area.
+fn ring_ranges(mda_size: u64, locdesc: &LocationDescriptor) -> impl Iterator<Item = Range<u64>> {
+    let start = locdesc.data_area_offset;
+    let first = if start.saturating_add(locdesc.data_area_size) > mda_size {
+        mda_size.saturating_sub(start)
+    } else {
+        locdesc.data_area_size
+    };
+    // a blob never laps the ring
+    let rest = (locdesc.data_area_size - first).min(mda_size.saturating_sub(512));
+    [start..start + first, 512..512 + rest]
+        .into_iter()
+        .filter(|range| !range.is_empty())
+}
+
+fn read_ring<T: Read + Seek>(
+    reader: &mut T,
+    mda_offset: u64,
+    mda_size: u64,
+    locdesc: &LocationDescriptor,
+    out: &mut Vec<u8>,
+    record: &mut dyn FnMut(u64, u64),
+) -> Result<(), Error> {
+    for range in ring_ranges(mda_size, locdesc) {
+        let len = range.end - range.start;
+        read_exact_at(reader, mda_offset + range.start, len, out)?;
+        record(mda_offset + range.start, len);
+    }
+    Ok(())
+}
+
+// appends exactly `len` bytes read at `offset`; a short read is an error rather
+// than a truncated blob
+fn read_exact_at<T: Read + Seek>(
+    reader: &mut T,
+    offset: u64,
+    len: u64,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let start = out.len();
+    out.resize(start + len as usize, 0);
+    reader.seek(SeekFrom::Start(offset)).context(IoSnafu)?;
+    reader.read_exact(&mut out[start..]).context(IoSnafu)
+}
+
+// Each metadata generation is written at a sheet-aligned position in the ring, so
+// older ones survive until they are overwritten. Looks for NUL-terminated texts
+// starting with a section header (`name {`) on a sheet boundary.
+fn scan_history<T: Read + Seek>(
+    reader: &mut T,
+    mda: usize,
+    mda_offset: u64,
+    mah: &MetadataAreaHeader,
+    current: &str,
+    options: &Lvm2Options,
+) -> Result<Vec<String>, Error> {
+    // anything past the size limit (or the end of a truncated image) is left unscanned
+    let mut area = Vec::new();
+    reader.seek(SeekFrom::Start(mda_offset)).context(IoSnafu)?;
+    reader
+        .by_ref()
+        .take(mah.metadata_area_size.min(options.max_metadata_size))
+        .read_to_end(&mut area)
+        .context(IoSnafu)?;
+    options.record(mda_offset, area.len() as u64, || {
+        ReadReason::MetadataHistory { mda }
+    });
+
+    let mut texts = Vec::new();
+    let mut pos = 512;
+    while pos < area.len() {
+        let rest = &area[pos..];
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        let candidate = &rest[..len];
+        let first_line = candidate.split(|&b| b == b'\n').next().unwrap_or_default();
+        let looks_like_vg =
+            candidate.first().is_some_and(u8::is_ascii_alphanumeric) && first_line.ends_with(b" {");
+        // read like the current text, replacing bytes that are not UTF-8
+        let text = String::from_utf8_lossy(candidate);
+        if looks_like_vg && text != current.trim_end_matches('\0') {
+            texts.push(text.into_owned());
+            pos += len.div_ceil(512) * 512;
+        } else {
+            pos += 512;
+        }
+    }
+    Ok(texts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_image::{self, MDA_OFFSET};
+
+    #[test]
+    fn opens_without_a_readable_metadata_area() {
+        let mut image = test_image::image("", 4);
+        image[MDA_OFFSET as usize + 4] ^= 0xff;
+        let pv = Pv::open(&mut test_image::reader(image)).unwrap();
+        assert!(pv.metadata_area_header().is_none());
+        assert!(matches!(
+            pv.findings()[..],
+            [Finding::UnusableMetadataArea {
+                mda: 0,
+                offset: MDA_OFFSET,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn looks_for_the_label_in_the_first_four_sectors() {
+        let mut image = test_image::image("", 4);
+        // a label in the fifth sector is not one LVM would find
+        image.copy_within(512..1024, 4 * 512);
+        image[512..1024].fill(0);
+        assert!(matches!(
+            Pv::open(&mut test_image::reader(image)),
+            Err(Error::LabelNotFound { scanned }) if scanned == (0..2048)
+        ));
+    }
+}
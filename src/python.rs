@@ -0,0 +1,281 @@
+// python.rs
+// Python bindings, built into the `exhume_lvm` extension module with maturin.
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use pyo3::exceptions::{PyIOError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+
+use crate::{Error, LogicalToPhysical, Lvm2};
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io { source } => PyIOError::new_err(source.to_string()),
+            e => PyValueError::new_err(e.to_string()),
+        }
+    }
+}
+
+// a Python object with `read(n)` and `seek(offset, whence)`
+struct PyFileLike(PyObject);
+
+impl Read for PyFileLike {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Python::with_gil(|py| {
+            let data = self
+                .0
+                .call_method1(py, "read", (buf.len(),))
+                .map_err(py_io)?;
+            let data = data
+                .downcast_bound::<PyBytes>(py)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            let data = data.as_bytes();
+            let len = data.len().min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            Ok(len)
+        })
+    }
+}
+
+impl Seek for PyFileLike {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (offset as i64, 0),
+            SeekFrom::Current(offset) => (offset, 1),
+            SeekFrom::End(offset) => (offset, 2),
+        };
+        Python::with_gil(|py| {
+            self.0
+                .call_method1(py, "seek", (offset, whence))
+                .and_then(|pos| pos.extract::<u64>(py))
+                .map_err(py_io)
+        })
+    }
+}
+
+fn py_io(e: PyErr) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+enum Source {
+    File(File),
+    FileLike(PyFileLike),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(f) => f.read(buf),
+            Source::FileLike(f) => f.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(f) => f.seek(pos),
+            Source::FileLike(f) => f.seek(pos),
+        }
+    }
+}
+
+/// An opened PV together with the VG it belongs to.
+#[pyclass(name = "Lvm2", module = "exhume_lvm")]
+struct PyLvm2 {
+    inner: Lvm2,
+    source: Source,
+}
+
+#[pymethods]
+impl PyLvm2 {
+    /// Opens a PV from a path or a binary file object positioned anywhere.
+    #[staticmethod]
+    fn open(path_or_fileobj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let mut source = match path_or_fileobj.extract::<std::path::PathBuf>() {
+            Ok(path) => Source::File(File::open(path)?),
+            Err(_) => Source::FileLike(PyFileLike(path_or_fileobj.clone().unbind())),
+        };
+        let inner = Lvm2::open(&mut source)?;
+        Ok(PyLvm2 { inner, source })
+    }
+
+    #[getter]
+    fn pv_name(&self) -> &str {
+        self.inner.pv_name()
+    }
+
+    #[getter]
+    fn pv_id(&self) -> &str {
+        self.inner.pv_id()
+    }
+
+    #[getter]
+    fn vg_name(&self) -> &str {
+        self.inner.vg_name()
+    }
+
+    #[getter]
+    fn vg_id(&self) -> &str {
+        self.inner.vg_id()
+    }
+
+    /// Extent size in bytes.
+    #[getter]
+    fn extent_size(&self) -> u64 {
+        self.inner.extent_size()
+    }
+
+    /// The VG config text exactly as it was read from the metadata area.
+    #[getter]
+    fn raw_metadata_text(&self) -> &str {
+        self.inner.raw_metadata_text()
+    }
+
+    /// The LVs of the VG as a list of dicts with `name`, `id`, `size`, `hidden`,
+    /// `segments` (their types) and `stripes`: for each segment, its `(pv,
+    /// start_extent)` pairs. Hidden LVs, such as thin pool and RAID sub-LVs, only
+    /// with `all`.
+    #[pyo3(signature = (all = false))]
+    fn lvs<'py>(&self, py: Python<'py>, all: bool) -> PyResult<Bound<'py, PyList>> {
+        let lvs = PyList::empty(py);
+        for lv in self.inner.lvs_all().filter(|lv| all || !lv.is_hidden()) {
+            let dict = PyDict::new(py);
+            dict.set_item("name", lv.name())?;
+            dict.set_item("id", lv.id())?;
+            dict.set_item("size", lv.size_bytes())?;
+            dict.set_item("hidden", lv.is_hidden())?;
+            let segments: Vec<&str> = lv.segments().map(|(_, seg)| seg.kind.type_name()).collect();
+            dict.set_item("segments", segments)?;
+            let stripes: Vec<Vec<(&str, u64)>> = lv
+                .segments()
+                .map(|(_, seg)| {
+                    seg.stripes()
+                        .iter()
+                        .map(|stripe| (stripe.pv.as_str(), stripe.start_extent))
+                        .collect()
+                })
+                .collect();
+            dict.set_item("stripes", stripes)?;
+            lvs.append(dict)?;
+        }
+        Ok(lvs)
+    }
+
+    /// Opens an LV by name (or UUID) as a read-only file object.
+    fn open_lv(slf: Bound<'_, Self>, name: &str) -> PyResult<LvReader> {
+        let (name, size) = {
+            let this = slf.borrow();
+            let lv = this
+                .inner
+                .vg()
+                .lv_by_name(name)
+                .or_else(|| this.inner.vg().lv_by_id(name))
+                .ok_or_else(|| PyKeyError::new_err(name.to_owned()))?;
+            (lv.name().to_owned(), lv.size_bytes())
+        };
+        Ok(LvReader {
+            lvm: slf.unbind(),
+            name,
+            size,
+            position: 0,
+        })
+    }
+}
+
+/// A read-only, seekable file object over the contents of an LV.
+#[pyclass(module = "exhume_lvm")]
+struct LvReader {
+    lvm: Py<PyLvm2>,
+    name: String,
+    size: u64,
+    position: u64,
+}
+
+#[pymethods]
+impl LvReader {
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[getter]
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Reads up to `size` bytes, or to the end of the LV when `size` is negative.
+    #[pyo3(signature = (size = -1))]
+    fn read<'py>(&mut self, py: Python<'py>, size: i64) -> PyResult<Bound<'py, PyBytes>> {
+        let remaining = self.size.saturating_sub(self.position);
+        let len = match u64::try_from(size) {
+            Ok(size) => size.min(remaining),
+            Err(_) => remaining,
+        } as usize;
+
+        let mut buf = vec![0; len];
+        let mut filled = 0;
+        {
+            let mut lvm = self.lvm.borrow_mut(py);
+            let PyLvm2 { inner, source } = &mut *lvm;
+            let mut lv = inner
+                .open_lv_by_name(&self.name, source)
+                .ok_or_else(|| PyKeyError::new_err(self.name.clone()))?;
+            lv.seek(SeekFrom::Start(self.position))?;
+            while filled < len {
+                match lv.read(&mut buf[filled..])? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+        }
+        self.position += filled as u64;
+        Ok(PyBytes::new(py, &buf[..filled]))
+    }
+
+    #[pyo3(signature = (offset, whence = 0))]
+    fn seek(&mut self, offset: i64, whence: u8) -> PyResult<u64> {
+        let base = match whence {
+            0 => 0,
+            1 => self.position,
+            2 => self.size,
+            _ => return Err(PyValueError::new_err("invalid whence")),
+        };
+        self.position = base
+            .checked_add_signed(offset)
+            .ok_or_else(|| PyValueError::new_err("negative seek position"))?;
+        Ok(self.position)
+    }
+
+    fn tell(&self) -> u64 {
+        self.position
+    }
+
+    /// Offset within the opened image of the LV byte at `offset`, or `None` if it
+    /// is not on this PV.
+    fn physical_offset(&self, py: Python<'_>, offset: u64) -> PyResult<Option<u64>> {
+        let mut lvm = self.lvm.borrow_mut(py);
+        let PyLvm2 { inner, source } = &mut *lvm;
+        let lv = inner
+            .open_lv_by_name(&self.name, source)
+            .ok_or_else(|| PyKeyError::new_err(self.name.clone()))?;
+        Ok(lv.physical_offset(offset))
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+}
+
+#[pymodule]
+fn exhume_lvm(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyLvm2>()?;
+    m.add_class::<LvReader>()?;
+    Ok(())
+}
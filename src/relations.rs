@@ -0,0 +1,311 @@
+// relations.rs
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use crate::metadata::Segment;
+use crate::{VolumeGroup, LV};
+
+/// What an LV is to another LV whose segment names it.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LvRole {
+    /// The pool a thin LV allocates from.
+    ThinPool,
+    /// The thin LV a thin snapshot was taken of. The two share the pool's blocks, so
+    /// the snapshot does not need it to be read.
+    ThinOrigin,
+    /// A thin pool's `_tdata` LV, holding the pool's blocks.
+    ThinPoolData,
+    /// A thin pool's `_tmeta` LV.
+    ThinPoolMetadata,
+    /// The cache pool (`_cpool`) or cachevol (`_cvol`) caching an LV.
+    Cache,
+    /// A cached LV's `_corig` LV, holding its uncached data.
+    CacheOrigin,
+    /// A cache pool's `_cdata` LV, holding the cached blocks.
+    CachePoolData,
+    /// A cache pool's `_cmeta` LV.
+    CachePoolMetadata,
+    /// The origin of an old-style snapshot.
+    SnapshotOrigin,
+    /// The COW store of an old-style snapshot, the LV users see as the snapshot.
+    SnapshotStore,
+    /// A mirror's `_mimage_N` LV.
+    MirrorImage,
+    /// A mirror's `_mlog` LV.
+    MirrorLog,
+    /// A RAID LV's `_rimage_N` LV.
+    RaidImage,
+    /// A RAID LV's `_rmeta_N` LV.
+    RaidMetadata,
+}
+impl LvRole {
+    /// The name it serializes as, e.g. `thin_pool_data`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LvRole::ThinPool => "thin_pool",
+            LvRole::ThinOrigin => "thin_origin",
+            LvRole::ThinPoolData => "thin_pool_data",
+            LvRole::ThinPoolMetadata => "thin_pool_metadata",
+            LvRole::Cache => "cache",
+            LvRole::CacheOrigin => "cache_origin",
+            LvRole::CachePoolData => "cache_pool_data",
+            LvRole::CachePoolMetadata => "cache_pool_metadata",
+            LvRole::SnapshotOrigin => "snapshot_origin",
+            LvRole::SnapshotStore => "snapshot_store",
+            LvRole::MirrorImage => "mirror_image",
+            LvRole::MirrorLog => "mirror_log",
+            LvRole::RaidImage => "raid_image",
+            LvRole::RaidMetadata => "raid_metadata",
+        }
+    }
+
+    /// Whether LVM made the LV in this role as an internal part of the other one,
+    /// rather than it being an LV of its own that the other is built on, like a thin
+    /// LV's pool or a snapshot's origin.
+    pub fn is_component(self) -> bool {
+        !matches!(
+            self,
+            LvRole::ThinPool | LvRole::ThinOrigin | LvRole::SnapshotOrigin | LvRole::SnapshotStore
+        )
+    }
+}
+
+/// A segment of LV `lv` naming LV `target`, see [`LV::relations`].
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LvRelation<'a> {
+    pub lv: &'a str,
+    /// Key of the segment, e.g. `segment1`.
+    pub segment: &'a str,
+    pub role: LvRole,
+    /// Position of `target` among the LVs of its role in the segment, e.g. N for
+    /// `_rimage_N`; 0 for roles a segment has one of.
+    pub index: usize,
+    /// Name of the LV named. The VG has no such LV if the metadata is inconsistent.
+    pub target: &'a str,
+}
+
+impl<'a> LV<'a> {
+    /// The LVs this LV's segments are built on and what each is to it, in segment
+    /// order, from the segment fields rather than the `_tdata` or `_rimage_N`
+    /// naming conventions.
+    pub fn relations(&self) -> Vec<LvRelation<'a>> {
+        let names = |lvs: &'a [String]| lvs.iter().map(String::as_str).collect();
+        let mut relations = Vec::new();
+        for (segment, desc) in self.segments() {
+            let roles: Vec<(LvRole, Vec<&'a str>)> = match &desc.kind {
+                Segment::Linear { .. } | Segment::Striped { .. } | Segment::Unknown { .. } => {
+                    Vec::new()
+                }
+                Segment::Thin { pool, origin, .. } => vec![
+                    (LvRole::ThinPool, vec![pool.as_str()]),
+                    (
+                        LvRole::ThinOrigin,
+                        origin.iter().map(String::as_str).collect(),
+                    ),
+                ],
+                Segment::ThinPool { metadata, pool, .. } => vec![
+                    (LvRole::ThinPoolData, vec![pool.as_str()]),
+                    (LvRole::ThinPoolMetadata, vec![metadata.as_str()]),
+                ],
+                Segment::Cache { pool, origin, .. } => vec![
+                    (LvRole::Cache, vec![pool.as_str()]),
+                    (LvRole::CacheOrigin, vec![origin.as_str()]),
+                ],
+                Segment::CachePool { data, metadata, .. } => vec![
+                    (LvRole::CachePoolData, vec![data.as_str()]),
+                    (LvRole::CachePoolMetadata, vec![metadata.as_str()]),
+                ],
+                Segment::Snapshot {
+                    origin, cow_store, ..
+                } => vec![
+                    (LvRole::SnapshotOrigin, vec![origin.as_str()]),
+                    (LvRole::SnapshotStore, vec![cow_store.as_str()]),
+                ],
+                Segment::Mirror { images, log, .. } => vec![
+                    (
+                        LvRole::MirrorImage,
+                        images.iter().map(|image| image.name.as_str()).collect(),
+                    ),
+                    (LvRole::MirrorLog, log.iter().map(String::as_str).collect()),
+                ],
+                Segment::Raid0 { images, .. } => vec![(LvRole::RaidImage, names(images))],
+                Segment::Raid1 {
+                    images,
+                    meta_images,
+                    ..
+                }
+                | Segment::Raid {
+                    images,
+                    meta_images,
+                    ..
+                } => vec![
+                    (LvRole::RaidImage, names(images)),
+                    (LvRole::RaidMetadata, names(meta_images)),
+                ],
+            };
+            for (role, targets) in roles {
+                relations.extend(targets.into_iter().enumerate().map(|(index, target)| {
+                    LvRelation {
+                        lv: self.name,
+                        segment,
+                        role,
+                        index,
+                        target,
+                    }
+                }));
+            }
+        }
+        relations
+    }
+}
+
+impl VolumeGroup {
+    /// The relations of every LV, see [`LV::relations`], in LV name order.
+    pub fn relations(&self) -> Vec<LvRelation<'_>> {
+        self.lvs_all().flat_map(|lv| lv.relations()).collect()
+    }
+
+    /// The LV that LV `name` is an internal part of, e.g. the thin pool of a `_tdata` LV
+    /// or the RAID LV of a `_rimage_N` one, with the part it plays (see
+    /// [`LvRole::is_component`]). `lvs -o lv_parent` shows the same. Components
+    /// can nest, such as the `_rimage_N` of a RAID `_tdata`.
+    pub fn component_of(&self, name: &str) -> Option<LvRelation<'_>> {
+        self.lvs_all().find_map(|lv| {
+            lv.relations()
+                .into_iter()
+                .find(|relation| relation.target == name && relation.role.is_component())
+        })
+    }
+}
+
+/// The LVs of a VG as a graph, with an edge for each [`LvRelation`], from
+/// [`VolumeGroup::lv_graph`].
+pub struct LvGraph<'a> {
+    vg: &'a VolumeGroup,
+    relations: Vec<LvRelation<'a>>,
+}
+
+impl VolumeGroup {
+    pub fn lv_graph(&self) -> LvGraph<'_> {
+        LvGraph {
+            vg: self,
+            relations: self.relations(),
+        }
+    }
+}
+
+impl<'a> LvGraph<'a> {
+    pub fn relations(&self) -> &[LvRelation<'a>] {
+        &self.relations
+    }
+
+    /// The relations of `lv` to the LVs it is built on.
+    pub fn dependencies<'g>(&'g self, lv: &'g str) -> impl Iterator<Item = &'g LvRelation<'a>> {
+        self.relations
+            .iter()
+            .filter(move |relation| relation.lv == lv)
+    }
+
+    /// The relations of other LVs to `lv`, the ones built on it.
+    pub fn dependents<'g>(&'g self, lv: &'g str) -> impl Iterator<Item = &'g LvRelation<'a>> {
+        self.relations
+            .iter()
+            .filter(move |relation| relation.target == lv)
+    }
+
+    /// Every LV that reading `lv` needs, directly or through others, each once and
+    /// nearest first: e.g. a thin LV's pool, then the pool's `_tdata` and `_tmeta`,
+    /// then their images if they are RAID LVs. An old-style snapshot's COW store
+    /// needs its origin, named by the hidden `snapshotN` LV pairing the two; a thin
+    /// snapshot does not need its origin. Names the VG has no LV for are included,
+    /// as they are needed all the same.
+    pub fn requires(&self, lv: &str) -> Vec<&'a str> {
+        self.walk(lv, |relation, lv| {
+            if relation.role == LvRole::ThinOrigin {
+                None
+            } else if relation.lv == lv {
+                Some(relation.target)
+            } else if relation.target == lv && relation.role == LvRole::SnapshotStore {
+                Some(relation.lv)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Every LV whose reading needs `lv`, the reverse of [`LvGraph::requires`]: what
+    /// else is lost if `lv` is damaged.
+    pub fn required_by(&self, lv: &str) -> Vec<&'a str> {
+        self.walk(lv, |relation, lv| {
+            if relation.role == LvRole::ThinOrigin {
+                None
+            } else if relation.target == lv {
+                Some(relation.lv)
+            } else if relation.lv == lv && relation.role == LvRole::SnapshotStore {
+                Some(relation.target)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The PVs holding extents of `lv` or of any LV it requires, by name in the
+    /// metadata: what must be intact to read it.
+    pub fn required_pvs(&self, lv: &str) -> Vec<&'a str> {
+        let mut pvs: Vec<&'a str> = core::iter::once(self.vg.lv_by_name(lv))
+            .chain(
+                self.requires(lv)
+                    .into_iter()
+                    .map(|lv| self.vg.lv_by_name(lv)),
+            )
+            .flatten()
+            .flat_map(|lv| lv.segments())
+            .flat_map(|(_, segment)| segment.stripes())
+            .map(|stripe| stripe.pv.as_str())
+            .collect();
+        pvs.sort_unstable();
+        pvs.dedup();
+        pvs
+    }
+
+    /// Names the relations give that the VG has no LV for.
+    pub fn missing_lvs(&self) -> Vec<&'a str> {
+        let mut missing: Vec<&'a str> = self
+            .relations
+            .iter()
+            .map(|relation| relation.target)
+            .filter(|&target| self.vg.lv_by_name(target).is_none())
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        missing
+    }
+
+    // breadth first from `start`, following the LVs `next` gives for a relation
+    fn walk(
+        &self,
+        start: &str,
+        next: impl Fn(&LvRelation<'a>, &str) -> Option<&'a str>,
+    ) -> Vec<&'a str> {
+        let mut found: Vec<&'a str> = Vec::new();
+        let (mut lv, mut visited) = (start, 0);
+        loop {
+            for relation in &self.relations {
+                if let Some(other) = next(relation, lv) {
+                    if other != start && !found.contains(&other) {
+                        found.push(other);
+                    }
+                }
+            }
+            match found.get(visited) {
+                Some(&other) => lv = other,
+                None => break,
+            }
+            visited += 1;
+        }
+        found
+    }
+}
@@ -0,0 +1,312 @@
+// salvage.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+
+use crate::lv::segment_target;
+use crate::metadata::Segment;
+use crate::{Error, Lvm2, RunTarget, ThinProvisioning, LV};
+
+// how deep RAID0 images within mirror legs and the like are followed
+const MAX_DEPTH: u8 = 8;
+const SECTOR: u64 = 512;
+
+/// What bytes read through a [`SalvagedLV`] are.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SalvagedKind {
+    /// Read from the LV's data.
+    Data,
+    /// Zeroes for bytes nothing stores: thin LV blocks no pool block backs, which
+    /// read as zeroes, or extents no segment maps.
+    Hole,
+    /// Zeroes in place of bytes that could not be read: on a PV that was not
+    /// opened, in a segment type that cannot be read, or on sectors the reader
+    /// failed on.
+    Unreadable,
+}
+
+/// A range of an LV read through a [`SalvagedLV`] and what it held.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SalvagedRange {
+    pub range: Range<u64>,
+    pub kind: SalvagedKind,
+}
+
+// where a salvaged LV's data is
+enum Source<'a> {
+    Segments,
+    // the provisioned runs of a thin LV, in its pool's `_tdata` LV
+    Thin {
+        data: Option<LV<'a>>,
+        provisioning: ThinProvisioning,
+    },
+    // a thin LV whose pool metadata could not be read
+    Unreadable,
+}
+
+impl Lvm2 {
+    /// Opens `lv` for reading as much of it as can be read, with zeroes standing in
+    /// for the rest, rather than failing: for extracting from a damaged or partly
+    /// present VG. [`SalvagedLV::map`] tells the data read from the zeroes, and which
+    /// zeroes are holes and which are unreadable.
+    ///
+    /// Reads linear, striped and RAID0 segments, RAID1 and mirror segments from
+    /// their first readable leg, and thin LVs through their pool. A read error is
+    /// retried a sector at a time, so only the failing sectors are lost.
+    pub fn open_lv_salvaging<'a, 'r, T: Read + Seek>(
+        &'a self,
+        lv: LV<'a>,
+        reader: &'r mut T,
+    ) -> SalvagedLV<'a, 'r, T> {
+        let source = match self.thin_provisioning(lv, &mut *reader) {
+            Ok(provisioning) => Source::Thin {
+                data: self
+                    .vg()
+                    .lv_by_name(&provisioning.pool)
+                    .and_then(|pool| pool.thin_pool())
+                    .and_then(|pool| self.vg().lv_by_name(pool.data_lv())),
+                provisioning,
+            },
+            Err(Error::NotAThinVolume { .. }) => Source::Segments,
+            Err(e) => {
+                tracing::warn!(lv = lv.name(), error = %e, "reading the thin pool metadata failed");
+                Source::Unreadable
+            }
+        };
+        SalvagedLV {
+            lvm: self,
+            lv,
+            source,
+            map: Vec::new(),
+            reader,
+            position: 0,
+        }
+    }
+}
+
+/// An LV opened through [`Lvm2::open_lv_salvaging`]. Reads never fail.
+pub struct SalvagedLV<'a, 'r, T> {
+    lvm: &'a Lvm2,
+    lv: LV<'a>,
+    source: Source<'a>,
+    map: Vec<SalvagedRange>,
+    reader: &'r mut T,
+    position: u64,
+}
+impl<'a, T> SalvagedLV<'a, '_, T> {
+    pub fn lv(&self) -> LV<'a> {
+        self.lv
+    }
+
+    pub fn size(&self) -> u64 {
+        self.lv.size_bytes()
+    }
+
+    /// What the bytes read so far were, in the order read; adjacent ranges of the
+    /// same kind are merged.
+    pub fn map(&self) -> &[SalvagedRange] {
+        &self.map
+    }
+
+    /// Bytes read so far that were not [`SalvagedKind::Data`].
+    pub fn zeroed_bytes(&self) -> u64 {
+        self.map
+            .iter()
+            .filter(|range| range.kind != SalvagedKind::Data)
+            .map(|range| range.range.end - range.range.start)
+            .sum()
+    }
+}
+impl<T: Read + Seek> Read for SalvagedLV<'_, '_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        let position = self.position;
+        let buf = capped(buf, self.size().saturating_sub(position));
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let (n, kind) = match &self.source {
+            Source::Segments => read_salvaging(self.lvm, self.lv, position, buf, self.reader, 0),
+            Source::Thin { data, provisioning } => {
+                let provisioned = provisioning
+                    .provisioned
+                    .iter()
+                    .find(|run| (run.lv_offset..run.lv_offset + run.len).contains(&position));
+                match (provisioned, data) {
+                    (Some(run), Some(data)) => {
+                        let within = position - run.lv_offset;
+                        let buf = capped(buf, run.len - within);
+                        read_salvaging(
+                            self.lvm,
+                            *data,
+                            run.data_offset + within,
+                            buf,
+                            self.reader,
+                            0,
+                        )
+                    }
+                    (Some(run), None) => zeroes(
+                        buf,
+                        run.lv_offset + run.len - position,
+                        SalvagedKind::Unreadable,
+                    ),
+                    (None, _) => {
+                        let next = provisioning
+                            .provisioned
+                            .iter()
+                            .map(|run| run.lv_offset)
+                            .find(|&start| start > position)
+                            .unwrap_or(u64::MAX);
+                        zeroes(buf, next - position, SalvagedKind::Hole)
+                    }
+                }
+            }
+            Source::Unreadable => zeroes(buf, u64::MAX, SalvagedKind::Unreadable),
+        };
+        let range = position..position + n as u64;
+        match self.map.last_mut() {
+            Some(last) if last.range.end == range.start && last.kind == kind => {
+                last.range.end = range.end;
+            }
+            _ => self.map.push(SalvagedRange { range, kind }),
+        }
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+impl<T> Seek for SalvagedLV<'_, '_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size().checked_add_signed(x),
+            SeekFrom::Current(x) => self.position.checked_add_signed(x),
+        }
+        .ok_or(crate::io_error("seek before the start of the LV"))?;
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+// reads from byte `offset` of `lv` into `buf`, as far as the bytes are of one kind
+fn read_salvaging<T: Read + Seek>(
+    lvm: &Lvm2,
+    lv: LV<'_>,
+    offset: u64,
+    buf: &mut [u8],
+    reader: &mut T,
+    depth: u8,
+) -> (usize, SalvagedKind) {
+    let size = lv.size_bytes();
+    if offset >= size || buf.is_empty() {
+        return (0, SalvagedKind::Data);
+    }
+    let buf = capped(buf, size - offset);
+    let extent_size = lv.extent_size();
+    let extent = offset / extent_size;
+    let Some((name, segment)) = lv
+        .segments()
+        .find(|(_, segment)| segment.extents().contains(&extent))
+    else {
+        let next = lv
+            .segments()
+            .map(|(_, segment)| segment.start_extent.saturating_mul(extent_size))
+            .filter(|&start| start > offset)
+            .min()
+            .unwrap_or(size);
+        return zeroes(buf, next - offset, SalvagedKind::Hole);
+    };
+    let within = offset - segment.start_extent * extent_size;
+    let segment_left = segment.extent_count * extent_size - within;
+    if depth >= MAX_DEPTH {
+        return zeroes(buf, segment_left, SalvagedKind::Unreadable);
+    }
+    // where each leg's copy of the segment starts
+    let legs: Option<Vec<(&str, u64)>> = match &segment.kind {
+        Segment::Raid1 { images, layout, .. } => {
+            let data_offset = layout.data_offset.unwrap_or(0).saturating_mul(SECTOR);
+            Some(
+                images
+                    .iter()
+                    .map(|image| (image.as_str(), data_offset))
+                    .collect(),
+            )
+        }
+        Segment::Mirror { images, .. } => Some(
+            images
+                .iter()
+                .map(|image| {
+                    let offset = image.start_extent.saturating_mul(extent_size);
+                    (image.name.as_str(), offset)
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+    if let Some(legs) = legs {
+        // from the first leg that reads
+        let buf = capped(buf, segment_left);
+        for (image, start) in legs {
+            let offset = start.saturating_add(within);
+            let read = match lvm.vg().lv_by_name(image) {
+                Some(leg) => read_salvaging(lvm, leg, offset, buf, reader, depth + 1),
+                // a `pvmove` LV's legs are areas of its PVs
+                None if image == lvm.pv_name() => read_retrying(lv, offset, buf, |offset, buf| {
+                    lvm.read_data_area(offset, buf, &mut *reader, lv.name(), name)
+                }),
+                None => continue,
+            };
+            if read.1 != SalvagedKind::Unreadable {
+                return read;
+            }
+        }
+        return zeroes(buf, segment_left, SalvagedKind::Unreadable);
+    }
+    match segment_target(segment, within, extent_size) {
+        (RunTarget::Pv { pv, .. }, len) if pv == lvm.pv_name() => {
+            let mut open = lvm.open_lv(lv, &mut *reader);
+            read_retrying(lv, offset, capped(buf, len), |offset, buf| {
+                open.seek(SeekFrom::Start(offset))?;
+                open.read(buf)
+            })
+        }
+        (RunTarget::Lv { lv: image, offset }, len) => match lvm.vg().lv_by_name(image) {
+            Some(image) => read_salvaging(lvm, image, offset, capped(buf, len), reader, depth + 1),
+            None => zeroes(buf, len, SalvagedKind::Unreadable),
+        },
+        (_, len) => zeroes(buf, len, SalvagedKind::Unreadable),
+    }
+}
+
+// reads byte `offset` of `lv` on with `read`, retrying a failed read on the sector
+// the error is in only
+fn read_retrying(
+    lv: LV<'_>,
+    offset: u64,
+    buf: &mut [u8],
+    mut read: impl FnMut(u64, &mut [u8]) -> acid_io::Result<usize>,
+) -> (usize, SalvagedKind) {
+    if let Ok(n @ 1..) = read(offset, buf) {
+        return (n, SalvagedKind::Data);
+    }
+    let sector = capped(buf, SECTOR - offset % SECTOR);
+    match read(offset, sector) {
+        Ok(n @ 1..) => (n, SalvagedKind::Data),
+        _ => {
+            tracing::warn!(lv = lv.name(), offset, "reading failed");
+            zeroes(sector, SECTOR, SalvagedKind::Unreadable)
+        }
+    }
+}
+
+fn capped(buf: &mut [u8], len: u64) -> &mut [u8] {
+    let len = buf.len().min(usize::try_from(len).unwrap_or(usize::MAX));
+    &mut buf[..len]
+}
+
+// zero-fills up to `len` bytes of `buf`
+fn zeroes(buf: &mut [u8], len: u64, kind: SalvagedKind) -> (usize, SalvagedKind) {
+    let buf = capped(buf, len);
+    buf.fill(0);
+    (buf.len(), kind)
+}
@@ -0,0 +1,69 @@
+// shared_reader.rs
+use acid_io::{Read, Seek, SeekFrom};
+use core::cell::RefCell;
+
+/// Shares one reader between several LVs open at the same time, e.g. root and swap
+/// of one image. [`Lvm2::open_lv`](crate::Lvm2::open_lv) borrows its reader mutably
+/// for as long as the LV is open, so give each LV its own [`SharedReader::handle`]
+/// instead. Not `Sync`: use one reader per thread.
+pub struct SharedReader<T> {
+    inner: RefCell<T>,
+}
+impl<T> SharedReader<T> {
+    pub fn new(reader: T) -> Self {
+        Self {
+            inner: RefCell::new(reader),
+        }
+    }
+
+    /// A reader of its own over the shared one, starting at offset 0.
+    pub fn handle(&self) -> SharedReaderHandle<'_, T> {
+        SharedReaderHandle {
+            shared: &self.inner,
+            position: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+/// One user of a [`SharedReader`]. It keeps its own position and seeks the shared
+/// reader there before each read, so handles do not disturb one another. A clone
+/// reads on from the same position independently.
+#[derive(Clone)]
+pub struct SharedReaderHandle<'s, T> {
+    shared: &'s RefCell<T>,
+    position: u64,
+}
+impl<T: Read + Seek> Read for SharedReaderHandle<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        let mut inner = self
+            .shared
+            .try_borrow_mut()
+            .map_err(|_| crate::io_error("shared reader is already in use"))?;
+        inner.seek(SeekFrom::Start(self.position))?;
+        let n = inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+impl<T: Seek> Seek for SharedReaderHandle<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> acid_io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(x) => x,
+            SeekFrom::Current(x) => self
+                .position
+                .checked_add_signed(x)
+                .ok_or(crate::io_error("seek before the start of the reader"))?,
+            // only the shared reader knows where it ends
+            SeekFrom::End(_) => self
+                .shared
+                .try_borrow_mut()
+                .map_err(|_| crate::io_error("shared reader is already in use"))?
+                .seek(pos)?,
+        };
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,283 @@
+// snapshot.rs
+use acid_io::{Read, Seek, SeekFrom};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde::Serialize;
+use snafu::{OptionExt, ResultExt};
+
+use crate::metadata::{LVSegmentDesc, Segment};
+use crate::{Error, InvalidCowStoreSnafu, IoSnafu, Lvm2, NotASnapshotSnafu, VolumeGroup, LV};
+
+// dm-snapshot's persistent exception store, see drivers/md/dm-snap-persistent.c
+const SNAPSHOT_MAGIC: u32 = 0x7041_6e53;
+
+/// Where an old-style snapshot and its origin have diverged.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotDelta {
+    /// Chunk size in bytes.
+    pub chunk_size: u64,
+    /// Whether the kernel still considered the snapshot usable. One that overflowed
+    /// its COW store is invalidated and its contents are incomplete.
+    pub valid: bool,
+    /// Whether the snapshot is being merged into its origin, see
+    /// [`VolumeGroup::snapshot_merges`]. The merge takes chunks out of the COW store
+    /// as it copies them back, so `changed` then only lists those still to be merged.
+    pub merging: bool,
+    /// Byte ranges of the origin written to since the snapshot was taken, merged and
+    /// in order. Everywhere else the two read the same.
+    pub changed: Vec<Range<u64>>,
+}
+
+impl Lvm2 {
+    /// The chunks of `snapshot`'s origin that were copied out to its COW store before
+    /// being overwritten. `snapshot` is either the LV users see (the COW store) or
+    /// the hidden `snapshotN` LV describing it.
+    ///
+    /// Thin snapshots share blocks through the pool's metadata, which is not read,
+    /// so they are rejected with [`Error::UnsupportedSegment`].
+    pub fn snapshot_delta<T: Read + Seek>(
+        &self,
+        snapshot: LV<'_>,
+        reader: &mut T,
+    ) -> Result<SnapshotDelta, Error> {
+        let found = snapshot
+            .segments()
+            .find_map(|(_, seg)| snapshot_store(seg))
+            .or_else(|| {
+                self.lvs_all()
+                    .flat_map(|lv| lv.segments())
+                    .find_map(|(_, seg)| {
+                        snapshot_store(seg)
+                            .filter(|&(_, cow_store, ..)| cow_store == snapshot.name())
+                    })
+            });
+        let Some((origin, cow_store, chunk_size, merging)) = found else {
+            if let Some((name, seg)) = snapshot.segments().find(|(_, seg)| {
+                matches!(
+                    seg.kind,
+                    Segment::Thin {
+                        origin: Some(_),
+                        ..
+                    }
+                )
+            }) {
+                return Err(Error::UnsupportedSegment {
+                    lv: snapshot.name().to_string(),
+                    segment: name.to_string(),
+                    kind: seg.kind.type_name().to_string(),
+                });
+            }
+            return NotASnapshotSnafu {
+                lv: snapshot.name(),
+            }
+            .fail();
+        };
+
+        let cow = self
+            .vg()
+            .lv_by_name(cow_store)
+            .context(NotASnapshotSnafu { lv: cow_store })?;
+        let cow_size = cow.size_bytes();
+        let mut cow = self.open_lv(cow, reader);
+
+        let mut header = [0u8; 16];
+        cow.read_exact(&mut header).context(IoSnafu)?;
+        let (valid, chunk_size) = cow_header(&header, chunk_size).map_err(|reason| {
+            InvalidCowStoreSnafu {
+                lv: cow_store,
+                reason,
+            }
+            .build()
+        })?;
+        // exceptions of a damaged store can name any chunk; only the origin's are kept
+        let origin_size = self
+            .vg()
+            .lv_by_name(origin)
+            .map_or(u64::MAX, |lv| lv.size_bytes());
+        let origin_chunks = origin_size.div_ceil(chunk_size);
+
+        // exception areas of one chunk each, every one followed by the data chunks it
+        // describes, starting with chunk 1
+        let per_area = chunk_size / 16;
+        let mut old_chunks = Vec::new();
+        let mut skipped = 0u64;
+        let mut area = Vec::new();
+        'areas: for index in 0u64.. {
+            let Some(offset) = index
+                .checked_mul(per_area + 1)
+                .and_then(|chunk| chunk.checked_add(1))
+                .and_then(|chunk| chunk.checked_mul(chunk_size))
+                .filter(|offset| offset.saturating_add(chunk_size) <= cow_size)
+            else {
+                break;
+            };
+            area.resize(chunk_size as usize, 0);
+            cow.seek(SeekFrom::Start(offset)).context(IoSnafu)?;
+            cow.read_exact(&mut area).context(IoSnafu)?;
+            for exception in area.chunks_exact(16) {
+                let old_chunk = u64::from_le_bytes(exception[..8].try_into().unwrap_or_default());
+                let new_chunk = u64::from_le_bytes(exception[8..].try_into().unwrap_or_default());
+                // chunk 0 is the header, so no exception points there
+                if new_chunk == 0 {
+                    break 'areas;
+                }
+                if old_chunk < origin_chunks {
+                    old_chunks.push(old_chunk);
+                } else {
+                    skipped += 1;
+                }
+            }
+        }
+        if skipped > 0 {
+            tracing::warn!(
+                lv = cow_store,
+                skipped,
+                "exceptions name chunks past the end of the origin"
+            );
+        }
+
+        old_chunks.sort_unstable();
+        old_chunks.dedup();
+        let mut changed: Vec<Range<u64>> = Vec::new();
+        for chunk in old_chunks {
+            let range = chunk * chunk_size..(chunk + 1).saturating_mul(chunk_size).min(origin_size);
+            match changed.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => changed.push(range),
+            }
+        }
+        Ok(SnapshotDelta {
+            chunk_size,
+            valid,
+            merging,
+            changed,
+        })
+    }
+}
+
+// whether the COW store header marks the snapshot valid, and its chunk size in bytes,
+// with the one from the VG metadata (in sheets) for a header without it
+fn cow_header(header: &[u8; 16], chunk_size: u64) -> Result<(bool, u64), &'static str> {
+    let field =
+        |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap_or_default());
+    if field(0) != SNAPSHOT_MAGIC {
+        return Err("missing snapshot magic");
+    }
+    // the header's chunk size is what the kernel used; the metadata should agree
+    let chunk_size = match field(3) {
+        0 => chunk_size,
+        sheets => u64::from(sheets),
+    }
+    .checked_mul(512)
+    .ok_or("chunk size too large")?;
+    if chunk_size < 16 {
+        return Err("chunk size too small");
+    }
+    Ok((field(1) != 0, chunk_size))
+}
+
+// the origin, COW store, chunk size (in sheets) and merge state of a snapshot segment
+fn snapshot_store(seg: &LVSegmentDesc) -> Option<(&str, &str, u64, bool)> {
+    match &seg.kind {
+        Segment::Snapshot {
+            origin,
+            cow_store,
+            chunk_size,
+            merging,
+        } => Some((origin, cow_store, *chunk_size, *merging)),
+        _ => None,
+    }
+}
+
+/// A snapshot `lvconvert --merge` was merging back into its origin when the
+/// metadata was written. If the origin was in use, LVM put the merge off until it
+/// was next activated, so it may also not have started at all.
+///
+/// LVs are read as stored: the origin then holds the chunks merged so far and its
+/// old data elsewhere, a mix that never existed as such. What the kernel showed as
+/// the origin, and what it ends up holding, is the snapshot's view: for an
+/// old-style snapshot, the origin with the chunks [`Lvm2::snapshot_delta`] lists
+/// taken from the COW store.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotMerge {
+    pub origin: String,
+    /// The snapshot LV users see, i.e. the COW store of an old-style snapshot.
+    pub snapshot: String,
+    /// Whether both are thin LVs, merged by the thin pool rather than by copying
+    /// chunks out of a COW store.
+    pub thin: bool,
+}
+
+impl VolumeGroup {
+    /// The snapshot merges in progress.
+    pub fn snapshot_merges(&self) -> Vec<SnapshotMerge> {
+        let mut merges = Vec::new();
+        for lv in self.lvs_all() {
+            for (_, seg) in lv.segments() {
+                match &seg.kind {
+                    Segment::Snapshot {
+                        origin,
+                        cow_store,
+                        merging: true,
+                        ..
+                    } => merges.push(SnapshotMerge {
+                        origin: origin.clone(),
+                        snapshot: cow_store.clone(),
+                        thin: false,
+                    }),
+                    Segment::Thin {
+                        merge: Some(snapshot),
+                        ..
+                    } => merges.push(SnapshotMerge {
+                        origin: lv.name().to_string(),
+                        snapshot: snapshot.clone(),
+                        thin: true,
+                    }),
+                    _ => (),
+                }
+            }
+        }
+        merges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // magic, valid, version, chunk size in sheets
+    fn header(valid: u32, chunk_size: u32) -> [u8; 16] {
+        let mut header = [0u8; 16];
+        for (i, field) in [SNAPSHOT_MAGIC, valid, 1, chunk_size]
+            .into_iter()
+            .enumerate()
+        {
+            header[i * 4..i * 4 + 4].copy_from_slice(&field.to_le_bytes());
+        }
+        header
+    }
+
+    #[test]
+    fn cow_header_gives_validity_and_chunk_size() {
+        assert_eq!(cow_header(&header(1, 16), 8), Ok((true, 8192)));
+        assert_eq!(cow_header(&header(0, 16), 8), Ok((false, 8192)));
+    }
+
+    #[test]
+    fn cow_header_without_a_chunk_size_takes_the_metadata_one() {
+        assert_eq!(cow_header(&header(1, 0), 8), Ok((true, 4096)));
+        assert_eq!(cow_header(&header(1, 0), 0), Err("chunk size too small"));
+        assert_eq!(
+            cow_header(&header(1, 0), u64::MAX),
+            Err("chunk size too large")
+        );
+    }
+
+    #[test]
+    fn cow_header_needs_its_magic() {
+        let mut header = header(1, 16);
+        header[0] ^= 1;
+        assert_eq!(cow_header(&header, 8), Err("missing snapshot magic"));
+    }
+}
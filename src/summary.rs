@@ -0,0 +1,57 @@
+// summary.rs
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use crate::metadata::LVDesc;
+use crate::{Lvm2, VgUsage, LV};
+
+/// A serializable overview of an opened PV and the VG it belongs to.
+#[derive(Serialize, Clone, Debug)]
+pub struct Lvm2Summary<'a> {
+    pub pv_name: &'a str,
+    pub pv_id: &'a str,
+    pub vg_name: &'a str,
+    pub vg_id: &'a str,
+    pub extent_size: u64,
+    /// Size and free space of the VG and each of its PVs.
+    pub usage: VgUsage<'a>,
+    pub logical_volumes: Vec<LVSummary<'a>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LVSummary<'a> {
+    pub name: &'a str,
+    pub size_in_extents: u64,
+    pub size_bytes: u64,
+    /// See [`LV::is_hidden`](crate::LV::is_hidden).
+    pub hidden: bool,
+    #[serde(flatten)]
+    pub desc: &'a LVDesc,
+}
+
+impl Lvm2 {
+    /// Lists every LV, hidden ones included.
+    pub fn to_summary(&self) -> Lvm2Summary<'_> {
+        Lvm2Summary {
+            pv_name: self.pv_name(),
+            pv_id: self.pv_id(),
+            vg_name: self.vg_name(),
+            vg_id: self.vg_id(),
+            extent_size: self.extent_size(),
+            usage: self.vg().usage(),
+            logical_volumes: self.lvs_all().map(LVSummary::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<LV<'a>> for LVSummary<'a> {
+    fn from(lv: LV<'a>) -> Self {
+        LVSummary {
+            name: lv.name(),
+            size_in_extents: lv.size_in_extents(),
+            size_bytes: lv.size_bytes(),
+            hidden: lv.is_hidden(),
+            desc: lv.raw_metadata(),
+        }
+    }
+}
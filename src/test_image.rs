@@ -0,0 +1,174 @@
+// test_image.rs
+// single-PV images for the tests, laid out as pvcreate and vgcreate would
+use acid_io::Cursor;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::checksum::lvm_crc;
+
+pub(crate) const SHEET: u64 = 512;
+// 4 KiB extents, the smallest LVM allows
+pub(crate) const EXTENT: u64 = 8 * SHEET;
+pub(crate) const PE_START: u64 = 2048 * SHEET;
+pub(crate) const MDA_OFFSET: u64 = 4096;
+pub(crate) const PV_ID: &str = "Rk3S1Mcg6lG3Ym4WsoB1K93x8o22VaZ3";
+const MDA_MAGIC: &[u8; 16] = b" LVM2 x[5A%r0N*>";
+
+/// A PV of `pe_count` extents in VG `vg0`, whose `logical_volumes` section holds
+/// `lvs`. Each 4-byte word of the data area holds its offset within the data area,
+/// see [`data_word`].
+pub(crate) fn image(lvs: &str, pe_count: u64) -> Vec<u8> {
+    let size = PE_START + pe_count * EXTENT;
+    let mut image = alloc::vec![0u8; size as usize];
+    label(&mut image, size);
+    let text = metadata_text(lvs, pe_count);
+    mda(
+        &mut image,
+        MDA_OFFSET,
+        PE_START - MDA_OFFSET,
+        text.as_bytes(),
+    );
+    for (i, word) in image[PE_START as usize..].chunks_exact_mut(4).enumerate() {
+        word.copy_from_slice(&data_word(i as u64 * 4).to_le_bytes());
+    }
+    image
+}
+
+pub(crate) fn reader(image: Vec<u8>) -> Cursor<Vec<u8>> {
+    Cursor::new(image)
+}
+
+/// The word [`image`] writes at byte `offset` of the data area, rounded down to a word.
+pub(crate) fn data_word(offset: u64) -> u32 {
+    (offset / 4) as u32
+}
+
+/// What [`image`] holds in `len` bytes of the data area from `offset` on.
+pub(crate) fn data(offset: u64, len: usize) -> Vec<u8> {
+    (offset..offset + len as u64)
+        .map(|at| data_word(at).to_le_bytes()[(at % 4) as usize])
+        .collect()
+}
+
+pub(crate) fn metadata_text(lvs: &str, pe_count: u64) -> String {
+    format!(
+        "vg0 {{\n\
+         id = \"Z8EhIL-B3Vd-4bR2-hmCm-vdif-3zVb-0KRTnA\"\n\
+         seqno = 1\n\
+         format = \"lvm2\"\n\
+         status = [\"RESIZEABLE\", \"READ\", \"WRITE\"]\n\
+         flags = []\n\
+         extent_size = {extent}\n\
+         max_lv = 0\n\
+         max_pv = 0\n\
+         metadata_copies = 0\n\
+         physical_volumes {{\n\
+         pv0 {{\n\
+         id = \"{id}\"\n\
+         device = \"/dev/sdb\"\n\
+         status = [\"ALLOCATABLE\"]\n\
+         flags = []\n\
+         dev_size = {dev_size}\n\
+         pe_start = {pe_start}\n\
+         pe_count = {pe_count}\n\
+         }}\n\
+         }}\n\
+         logical_volumes {{\n\
+         {lvs}\n\
+         }}\n\
+         }}\n\
+         contents = \"Text Format Volume Group\"\n\
+         version = 1\n\
+         description = \"\"\n\
+         creation_host = \"host\"\n\
+         creation_time = 1600000001\n",
+        extent = EXTENT / SHEET,
+        id = dashed(PV_ID),
+        dev_size = (PE_START + pe_count * EXTENT) / SHEET,
+        pe_start = PE_START / SHEET,
+    )
+}
+
+/// An LV section of `segments`, each a segment section's body.
+pub(crate) fn lv(name: &str, segments: &[&str]) -> String {
+    let mut lv = format!(
+        "{name} {{\n\
+         id = \"cbRHVu-6IJI-zYl1-B7Jw-n8Vo-zT8x-vpXN1S\"\n\
+         status = [\"READ\", \"WRITE\", \"VISIBLE\"]\n\
+         flags = []\n\
+         creation_time = 1600000000\n\
+         creation_host = \"host\"\n\
+         segment_count = {}\n",
+        segments.len()
+    );
+    for (i, segment) in segments.iter().enumerate() {
+        lv.push_str(&format!("segment{} {{\n{segment}\n}}\n", i + 1));
+    }
+    lv.push_str("}\n");
+    lv
+}
+
+fn dashed(id: &str) -> String {
+    let mut out = String::new();
+    let mut rest = id;
+    for (i, len) in [6, 4, 4, 4, 4, 4, 6].into_iter().enumerate() {
+        if i > 0 {
+            out.push('-');
+        }
+        out.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    out
+}
+
+fn put_u32(image: &mut [u8], at: u64, value: u32) {
+    image[at as usize..at as usize + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(image: &mut [u8], at: u64, value: u64) {
+    image[at as usize..at as usize + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+// the label in sheet 1, with the PV header after it
+fn label(image: &mut [u8], size: u64) {
+    let at = SHEET;
+    image[at as usize..at as usize + 8].copy_from_slice(b"LABELONE");
+    put_u64(image, at + 8, 1);
+    put_u32(image, at + 20, 32);
+    image[at as usize + 24..at as usize + 32].copy_from_slice(b"LVM2 001");
+    let pvh = at + 32;
+    image[pvh as usize..pvh as usize + 32].copy_from_slice(PV_ID.as_bytes());
+    put_u64(image, pvh + 32, size);
+    // one data area from pe_start to the end, then one metadata area
+    put_u64(image, pvh + 40, PE_START);
+    put_u64(image, pvh + 72, MDA_OFFSET);
+    put_u64(image, pvh + 80, PE_START - MDA_OFFSET);
+    // extension version 2, in use
+    put_u32(image, pvh + 104, 2);
+    put_u32(image, pvh + 108, 1);
+    let checksum = lvm_crc(&image[at as usize + 20..(at + SHEET) as usize]);
+    put_u32(image, at + 16, checksum);
+}
+
+/// Writes a metadata area at `offset` holding `text` right after its header.
+pub(crate) fn mda(image: &mut [u8], offset: u64, size: u64, text: &[u8]) {
+    let header = offset as usize;
+    image[header + 4..header + 20].copy_from_slice(MDA_MAGIC);
+    put_u32(image, offset + 20, 1);
+    put_u64(image, offset + 24, offset);
+    put_u64(image, offset + 32, size);
+    let start = offset + SHEET;
+    image[start as usize..start as usize + text.len()].copy_from_slice(text);
+    // NUL-terminated
+    let len = text.len() as u64 + 1;
+    put_u64(image, offset + 40, SHEET);
+    put_u64(image, offset + 48, len);
+    put_u32(
+        image,
+        offset + 56,
+        lvm_crc(&image[start as usize..(start + len) as usize]),
+    );
+    let checksum = lvm_crc(&image[header + 4..header + SHEET as usize]);
+    put_u32(image, offset, checksum);
+}
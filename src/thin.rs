@@ -0,0 +1,376 @@
+// thin.rs
+use acid_io::{Read, Seek};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+use snafu::OptionExt;
+
+use crate::metadata::Segment;
+use crate::persistent_data::{le32, le64, MetadataDevice};
+use crate::{Error, InvalidThinPoolSnafu, Lvm2, NotAThinVolumeSnafu, OpenLV, LV};
+
+// dm-thin's pool metadata, see drivers/md/dm-thin-metadata.c
+const THIN_MAGIC: u64 = 27022010;
+
+/// How much of a thin LV's virtual size is backed by pool blocks.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ThinProvisioning {
+    /// Name of the thin pool LV.
+    pub pool: String,
+    pub device_id: u64,
+    /// Pool block size in bytes.
+    pub block_size: u64,
+    pub virtual_size: u64,
+    /// Runs of the thin LV that are backed by pool blocks, merged and in order.
+    /// Reads anywhere else return zeroes.
+    pub provisioned: Vec<ThinMapping>,
+}
+impl ThinProvisioning {
+    /// Bytes of the thin LV backed by pool blocks.
+    pub fn provisioned_bytes(&self) -> u64 {
+        self.provisioned.iter().map(|run| run.len).sum()
+    }
+}
+
+/// How the data blocks of a thin pool are shared among its thin devices, from
+/// [`Lvm2::thin_pool_usage`].
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ThinPoolUsage {
+    /// Name of the thin pool LV.
+    pub pool: String,
+    /// Pool block size in bytes.
+    pub block_size: u64,
+    /// Bytes of the pool's data LV mapped by at least one device.
+    pub used_bytes: u64,
+    /// Every device in the pool metadata, in device id order.
+    pub devices: Vec<ThinDeviceUsage>,
+}
+
+/// The data a thin device stores, as opposed to its virtual size.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ThinDeviceUsage {
+    pub device_id: u64,
+    /// The thin LV with this device id. `None` if the VG lists none, e.g. because
+    /// the LV was removed without its device.
+    pub lv: Option<String>,
+    pub provisioned_bytes: u64,
+    /// Bytes in blocks no other device maps, i.e. data only this device holds.
+    pub unique_bytes: u64,
+    /// Bytes in blocks other devices map too, such as those not yet overwritten
+    /// since a snapshot was taken.
+    pub shared_bytes: u64,
+}
+
+/// A run of a thin LV stored contiguously in its pool's data LV.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThinMapping {
+    /// Offset within the thin LV.
+    pub lv_offset: u64,
+    /// Offset within the pool's data (`_tdata`) LV.
+    pub data_offset: u64,
+    pub len: u64,
+}
+
+/// The parameters of a thin pool LV, see [`LV::thin_pool`].
+#[derive(Clone, Copy, Debug)]
+pub struct ThinPool<'a> {
+    metadata: &'a str,
+    data: &'a str,
+    chunk_size: Option<u64>,
+    transaction_id: Option<u64>,
+    zero_new_blocks: Option<bool>,
+    discards: Option<&'a str>,
+}
+impl<'a> ThinPool<'a> {
+    /// Name of the `_tmeta` LV holding the pool metadata.
+    pub fn metadata_lv(&self) -> &'a str {
+        self.metadata
+    }
+    /// Name of the `_tdata` LV holding the pool blocks.
+    pub fn data_lv(&self) -> &'a str {
+        self.data
+    }
+    /// Size of a pool block in bytes, `None` if the metadata gives none or it does
+    /// not fit in a `u64`.
+    pub fn chunk_size(&self) -> Option<u64> {
+        self.chunk_size.and_then(|sheets| sheets.checked_mul(512))
+    }
+    /// The pool metadata transaction LVM last committed.
+    pub fn transaction_id(&self) -> Option<u64> {
+        self.transaction_id
+    }
+    /// Whether newly provisioned blocks are zeroed, so they never show what the
+    /// pool data LV held before.
+    pub fn zero_new_blocks(&self) -> Option<bool> {
+        self.zero_new_blocks
+    }
+    /// `ignore`, `nopassdown` or `passdown`: with `passdown`, discarded thin blocks
+    /// are discarded on the PVs as well.
+    pub fn discards(&self) -> Option<&'a str> {
+        self.discards
+    }
+}
+
+impl<'a> LV<'a> {
+    /// The pool parameters, if this is a thin pool.
+    pub fn thin_pool(&self) -> Option<ThinPool<'a>> {
+        self.segments().find_map(|(_, seg)| match &seg.kind {
+            Segment::ThinPool {
+                metadata,
+                pool,
+                chunk_size,
+                transaction_id,
+                zero_new_blocks,
+                discards,
+            } => Some(ThinPool {
+                metadata,
+                data: pool,
+                chunk_size: *chunk_size,
+                transaction_id: *transaction_id,
+                zero_new_blocks: *zero_new_blocks,
+                discards: discards.as_deref(),
+            }),
+            _ => None,
+        })
+    }
+}
+
+impl Lvm2 {
+    /// Reads the block mappings of thin LV `lv` from its pool's metadata LV.
+    ///
+    /// Only the superblock magic is checked: block checksums are not verified, and a
+    /// pool whose metadata was not committed cleanly may report stale mappings.
+    pub fn thin_provisioning<T: Read + Seek>(
+        &self,
+        lv: LV<'_>,
+        reader: &mut T,
+    ) -> Result<ThinProvisioning, Error> {
+        let (pool, device_id) = thin_device(lv).context(NotAThinVolumeSnafu { lv: lv.name() })?;
+        let (mut metadata, block_size, data_mapping_root) =
+            self.thin_pool_metadata(pool, reader)?;
+
+        // the top level maps device ids to the root of each device's block tree
+        let mut device_root = None;
+        metadata.walk(data_mapping_root, Some(device_id), |_, root| {
+            device_root = Some(root)
+        })?;
+        let provisioned = match device_root {
+            Some(root) => device_mappings(&mut metadata, root, block_size)?,
+            None => Vec::new(),
+        };
+
+        Ok(ThinProvisioning {
+            pool: pool.into(),
+            device_id,
+            block_size,
+            virtual_size: lv.size_bytes(),
+            provisioned,
+        })
+    }
+
+    /// Reads the block mappings of every thin device in thin pool `pool` and counts
+    /// the data blocks each shares with the others, such as its snapshots or their
+    /// origin, under the same caveats as [`Lvm2::thin_provisioning`].
+    pub fn thin_pool_usage<T: Read + Seek>(
+        &self,
+        pool: LV<'_>,
+        reader: &mut T,
+    ) -> Result<ThinPoolUsage, Error> {
+        let (mut metadata, block_size, data_mapping_root) =
+            self.thin_pool_metadata(pool.name(), reader)?;
+        let mut roots = Vec::new();
+        metadata.walk(data_mapping_root, None, |device_id, root| {
+            roots.push((device_id, root))
+        })?;
+        let mut devices = Vec::new();
+        for (device_id, root) in roots {
+            devices.push((device_id, device_mappings(&mut metadata, root, block_size)?));
+        }
+
+        // how many devices map each data block
+        let mut references: BTreeMap<u64, u32> = BTreeMap::new();
+        for (_, mappings) in &devices {
+            for run in mappings {
+                let first = run.data_offset / block_size;
+                for block in first..first + run.len / block_size {
+                    *references.entry(block).or_default() += 1;
+                }
+            }
+        }
+        let devices = devices
+            .into_iter()
+            .map(|(device_id, mappings)| {
+                let mut usage = ThinDeviceUsage {
+                    device_id,
+                    lv: self
+                        .lvs_all()
+                        .find(|lv| thin_device(*lv) == Some((pool.name(), device_id)))
+                        .map(|lv| lv.name().into()),
+                    provisioned_bytes: 0,
+                    unique_bytes: 0,
+                    shared_bytes: 0,
+                };
+                for run in &mappings {
+                    let first = run.data_offset / block_size;
+                    for block in first..first + run.len / block_size {
+                        if references.get(&block).is_some_and(|&n| n > 1) {
+                            usage.shared_bytes += block_size;
+                        } else {
+                            usage.unique_bytes += block_size;
+                        }
+                    }
+                    usage.provisioned_bytes += run.len;
+                }
+                usage
+            })
+            .collect();
+
+        Ok(ThinPoolUsage {
+            pool: pool.name().into(),
+            block_size,
+            used_bytes: references.len() as u64 * block_size,
+            devices,
+        })
+    }
+
+    // the metadata of thin pool `pool`, its block size and the root of its data mapping
+    // tree, after checking the superblock
+    fn thin_pool_metadata<'a, 'r, T: Read + Seek>(
+        &'a self,
+        pool: &'a str,
+        reader: &'r mut T,
+    ) -> Result<(MetadataDevice<'a, OpenLV<'a, 'r, T>>, u64, u64), Error> {
+        let invalid = |reason| InvalidThinPoolSnafu { lv: pool, reason };
+        let thin_pool = self
+            .vg()
+            .lv_by_name(pool)
+            .and_then(|pool| pool.thin_pool())
+            .context(invalid("not a thin pool"))?;
+        let metadata = self
+            .vg()
+            .lv_by_name(thin_pool.metadata_lv())
+            .context(invalid("metadata LV is missing"))?;
+        let mut metadata = MetadataDevice::new(
+            pool,
+            |lv, reason| InvalidThinPoolSnafu { lv, reason }.build(),
+            self.open_lv(metadata, reader),
+            0,
+            metadata.size_bytes(),
+        );
+
+        metadata.read_block(0)?;
+        let (block_size, data_mapping_root) =
+            superblock(&metadata.block, thin_pool.chunk_size())
+                .map_err(|reason| InvalidThinPoolSnafu { lv: pool, reason }.build())?;
+        Ok((metadata, block_size, data_mapping_root))
+    }
+}
+
+// the block size and data mapping root the pool's superblock gives, with the chunk
+// size from the VG metadata for a superblock without a block size
+fn superblock(block: &[u8], chunk_size: Option<u64>) -> Result<(u64, u64), &'static str> {
+    if le64(block, 32) != THIN_MAGIC {
+        return Err("missing superblock magic");
+    }
+    let data_mapping_root = le64(block, 320);
+    // the superblock's block size is what the kernel used; the metadata should agree
+    let block_size = match le32(block, 336) {
+        0 => chunk_size.unwrap_or(0),
+        sheets => u64::from(sheets) * 512,
+    };
+    if block_size == 0 {
+        return Err("block size is zero");
+    }
+    Ok((block_size, data_mapping_root))
+}
+
+// the pool and device id of a thin LV
+fn thin_device(lv: LV<'_>) -> Option<(&str, u64)> {
+    lv.segments().find_map(|(_, seg)| match &seg.kind {
+        Segment::Thin {
+            pool, device_id, ..
+        } => Some((pool.as_str(), *device_id)),
+        _ => None,
+    })
+}
+
+// the runs of the device whose block tree is rooted at `root`, merged and in order
+fn device_mappings<T: Read + Seek>(
+    metadata: &mut MetadataDevice<'_, T>,
+    root: u64,
+    block_size: u64,
+) -> Result<Vec<ThinMapping>, Error> {
+    let mut provisioned: Vec<ThinMapping> = Vec::new();
+    let mut out_of_range = false;
+    metadata.walk(root, None, |virt, value| {
+        // the low 24 bits hold the time the block was provisioned. Keys and values
+        // of damaged metadata can be anything, so the offsets must fit with the run
+        let offset = |block: u64| {
+            block
+                .checked_mul(block_size)
+                .filter(|offset| offset.checked_add(block_size).is_some())
+        };
+        let (Some(lv_offset), Some(data_offset)) = (offset(virt), offset(value >> 24)) else {
+            out_of_range = true;
+            return;
+        };
+        let run = ThinMapping {
+            lv_offset,
+            data_offset,
+            len: block_size,
+        };
+        match provisioned.last_mut() {
+            Some(last)
+                if last.lv_offset + last.len == run.lv_offset
+                    && last.data_offset + last.len == run.data_offset =>
+            {
+                last.len += run.len
+            }
+            _ => provisioned.push(run),
+        }
+    })?;
+    if out_of_range {
+        return Err(metadata.invalid("block mapping is out of range"));
+    }
+    Ok(provisioned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistent_data::METADATA_BLOCK;
+
+    fn superblock_block(block_size: u32) -> Vec<u8> {
+        let mut block = alloc::vec![0u8; METADATA_BLOCK];
+        block[32..40].copy_from_slice(&THIN_MAGIC.to_le_bytes());
+        block[320..328].copy_from_slice(&17u64.to_le_bytes());
+        block[336..340].copy_from_slice(&block_size.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn superblock_gives_the_block_size_and_mapping_root() {
+        // 64 KiB blocks
+        assert_eq!(
+            superblock(&superblock_block(128), Some(4096)),
+            Ok((65536, 17))
+        );
+    }
+
+    #[test]
+    fn superblock_without_a_block_size_takes_the_chunk_size() {
+        assert_eq!(superblock(&superblock_block(0), Some(4096)), Ok((4096, 17)));
+        assert_eq!(
+            superblock(&superblock_block(0), None),
+            Err("block size is zero")
+        );
+    }
+
+    #[test]
+    fn superblock_needs_its_magic() {
+        let mut block = superblock_block(128);
+        block[32] ^= 1;
+        assert_eq!(superblock(&block, None), Err("missing superblock magic"));
+    }
+}
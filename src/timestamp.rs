@@ -0,0 +1,96 @@
+// timestamp.rs
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// A time LVM recorded, such as `creation_time`, in seconds since the Unix epoch.
+/// Serializes as the plain number it is stored as.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(transparent)]
+pub struct Timestamp(u64);
+
+/// A [`Timestamp`] broken down into its UTC calendar date and time of day.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtcDateTime {
+    pub year: i64,
+    /// 1 to 12.
+    pub month: u8,
+    /// 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Timestamp {
+    pub const fn from_unix(seconds: u64) -> Self {
+        Self(seconds)
+    }
+
+    pub const fn as_unix(self) -> u64 {
+        self.0
+    }
+
+    pub fn to_utc(self) -> UtcDateTime {
+        let days = self.0 / 86400;
+        let seconds = self.0 % 86400;
+        // Howard Hinnant's civil_from_days, shifted to years starting in March
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        UtcDateTime {
+            year: year_of_era + era * 400 + i64::from(month <= 2),
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn to_system_time(self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + core::time::Duration::from_secs(self.0)
+    }
+
+    /// `None` past the range `chrono` can represent.
+    #[cfg(feature = "chrono")]
+    pub fn to_chrono(self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(i64::try_from(self.0).ok()?, 0)
+    }
+
+    /// `None` past the range `time` can represent.
+    #[cfg(feature = "time")]
+    pub fn to_offset_date_time(self) -> Option<time::OffsetDateTime> {
+        time::OffsetDateTime::from_unix_timestamp(i64::try_from(self.0).ok()?).ok()
+    }
+}
+
+impl From<u64> for Timestamp {
+    fn from(seconds: u64) -> Self {
+        Self(seconds)
+    }
+}
+
+/// RFC 3339 in UTC, e.g. `2020-09-13T12:26:40Z`.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let t = self.to_utc();
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            t.year, t.month, t.day, t.hour, t.minute, t.second
+        )
+    }
+}
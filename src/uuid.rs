@@ -0,0 +1,92 @@
+// uuid.rs
+use alloc::string::String;
+use core::fmt;
+use serde::Serialize;
+
+/// An LVM UUID. LVM writes UUIDs with dashes in the metadata text (`6-4-4-4-4-4-6`)
+/// and without them in the PV header; both forms parse to the same `LvmUuid`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LvmUuid(String);
+
+impl LvmUuid {
+    pub fn new(s: &str) -> Self {
+        Self(s.chars().filter(|&c| c != '-').collect())
+    }
+
+    /// The UUID without dashes, as stored in the PV header.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether two UUIDs in either form denote the same UUID.
+    pub fn matches(a: &str, b: &str) -> bool {
+        a.chars()
+            .filter(|&c| c != '-')
+            .eq(b.chars().filter(|&c| c != '-'))
+    }
+}
+
+impl From<&str> for LvmUuid {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl PartialEq<str> for LvmUuid {
+    fn eq(&self, other: &str) -> bool {
+        Self::matches(&self.0, other)
+    }
+}
+
+impl PartialEq<&str> for LvmUuid {
+    fn eq(&self, other: &&str) -> bool {
+        Self::matches(&self.0, other)
+    }
+}
+
+/// Formats the UUID in the dashed form used by the LVM tools.
+impl fmt::Display for LvmUuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // a non-ASCII id may have no character boundary where a dash goes
+        if self.0.len() != 32 || !self.0.is_ascii() {
+            return f.write_str(&self.0);
+        }
+        let mut start = 0;
+        for (i, len) in [6, 4, 4, 4, 4, 4, 6].into_iter().enumerate() {
+            if i > 0 {
+                f.write_str("-")?;
+            }
+            f.write_str(&self.0[start..start + len])?;
+            start += len;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for LvmUuid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn displays_dashed() {
+        let uuid = LvmUuid::from("Rk3S1Mcg6lG3Ym4WsoB1K93x8o22VaZ3");
+        assert_eq!(uuid.to_string(), "Rk3S1M-cg6l-G3Ym-4Wso-B1K9-3x8o-22VaZ3");
+    }
+
+    #[test]
+    fn displays_non_ascii_ids_as_they_are() {
+        // 32 bytes, with a two-byte character across the first dash
+        let id = "Rk3S1\u{e9}g6lG3Ym4WsoB1K93x8o22VaZ3";
+        assert_eq!(LvmUuid::from(id).to_string(), id);
+    }
+}
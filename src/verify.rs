@@ -0,0 +1,55 @@
+// verify.rs
+use acid_io::{Read, Write};
+use alloc::format;
+use alloc::string::String;
+use sha2::{Digest, Sha256};
+use snafu::{ensure, ResultExt};
+
+use crate::{Error, HashMismatchSnafu, IoSnafu};
+
+/// Copies everything `source` yields to `sink`, hashing it on the way. Returns the
+/// byte count and the SHA-256 of the copied data.
+pub fn copy_sha256<R: Read, W: Write>(
+    source: &mut R,
+    sink: &mut W,
+) -> Result<(u64, [u8; 32]), Error> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        let n = match source.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == acid_io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context(IoSnafu),
+        };
+        hasher.update(&buf[..n]);
+        sink.write_all(&buf[..n]).context(IoSnafu)?;
+        copied += n as u64;
+    }
+    Ok((copied, hasher.finalize().into()))
+}
+
+/// Like [`copy_sha256`], but fails with [`Error::HashMismatch`] unless the copied
+/// data hashes to `expected`, given in hex. The sink has received it all either way.
+pub fn copy_verified<R: Read, W: Write>(
+    source: &mut R,
+    sink: &mut W,
+    expected: &str,
+) -> Result<u64, Error> {
+    let (copied, hash) = copy_sha256(source, sink)?;
+    let computed = to_hex(&hash);
+    ensure!(
+        computed.eq_ignore_ascii_case(expected.trim()),
+        HashMismatchSnafu {
+            expected: expected.trim(),
+            computed,
+        }
+    );
+    Ok(copied)
+}
+
+/// Lowercase hex, as printed by `sha256sum`.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
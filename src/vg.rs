@@ -0,0 +1,269 @@
+// vg.rs
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use once_cell::race::OnceBox;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt};
+
+use crate::metadata::{
+    ensure_fully_parsed, metadata_parse_error, LazyLVDesc, MetadataEntry, MetadataParser,
+    MetadataRoot, MetadataValue,
+};
+use crate::{Error, LvmUuid, MultipleVGsSnafu, SerdeSnafu, Timestamp, LV, PV};
+
+/// A volume group as described by a parsed metadata config, independent of the
+/// PV that supplied it.
+pub struct VolumeGroup {
+    name: String,
+    config: MetadataRoot,
+    // the section without its LVs, and where they were among its entries
+    skeleton: MetadataValue,
+    lvs_at: Option<usize>,
+    metadata: OnceBox<MetadataValue>,
+    header: MetadataHeader,
+    metadata_text: String,
+}
+
+/// The top-level fields LVM writes next to the VG section, describing the command
+/// that produced the config.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataHeader {
+    pub description: Option<String>,
+    pub creation_host: Option<String>,
+    /// Seconds since the epoch.
+    pub creation_time: Option<Timestamp>,
+}
+
+impl VolumeGroup {
+    /// Parses the VG config text found in a metadata area, or in a file LVM keeps
+    /// in `/etc/lvm/backup` or `/etc/lvm/archive`.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut vgs = Self::parse_all(text)?;
+        ensure!(vgs.len() == 1, MultipleVGsSnafu);
+        Ok(vgs.remove(0))
+    }
+
+    /// Parses config text that may describe several VGs.
+    pub fn parse_all(text: &str) -> Result<Vec<Self>, Error> {
+        let _span = tracing::debug_span!("parse_metadata", len = text.len()).entered();
+        // LV sections are only delimited here, and parsed when first used
+        let mut parser = MetadataParser::skeleton(text.as_bytes());
+        let mut vgs = BTreeMap::new();
+        let mut first_error = None;
+        let mut header = MetadataHeader::default();
+        for entry in parser.by_ref() {
+            let MetadataEntry {
+                name,
+                value,
+                lvs,
+                lvs_at,
+            } = entry.map_err(|rest| {
+                metadata_parse_error(text, text.len() - rest.len(), "valid config syntax")
+            })?;
+            match (name, &value) {
+                ("description", MetadataValue::String(s)) if header.description.is_none() => {
+                    header.description = Some(s.clone())
+                }
+                ("creation_host", MetadataValue::String(s)) if header.creation_host.is_none() => {
+                    header.creation_host = Some(s.clone())
+                }
+                ("creation_time", &MetadataValue::Number(t)) if header.creation_time.is_none() => {
+                    header.creation_time = u64::try_from(t).ok().map(Timestamp::from_unix)
+                }
+                _ => (),
+            }
+            match MetadataRoot::deserialize(&value) {
+                Ok(mut config) => {
+                    config.logical_volumes = lvs
+                        .into_iter()
+                        .map(|(lv, text)| (lv.to_owned(), LazyLVDesc::from_text(text)))
+                        .collect();
+                    tracing::debug!(vg = name, ?config);
+                    vgs.insert(name.to_owned(), (config, value, lvs_at));
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        ensure_fully_parsed(text, parser.remaining())?;
+        if let (true, Some(e)) = (vgs.is_empty(), first_error) {
+            return Err(e).context(SerdeSnafu);
+        }
+
+        Ok(vgs
+            .into_iter()
+            .map(|(name, (config, skeleton, lvs_at))| Self {
+                name,
+                config,
+                skeleton,
+                lvs_at,
+                metadata: OnceBox::new(),
+                header: header.clone(),
+                metadata_text: text.to_owned(),
+            })
+            .collect())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    pub fn extent_size(&self) -> u64 {
+        self.config.extent_size * 512
+    }
+
+    /// The config text this VG was parsed from.
+    pub fn raw_metadata_text(&self) -> &str {
+        &self.metadata_text
+    }
+
+    pub fn config(&self) -> &MetadataRoot {
+        &self.config
+    }
+
+    pub fn header(&self) -> &MetadataHeader {
+        &self.header
+    }
+
+    /// The untyped contents of this VG's section of the config, parsed in full on
+    /// first use.
+    pub fn metadata(&self) -> &MetadataValue {
+        self.metadata
+            .get_or_init(|| Box::new(self.parse_metadata()))
+    }
+
+    fn parse_metadata(&self) -> MetadataValue {
+        let full = MetadataParser::new(self.metadata_text.as_bytes())
+            .map_while(Result::ok)
+            .find(|entry| entry.name == self.name)
+            .map(|entry| entry.value);
+        // an LV section that does not parse stops the full parse; leave it out
+        full.unwrap_or_else(|| {
+            let mut entries = self.skeleton.as_section().unwrap_or_default().to_vec();
+            let lvs = self
+                .config
+                .logical_volumes
+                .iter()
+                .filter_map(|(name, lazy)| Some((name.clone(), lazy.raw().ok()?.clone())))
+                .collect();
+            let at = self.lvs_at.unwrap_or(entries.len());
+            entries.insert(
+                at,
+                ("logical_volumes".to_owned(), MetadataValue::Section(lvs)),
+            );
+            MetadataValue::Section(entries)
+        })
+    }
+
+    /// This VG's config written back out as LVM config text.
+    pub fn to_config_text(&self) -> String {
+        self.config.to_config_text(&self.name)
+    }
+
+    /// The whole config this VG was read from, unknown keys included: its section as
+    /// [`VolumeGroup::metadata`] and the entries LVM writes beside it, such as
+    /// `description` and `creation_time`.
+    pub fn full_metadata(&self) -> MetadataValue {
+        let mut entries: Vec<(String, MetadataValue)> =
+            MetadataParser::skeleton(self.metadata_text.as_bytes())
+                .map_while(Result::ok)
+                .filter(|entry| entry.name != self.name)
+                .map(|entry| (entry.name.to_owned(), entry.value))
+                .collect();
+        // LVM writes the VG section first
+        entries.insert(0, (self.name.clone(), self.metadata().clone()));
+        MetadataValue::Section(entries)
+    }
+
+    /// [`VolumeGroup::full_metadata`] formatted for review by
+    /// [`MetadataValue::to_pretty_text`].
+    pub fn to_pretty_text(&self) -> String {
+        self.full_metadata().to_pretty_text()
+    }
+
+    /// Looks up a value by its `/`-separated path within the VG section, e.g.
+    /// `logical_volumes/root/segment1/type`.
+    pub fn metadata_get(&self, path: &str) -> Option<&MetadataValue> {
+        self.metadata().get(path)
+    }
+
+    /// The LVs users see, in name order; [`VolumeGroup::lvs_all`] includes the
+    /// hidden ones (see [`LV::is_hidden`]).
+    pub fn lvs(&self) -> impl Iterator<Item = LV<'_>> + '_ {
+        self.lvs_all().filter(|lv| !lv.is_hidden())
+    }
+
+    /// All LVs in name order, leaving out any whose section does not describe an LV
+    /// (see [`VolumeGroup::invalid_lvs`]). Each is deserialized on first use.
+    pub fn lvs_all(&self) -> impl Iterator<Item = LV<'_>> + '_ {
+        self.config
+            .logical_volumes
+            .iter()
+            .filter_map(|(name, lazy)| {
+                Some(LV {
+                    name,
+                    desc: lazy.desc().ok()?,
+                    extent_size: self.extent_size(),
+                })
+            })
+    }
+
+    /// The PVs in name order.
+    pub fn pvs(&self) -> impl Iterator<Item = PV<'_>> + '_ {
+        self.config
+            .physical_volumes
+            .iter()
+            .map(|(name, desc)| PV { name, desc })
+    }
+
+    pub fn lv_by_name(&self, name: &str) -> Option<LV<'_>> {
+        let (name, lazy) = self.config.logical_volumes.get_key_value(name)?;
+        Some(LV {
+            name,
+            desc: lazy.desc().ok()?,
+            extent_size: self.extent_size(),
+        })
+    }
+
+    /// Looks up an LV by UUID, with or without dashes.
+    pub fn lv_by_id(&self, id: &str) -> Option<LV<'_>> {
+        // matched on the raw sections, so only the LV found gets deserialized
+        let name = self
+            .config
+            .logical_volumes
+            .iter()
+            .find_map(|(name, lazy)| {
+                let raw_id = lazy.raw().ok()?.get("id").and_then(MetadataValue::as_str)?;
+                LvmUuid::matches(raw_id, id).then_some(name)
+            })?;
+        self.lv_by_name(name)
+    }
+
+    /// LV sections that do not deserialize into an LV, with the reason.
+    pub fn invalid_lvs(&self) -> impl Iterator<Item = (&str, &serde::de::value::Error)> + '_ {
+        self.config
+            .logical_volumes
+            .iter()
+            .filter_map(|(name, lazy)| Some((name.as_str(), lazy.desc().err()?)))
+    }
+
+    /// Looks up a PV by UUID, with or without dashes.
+    pub fn pv_by_id(&self, id: &str) -> Option<PV<'_>> {
+        self.pvs().find(|pv| LvmUuid::matches(pv.id(), id))
+    }
+
+    pub fn pv_by_name(&self, name: &str) -> Option<PV<'_>> {
+        self.config
+            .physical_volumes
+            .get_key_value(name)
+            .map(|(name, desc)| PV { name, desc })
+    }
+}
@@ -0,0 +1,375 @@
+// vg_set.rs
+use acid_io::{Read, Seek};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Serialize;
+use snafu::{ensure, OptionExt};
+
+use crate::{
+    AmbiguousVgSnafu, DuplicatePvSnafu, Error, LvmUuid, MissingMetadataSnafu, OpenLV,
+    PVNotInVGSnafu, Pv, PvHandle, SeqnoMismatchSnafu, VGMismatchSnafu, VgNotFoundSnafu,
+    VolumeGroup, LV,
+};
+
+/// A VG some of the PVs handed to [`group_by_vg`] or [`VolumeGroupSet::find_vgs`]
+/// belong to.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct VgCandidate {
+    pub id: String,
+    pub name: String,
+    /// Seqno of the first copy of its metadata found.
+    pub seqno: u64,
+    /// Positions of its PVs among those handed over, including PVs without metadata
+    /// of their own that the VG lists.
+    pub pvs: Vec<usize>,
+    /// Names of the PVs the VG lists that were not among them.
+    pub missing: Vec<String>,
+}
+impl VgCandidate {
+    /// Whether `vg` is this VG's name or UUID.
+    pub fn matches(&self, vg: &str) -> bool {
+        self.name == vg || LvmUuid::matches(&self.id, vg)
+    }
+}
+
+/// Groups PVs, each given with the VG its metadata describes if it has any, by VG
+/// UUID, in the order the VGs are first seen. Different VGs may share a name, e.g.
+/// the default one of two installs. A PV without metadata joins the first VG listing
+/// it; one that no VG lists is left out.
+pub fn group_by_vg<'a>(
+    pvs: impl IntoIterator<Item = (&'a Pv, Option<&'a VolumeGroup>)>,
+) -> Vec<VgCandidate> {
+    let pvs: Vec<_> = pvs.into_iter().collect();
+    let mut vgs: Vec<&VolumeGroup> = Vec::new();
+    let mut groups: Vec<VgCandidate> = Vec::new();
+    for (i, &(_, vg)) in pvs.iter().enumerate() {
+        let Some(vg) = vg else { continue };
+        match vgs.iter().position(|known| known.id() == vg.id()) {
+            Some(group) => groups[group].pvs.push(i),
+            None => {
+                vgs.push(vg);
+                groups.push(VgCandidate {
+                    id: vg.id().to_string(),
+                    name: vg.name().to_string(),
+                    seqno: vg.config().seqno,
+                    pvs: Vec::from([i]),
+                    missing: Vec::new(),
+                });
+            }
+        }
+    }
+    for (i, &(pv, vg)) in pvs.iter().enumerate() {
+        if vg.is_some() {
+            continue;
+        }
+        let listed = |vg: &&VolumeGroup| vg.pvs().any(|v| LvmUuid::matches(v.id(), pv.id()));
+        if let Some(group) = vgs.iter().position(listed) {
+            groups[group].pvs.push(i);
+            groups[group].pvs.sort_unstable();
+        }
+    }
+    for (group, vg) in groups.iter_mut().zip(vgs) {
+        group.missing = vg
+            .pvs()
+            .filter(|v| {
+                !group
+                    .pvs
+                    .iter()
+                    .any(|&i| LvmUuid::matches(v.id(), pvs[i].0.id()))
+            })
+            .map(|v| v.name().to_string())
+            .collect();
+    }
+    groups
+}
+
+struct Member<T> {
+    name: String,
+    pv: Pv,
+    reader: T,
+}
+
+/// The PVs of one VG together with their readers, keyed by PV UUID.
+/// LVs spanning several PVs are opened from here.
+///
+/// Every PV may only be supplied once: two readers carrying the same PV UUID, such as
+/// a disk and its clone, fail with [`Error::DuplicatePv`] rather than have one of
+/// them picked silently, since LVs read from a mix of both could be assembled from
+/// data that never existed together. [`VolumeGroupSet::replace`] swaps in another
+/// instance of a PV. [`VolumeGroupSet::open_vg`] picks one VG out of bodies holding
+/// PVs of several.
+pub struct VolumeGroupSet<T> {
+    vg: VolumeGroup,
+    // whether `vg` came from elsewhere, so the PVs' own metadata is not compared
+    external: bool,
+    members: BTreeMap<LvmUuid, Member<T>>,
+}
+
+impl<T: Read + Seek> VolumeGroupSet<T> {
+    /// Opens every reader as a PV. The VG config is taken from the first PV that
+    /// carries metadata; every other copy must describe the same VG at the same seqno,
+    /// and no PV may be supplied twice.
+    pub fn open(readers: impl IntoIterator<Item = T>) -> Result<Self, Error> {
+        let mut opened = Vec::new();
+        let mut vg: Option<VolumeGroup> = None;
+        for mut reader in readers {
+            let mut pv = Pv::open(&mut reader)?;
+            if pv.metadata_area_header().is_some() {
+                let pv_vg = pv.volume_group(&mut reader)?;
+                match &vg {
+                    Some(vg) => Self::check_same_vg(vg, &pv_vg, pv.id())?,
+                    None => vg = Some(pv_vg),
+                }
+            }
+            opened.push((pv, reader));
+        }
+        Self::assemble(vg, opened)
+    }
+
+    /// Opens the PVs of the VG named `vg`, or with UUID `vg`, among `readers`, which
+    /// may hold PVs of other VGs as well, e.g. every disk of a machine; those are left
+    /// out, as are readers that do not hold a PV or whose metadata cannot be read.
+    /// Otherwise as [`VolumeGroupSet::open`]. Fails with [`Error::AmbiguousVg`] when
+    /// several VGs have that name.
+    pub fn open_vg(readers: impl IntoIterator<Item = T>, vg: &str) -> Result<Self, Error> {
+        let mut opened = Vec::new();
+        for mut reader in readers {
+            // as in `find_vgs`, a disk that is not a readable PV is not one of the VG's
+            let Ok(mut pv) = Pv::open(&mut reader) else {
+                continue;
+            };
+            let pv_vg = match pv.metadata_area_header() {
+                Some(_) => match pv.volume_group(&mut reader) {
+                    Ok(pv_vg) => Some(pv_vg),
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+            opened.push((pv, pv_vg, reader));
+        }
+        let selected = {
+            let groups = group_by_vg(opened.iter().map(|(pv, pv_vg, _)| (pv, pv_vg.as_ref())));
+            let mut matching = groups.iter().filter(|group| group.matches(vg));
+            let group = matching.next().context(VgNotFoundSnafu { vg })?;
+            if matching.next().is_some() {
+                let ids: Vec<&str> = groups
+                    .iter()
+                    .filter(|group| group.matches(vg))
+                    .map(|group| group.id.as_str())
+                    .collect();
+                return AmbiguousVgSnafu {
+                    vg,
+                    ids: ids.join(", "),
+                }
+                .fail();
+            }
+            group.pvs.clone()
+        };
+
+        let mut config: Option<VolumeGroup> = None;
+        let mut members = Vec::new();
+        for (i, (pv, pv_vg, reader)) in opened.into_iter().enumerate() {
+            if !selected.contains(&i) {
+                continue;
+            }
+            if let Some(pv_vg) = pv_vg {
+                match &config {
+                    Some(config) => Self::check_same_vg(config, &pv_vg, pv.id())?,
+                    None => config = Some(pv_vg),
+                }
+            }
+            members.push((pv, reader));
+        }
+        Self::assemble(config, members)
+    }
+
+    /// Groups the PVs among `readers` by VG like [`group_by_vg`], to choose one to pass
+    /// to [`VolumeGroupSet::open_vg`]. [`VgCandidate::pvs`] are indices into `readers`.
+    /// Readers that do not hold a PV, or whose metadata cannot be read, are skipped.
+    pub fn find_vgs(readers: &mut [T]) -> Vec<VgCandidate> {
+        let mut opened = Vec::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            let Ok(mut pv) = Pv::open(reader) else {
+                continue;
+            };
+            let pv_vg = match pv.metadata_area_header() {
+                Some(_) => match pv.volume_group(reader) {
+                    Ok(pv_vg) => Some(pv_vg),
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+            opened.push((i, pv, pv_vg));
+        }
+        let mut groups = group_by_vg(opened.iter().map(|(_, pv, pv_vg)| (pv, pv_vg.as_ref())));
+        for group in &mut groups {
+            for i in &mut group.pvs {
+                *i = opened[*i].0;
+            }
+        }
+        groups
+    }
+
+    fn assemble(vg: Option<VolumeGroup>, opened: Vec<(Pv, T)>) -> Result<Self, Error> {
+        let mut set = Self {
+            vg: vg.context(MissingMetadataSnafu)?,
+            external: false,
+            members: BTreeMap::new(),
+        };
+        for (pv, reader) in opened {
+            set.insert(pv, reader)?;
+        }
+        Ok(set)
+    }
+
+    /// Opens every reader as a PV of `vg`, e.g. a VG parsed by
+    /// [`VolumeGroup::parse`] from a `vgcfgbackup` or archive file. The PVs' own
+    /// metadata is not read, so it may be newer, damaged or missing altogether, here
+    /// or in [`VolumeGroupSet::add`].
+    pub fn with_metadata(
+        vg: VolumeGroup,
+        readers: impl IntoIterator<Item = T>,
+    ) -> Result<Self, Error> {
+        let mut set = Self {
+            vg,
+            external: true,
+            members: BTreeMap::new(),
+        };
+        for mut reader in readers {
+            let pv = Pv::open(&mut reader)?;
+            set.insert(pv, reader)?;
+        }
+        Ok(set)
+    }
+
+    /// Adds another PV of this VG, one not supplied yet.
+    pub fn add(&mut self, mut reader: T) -> Result<(), Error> {
+        let pv = self.open_member(&mut reader)?;
+        self.insert(pv, reader)
+    }
+
+    /// Uses `reader` for the PV it holds instead of the instance supplied before,
+    /// e.g. a clone instead of the original disk, and returns the reader it replaces.
+    /// The PV is added if it was not supplied yet.
+    pub fn replace(&mut self, mut reader: T) -> Result<Option<T>, Error> {
+        let pv = self.open_member(&mut reader)?;
+        let old = self.members.remove(&LvmUuid::new(pv.id()));
+        self.insert(pv, reader)?;
+        Ok(old.map(|member| member.reader))
+    }
+
+    fn open_member(&self, reader: &mut T) -> Result<Pv, Error> {
+        let mut pv = Pv::open(reader)?;
+        if !self.external && pv.metadata_area_header().is_some() {
+            Self::check_same_vg(&self.vg, &pv.volume_group(reader)?, pv.id())?;
+        }
+        Ok(pv)
+    }
+
+    fn check_same_vg(vg: &VolumeGroup, other: &VolumeGroup, pv: &str) -> Result<(), Error> {
+        ensure!(
+            other.id() == vg.id(),
+            VGMismatchSnafu {
+                pv,
+                expected: vg.id(),
+                found: other.id(),
+            }
+        );
+        ensure!(
+            other.config().seqno == vg.config().seqno,
+            SeqnoMismatchSnafu {
+                pv,
+                expected: vg.config().seqno,
+                found: other.config().seqno,
+            }
+        );
+        Ok(())
+    }
+
+    fn insert(&mut self, pv: Pv, reader: T) -> Result<(), Error> {
+        let name = self
+            .vg
+            .pvs()
+            .find(|v| LvmUuid::matches(v.id(), pv.id()))
+            .context(PVNotInVGSnafu { pv: pv.id() })?
+            .name()
+            .to_string();
+        let id = LvmUuid::new(pv.id());
+        ensure!(
+            !self.members.contains_key(&id),
+            DuplicatePvSnafu { pv: name.as_str() }
+        );
+        self.members.insert(id, Member { name, pv, reader });
+        Ok(())
+    }
+
+    pub fn vg(&self) -> &VolumeGroup {
+        &self.vg
+    }
+
+    /// The opened PVs, keyed by UUID.
+    pub fn pvs(&self) -> impl Iterator<Item = (&LvmUuid, &Pv)> + '_ {
+        self.members.iter().map(|(id, m)| (id, &m.pv))
+    }
+
+    /// The opened PV with this UUID, in either form.
+    pub fn pv(&self, id: &str) -> Option<&Pv> {
+        self.members.get(&LvmUuid::new(id)).map(|m| &m.pv)
+    }
+
+    /// Names of the PVs listed in the VG metadata that have not been supplied.
+    pub fn missing_pvs(&self) -> impl Iterator<Item = &str> + '_ {
+        self.vg
+            .pvs()
+            .filter(|v| !self.members.values().any(|m| m.name == v.name()))
+            .map(|v| v.name())
+    }
+
+    pub fn open_lv_by_name(&mut self, name: &str) -> Option<OpenLV<'_, '_, T>> {
+        let Self { vg, members, .. } = self;
+        let lv = vg.lv_by_name(name)?;
+        Some(open_lv(members, lv))
+    }
+
+    pub fn open_lv_by_id(&mut self, id: &str) -> Option<OpenLV<'_, '_, T>> {
+        let Self { vg, members, .. } = self;
+        let lv = vg.lv_by_id(id)?;
+        Some(open_lv(members, lv))
+    }
+}
+
+fn open_lv<'a, T: Read + Seek>(
+    members: &'a mut BTreeMap<LvmUuid, Member<T>>,
+    lv: LV<'a>,
+) -> OpenLV<'a, 'a, T> {
+    OpenLV {
+        lv,
+        pvs: members
+            .values_mut()
+            .map(|m| PvHandle {
+                name: &m.name,
+                pv: &m.pv,
+                reader: &mut m.reader,
+            })
+            .collect(),
+        position: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_image;
+
+    #[test]
+    fn open_vg_skips_readers_without_a_pv() {
+        let readers = [
+            test_image::reader(alloc::vec![0; 4096]),
+            test_image::reader(test_image::image("", 4)),
+        ];
+        let set = VolumeGroupSet::open_vg(readers, "vg0").unwrap();
+        assert_eq!(set.vg().name(), "vg0");
+        assert_eq!(set.pvs().count(), 1);
+    }
+}